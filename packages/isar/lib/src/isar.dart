@@ -92,6 +92,10 @@ abstract class Isar {
     int maxSizeMiB = Isar.defaultMaxSizeMiB,
     bool relaxedDurability = true,
     CompactCondition? compactOnLaunch,
+    int? pageSize,
+    bool createDirectory = true,
+    int openRetryAttempts = 0,
+    Duration openRetryDelay = Duration.zero,
     bool inspector = true,
   }) {
     _checkOpen(name, schemas);
@@ -111,6 +115,10 @@ abstract class Isar {
       maxSizeMiB: maxSizeMiB,
       relaxedDurability: relaxedDurability,
       compactOnLaunch: compactOnLaunch,
+      pageSize: pageSize,
+      createDirectory: createDirectory,
+      openRetryAttempts: openRetryAttempts,
+      openRetryDelay: openRetryDelay,
     );
   }
 
@@ -122,6 +130,10 @@ abstract class Isar {
     int maxSizeMiB = Isar.defaultMaxSizeMiB,
     bool relaxedDurability = true,
     CompactCondition? compactOnLaunch,
+    int? pageSize,
+    bool createDirectory = true,
+    int openRetryAttempts = 0,
+    Duration openRetryDelay = Duration.zero,
     bool inspector = true,
   }) {
     _checkOpen(name, schemas);
@@ -141,6 +153,10 @@ abstract class Isar {
       maxSizeMiB: maxSizeMiB,
       relaxedDurability: relaxedDurability,
       compactOnLaunch: compactOnLaunch,
+      pageSize: pageSize,
+      createDirectory: createDirectory,
+      openRetryAttempts: openRetryAttempts,
+      openRetryDelay: openRetryDelay,
     );
   }
 
@@ -1286,10 +1286,14 @@ class IsarCoreBindings {
     ffi.Pointer<ffi.Char> path,
     ffi.Pointer<ffi.Char> schema_json,
     int max_size_mib,
+    int page_size,
     bool relaxed_durability,
     int compact_min_file_size,
     int compact_min_bytes,
     double compact_min_ratio,
+    bool create_dir,
+    int open_retry_attempts,
+    int open_retry_delay_ms,
   ) {
     return _isar_instance_create(
       isar,
@@ -1297,10 +1301,14 @@ class IsarCoreBindings {
       path,
       schema_json,
       max_size_mib,
+      page_size,
       relaxed_durability,
       compact_min_file_size,
       compact_min_bytes,
       compact_min_ratio,
+      create_dir,
+      open_retry_attempts,
+      open_retry_delay_ms,
     );
   }
 
@@ -1312,10 +1320,14 @@ class IsarCoreBindings {
               ffi.Pointer<ffi.Char>,
               ffi.Pointer<ffi.Char>,
               ffi.Int64,
+              ffi.Int64,
               ffi.Bool,
               ffi.Uint32,
               ffi.Uint32,
-              ffi.Double)>>('isar_instance_create');
+              ffi.Double,
+              ffi.Bool,
+              ffi.Uint32,
+              ffi.Uint32)>>('isar_instance_create');
   late final _isar_instance_create = _isar_instance_createPtr.asFunction<
       int Function(
           ffi.Pointer<ffi.Pointer<CIsarInstance>>,
@@ -1323,10 +1335,14 @@ class IsarCoreBindings {
           ffi.Pointer<ffi.Char>,
           ffi.Pointer<ffi.Char>,
           int,
+          int,
           bool,
           int,
           int,
-          double)>();
+          double,
+          bool,
+          int,
+          int)>();
 
   void isar_instance_create_async(
     ffi.Pointer<ffi.Pointer<CIsarInstance>> isar,
@@ -1334,10 +1350,14 @@ class IsarCoreBindings {
     ffi.Pointer<ffi.Char> path,
     ffi.Pointer<ffi.Char> schema_json,
     int max_size_mib,
+    int page_size,
     bool relaxed_durability,
     int compact_min_file_size,
     int compact_min_bytes,
     double compact_min_ratio,
+    bool create_dir,
+    int open_retry_attempts,
+    int open_retry_delay_ms,
     int port,
   ) {
     return _isar_instance_create_async(
@@ -1346,10 +1366,14 @@ class IsarCoreBindings {
       path,
       schema_json,
       max_size_mib,
+      page_size,
       relaxed_durability,
       compact_min_file_size,
       compact_min_bytes,
       compact_min_ratio,
+      create_dir,
+      open_retry_attempts,
+      open_retry_delay_ms,
       port,
     );
   }
@@ -1362,10 +1386,14 @@ class IsarCoreBindings {
               ffi.Pointer<ffi.Char>,
               ffi.Pointer<ffi.Char>,
               ffi.Int64,
+              ffi.Int64,
               ffi.Bool,
               ffi.Uint32,
               ffi.Uint32,
               ffi.Double,
+              ffi.Bool,
+              ffi.Uint32,
+              ffi.Uint32,
               DartPort)>>('isar_instance_create_async');
   late final _isar_instance_create_async =
       _isar_instance_create_asyncPtr.asFunction<
@@ -1375,13 +1403,17 @@ class IsarCoreBindings {
               ffi.Pointer<ffi.Char>,
               ffi.Pointer<ffi.Char>,
               int,
+              int,
               bool,
               int,
               int,
               double,
+              bool,
+              int,
+              int,
               int)>();
 
-  bool isar_instance_close(
+  int isar_instance_close(
     ffi.Pointer<CIsarInstance> isar,
   ) {
     return _isar_instance_close(
@@ -1390,12 +1422,12 @@ class IsarCoreBindings {
   }
 
   late final _isar_instance_closePtr = _lookup<
-          ffi.NativeFunction<ffi.Bool Function(ffi.Pointer<CIsarInstance>)>>(
+          ffi.NativeFunction<ffi.Int32 Function(ffi.Pointer<CIsarInstance>)>>(
       'isar_instance_close');
   late final _isar_instance_close = _isar_instance_closePtr
-      .asFunction<bool Function(ffi.Pointer<CIsarInstance>)>();
+      .asFunction<int Function(ffi.Pointer<CIsarInstance>)>();
 
-  bool isar_instance_close_and_delete(
+  int isar_instance_close_and_delete(
     ffi.Pointer<CIsarInstance> isar,
   ) {
     return _isar_instance_close_and_delete(
@@ -1404,11 +1436,11 @@ class IsarCoreBindings {
   }
 
   late final _isar_instance_close_and_deletePtr = _lookup<
-          ffi.NativeFunction<ffi.Bool Function(ffi.Pointer<CIsarInstance>)>>(
+          ffi.NativeFunction<ffi.Int32 Function(ffi.Pointer<CIsarInstance>)>>(
       'isar_instance_close_and_delete');
   late final _isar_instance_close_and_delete =
       _isar_instance_close_and_deletePtr
-          .asFunction<bool Function(ffi.Pointer<CIsarInstance>)>();
+          .asFunction<int Function(ffi.Pointer<CIsarInstance>)>();
 
   ffi.Pointer<ffi.Char> isar_instance_get_path(
     ffi.Pointer<CIsarInstance> isar,
@@ -71,11 +71,11 @@ class IsarImpl extends IsarCommon implements Finalizable {
   @override
   bool performClose(bool deleteFromDisk) {
     _finalizer.detach(this);
-    if (deleteFromDisk) {
-      return IC.isar_instance_close_and_delete(ptr);
-    } else {
-      return IC.isar_instance_close(ptr);
-    }
+    // -1 means closed, other values are errors or the number of remaining references
+    final result = deleteFromDisk
+        ? IC.isar_instance_close_and_delete(ptr)
+        : IC.isar_instance_close(ptr);
+    return result == -1;
   }
 
   @override
@@ -77,6 +77,10 @@ Future<Isar> openIsar({
   required int maxSizeMiB,
   required bool relaxedDurability,
   CompactCondition? compactOnLaunch,
+  int? pageSize,
+  required bool createDirectory,
+  required int openRetryAttempts,
+  required Duration openRetryDelay,
 }) async {
   initializeCoreBinary();
   IC.isar_connect_dart_api(NativeApi.postCObject.cast());
@@ -102,10 +106,14 @@ Future<Isar> openIsar({
       dirPtr,
       schemaStrPtr,
       maxSizeMiB,
+      pageSize ?? 0,
       relaxedDurability,
       compactMinFileSize ?? 0,
       compactMinBytes ?? 0,
       compactMinRatio ?? 0,
+      createDirectory,
+      openRetryAttempts,
+      openRetryDelay.inMilliseconds,
       nativePort,
     );
     await stream.first;
@@ -123,6 +131,10 @@ Isar openIsarSync({
   required int maxSizeMiB,
   required bool relaxedDurability,
   CompactCondition? compactOnLaunch,
+  int? pageSize,
+  required bool createDirectory,
+  required int openRetryAttempts,
+  required Duration openRetryDelay,
 }) {
   initializeCoreBinary();
   IC.isar_connect_dart_api(NativeApi.postCObject.cast());
@@ -145,10 +157,14 @@ Isar openIsarSync({
         dirPtr,
         schemaStrPtr,
         maxSizeMiB,
+        pageSize ?? 0,
         relaxedDurability,
         compactMinFileSize ?? 0,
         compactMinBytes ?? 0,
         compactMinRatio ?? 0,
+        createDirectory,
+        openRetryAttempts,
+        openRetryDelay.inMilliseconds,
       ),
     );
 
@@ -45,6 +45,10 @@ Future<Isar> openIsar({
   required int maxSizeMiB,
   required bool relaxedDurability,
   CompactCondition? compactOnLaunch,
+  int? pageSize,
+  required bool createDirectory,
+  required int openRetryAttempts,
+  required Duration openRetryDelay,
 }) async {
   throw IsarError('Please use Isar 2.5.0 if you need web support. '
       'A 3.x version with web support will be released soon.');
@@ -78,5 +82,9 @@ Isar openIsarSync({
   required int maxSizeMiB,
   required bool relaxedDurability,
   CompactCondition? compactOnLaunch,
+  int? pageSize,
+  required bool createDirectory,
+  required int openRetryAttempts,
+  required Duration openRetryDelay,
 }) =>
     unsupportedOnWeb();
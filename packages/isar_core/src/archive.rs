@@ -0,0 +1,213 @@
+use crate::error::{IsarError, Result};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{self, Read, Write};
+
+/*
+Archive layout:
+
+8 bytes magic "ISARARC1"
+u64 id of the exported transaction
+frames:
+u8 kind
+u32 payload length
+... payload ...
+
+The first frame contains the schema as JSON. Every collection frame (the collection name) is
+followed by the object and link frames of that collection. Delta exports also contain delete
+frames with the ids that have to be removed. The archive ends with an end frame.
+*/
+
+const MAGIC: &[u8; 8] = b"ISARARC1";
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CollectionManifest {
+    pub name: String,
+    pub objects: u64,
+    pub links: u64,
+    pub deleted: u64,
+}
+
+/// Describes the content of an archive written by [crate::instance::IsarInstance::export_all].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ExportManifest {
+    /// The id of the transaction the snapshot was taken in.
+    pub txn_id: u64,
+    pub collections: Vec<CollectionManifest>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum Frame {
+    Schema(Vec<u8>),
+    Collection(String),
+    Object(i64, Vec<u8>),
+    Link(u64, i64, i64),
+    Delete(i64),
+    End,
+}
+
+impl Frame {
+    const SCHEMA: u8 = 1;
+    const COLLECTION: u8 = 2;
+    const OBJECT: u8 = 3;
+    const LINK: u8 = 4;
+    const END: u8 = 5;
+    const DELETE: u8 = 6;
+}
+
+fn io_error(e: io::Error) -> IsarError {
+    IsarError::IoError {
+        message: e.to_string(),
+    }
+}
+
+fn invalid<T>() -> Result<T> {
+    Err(IsarError::InvalidArchive {})
+}
+
+pub(crate) struct ArchiveWriter<'a, W: Write> {
+    writer: &'a mut W,
+}
+
+impl<'a, W: Write> ArchiveWriter<'a, W> {
+    pub fn new(writer: &'a mut W, txn_id: u64) -> Result<Self> {
+        writer.write_all(MAGIC).map_err(io_error)?;
+        writer.write_u64::<LittleEndian>(txn_id).map_err(io_error)?;
+        Ok(ArchiveWriter { writer })
+    }
+
+    fn write_raw(&mut self, kind: u8, parts: &[&[u8]]) -> Result<()> {
+        let len = parts.iter().map(|p| p.len()).sum::<usize>();
+        self.writer.write_u8(kind).map_err(io_error)?;
+        self.writer
+            .write_u32::<LittleEndian>(len as u32)
+            .map_err(io_error)?;
+        for part in parts {
+            self.writer.write_all(part).map_err(io_error)?;
+        }
+        Ok(())
+    }
+
+    pub fn write(&mut self, frame: &Frame) -> Result<()> {
+        match frame {
+            Frame::Schema(json) => self.write_raw(Frame::SCHEMA, &[json]),
+            Frame::Collection(name) => self.write_raw(Frame::COLLECTION, &[name.as_bytes()]),
+            Frame::Object(id, bytes) => self.write_raw(Frame::OBJECT, &[&id.to_le_bytes(), bytes]),
+            Frame::Link(link_id, source_id, target_id) => self.write_raw(
+                Frame::LINK,
+                &[
+                    &link_id.to_le_bytes(),
+                    &source_id.to_le_bytes(),
+                    &target_id.to_le_bytes(),
+                ],
+            ),
+            Frame::Delete(id) => self.write_raw(Frame::DELETE, &[&id.to_le_bytes()]),
+            Frame::End => self.write_raw(Frame::END, &[]),
+        }
+    }
+}
+
+pub(crate) struct ArchiveReader<'a, R: Read> {
+    reader: &'a mut R,
+    pub txn_id: u64,
+}
+
+impl<'a, R: Read> ArchiveReader<'a, R> {
+    pub fn new(reader: &'a mut R) -> Result<Self> {
+        let mut magic = [0u8; 8];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|_| IsarError::InvalidArchive {})?;
+        if &magic != MAGIC {
+            return invalid();
+        }
+        let txn_id = reader
+            .read_u64::<LittleEndian>()
+            .map_err(|_| IsarError::InvalidArchive {})?;
+        Ok(ArchiveReader { reader, txn_id })
+    }
+
+    pub fn read(&mut self) -> Result<Frame> {
+        let kind = self
+            .reader
+            .read_u8()
+            .map_err(|_| IsarError::InvalidArchive {})?;
+        let len = self
+            .reader
+            .read_u32::<LittleEndian>()
+            .map_err(|_| IsarError::InvalidArchive {})? as usize;
+        let mut payload = vec![];
+        self.reader
+            .take(len as u64)
+            .read_to_end(&mut payload)
+            .map_err(io_error)?;
+        if payload.len() != len {
+            return invalid();
+        }
+
+        let read_i64 = |bytes: &[u8]| i64::from_le_bytes(bytes.try_into().unwrap());
+        let frame = match kind {
+            Frame::SCHEMA => Frame::Schema(payload),
+            Frame::COLLECTION => match String::from_utf8(payload) {
+                Ok(name) => Frame::Collection(name),
+                Err(_) => return invalid(),
+            },
+            Frame::OBJECT if len >= 8 => {
+                Frame::Object(read_i64(&payload[..8]), payload.split_off(8))
+            }
+            Frame::LINK if len == 24 => Frame::Link(
+                read_i64(&payload[..8]) as u64,
+                read_i64(&payload[8..16]),
+                read_i64(&payload[16..]),
+            ),
+            Frame::DELETE if len == 8 => Frame::Delete(read_i64(&payload)),
+            Frame::END if len == 0 => Frame::End,
+            _ => return invalid(),
+        };
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let frames = vec![
+            Frame::Schema(b"{}".to_vec()),
+            Frame::Collection("users".to_string()),
+            Frame::Object(-5, vec![1, 2, 3]),
+            Frame::Object(7, vec![]),
+            Frame::Link(u64::MAX, 1, i64::MIN),
+            Frame::Delete(-3),
+            Frame::End,
+        ];
+        let mut bytes = vec![];
+        let mut writer = ArchiveWriter::new(&mut bytes, 42).unwrap();
+        for frame in &frames {
+            writer.write(frame).unwrap();
+        }
+
+        let mut slice = bytes.as_slice();
+        let mut reader = ArchiveReader::new(&mut slice).unwrap();
+        assert_eq!(reader.txn_id, 42);
+        for frame in frames {
+            assert_eq!(reader.read().unwrap(), frame);
+        }
+        assert!(reader.read().is_err());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut not_an_archive: &[u8] = b"ISARARC0";
+        assert!(ArchiveReader::new(&mut not_an_archive).is_err());
+
+        let mut bytes = vec![];
+        let mut writer = ArchiveWriter::new(&mut bytes, 1).unwrap();
+        writer.write(&Frame::Object(1, vec![0; 10])).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        let mut truncated = bytes.as_slice();
+        let mut reader = ArchiveReader::new(&mut truncated).unwrap();
+        assert!(reader.read().is_err());
+    }
+}
@@ -1,24 +1,133 @@
+use crate::collection_diff::CollectionDiff;
 use crate::cursor::IsarCursors;
 use crate::error::{illegal_arg, IsarError, Result};
 use crate::index::index_key::IndexKey;
 use crate::index::index_key_builder::IndexKeyBuilder;
-use crate::index::IsarIndex;
+use crate::index::{IndexStats, IsarIndex};
 use crate::link::IsarLink;
 use crate::mdbx::db::Db;
+use crate::object::data_type::DataType;
 use crate::object::id::BytesToId;
 use crate::object::isar_object::IsarObject;
 use crate::object::json_encode_decode::JsonEncodeDecode;
+use crate::object::msgpack;
 use crate::object::object_builder::ObjectBuilder;
+use crate::object::projected_object::ProjectedObject;
 use crate::object::property::Property;
+use crate::oplog::{self, OpType};
+use crate::query::filter::Filter;
+use crate::query::knn::{Metric, NearestNeighbors};
 use crate::query::query_builder::QueryBuilder;
+use crate::query::Query;
+use crate::schema::collection_schema::CollectionSchema;
+use crate::schema::index_schema::IndexType;
+use crate::schema::property_schema::{
+    check_value_sizes, check_vector_dimensions, EnumMapping, NonFinitePolicy, StringTransform,
+};
 use crate::txn::IsarTxn;
 use crate::watch::change_set::ChangeSet;
+use byteorder::{ByteOrder, LittleEndian};
 use intmap::IntMap;
 use itertools::Itertools;
+use serde::de::{Error, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
+use serde::{Deserializer, Serializer};
 use serde_json::Value;
+use std::borrow::Cow;
 use std::cell::Cell;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Read;
 use std::ops::Deref;
-use xxhash_rust::xxh3::xxh3_64;
+use std::sync::atomic::AtomicBool;
+use xxhash_rust::xxh3::{xxh3_64, xxh3_64_with_seed};
+
+/// Old and new id of every object, ordered by id.
+pub type IdRemap = Vec<(i64, i64)>;
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum InsertConflict {
+    Overwrite,
+    Abort,
+    Ignore,
+}
+
+/// How many objects an import inserted, skipped or overwritten because their id already existed.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug)]
+pub struct ImportResult {
+    pub inserted: u32,
+    pub skipped: u32,
+    pub overwritten: u32,
+}
+
+impl ImportResult {
+    pub fn total(&self) -> u32 {
+        self.inserted + self.skipped + self.overwritten
+    }
+}
+
+/// The transactions that created and last modified an object of a versioned collection. Objects
+/// written before creation tracking was added report 0 as `created_txn`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct RowMeta {
+    pub created_txn: u64,
+    pub modified_txn: u64,
+}
+
+impl RowMeta {
+    // version entries store the modified txn first so they can still be read as a version
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let modified_txn = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        let created_txn = bytes
+            .get(8..16)
+            .map_or(0, |b| u64::from_le_bytes(b.try_into().unwrap()));
+        RowMeta {
+            created_txn,
+            modified_txn,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[0..8].copy_from_slice(&self.modified_txn.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.created_txn.to_le_bytes());
+        bytes
+    }
+}
+
+/// The optional features of a collection that are configured in its schema.
+pub(crate) struct CollectionOptions {
+    pub version_db: Option<Db>,
+    pub oplog_db: Option<Db>,
+    pub non_finite_policies: Vec<(Property, NonFinitePolicy)>,
+    pub string_transforms: Vec<(Property, Vec<StringTransform>)>,
+    pub value_size_limits: Vec<(Property, usize)>,
+    pub vector_dimensions: Vec<(Property, usize)>,
+    pub enum_mappings: Vec<EnumMapping>,
+    pub ttl_property: Option<Property>,
+    pub descending_ids: bool,
+}
+
+impl CollectionOptions {
+    /// Reads the options from `schema`. The version and oplog databases are opened by the caller.
+    pub(crate) fn new(
+        schema: &CollectionSchema,
+        version_db: Option<Db>,
+        oplog_db: Option<Db>,
+    ) -> Self {
+        CollectionOptions {
+            version_db,
+            oplog_db,
+            non_finite_policies: schema.get_non_finite_policies(),
+            string_transforms: schema.get_string_transforms(),
+            value_size_limits: schema.get_value_size_limits(),
+            vector_dimensions: schema.get_vector_dimensions(),
+            enum_mappings: schema.get_enum_mappings(),
+            ttl_property: schema.get_ttl_property(),
+            descending_ids: schema.descending_ids,
+        }
+    }
+}
 
 pub struct IsarCollection {
     pub name: String,
@@ -29,12 +138,23 @@ pub struct IsarCollection {
 
     pub(crate) instance_id: u64,
     pub(crate) db: Db,
+    pub(crate) version_db: Option<Db>,
+    oplog_db: Option<Db>,
+    non_finite_policies: Vec<(Property, NonFinitePolicy)>,
+    string_transforms: Vec<(Property, Vec<StringTransform>)>,
+    value_size_limits: Vec<(Property, usize)>,
+    vector_dimensions: Vec<(Property, usize)>,
+    pub(crate) enum_mappings: Vec<EnumMapping>,
+    ttl_property: Option<Property>,
+    descending_ids: bool,
 
     pub(crate) indexes: Vec<IsarIndex>,
     pub(crate) links: Vec<IsarLink>, // links from this collection
     backlinks: Vec<IsarLink>,        // links to this collection
 
     auto_increment: Cell<i64>,
+    indexing_enabled: Cell<bool>,
+    indexes_outdated: Cell<bool>,
 }
 
 unsafe impl Send for IsarCollection {}
@@ -51,8 +171,20 @@ impl IsarCollection {
         indexes: Vec<IsarIndex>,
         links: Vec<IsarLink>,
         backlinks: Vec<IsarLink>,
+        options: CollectionOptions,
     ) -> Self {
         let id = xxh3_64(name.as_bytes());
+        let CollectionOptions {
+            version_db,
+            oplog_db,
+            non_finite_policies,
+            string_transforms,
+            value_size_limits,
+            vector_dimensions,
+            enum_mappings,
+            ttl_property,
+            descending_ids,
+        } = options;
         IsarCollection {
             name: name.to_string(),
             id,
@@ -60,10 +192,46 @@ impl IsarCollection {
             embedded_properties,
             instance_id,
             db,
+            version_db,
+            oplog_db,
+            non_finite_policies,
+            string_transforms,
+            value_size_limits,
+            vector_dimensions,
+            enum_mappings,
+            ttl_property,
+            descending_ids,
             indexes,
             links,
             backlinks,
-            auto_increment: Cell::new(0),
+            auto_increment: Cell::new(Self::initial_auto_increment(descending_ids)),
+            indexing_enabled: Cell::new(true),
+            indexes_outdated: Cell::new(false),
+        }
+    }
+
+    /// The auto increment of an empty collection. It stores the largest id or for descending ids
+    /// the smallest id and is never assigned itself.
+    fn initial_auto_increment(descending_ids: bool) -> i64 {
+        if descending_ids {
+            i64::MAX
+        } else {
+            0
+        }
+    }
+
+    /// Applies the transforms of `property` to a value it is compared with so queries match the
+    /// stored values.
+    pub fn normalize_string<'a>(
+        &self,
+        property: &Property,
+        value: &'a str,
+    ) -> Result<Cow<'a, str>> {
+        let transforms = self.string_transforms.iter().find(|(p, _)| p == property);
+        if let Some((_, transforms)) = transforms {
+            Ok(Cow::Owned(StringTransform::transform(transforms, value)?))
+        } else {
+            Ok(Cow::Borrowed(value))
         }
     }
 
@@ -77,7 +245,12 @@ impl IsarCollection {
 
     pub(crate) fn init_auto_increment(&self, cursors: &IsarCursors) -> Result<()> {
         let mut cursor = cursors.get_cursor(self.db)?;
-        if let Some((key, _)) = cursor.move_to_last()? {
+        let entry = if self.descending_ids {
+            cursor.move_to_first()?
+        } else {
+            cursor.move_to_last()?
+        };
+        if let Some((key, _)) = entry {
             let id = key.deref().to_id();
             self.update_auto_increment(id);
         }
@@ -85,18 +258,79 @@ impl IsarCollection {
     }
 
     pub(crate) fn update_auto_increment(&self, id: i64) {
-        if id > self.auto_increment.get() {
+        let current = self.auto_increment.get();
+        if (!self.descending_ids && id > current) || (self.descending_ids && id < current) {
             self.auto_increment.set(id);
         }
     }
 
+    fn reserved_ids_key(&self) -> IndexKey {
+        IndexKey::from_bytes(format!("_isar_reserved_ids_{}", self.name).into_bytes())
+    }
+
+    /// Makes sure ids reserved in a previous session are not generated again.
+    pub(crate) fn init_reserved_ids(&self, cursors: &IsarCursors, counter_db: Db) -> Result<()> {
+        let mut cursor = cursors.get_cursor(counter_db)?;
+        if let Some((_, bytes)) = cursor.move_to(&self.reserved_ids_key())? {
+            self.update_auto_increment(LittleEndian::read_i64(bytes));
+        }
+        Ok(())
+    }
+
+    /// Advances the auto increment by `count` and returns the first id of the skipped block. The
+    /// end of the block is stored in `counter_db` so the ids stay reserved after reopening.
+    pub(crate) fn reserve_ids(
+        &self,
+        cursors: &IsarCursors,
+        counter_db: Db,
+        count: u32,
+    ) -> Result<i64> {
+        if count == 0 {
+            illegal_arg("At least one id needs to be reserved.")?;
+        }
+        let last = self.auto_increment.get();
+        let end = if self.descending_ids {
+            // i64::MIN is reserved for "no id"
+            last.checked_sub(count as i64).filter(|end| *end > i64::MIN)
+        } else {
+            last.checked_add(count as i64)
+        };
+        let end = if let Some(end) = end {
+            end
+        } else {
+            return Err(IsarError::AutoIncrementOverflow {});
+        };
+        cursors
+            .get_cursor(counter_db)?
+            .put(&self.reserved_ids_key(), &end.to_le_bytes())?;
+        self.auto_increment.set(end);
+        if self.descending_ids {
+            Ok(end)
+        } else {
+            Ok(last + 1)
+        }
+    }
+
+    /// Moves the auto increment past the largest id (or the smallest one for descending ids) in
+    /// the collection so generated ids cannot collide with imported ones. Returns the new value;
+    /// the next generated id follows it.
+    pub fn repair_id_counter(&self, txn: &mut IsarTxn) -> Result<i64> {
+        txn.read(self.instance_id, |cursors| {
+            self.init_auto_increment(cursors)?;
+            Ok(self.auto_increment.get())
+        })
+    }
+
     pub fn auto_increment(&self, _: &mut IsarTxn) -> Result<i64> {
         self.auto_increment_internal()
     }
 
     pub(crate) fn auto_increment_internal(&self) -> Result<i64> {
         let last = self.auto_increment.get();
-        if last < i64::MAX {
+        if self.descending_ids && last > i64::MIN + 1 {
+            self.auto_increment.set(last - 1);
+            Ok(last - 1)
+        } else if !self.descending_ids && last < i64::MAX {
             self.auto_increment.set(last + 1);
             Ok(last + 1)
         } else {
@@ -104,6 +338,11 @@ impl IsarCollection {
         }
     }
 
+    /// Whether inserts, updates and deletes are recorded in the operation log of the instance.
+    pub fn has_oplog(&self) -> bool {
+        self.oplog_db.is_some()
+    }
+
     pub fn get<'txn>(&self, txn: &'txn mut IsarTxn, id: i64) -> Result<Option<IsarObject<'txn>>> {
         txn.read(self.instance_id, |cursors| {
             let mut cursor = cursors.get_cursor(self.db)?;
@@ -114,6 +353,49 @@ impl IsarCollection {
         })
     }
 
+    /// Returns the objects with the given ids in the order of `ids` and `None` for missing ids.
+    /// Duplicate ids are allowed. All objects are read with a single cursor that visits the ids in
+    /// ascending order, which is much cheaper than calling [IsarCollection::get] for every id.
+    pub fn get_many<'txn>(
+        &self,
+        txn: &'txn mut IsarTxn,
+        ids: &[i64],
+    ) -> Result<Vec<Option<IsarObject<'txn>>>> {
+        let mut order = (0..ids.len()).collect_vec();
+        order.sort_unstable_by_key(|i| ids[*i]);
+        txn.read(self.instance_id, |cursors| {
+            let mut cursor = cursors.get_cursor(self.db)?;
+            let mut objects = vec![None; ids.len()];
+            for i in order {
+                objects[i] = cursor
+                    .move_to(&ids[i])?
+                    .map(|(_, v)| IsarObject::from_bytes(v));
+            }
+            Ok(objects)
+        })
+    }
+
+    /// Returns a copy of the serialized object. Objects of collections with identical schemas can
+    /// be transferred with [IsarCollection::put_raw] without decoding them.
+    pub fn get_raw(&self, txn: &mut IsarTxn, id: i64) -> Result<Option<Vec<u8>>> {
+        Ok(self.get(txn, id)?.map(|object| object.as_bytes().to_vec()))
+    }
+
+    /// Like [IsarCollection::get] but only the properties at `property_indices` can be read.
+    pub fn get_projected<'txn>(
+        &self,
+        txn: &'txn mut IsarTxn,
+        id: i64,
+        property_indices: &[usize],
+    ) -> Result<Option<ProjectedObject<'txn>>> {
+        if let Some(object) = self.get(txn, id)? {
+            let projected = ProjectedObject::new(object, &self.properties, property_indices)?;
+            Ok(Some(projected))
+        } else {
+            Ok(None)
+        }
+    }
+
     pub(crate) fn get_index_by_id(&self, index_id: u64) -> Result<&IsarIndex> {
         self.indexes
             .iter()
@@ -121,13 +403,173 @@ impl IsarCollection {
             .ok_or(IsarError::UnknownIndex {})
     }
 
+    /// Returns the index if it can be used to look up objects.
+    pub(crate) fn get_usable_index(&self, index_id: u64) -> Result<&IsarIndex> {
+        if self.indexes_outdated.get() {
+            return Err(IsarError::IndexesOutdated {});
+        }
+        self.get_index_by_id(index_id)
+    }
+
+    /// Whether writes skipped index maintenance since the indexes were last rebuilt.
+    pub(crate) fn indexes_outdated(&self) -> bool {
+        self.indexes_outdated.get()
+    }
+
+    /// Disables index maintenance to speed up bulk loads. Until indexing is enabled again,
+    /// written and deleted objects are not added to or removed from the indexes, unique indexes
+    /// are not enforced and lookups by index fail with `IsarError::IndexesOutdated`. Enabling
+    /// indexing rebuilds all indexes in `txn` if objects were written in the meantime.
+    ///
+    /// The setting is not persisted. Enable indexing again before the instance is closed,
+    /// otherwise the indexes stay outdated.
+    pub fn set_indexing_enabled(&self, txn: &mut IsarTxn, enabled: bool) -> Result<()> {
+        self.indexing_enabled.set(enabled);
+        if enabled && self.indexes_outdated.get() {
+            txn.write(self.instance_id, |cursors, change_set| {
+                if let Some(change_set) = change_set {
+                    change_set.mark_changed();
+                }
+                for index in &self.indexes {
+                    index.clear(cursors)?;
+                }
+                let index_ids = self.indexes.iter().map(|i| i.id).collect_vec();
+                self.fill_indexes(&index_ids, cursors)
+            })?;
+            self.indexes_outdated.set(false);
+        }
+        Ok(())
+    }
+
+    pub fn get_index_stats(&self, txn: &mut IsarTxn, index_id: u64) -> Result<IndexStats> {
+        let index = self.get_index_by_id(index_id)?;
+        txn.read(self.instance_id, |cursors| index.get_stats(cursors))
+    }
+
+    /// Clears the index and repopulates it from the objects of this collection. Returns the
+    /// number of index entries afterwards.
+    pub fn rebuild_index(&self, txn: &mut IsarTxn, index_id: u64) -> Result<u32> {
+        let index = self.get_index_by_id(index_id)?;
+        txn.write(self.instance_id, |cursors, change_set| {
+            if let Some(change_set) = change_set {
+                change_set.mark_changed();
+            }
+            index.clear(cursors)?;
+            self.fill_indexes(&[index_id], cursors)?;
+            Ok(index.get_entries(cursors)? as u32)
+        })
+    }
+
+    /// Renumbers all objects to the contiguous range `1..=N` while keeping their order and
+    /// rebuilds the indexes. Links to or from this collection are only remapped with `force`,
+    /// otherwise the collection must not have any links.
+    pub fn reindex_ids(&self, txn: &mut IsarTxn, force: bool) -> Result<IdRemap> {
+        txn.write(self.instance_id, |cursors, change_set| {
+            let mut links = vec![];
+            for link in &self.links {
+                links.push((link, link.get_all(cursors)?));
+            }
+            for link in self.backlinks.iter().filter(|l| !l.is_self_link()) {
+                links.push((link, link.get_all(cursors)?));
+            }
+            if !force && links.iter().any(|(_, entries)| !entries.is_empty()) {
+                illegal_arg("Collection has links. Use force to remap them.")?;
+            }
+
+            let mut objects = vec![];
+            let mut cursor = cursors.get_cursor(self.db)?;
+            cursor.iter_all(false, true, |_, id_bytes, bytes| {
+                objects.push((id_bytes.to_id(), bytes.to_vec()));
+                Ok(true)
+            })?;
+            let mut versions = IntMap::new();
+            if let Some(version_db) = self.version_db {
+                let mut version_cursor = cursors.get_cursor(version_db)?;
+                for (i, (id, _)) in objects.iter().enumerate() {
+                    if let Some((_, version)) = version_cursor.move_to(id)? {
+                        versions.insert(i as u64, version.to_vec());
+                    }
+                }
+            }
+
+            let remap: IdRemap = objects
+                .iter()
+                .enumerate()
+                .map(|(i, (id, _))| (*id, i as i64 + 1))
+                .collect();
+            let remapped = |id: i64| {
+                let i = remap.binary_search_by_key(&id, |(old, _)| *old).unwrap();
+                remap[i].1
+            };
+
+            for index in &self.indexes {
+                index.clear(cursors)?;
+            }
+            for (link, _) in &links {
+                link.clear(cursors)?;
+            }
+            cursors.clear_db(self.db)?;
+            if let Some(version_db) = self.version_db {
+                cursors.clear_db(version_db)?;
+            }
+
+            let mut change_set = change_set;
+            let mut cursor = cursors.get_cursor(self.db)?;
+            for (i, (id, bytes)) in objects.iter().enumerate() {
+                let new_id = i as i64 + 1;
+                cursor.put(&new_id, bytes)?;
+                if let (Some(version_db), Some(version)) = (self.version_db, versions.get(i as u64))
+                {
+                    cursors.get_cursor(version_db)?.put(&new_id, version)?;
+                }
+                if *id != new_id {
+                    if let Some(oplog_db) = self.oplog_db {
+                        oplog::append(cursors, oplog_db, self.id, *id, OpType::Delete)?;
+                        oplog::append(cursors, oplog_db, self.id, new_id, OpType::Insert)?;
+                    }
+                    if let Some(change_set) = change_set.as_deref_mut() {
+                        change_set.register_all_ids(self.id, &[*id]);
+                        change_set.register_change(self.id, new_id, IsarObject::from_bytes(bytes));
+                    }
+                }
+            }
+            self.auto_increment
+                .set(Self::initial_auto_increment(self.descending_ids));
+            if !objects.is_empty() {
+                self.update_auto_increment(if self.descending_ids {
+                    1
+                } else {
+                    objects.len() as i64
+                });
+            }
+
+            let index_ids = self.indexes.iter().map(|i| i.id).collect_vec();
+            self.fill_indexes(&index_ids, cursors)?;
+            for (link, entries) in &links {
+                let self_link = link.is_self_link();
+                for (source_id, target_id) in entries {
+                    let target_id = if self_link {
+                        remapped(*target_id)
+                    } else {
+                        *target_id
+                    };
+                    link.create(cursors, remapped(*source_id), target_id)?;
+                }
+            }
+            if let Some(change_set) = change_set {
+                change_set.mark_changed();
+            }
+            Ok(remap)
+        })
+    }
+
     pub fn get_by_index<'txn>(
         &self,
         txn: &'txn mut IsarTxn,
         index_id: u64,
         key: &IndexKey,
     ) -> Result<Option<(i64, IsarObject<'txn>)>> {
-        let index = self.get_index_by_id(index_id)?;
+        let index = self.get_usable_index(index_id)?;
         txn.read(self.instance_id, |cursors| {
             if let Some(id) = index.get_id(cursors, key)? {
                 let mut cursor = cursors.get_cursor(self.db)?;
@@ -142,19 +584,124 @@ impl IsarCollection {
         })
     }
 
+    /// Counts the objects matching `key` without iterating them.
+    pub fn count_by_index(&self, txn: &mut IsarTxn, index_id: u64, key: &IndexKey) -> Result<u64> {
+        let index = self.get_usable_index(index_id)?;
+        txn.read(self.instance_id, |cursors| index.count_key(cursors, key))
+    }
+
     pub fn put(&self, txn: &mut IsarTxn, id: Option<i64>, object: IsarObject) -> Result<i64> {
         txn.write(self.instance_id, |cursors, change_set| {
             self.put_internal(cursors, change_set, id, object)
         })
     }
 
+    /// Stores already serialized object bytes. The bytes are validated against the schema of
+    /// this collection but not re-encoded.
+    pub fn put_raw(&self, txn: &mut IsarTxn, id: Option<i64>, bytes: &[u8]) -> Result<i64> {
+        if !IsarObject::is_valid(bytes, &self.properties, &self.embedded_properties) {
+            illegal_arg("Object does not match the schema.")?;
+        }
+        self.put(txn, id, IsarObject::from_bytes(bytes))
+    }
+
+    /// Like [IsarCollection::put] but `conflict` decides what happens if an object with the id
+    /// already exists. Returns `None` if the object was skipped.
+    pub fn put_with_conflict(
+        &self,
+        txn: &mut IsarTxn,
+        id: Option<i64>,
+        object: IsarObject,
+        conflict: InsertConflict,
+    ) -> Result<Option<i64>> {
+        txn.write(self.instance_id, |cursors, change_set| {
+            let mut result = ImportResult::default();
+            self.put_with_conflict_internal(cursors, change_set, id, object, conflict, &mut result)
+        })
+    }
+
+    /// Puts all objects like [IsarCollection::put_with_conflict]. Returns the id of every object,
+    /// `None` for the skipped ones, and how many objects were inserted, skipped or overwritten.
+    pub fn put_all_with_conflict(
+        &self,
+        txn: &mut IsarTxn,
+        objects: &[(Option<i64>, IsarObject)],
+        conflict: InsertConflict,
+    ) -> Result<(Vec<Option<i64>>, ImportResult)> {
+        txn.write(self.instance_id, |cursors, mut change_set| {
+            let mut result = ImportResult::default();
+            let ids = objects
+                .iter()
+                .map(|(id, object)| {
+                    self.put_with_conflict_internal(
+                        cursors,
+                        change_set.as_deref_mut(),
+                        *id,
+                        *object,
+                        conflict,
+                        &mut result,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok((ids, result))
+        })
+    }
+
+    fn put_with_conflict_internal(
+        &self,
+        cursors: &IsarCursors,
+        change_set: Option<&mut ChangeSet>,
+        id: Option<i64>,
+        object: IsarObject,
+        conflict: InsertConflict,
+        result: &mut ImportResult,
+    ) -> Result<Option<i64>> {
+        if !self.count_conflict(cursors, id, conflict, result)? {
+            return Ok(None);
+        }
+        let id = self.put_internal(cursors, change_set, id, object)?;
+        Ok(Some(id))
+    }
+
+    /// Adds the object with `id` to `result` and returns whether it should be written.
+    /// `InsertConflict::Abort` fails with `IsarError::UniqueViolated` if the id already exists.
+    fn count_conflict(
+        &self,
+        cursors: &IsarCursors,
+        id: Option<i64>,
+        conflict: InsertConflict,
+        result: &mut ImportResult,
+    ) -> Result<bool> {
+        let existing = match id {
+            Some(id) if cursors.get_cursor(self.db)?.move_to(&id)?.is_some() => id,
+            _ => {
+                result.inserted += 1;
+                return Ok(true);
+            }
+        };
+        match conflict {
+            InsertConflict::Overwrite => {
+                result.overwritten += 1;
+                Ok(true)
+            }
+            InsertConflict::Ignore => {
+                result.skipped += 1;
+                Ok(false)
+            }
+            InsertConflict::Abort => Err(IsarError::UniqueViolated {
+                index_name: "id".to_string(),
+                key: existing.to_string(),
+            }),
+        }
+    }
+
     pub fn put_by_index(
         &self,
         txn: &mut IsarTxn,
         index_id: u64,
         object: IsarObject,
     ) -> Result<i64> {
-        let index = self.get_index_by_id(index_id)?;
+        let index = self.get_usable_index(index_id)?;
         if index.multi_entry {
             illegal_arg("Cannot put by a multi-entry index")?;
         }
@@ -167,7 +714,7 @@ impl IsarCollection {
         })
     }
 
-    fn put_internal(
+    pub(crate) fn put_internal(
         &self,
         cursors: &IsarCursors,
         mut change_set: Option<&mut ChangeSet>,
@@ -178,42 +725,172 @@ impl IsarCollection {
             illegal_arg("Object is bigger than 16MB")?;
         }
 
+        let patched = NonFinitePolicy::apply(&self.non_finite_policies, object)?;
+        let object = patched.as_deref().map_or(object, IsarObject::from_bytes);
+        let transformed =
+            StringTransform::apply(&self.string_transforms, &self.properties, object)?;
+        let object = transformed
+            .as_deref()
+            .map_or(object, IsarObject::from_bytes);
+        check_value_sizes(&self.value_size_limits, object)?;
+        check_vector_dimensions(&self.vector_dimensions, object)?;
+
+        let mut op = OpType::Insert;
+        let mut created_txn = None;
+        if let (Some(version_db), Some(id)) = (self.version_db, id) {
+            let mut version_cursor = cursors.get_cursor(version_db)?;
+            if let Some((_, bytes)) = version_cursor.move_to(&id)? {
+                created_txn = Some(RowMeta::from_bytes(bytes).created_txn);
+            }
+        }
         let id = if let Some(id) = id {
-            self.delete_internal(cursors, false, change_set.as_deref_mut(), id)?;
+            if self.delete_internal(cursors, false, change_set.as_deref_mut(), id)? {
+                op = OpType::Update;
+            }
             self.update_auto_increment(id);
             id
         } else {
             self.auto_increment_internal()?
         };
 
-        for index in &self.indexes {
-            index.create_for_object(cursors, id, object, |id| {
-                self.delete_internal(cursors, true, change_set.as_deref_mut(), id)?;
-                Ok(())
-            })?;
+        if self.indexing_enabled.get() {
+            for index in &self.indexes {
+                index.create_for_object(cursors, id, object, |id| {
+                    self.delete_internal(cursors, true, change_set.as_deref_mut(), id)?;
+                    Ok(())
+                })?;
+            }
+        } else if !self.indexes.is_empty() {
+            self.indexes_outdated.set(true);
         }
 
         let mut cursor = cursors.get_cursor(self.db)?;
         cursor.put(&id, object.as_bytes())?;
+        if let Some(version_db) = self.version_db {
+            let mut version_cursor = cursors.get_cursor(version_db)?;
+            let txn_id = cursors.txn_id();
+            let meta = RowMeta {
+                created_txn: created_txn.unwrap_or(txn_id),
+                modified_txn: txn_id,
+            };
+            version_cursor.put(&id, &meta.to_bytes())?;
+        }
+        if let Some(oplog_db) = self.oplog_db {
+            oplog::append(cursors, oplog_db, self.id, id, op)?;
+        }
         if let Some(change_set) = change_set {
             change_set.register_change(self.id, id, object);
         }
         Ok(id)
     }
 
+    fn get_row_meta_internal(&self, cursors: &IsarCursors, id: i64) -> Result<Option<RowMeta>> {
+        let version_db = if let Some(version_db) = self.version_db {
+            version_db
+        } else {
+            return illegal_arg("Collection is not versioned");
+        };
+
+        let mut cursor = cursors.get_cursor(self.db)?;
+        if cursor.move_to(&id)?.is_none() {
+            return Ok(None);
+        }
+
+        let mut version_cursor = cursors.get_cursor(version_db)?;
+        let meta = version_cursor.move_to(&id)?.map_or(
+            RowMeta {
+                created_txn: 0,
+                modified_txn: 0,
+            },
+            |(_, v)| RowMeta::from_bytes(v),
+        );
+        Ok(Some(meta))
+    }
+
+    fn get_version_internal(&self, cursors: &IsarCursors, id: i64) -> Result<Option<u64>> {
+        let meta = self.get_row_meta_internal(cursors, id)?;
+        Ok(meta.map(|m| m.modified_txn))
+    }
+
+    /// Returns the transactions that created and last modified the object with `id`. The
+    /// collection needs to be versioned.
+    pub fn get_row_meta(&self, txn: &mut IsarTxn, id: i64) -> Result<Option<RowMeta>> {
+        txn.read(self.instance_id, |cursors| {
+            self.get_row_meta_internal(cursors, id)
+        })
+    }
+
+    pub fn get_version(&self, txn: &mut IsarTxn, id: i64) -> Result<Option<u64>> {
+        txn.read(self.instance_id, |cursors| {
+            self.get_version_internal(cursors, id)
+        })
+    }
+
+    pub fn put_if_version(
+        &self,
+        txn: &mut IsarTxn,
+        id: i64,
+        expected_version: u64,
+        object: IsarObject,
+    ) -> Result<bool> {
+        txn.write(self.instance_id, |cursors, change_set| {
+            let version = self.get_version_internal(cursors, id)?;
+            if version == Some(expected_version) {
+                self.put_internal(cursors, change_set, Some(id), object)?;
+                Ok(true)
+            } else {
+                Ok(false)
+            }
+        })
+    }
+
     pub fn delete(&self, txn: &mut IsarTxn, id: i64) -> Result<bool> {
         txn.write(self.instance_id, |cursors, change_set| {
             self.delete_internal(cursors, true, change_set, id)
         })
     }
 
+    /// Marks the object with `id` as modified without changing it. Watchers are notified, the
+    /// oplog records an update and the modified transaction of versioned collections is bumped.
+    /// Returns `false` if the object does not exist.
+    pub fn touch(&self, txn: &mut IsarTxn, id: i64) -> Result<bool> {
+        txn.write(self.instance_id, |cursors, change_set| {
+            let mut cursor = cursors.get_cursor(self.db)?;
+            let object = if let Some((_, bytes)) = cursor.move_to(&id)? {
+                bytes.to_vec()
+            } else {
+                return Ok(false);
+            };
+
+            if let Some(version_db) = self.version_db {
+                let mut version_cursor = cursors.get_cursor(version_db)?;
+                let txn_id = cursors.txn_id();
+                let created_txn = version_cursor
+                    .move_to(&id)?
+                    .map_or(txn_id, |(_, bytes)| RowMeta::from_bytes(bytes).created_txn);
+                let meta = RowMeta {
+                    created_txn,
+                    modified_txn: txn_id,
+                };
+                version_cursor.put(&id, &meta.to_bytes())?;
+            }
+            if let Some(oplog_db) = self.oplog_db {
+                oplog::append(cursors, oplog_db, self.id, id, OpType::Update)?;
+            }
+            if let Some(change_set) = change_set {
+                change_set.register_change(self.id, id, IsarObject::from_bytes(&object));
+            }
+            Ok(true)
+        })
+    }
+
     pub fn delete_by_index(
         &self,
         txn: &mut IsarTxn,
         index_id: u64,
         key: &IndexKey,
     ) -> Result<bool> {
-        let index = self.get_index_by_id(index_id)?;
+        let index = self.get_usable_index(index_id)?;
         txn.write(self.instance_id, |cursors, change_set| {
             if let Some(id) = index.get_id(cursors, key)? {
                 self.delete_internal(cursors, true, change_set, id)?;
@@ -224,7 +901,7 @@ impl IsarCollection {
         })
     }
 
-    fn delete_internal(
+    pub(crate) fn delete_internal(
         &self,
         cursors: &IsarCursors,
         delete_links: bool,
@@ -234,20 +911,7 @@ impl IsarCollection {
         let mut cursor = cursors.get_cursor(self.db)?;
         if let Some((_, object)) = cursor.move_to(&id)? {
             let object = IsarObject::from_bytes(&object);
-            for index in &self.indexes {
-                index.delete_for_object(cursors, id, object)?;
-            }
-            if delete_links {
-                for link in &self.links {
-                    link.delete_all_for_object(cursors, id)?;
-                }
-                for link in &self.backlinks {
-                    link.delete_all_for_object(cursors, id)?;
-                }
-            }
-            if let Some(change_set) = change_set {
-                change_set.register_change(self.id, id, object);
-            }
+            self.delete_object(cursors, delete_links, change_set, id, object)?;
             cursor.delete_current()?;
             Ok(true)
         } else {
@@ -255,9 +919,134 @@ impl IsarCollection {
         }
     }
 
-    pub(crate) fn get_link_backlink(&self, link_id: u64) -> Result<&IsarLink> {
-        if let Some(link) = self.links.iter().find(|l| l.id == link_id) {
-            Ok(link)
+    /// Removes the index entries, links, versions and change events of an object. The caller
+    /// deletes the object itself.
+    fn delete_object(
+        &self,
+        cursors: &IsarCursors,
+        delete_links: bool,
+        change_set: Option<&mut ChangeSet>,
+        id: i64,
+        object: IsarObject,
+    ) -> Result<()> {
+        if self.indexing_enabled.get() {
+            for index in &self.indexes {
+                index.delete_for_object(cursors, id, object)?;
+            }
+        } else if !self.indexes.is_empty() {
+            self.indexes_outdated.set(true);
+        }
+        if delete_links {
+            for link in &self.links {
+                link.delete_all_for_object(cursors, id)?;
+            }
+            for link in &self.backlinks {
+                link.delete_all_for_object(cursors, id)?;
+            }
+        }
+        if let Some(version_db) = self.version_db {
+            let mut version_cursor = cursors.get_cursor(version_db)?;
+            if version_cursor.move_to(&id)?.is_some() {
+                version_cursor.delete_current()?;
+            }
+        }
+        // objects replaced by a put are logged as updates by put_internal
+        if let (Some(oplog_db), true) = (self.oplog_db, delete_links) {
+            oplog::append(cursors, oplog_db, self.id, id, OpType::Delete)?;
+        }
+        if let Some(change_set) = change_set {
+            change_set.register_change(self.id, id, object);
+        }
+        Ok(())
+    }
+
+    /// Deletes up to `limit` objects with ids between `lower` and `upper` that match `filter`.
+    /// Objects are deleted during the scan without collecting their ids first.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn delete_between(
+        &self,
+        cursors: &IsarCursors,
+        mut change_set: Option<&mut ChangeSet>,
+        lower: i64,
+        upper: i64,
+        ascending: bool,
+        filter: &Filter,
+        limit: usize,
+    ) -> Result<u32> {
+        let mut count = 0;
+        if limit == 0 {
+            return Ok(count);
+        }
+        let mut cursor = cursors.get_cursor(self.db)?;
+        cursor.iter_between(
+            &lower,
+            &upper,
+            false,
+            false,
+            ascending,
+            |cursor, id_bytes, bytes| {
+                let id = id_bytes.to_id();
+                let object = IsarObject::from_bytes(bytes);
+                if filter.evaluate(id, object, Some(cursors))? {
+                    self.delete_object(cursors, true, change_set.as_deref_mut(), id, object)?;
+                    // the cursor moves to the next entry
+                    cursor.delete_current()?;
+                    count += 1;
+                }
+                Ok((count as usize) < limit)
+            },
+        )?;
+        Ok(count)
+    }
+
+    /// Deletes all objects whose TTL property is less than or equal to `now`. Objects with a
+    /// null TTL never expire. A single value index on the TTL property is used if it exists.
+    pub fn purge_expired(&self, txn: &mut IsarTxn, now: i64) -> Result<u32> {
+        let ttl_property = if let Some(ttl_property) = &self.ttl_property {
+            ttl_property
+        } else {
+            return illegal_arg("Collection has no TTL property");
+        };
+
+        let ttl_index = self.indexes.iter().find(|index| {
+            !self.indexes_outdated.get()
+                && index.properties.len() == 1
+                && index.properties[0].property == *ttl_property
+                && index.properties[0].index_type == IndexType::Value
+        });
+
+        txn.write(self.instance_id, |cursors, mut change_set| {
+            let mut expired_ids = vec![];
+            if let Some(ttl_index) = ttl_index {
+                let mut lower_key = IndexKey::new();
+                lower_key.add_long(IsarObject::NULL_LONG + 1);
+                let mut upper_key = IndexKey::new();
+                upper_key.add_long(now);
+                ttl_index.iter_between(cursors, &lower_key, &upper_key, false, true, |id| {
+                    expired_ids.push(id);
+                    Ok(true)
+                })?;
+            } else {
+                let mut cursor = cursors.get_cursor(self.db)?;
+                cursor.iter_all(false, true, |_, id_bytes, object| {
+                    let expires_at = IsarObject::from_bytes(object).read_long(ttl_property.offset);
+                    if expires_at != IsarObject::NULL_LONG && expires_at <= now {
+                        expired_ids.push(id_bytes.to_id());
+                    }
+                    Ok(true)
+                })?;
+            }
+
+            for id in &expired_ids {
+                self.delete_internal(cursors, true, change_set.as_deref_mut(), *id)?;
+            }
+            Ok(expired_ids.len() as u32)
+        })
+    }
+
+    pub(crate) fn get_link_backlink(&self, link_id: u64) -> Result<&IsarLink> {
+        if let Some(link) = self.links.iter().find(|l| l.id == link_id) {
+            Ok(link)
         } else if let Some(link) = self.backlinks.iter().find(|l| l.id == link_id) {
             Ok(link)
         } else {
@@ -265,53 +1054,257 @@ impl IsarCollection {
         }
     }
 
+    pub(crate) fn get_link_by_name(&self, name: &str) -> Result<&IsarLink> {
+        if let Some(link) = self.links.iter().find(|l| l.name == name) {
+            Ok(link)
+        } else {
+            illegal_arg("IsarLink does not exist")
+        }
+    }
+
     pub fn link(&self, txn: &mut IsarTxn, link_id: u64, id: i64, target_id: i64) -> Result<bool> {
         let link = self.get_link_backlink(link_id)?;
-        txn.write(self.instance_id, |cursors, _| {
+        txn.write(self.instance_id, |cursors, change_set| {
+            if let Some(change_set) = change_set {
+                change_set.mark_changed();
+            }
             link.create(cursors, id, target_id)
         })
     }
 
     pub fn unlink(&self, txn: &mut IsarTxn, link_id: u64, id: i64, target_id: i64) -> Result<bool> {
         let link = self.get_link_backlink(link_id)?;
-        txn.write(self.instance_id, |cursors, _| {
+        txn.write(self.instance_id, |cursors, change_set| {
+            if let Some(change_set) = change_set {
+                change_set.mark_changed();
+            }
             link.delete(cursors, id, target_id)
         })
     }
 
     pub fn unlink_all(&self, txn: &mut IsarTxn, link_id: u64, id: i64) -> Result<()> {
         let link = self.get_link_backlink(link_id)?;
-        txn.write(self.instance_id, |cursors, _| {
+        txn.write(self.instance_id, |cursors, change_set| {
+            if let Some(change_set) = change_set {
+                change_set.mark_changed();
+            }
             link.delete_all_for_object(cursors, id)
         })
     }
 
     pub fn clear(&self, txn: &mut IsarTxn) -> Result<()> {
         txn.write(self.instance_id, |cursors, change_set| {
-            for index in &self.indexes {
-                index.clear(cursors)?;
-            }
-            for link in &self.links {
-                link.clear(cursors)?;
-            }
-            for link in &self.backlinks {
-                link.clear(cursors)?;
-            }
-            cursors.clear_db(self.db)?;
-            self.auto_increment.set(0);
+            self.clear_internal(cursors, change_set)
+        })
+    }
 
-            if let Some(change_set) = change_set {
-                change_set.register_all(self.id);
+    pub(crate) fn clear_internal(
+        &self,
+        cursors: &IsarCursors,
+        change_set: Option<&mut ChangeSet>,
+    ) -> Result<()> {
+        let mut change_set = change_set;
+        if let Some(change_set) = change_set.as_deref_mut() {
+            if change_set.has_ids_watchers(self.id) {
+                let mut ids = vec![];
+                let mut cursor = cursors.get_cursor(self.db)?;
+                cursor.iter_all(false, true, |_, id_bytes, _| {
+                    ids.push(id_bytes.to_id());
+                    Ok(true)
+                })?;
+                change_set.register_all_ids(self.id, &ids);
             }
+        }
 
-            Ok(())
-        })
+        for index in &self.indexes {
+            index.clear(cursors)?;
+        }
+        for link in &self.links {
+            link.clear(cursors)?;
+        }
+        for link in &self.backlinks {
+            link.clear(cursors)?;
+        }
+        cursors.clear_db(self.db)?;
+        if let Some(version_db) = self.version_db {
+            cursors.clear_db(version_db)?;
+        }
+        self.auto_increment
+            .set(Self::initial_auto_increment(self.descending_ids));
+        self.indexes_outdated.set(false);
+        if let Some(oplog_db) = self.oplog_db {
+            oplog::append(cursors, oplog_db, self.id, 0, OpType::Clear)?;
+        }
+
+        if let Some(change_set) = change_set {
+            change_set.register_all(self.id);
+        }
+
+        Ok(())
     }
 
     pub fn count(&self, txn: &mut IsarTxn) -> Result<u64> {
         txn.read(self.instance_id, |cursors| Ok(cursors.db_stat(self.db)?.0))
     }
 
+    pub fn is_empty(&self, txn: &mut IsarTxn) -> Result<bool> {
+        txn.read(self.instance_id, |cursors| {
+            let mut cursor = cursors.get_cursor(self.db)?;
+            Ok(cursor.move_to_first()?.is_none())
+        })
+    }
+
+    /// Returns the smallest and largest id of the collection or `None` if it is empty.
+    pub fn id_range(&self, txn: &mut IsarTxn) -> Result<Option<(i64, i64)>> {
+        txn.read(self.instance_id, |cursors| {
+            let mut cursor = cursors.get_cursor(self.db)?;
+            let first = cursor.move_to_first()?;
+            let last = cursor.move_to_last()?;
+            if let (Some((first, _)), Some((last, _))) = (first, last) {
+                Ok(Some((first.to_id(), last.to_id())))
+            } else {
+                Ok(None)
+            }
+        })
+    }
+
+    /// Combines the hashes of all objects including their ids. The result does not depend on the
+    /// order in which the objects were written so it can be used to compare collections.
+    pub fn hash(&self, txn: &mut IsarTxn) -> Result<u64> {
+        txn.read(self.instance_id, |cursors| {
+            let mut hash = 0;
+            let mut cursor = cursors.get_cursor(self.db)?;
+            cursor.iter_all(false, true, |_, id_bytes, object| {
+                hash ^= xxh3_64_with_seed(object, id_bytes.to_id() as u64);
+                Ok(true)
+            })?;
+            Ok(hash)
+        })
+    }
+
+    /// Compares the objects of this collection with `other`, usually the same collection of a
+    /// different instance. Objects are compared by their raw bytes so both collections need the
+    /// same schema.
+    pub fn diff(
+        &self,
+        txn: &mut IsarTxn,
+        other: &IsarCollection,
+        other_txn: &mut IsarTxn,
+    ) -> Result<CollectionDiff> {
+        let hashes = self.object_hashes(txn)?;
+        let other_hashes = other.object_hashes(other_txn)?;
+        Ok(CollectionDiff::from_sorted_hashes(&hashes, &other_hashes))
+    }
+
+    /// Returns the ids of the `k` objects whose vectors in the FloatList `property` are nearest to
+    /// `query` together with their distances, nearest first. Every object is compared so the
+    /// cost grows linearly with the size of the collection. Objects without a vector or with a
+    /// vector of a different length are skipped.
+    pub fn query_knn(
+        &self,
+        txn: &mut IsarTxn,
+        property: &Property,
+        query: &[f32],
+        k: u32,
+        metric: Metric,
+    ) -> Result<Vec<(i64, f32)>> {
+        if property.data_type != DataType::FloatList {
+            illegal_arg("Only FloatList properties can be searched for nearest neighbors.")?;
+        }
+        let dimension = self
+            .vector_dimensions
+            .iter()
+            .find(|(p, _)| p == property)
+            .map(|(_, dimension)| *dimension);
+        if let Some(expected) = dimension.filter(|d| *d != query.len()) {
+            return Err(IsarError::VectorDimensionMismatch {
+                property: property.name.clone(),
+                len: query.len(),
+                expected,
+            });
+        }
+
+        txn.read(self.instance_id, |cursors| {
+            let mut neighbors = NearestNeighbors::new(k as usize);
+            let mut cursor = cursors.get_cursor(self.db)?;
+            cursor.iter_all(false, true, |_, id_bytes, bytes| {
+                let object = IsarObject::from_bytes(bytes);
+                if let Some(vector) = object.read_float_list(property.offset) {
+                    if vector.len() == query.len() {
+                        neighbors.add(id_bytes.to_id(), metric.distance(query, &vector));
+                    }
+                }
+                Ok(true)
+            })?;
+            Ok(neighbors.into_sorted())
+        })
+    }
+
+    /// Returns the distinct values of `property`, at most `limit` of them. If the property has
+    /// its own value index, the values are read in index order and duplicates are skipped in
+    /// the index. Otherwise all objects are scanned and the values are returned in id order.
+    pub fn query_distinct_values(
+        &self,
+        txn: &mut IsarTxn,
+        property: &Property,
+        limit: Option<u32>,
+    ) -> Result<Vec<Value>> {
+        let limit = limit.map_or(usize::MAX, |l| l as usize);
+        let index = if self.indexes_outdated.get() {
+            None
+        } else {
+            self.indexes.iter().find(|i| i.has_distinct_keys(property))
+        };
+        let read_value = |object: IsarObject| {
+            let properties = std::slice::from_ref(property);
+            JsonEncodeDecode::encode(properties, &self.embedded_properties, object, true)
+                .remove(&property.name)
+                .unwrap_or(Value::Null)
+        };
+
+        txn.read(self.instance_id, |cursors| {
+            let mut values = vec![];
+            if limit == 0 {
+                return Ok(values);
+            }
+            let mut cursor = cursors.get_cursor(self.db)?;
+            if let Some(index) = index {
+                index.iter_distinct(cursors, |id| {
+                    let (_, bytes) = cursor.move_to(&id)?.ok_or(IsarError::DbCorrupted {
+                        message: "Invalid index entry".to_string(),
+                    })?;
+                    values.push(read_value(IsarObject::from_bytes(bytes)));
+                    Ok(values.len() < limit)
+                })?;
+            } else {
+                let mut seen = HashSet::new();
+                cursor.iter_all(false, true, |_, _, bytes| {
+                    let value = read_value(IsarObject::from_bytes(bytes));
+                    if seen.insert(value.to_string()) {
+                        values.push(value);
+                    }
+                    Ok(values.len() < limit)
+                })?;
+            }
+            Ok(values)
+        })
+    }
+
+    /// Returns the `(id, object hash)` pairs of all objects sorted by id. A peer sends these to
+    /// [crate::instance::IsarInstance::export_delta] to receive only what it is missing.
+    pub fn object_hashes(&self, txn: &mut IsarTxn) -> Result<Vec<(i64, u64)>> {
+        txn.read(self.instance_id, |cursors| {
+            let mut hashes = vec![];
+            let mut cursor = cursors.get_cursor(self.db)?;
+            cursor.iter_all(false, true, |_, id_bytes, object| {
+                let id = id_bytes.to_id();
+                hashes.push((id, xxh3_64_with_seed(object, id as u64)));
+                Ok(true)
+            })?;
+            Ok(hashes)
+        })
+    }
+
     pub fn get_size(
         &self,
         txn: &mut IsarTxn,
@@ -337,37 +1330,285 @@ impl IsarCollection {
         })
     }
 
+    /// Reads all pages of the collection and optionally its indexes so the first queries after
+    /// startup do not wait for the disk. Returns the number of touched bytes which matches
+    /// [IsarCollection::get_size] without links. Fails with `IsarError::Cancelled` once
+    /// `cancel_token` is set.
+    pub fn warm(
+        &self,
+        txn: &mut IsarTxn,
+        include_indexes: bool,
+        cancel_token: Option<&AtomicBool>,
+    ) -> Result<u64> {
+        txn.read(self.instance_id, |cursors| {
+            let mut size = cursors.warm_db(self.db, cancel_token)?;
+            if include_indexes {
+                for index in &self.indexes {
+                    size += index.warm(cursors, cancel_token)?;
+                }
+            }
+            Ok(size)
+        })
+    }
+
+    /// Rewrites the objects and index entries of this collection in key order so their pages are
+    /// densely filled again, for example after many objects were deleted. Returns how many bytes
+    /// fewer the pages of the collection use afterwards.
+    ///
+    /// This does not compact the file. Pages released by the rewrite can only be reused by later
+    /// transactions, so the file keeps its size, or even grows, until the instance is compacted.
+    pub fn defragment(&self, txn: &mut IsarTxn) -> Result<u64> {
+        txn.write(self.instance_id, |cursors, change_set| {
+            if let Some(change_set) = change_set {
+                change_set.mark_changed();
+            }
+            let size = |cursors: &IsarCursors| -> Result<u64> {
+                let mut size = cursors.db_stat(self.db)?.1;
+                for index in &self.indexes {
+                    size += index.get_size(cursors)?;
+                }
+                Ok(size)
+            };
+            let size_before = size(cursors)?;
+
+            let mut objects = vec![];
+            let mut cursor = cursors.get_cursor(self.db)?;
+            cursor.iter_all(false, true, |_, id_bytes, bytes| {
+                objects.push((id_bytes.to_id(), bytes.to_vec()));
+                Ok(true)
+            })?;
+
+            for index in &self.indexes {
+                index.clear(cursors)?;
+            }
+            cursors.clear_db(self.db)?;
+            let mut cursor = cursors.get_cursor(self.db)?;
+            for (id, bytes) in &objects {
+                cursor.put(id, bytes)?;
+            }
+            let index_ids = self.indexes.iter().map(|i| i.id).collect_vec();
+            self.fill_indexes(&index_ids, cursors)?;
+
+            Ok(size_before.saturating_sub(size(cursors)?))
+        })
+    }
+
+    /// Streams the objects matching `query`, or all objects, to `serializer` as a sequence of
+    /// JSON objects in the format read by [IsarCollection::import_json_reader]. Every property is
+    /// written and null values are written as `null`. Infinite floating point values cannot be
+    /// represented in JSON and are exported as `null`.
+    pub fn export_json<S: Serializer>(
+        &self,
+        txn: &mut IsarTxn,
+        id_name: Option<&str>,
+        query: Option<&Query>,
+        serializer: S,
+    ) -> Result<S::Ok> {
+        let serialize_error = |e: S::Error| IsarError::IoError {
+            message: e.to_string(),
+        };
+        let all;
+        let query = if let Some(query) = query {
+            query
+        } else {
+            all = self.new_query_builder().build()?;
+            &all
+        };
+
+        let mut seq = serializer.serialize_seq(None).map_err(serialize_error)?;
+        let mut result = Ok(());
+        query.find_while(txn, |id, object| {
+            let mut json =
+                JsonEncodeDecode::encode(&self.properties, &self.embedded_properties, object, true);
+            EnumMapping::encode_names(&self.enum_mappings, &mut json);
+            if let Some(id_name) = id_name {
+                json.insert(id_name.to_string(), Value::from(id));
+            }
+            result = seq.serialize_element(&json);
+            result.is_ok()
+        })?;
+        result.map_err(serialize_error)?;
+        seq.end().map_err(serialize_error)
+    }
+
     pub fn import_json(&self, txn: &mut IsarTxn, id_name: Option<&str>, json: Value) -> Result<()> {
+        let array = json.as_array().ok_or(IsarError::InvalidJson {})?;
+        self.import_json_values(txn, id_name, array)
+    }
+
+    pub(crate) fn import_json_values(
+        &self,
+        txn: &mut IsarTxn,
+        id_name: Option<&str>,
+        values: &[Value],
+    ) -> Result<()> {
         txn.write(self.instance_id, |cursors, mut change_set| {
-            let array = json.as_array().ok_or(IsarError::InvalidJson {})?;
             let mut ob_result_cache = None;
-            for value in array {
-                let id = if let Some(id_name) = id_name {
-                    if let Some(id) = value.get(id_name) {
-                        let id = id.as_i64().ok_or(IsarError::InvalidJson {})?;
-                        Some(id)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                };
-
-                let mut ob = ObjectBuilder::new(&self.properties, ob_result_cache);
-                JsonEncodeDecode::decode(
-                    &self.properties,
-                    &self.embedded_properties,
-                    &mut ob,
+            let mut result = ImportResult::default();
+            for value in values {
+                self.import_json_value(
+                    cursors,
+                    change_set.as_deref_mut(),
+                    id_name,
                     value,
+                    InsertConflict::Overwrite,
+                    &mut result,
+                    &mut ob_result_cache,
                 )?;
-                let object = ob.finish();
-                self.put_internal(cursors, change_set.as_deref_mut(), id, object)?;
-                ob_result_cache = Some(ob.recycle());
             }
             Ok(())
         })
     }
 
+    pub fn import_json_reader<R: Read>(
+        &self,
+        txn: &mut IsarTxn,
+        id_name: Option<&str>,
+        reader: R,
+    ) -> Result<u32> {
+        let result =
+            self.import_json_reader_with_conflict(txn, id_name, reader, InsertConflict::Overwrite)?;
+        Ok(result.total())
+    }
+
+    /// Imports a JSON array like [IsarCollection::import_json_reader]. `conflict` decides what
+    /// happens to objects whose id already exists. `InsertConflict::Abort` fails the import with
+    /// `IsarError::UniqueViolated`.
+    pub fn import_json_reader_with_conflict<R: Read>(
+        &self,
+        txn: &mut IsarTxn,
+        id_name: Option<&str>,
+        reader: R,
+        conflict: InsertConflict,
+    ) -> Result<ImportResult> {
+        txn.write(self.instance_id, |cursors, change_set| {
+            self.import_json_reader_internal(cursors, change_set, id_name, reader, conflict)
+        })
+    }
+
+    /// Replaces all objects of the collection with the objects of a JSON array in a single
+    /// transaction. Readers see either the old or the new objects. Returns the number of imported
+    /// objects.
+    pub fn replace_json_reader<R: Read>(
+        &self,
+        txn: &mut IsarTxn,
+        id_name: Option<&str>,
+        reader: R,
+    ) -> Result<u32> {
+        txn.write(self.instance_id, |cursors, mut change_set| {
+            self.clear_internal(cursors, change_set.as_deref_mut())?;
+            let result = self.import_json_reader_internal(
+                cursors,
+                change_set,
+                id_name,
+                reader,
+                InsertConflict::Overwrite,
+            )?;
+            Ok(result.total())
+        })
+    }
+
+    fn import_json_reader_internal<R: Read>(
+        &self,
+        cursors: &IsarCursors,
+        mut change_set: Option<&mut ChangeSet>,
+        id_name: Option<&str>,
+        reader: R,
+        conflict: InsertConflict,
+    ) -> Result<ImportResult> {
+        let mut ob_result_cache = None;
+        let mut import_result = ImportResult::default();
+        let mut error = None;
+        let visitor = JsonImportVisitor {
+            import: |value: Value| {
+                self.import_json_value(
+                    cursors,
+                    change_set.as_deref_mut(),
+                    id_name,
+                    &value,
+                    conflict,
+                    &mut import_result,
+                    &mut ob_result_cache,
+                )
+            },
+            error: &mut error,
+        };
+
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let result = deserializer
+            .deserialize_seq(visitor)
+            .and_then(|_| deserializer.end());
+        if let Some(error) = error {
+            Err(error)
+        } else if result.is_err() {
+            Err(IsarError::InvalidJson {})
+        } else {
+            Ok(import_result)
+        }
+    }
+
+    /// Imports a stream of MessagePack maps as written by [crate::query::Query::export_msgpack].
+    /// The reader is read byte by byte so it should be buffered.
+    pub fn import_msgpack<R: Read>(
+        &self,
+        txn: &mut IsarTxn,
+        id_name: Option<&str>,
+        mut reader: R,
+    ) -> Result<u32> {
+        txn.write(self.instance_id, |cursors, mut change_set| {
+            let mut ob_result_cache = None;
+            let mut result = ImportResult::default();
+            while let Some(value) = msgpack::read_value(&mut reader)? {
+                self.import_json_value(
+                    cursors,
+                    change_set.as_deref_mut(),
+                    id_name,
+                    &value,
+                    InsertConflict::Overwrite,
+                    &mut result,
+                    &mut ob_result_cache,
+                )?;
+            }
+            Ok(result.total())
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn import_json_value(
+        &self,
+        cursors: &IsarCursors,
+        change_set: Option<&mut ChangeSet>,
+        id_name: Option<&str>,
+        value: &Value,
+        conflict: InsertConflict,
+        result: &mut ImportResult,
+        ob_result_cache: &mut Option<Vec<u8>>,
+    ) -> Result<()> {
+        let decoded = EnumMapping::decode_names(&self.enum_mappings, value)?;
+        let value = decoded.as_ref().unwrap_or(value);
+        let id = if let Some(id_name) = id_name {
+            if let Some(id) = value.get(id_name) {
+                let id = id.as_i64().ok_or(IsarError::InvalidJson {})?;
+                Some(id)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        if !self.count_conflict(cursors, id, conflict, result)? {
+            return Ok(());
+        }
+
+        let mut ob = ObjectBuilder::new(&self.properties, ob_result_cache.take());
+        JsonEncodeDecode::decode(&self.properties, &self.embedded_properties, &mut ob, value)?;
+        let object = ob.finish();
+        self.put_internal(cursors, change_set, id, object)?;
+        ob_result_cache.replace(ob.recycle());
+        Ok(())
+    }
+
     pub(crate) fn fill_indexes(&self, index_ids: &[u64], cursors: &IsarCursors) -> Result<()> {
         let indexes = index_ids
             .iter()
@@ -445,3 +1686,27 @@ impl IsarCollection {
         txn.read(self.instance_id, |cursors| link.verify(cursors, links))
     }
 }
+
+struct JsonImportVisitor<'a, F: FnMut(Value) -> Result<()>> {
+    import: F,
+    error: &'a mut Option<IsarError>,
+}
+
+impl<'de, 'a, F: FnMut(Value) -> Result<()>> Visitor<'de> for JsonImportVisitor<'a, F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of objects")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> std::result::Result<(), A::Error> {
+        while let Some(value) = seq.next_element::<Value>()? {
+            if let Err(e) = (self.import)(value) {
+                let message = e.to_string();
+                self.error.replace(e);
+                return Err(A::Error::custom(message));
+            }
+        }
+        Ok(())
+    }
+}
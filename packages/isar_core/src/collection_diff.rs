@@ -0,0 +1,101 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// The differences between the objects of two collections.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CollectionDiff {
+    pub only_in_a: Vec<i64>,
+    pub only_in_b: Vec<i64>,
+    /// Ids that exist in both collections with different objects.
+    pub different: Vec<i64>,
+}
+
+/// What [crate::instance::IsarInstance::export_delta] wrote.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct DeltaStats {
+    /// Objects the peer is missing or has in a different version.
+    pub objects: u64,
+    /// Ids the peer has but this collection does not.
+    pub deleted: u64,
+    /// Objects the peer already has.
+    pub unchanged: u64,
+}
+
+impl CollectionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.different.is_empty()
+    }
+
+    /// Compares two lists of `(id, object hash)` pairs sorted by id.
+    pub(crate) fn from_sorted_hashes(a: &[(i64, u64)], b: &[(i64, u64)]) -> Self {
+        let mut diff = CollectionDiff::default();
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            let ((id_a, hash_a), (id_b, hash_b)) = (a[i], b[j]);
+            match id_a.cmp(&id_b) {
+                Ordering::Less => {
+                    diff.only_in_a.push(id_a);
+                    i += 1;
+                }
+                Ordering::Greater => {
+                    diff.only_in_b.push(id_b);
+                    j += 1;
+                }
+                Ordering::Equal => {
+                    if hash_a != hash_b {
+                        diff.different.push(id_a);
+                    }
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        diff.only_in_a.extend(a[i..].iter().map(|(id, _)| *id));
+        diff.only_in_b.extend(b[j..].iter().map(|(id, _)| *id));
+        diff
+    }
+
+    /// Compares local `(id, object hash)` pairs sorted by id with the hashes of a peer.
+    pub(crate) fn from_peer_hashes(local: &[(i64, u64)], peer: &HashMap<i64, u64>) -> Self {
+        let mut peer = peer
+            .iter()
+            .map(|(id, hash)| (*id, *hash))
+            .collect::<Vec<_>>();
+        peer.sort_unstable();
+        Self::from_sorted_hashes(local, &peer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_sorted_hashes() {
+        let a = vec![(1, 10), (2, 20), (4, 40), (5, 50), (7, 70)];
+        let b = vec![(2, 20), (3, 30), (4, 41), (5, 50), (8, 80), (9, 90)];
+        let diff = CollectionDiff::from_sorted_hashes(&a, &b);
+        assert_eq!(diff.only_in_a, vec![1, 7]);
+        assert_eq!(diff.only_in_b, vec![3, 8, 9]);
+        assert_eq!(diff.different, vec![4]);
+        assert!(!diff.is_empty());
+
+        assert!(CollectionDiff::from_sorted_hashes(&a, &a).is_empty());
+        let diff = CollectionDiff::from_sorted_hashes(&[], &b);
+        assert_eq!(diff.only_in_b.len(), b.len());
+        assert!(diff.only_in_a.is_empty() && diff.different.is_empty());
+    }
+
+    #[test]
+    fn test_from_peer_hashes() {
+        let local = vec![(1, 10), (2, 20), (3, 30), (5, 50)];
+        let peer = HashMap::from([(2, 20), (3, 31), (4, 40), (6, 60)]);
+        let diff = CollectionDiff::from_peer_hashes(&local, &peer);
+        assert_eq!(diff.only_in_a, vec![1, 5]);
+        assert_eq!(diff.different, vec![3]);
+        assert_eq!(diff.only_in_b, vec![4, 6]);
+
+        let diff = CollectionDiff::from_peer_hashes(&local, &HashMap::new());
+        assert_eq!(diff.only_in_a, vec![1, 2, 3, 5]);
+    }
+}
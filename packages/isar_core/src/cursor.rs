@@ -1,40 +1,87 @@
-use crate::error::Result;
+use crate::error::{IsarError, Result};
 use crate::mdbx::cursor::{Cursor, UnboundCursor};
 use crate::mdbx::db::Db;
 use crate::mdbx::txn::Txn;
+use crate::query::time_where_clause::now_micros;
 use intmap::IntMap;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Keeps unbound cursors for reuse together with the runtime id of the db they were last bound
+/// to. At most `capacity` cursors are kept, the least recently returned ones are dropped first.
+pub(crate) struct CursorPool<T = UnboundCursor> {
+    capacity: usize,
+    // most recently returned last
+    cursors: Vec<(u64, T)>,
+}
+
+impl<T> CursorPool<T> {
+    pub const DEFAULT_CAPACITY: usize = 3;
+
+    pub fn new(capacity: usize) -> Self {
+        CursorPool {
+            capacity,
+            cursors: Vec::with_capacity(capacity.min(64)),
+        }
+    }
+
+    /// Takes a cursor that was last bound to `db_id` or any other cursor if there is none.
+    pub fn take(&mut self, db_id: u64) -> Option<T> {
+        if let Some(index) = self.cursors.iter().rposition(|(id, _)| *id == db_id) {
+            Some(self.cursors.remove(index).1)
+        } else {
+            self.cursors.pop().map(|(_, cursor)| cursor)
+        }
+    }
+
+    pub fn put(&mut self, db_id: u64, cursor: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.cursors.len() >= self.capacity {
+            self.cursors.remove(0);
+        }
+        self.cursors.push((db_id, cursor));
+    }
+}
 
 pub(crate) struct IsarCursors<'txn, 'env> {
     txn: &'txn Txn<'env>,
-    unbound_cursors: RefCell<Vec<UnboundCursor>>,
+    unbound_cursors: RefCell<CursorPool>,
     cursors: RefCell<IntMap<Cursor<'txn>>>,
+    prefetch: Cell<bool>,
+    now: Cell<Option<i64>>,
 }
 
 impl<'txn, 'env> IsarCursors<'txn, 'env> {
-    pub fn new(
-        txn: &'txn Txn<'env>,
-        unbound_cursors: Vec<UnboundCursor>,
-    ) -> IsarCursors<'txn, 'env> {
+    pub fn new(txn: &'txn Txn<'env>, unbound_cursors: CursorPool) -> IsarCursors<'txn, 'env> {
         IsarCursors {
             txn,
             unbound_cursors: RefCell::new(unbound_cursors),
             cursors: RefCell::new(IntMap::new()),
+            prefetch: Cell::new(false),
+            now: Cell::new(None),
         }
     }
 
+    /// Enables prefetching for range scans of the cursors returned by [IsarCursors::get_cursor].
+    pub fn set_prefetch(&self, prefetch: bool) {
+        self.prefetch.set(prefetch);
+    }
+
     pub fn get_cursor<'a>(&'a self, db: Db) -> Result<IsarCursor<'a, 'txn, 'env>> {
-        let cursor = if let Some(cursor) = self.cursors.borrow_mut().remove(db.runtime_id()) {
+        let mut cursor = if let Some(cursor) = self.cursors.borrow_mut().remove(db.runtime_id()) {
             cursor
         } else {
             let unbound = self
                 .unbound_cursors
                 .borrow_mut()
-                .pop()
+                .take(db.runtime_id())
                 .unwrap_or_else(UnboundCursor::new);
             unbound.bind(self.txn, db)?
         };
+        cursor.set_prefetch(self.prefetch.get());
 
         Ok(IsarCursor {
             cursors: self,
@@ -43,18 +90,73 @@ impl<'txn, 'env> IsarCursors<'txn, 'env> {
         })
     }
 
+    /// Binds a cursor that is owned by the caller instead of being returned to the pool when it
+    /// is dropped. Used to keep several scans open at the same time.
+    pub fn bind_cursor(&self, db: Db) -> Result<Cursor<'txn>> {
+        let unbound = self
+            .unbound_cursors
+            .borrow_mut()
+            .take(db.runtime_id())
+            .unwrap_or_else(UnboundCursor::new);
+        let mut cursor = unbound.bind(self.txn, db)?;
+        cursor.set_prefetch(self.prefetch.get());
+        Ok(cursor)
+    }
+
+    /// The current time in microseconds since the Unix epoch. It is captured on the first call so
+    /// relative time conditions see the same time for the whole read.
+    pub fn now_micros(&self) -> i64 {
+        if let Some(now) = self.now.get() {
+            now
+        } else {
+            let now = now_micros();
+            self.now.set(Some(now));
+            now
+        }
+    }
+
+    pub fn txn_id(&self) -> u64 {
+        self.txn.id()
+    }
+
     pub fn db_stat(&self, db: Db) -> Result<(u64, u64)> {
         db.stat(&self.txn)
     }
 
+    /// Reads every page of `db` so the OS keeps it in the page cache. Returns the size of the
+    /// touched pages in bytes.
+    pub fn warm_db(&self, db: Db, cancel_token: Option<&AtomicBool>) -> Result<u64> {
+        const CANCEL_CHECK_INTERVAL: usize = 256;
+
+        let page_size = self.txn.page_size()?.max(1) as usize;
+        let mut touched = 0u8;
+        let mut entries = 0;
+        let mut cursor = self.get_cursor(db)?;
+        cursor.iter_all(false, true, |_, key, value| {
+            entries += 1;
+            if entries % CANCEL_CHECK_INTERVAL == 0
+                && cancel_token.is_some_and(|t| t.load(Ordering::Relaxed))
+            {
+                return Err(IsarError::Cancelled {});
+            }
+            touched ^= key.first().copied().unwrap_or_default();
+            for byte in value.iter().step_by(page_size) {
+                touched ^= *byte;
+            }
+            Ok(true)
+        })?;
+        std::hint::black_box(touched);
+        Ok(self.db_stat(db)?.1)
+    }
+
     pub fn clear_db(&self, db: Db) -> Result<()> {
         db.clear(&self.txn)
     }
 
-    pub fn close(self) -> Vec<UnboundCursor> {
-        let mut unbound_cursors = self.unbound_cursors.take();
-        for (_, cursor) in self.cursors.borrow_mut().drain() {
-            unbound_cursors.push(cursor.unbind())
+    pub fn close(self) -> CursorPool {
+        let mut unbound_cursors = self.unbound_cursors.into_inner();
+        for (db_id, cursor) in self.cursors.borrow_mut().drain() {
+            unbound_cursors.put(db_id, cursor.unbind())
         }
         unbound_cursors
     }
@@ -86,11 +188,85 @@ impl<'a, 'txn, 'env> Drop for IsarCursor<'a, 'txn, 'env> {
         let cursors = &self.cursors.cursors;
         if !cursors.borrow().contains_key(self.db_id) {
             cursors.borrow_mut().insert(self.db_id, cursor);
-        } else if self.cursors.unbound_cursors.borrow().len() < 3 {
+        } else {
             self.cursors
                 .unbound_cursors
                 .borrow_mut()
-                .push(cursor.unbind());
+                .put(self.db_id, cursor.unbind());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::IsarInstance;
+    use crate::object::id::BytesToId;
+
+    #[test]
+    fn test_cursor_pool() {
+        let mut pool = CursorPool::new(3);
+        assert_eq!(pool.take(1), None);
+
+        pool.put(1, "a");
+        pool.put(2, "b");
+        pool.put(1, "c");
+        assert_eq!(pool.take(2), Some("b"));
+        assert_eq!(pool.take(1), Some("c"));
+        assert_eq!(pool.take(5), Some("a"));
+        assert_eq!(pool.take(1), None);
+
+        for (db_id, cursor) in [(1, "a"), (2, "b"), (3, "c"), (4, "d")] {
+            pool.put(db_id, cursor);
         }
+        assert_eq!(pool.take(1), Some("d"));
+        assert_eq!(pool.take(3), Some("c"));
+        assert_eq!(pool.take(3), Some("b"));
+        assert_eq!(pool.take(3), None);
+
+        let mut disabled = CursorPool::new(0);
+        disabled.put(1, "a");
+        assert_eq!(disabled.take(1), None);
+    }
+
+    #[test]
+    fn test_current() {
+        let schema = r#"[{"name": "Item", "properties": [{"name": "value", "type": "Long"}]}]"#;
+        let instance = IsarInstance::open_test("cursor_current", schema);
+        let col = &instance.collections[0];
+
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        for id in 1..=3 {
+            let mut builder = col.new_object_builder(None);
+            builder.write_long(col.properties[0].offset, id * 10);
+            col.put(&mut txn, Some(id), builder.finish()).unwrap();
+        }
+        txn.commit().unwrap();
+
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        txn.read(instance.instance_id, |cursors| {
+            let mut cursor = cursors.get_cursor(col.db)?;
+            assert_eq!(cursor.current()?, None);
+
+            let mut seen = vec![];
+            cursor.iter_between(&2i64, &3i64, false, false, true, |cursor, key, val| {
+                let (current_key, current_val) = cursor.current()?.unwrap();
+                assert_eq!((current_key, current_val), (key, val));
+                seen.push(current_key.to_id());
+                Ok(false)
+            })?;
+            assert_eq!(seen, vec![2]);
+
+            let (key, val) = cursor.current()?.unwrap();
+            assert_eq!(key.to_id(), 2);
+            let (next_key, next_val) = cursor.move_to_next()?.unwrap();
+            assert_eq!(next_key.to_id(), 3);
+            assert_ne!(val, next_val);
+            assert_eq!(cursor.current()?, Some((next_key, next_val)));
+            Ok(())
+        })
+        .unwrap();
+        txn.abort();
+        instance.close_test();
     }
 }
@@ -2,15 +2,16 @@ use snafu::Snafu;
 
 pub type Result<T> = std::result::Result<T, IsarError>;
 
-#[derive(Debug, Snafu, Eq, PartialEq)]
+#[derive(Debug, Snafu, Clone, Eq, PartialEq)]
 pub enum IsarError {
     #[snafu(display("Isar version of the file is too new or too old to be used."))]
     VersionError {},
 
     #[snafu(display(
-        "No such file or directory. Please make sure that the provided path is valid."
+        "No such file or directory: {}. Please make sure that the provided path is valid.",
+        path
     ))]
-    PathError {},
+    PathError { path: String },
 
     #[snafu(display("Cannot open Environment: {}", error))]
     EnvError { error: Box<IsarError> },
@@ -18,12 +19,15 @@ pub enum IsarError {
     #[snafu(display("The database is full."))]
     DbFull {},
 
-    #[snafu(display("Unique index violated."))]
-    UniqueViolated {},
+    #[snafu(display("Unique index {} violated by {}.", index_name, key))]
+    UniqueViolated { index_name: String, key: String },
 
     #[snafu(display("Write transaction required."))]
     WriteTxnRequired {},
 
+    #[snafu(display("Read transactions cannot be committed."))]
+    ReadTxnCommit {},
+
     #[snafu(display("Auto increment id cannot be generated because the limit is reached."))]
     AutoIncrementOverflow {},
 
@@ -33,18 +37,77 @@ pub enum IsarError {
     #[snafu(display("The provided object is invalid."))]
     InvalidObject {},
 
+    #[snafu(display(
+        "TooManyInstances: At most {} instances can be open at the same time.",
+        max
+    ))]
+    TooManyInstances { max: usize },
+
     #[snafu(display("Transaction closed."))]
     TransactionClosed {},
 
     #[snafu(display("IllegalArg: {}.", message))]
     IllegalArg { message: String },
 
+    #[snafu(display(
+        "KeyTooLong: A value of index {} is {} bytes long but the index supports at most {} bytes.",
+        index_name,
+        len,
+        max
+    ))]
+    KeyTooLong {
+        index_name: String,
+        len: usize,
+        max: usize,
+    },
+
+    #[snafu(display(
+        "ValueTooLarge: The value of property {} is {} bytes long but at most {} bytes are allowed.",
+        property,
+        len,
+        max
+    ))]
+    ValueTooLarge {
+        property: String,
+        len: usize,
+        max: usize,
+    },
+
+    #[snafu(display(
+        "VectorDimensionMismatch: The vector of property {} has {} dimensions but {} are expected.",
+        property,
+        len,
+        expected
+    ))]
+    VectorDimensionMismatch {
+        property: String,
+        len: usize,
+        expected: usize,
+    },
+
+    #[snafu(display(
+        "IndexesOutdated: Indexing is disabled and the indexes have to be rebuilt before they can be used."
+    ))]
+    IndexesOutdated {},
+
     #[snafu(display("Index could not be found."))]
     UnknownIndex {},
 
     #[snafu(display("Invalid JSON."))]
     InvalidJson {},
 
+    #[snafu(display("Invalid MessagePack."))]
+    InvalidMsgPack {},
+
+    #[snafu(display("Invalid filter expression at {}: {}", position, message))]
+    InvalidFilterExpression { position: usize, message: String },
+
+    #[snafu(display("InvalidArchive: The data is not a valid Isar archive."))]
+    InvalidArchive {},
+
+    #[snafu(display("IoError: {}", message))]
+    IoError { message: String },
+
     #[snafu(display("DbCorrupted: {}", message))]
     DbCorrupted { message: String },
 
@@ -57,6 +120,12 @@ pub enum IsarError {
     #[snafu(display("InstanceMismatch: The transaction is from a different instance."))]
     InstanceMismatch {},
 
+    #[snafu(display("The query scanned more than {} objects.", limit))]
+    ScanLimitExceeded { limit: u32 },
+
+    #[snafu(display("The operation has been cancelled."))]
+    Cancelled {},
+
     #[snafu(display("MdbxError ({}): {}", code, message))]
     MdbxError { code: i32, message: String },
 }
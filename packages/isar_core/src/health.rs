@@ -0,0 +1,73 @@
+/// The result of [crate::instance::IsarInstance::health_check].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HealthStatus {
+    Healthy,
+    /// The instance works but is close to a limit. Contains one reason per limit.
+    Degraded(Vec<String>),
+}
+
+impl HealthStatus {
+    const MAX_MAP_USAGE_PERCENT: u64 = 90;
+    const MAX_READER_USAGE_PERCENT: u64 = 90;
+
+    pub fn is_healthy(&self) -> bool {
+        *self == HealthStatus::Healthy
+    }
+
+    pub(crate) fn from_usage(
+        used_bytes: u64,
+        map_size: u64,
+        num_readers: u32,
+        max_readers: u32,
+    ) -> Self {
+        let mut reasons = vec![];
+        let map_usage = percent(used_bytes, map_size);
+        if map_usage > Self::MAX_MAP_USAGE_PERCENT {
+            reasons.push(format!("The database map is {}% full.", map_usage));
+        }
+        let reader_usage = percent(num_readers as u64, max_readers as u64);
+        if reader_usage > Self::MAX_READER_USAGE_PERCENT {
+            reasons.push(format!(
+                "{} of {} reader slots are in use.",
+                num_readers, max_readers
+            ));
+        }
+        if reasons.is_empty() {
+            HealthStatus::Healthy
+        } else {
+            HealthStatus::Degraded(reasons)
+        }
+    }
+}
+
+fn percent(value: u64, max: u64) -> u64 {
+    if max == 0 {
+        0
+    } else {
+        (value as u128 * 100 / max as u128) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_usage() {
+        const MB: u64 = 1 << 20;
+        assert!(HealthStatus::from_usage(MB, 1024 * MB, 2, 126).is_healthy());
+        assert!(HealthStatus::from_usage(900 * MB, 1000 * MB, 113, 126).is_healthy());
+
+        let full = HealthStatus::from_usage(990 * MB, 1000 * MB, 2, 126);
+        assert_eq!(
+            full,
+            HealthStatus::Degraded(vec!["The database map is 99% full.".to_string()])
+        );
+
+        match HealthStatus::from_usage(999 * MB, 1000 * MB, 120, 126) {
+            HealthStatus::Degraded(reasons) => assert_eq!(reasons.len(), 2),
+            HealthStatus::Healthy => panic!("expected degraded"),
+        }
+        assert!(HealthStatus::from_usage(0, 0, 0, 0).is_healthy());
+    }
+}
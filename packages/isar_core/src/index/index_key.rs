@@ -86,6 +86,40 @@ impl IndexKey {
         }
     }
 
+    /// Blob keys start with a marker byte so null sorts before the empty blob. The bytes are stored
+    /// unescaped which allows 0x00 bytes. Unlike a length prefix this keeps the lexicographic order
+    /// that range queries rely on, but there is no terminator so blobs have to be the last
+    /// component of a key. Blobs are never hashed, objects with blobs that are too long for a key
+    /// are rejected when they are written.
+    pub fn add_blob(&mut self, value: Option<&[u8]>) {
+        if let Some(bytes) = value {
+            self.bytes.push(1);
+            self.bytes.extend_from_slice(bytes);
+        } else {
+            self.bytes.push(0);
+        }
+    }
+
+    /// Returns the bounds of all string keys starting with `prefix`. The range may contain a few
+    /// keys that do not start with `prefix` so the results have to be filtered. Returns `None`
+    /// for an empty prefix.
+    pub fn string_prefix_range(prefix: &str, case_sensitive: bool) -> Option<(Self, Self)> {
+        let prefix = if case_sensitive {
+            prefix.to_string()
+        } else {
+            prefix.to_lowercase()
+        };
+        let bytes = prefix.as_bytes();
+        if bytes.is_empty() {
+            return None;
+        }
+        let len = bytes.len().min(IsarIndex::MAX_STRING_INDEX_SIZE);
+        let lower = IndexKey::from_bytes(bytes[0..len].to_vec());
+        let mut upper = lower.clone();
+        upper.increase();
+        Some((lower, upper))
+    }
+
     pub fn add_hash(&mut self, value: u64) {
         let bytes: [u8; 8] = value.to_be_bytes();
         self.bytes.extend_from_slice(&bytes);
@@ -329,4 +363,66 @@ mod tests {
             assert_eq!(index_key.bytes, bytes_lc);
         }
     }
+
+    #[test]
+    fn test_add_blob() {
+        let key = |value: Option<&[u8]>| {
+            let mut key = IndexKey::new();
+            key.add_byte(123);
+            key.add_blob(value);
+            key
+        };
+        assert_eq!(key(None).bytes, vec![123, 0]);
+        assert_eq!(key(Some(&[])).bytes, vec![123, 1]);
+        assert_eq!(key(Some(&[0, 0, 5])).bytes, vec![123, 1, 0, 0, 5]);
+
+        assert!(key(Some(&[0])) != key(Some(&[0, 0])));
+        assert!(key(Some(&[1, 0])) != key(Some(&[1])));
+        assert!(key(Some(&[0, 1])) == key(Some(&[0, 1])));
+
+        let sorted: Vec<&[u8]> = vec![&[], &[0], &[0, 0], &[0, 0, 255], &[0, 1], &[1], &[255, 0]];
+        assert!(key(None) < key(Some(sorted[0])));
+        for pair in sorted.windows(2) {
+            assert!(key(Some(pair[0])) < key(Some(pair[1])));
+        }
+
+        let (lower, upper) = (key(Some(&[0, 0])), key(Some(&[0, 1])));
+        let in_range = sorted
+            .iter()
+            .copied()
+            .filter(|b| key(Some(b)) >= lower && key(Some(b)) <= upper)
+            .collect::<Vec<_>>();
+        assert_eq!(in_range, vec![&[0u8, 0][..], &[0, 0, 255], &[0, 1]]);
+
+        let long = vec![0; IsarIndex::MAX_STRING_INDEX_SIZE + 10];
+        let mut other = long.clone();
+        other.push(1);
+        assert!(key(Some(&long)) != key(Some(&other)));
+    }
+
+    #[test]
+    fn test_string_prefix_range() {
+        assert!(IndexKey::string_prefix_range("", true).is_none());
+
+        let (lower, upper) = IndexKey::string_prefix_range("ab", true).unwrap();
+        let key = |value: &str| {
+            let mut key = IndexKey::new();
+            key.add_string(Some(value), true);
+            key
+        };
+        for value in ["ab", "abc", "abä", &"ab".repeat(1000)] {
+            assert!(key(value) >= lower && key(value) <= upper);
+        }
+        for value in ["a", "aa", "b", "ba"] {
+            assert!(key(value) < lower || key(value) > upper);
+        }
+
+        let (lower, _) = IndexKey::string_prefix_range("AB", false).unwrap();
+        assert!(lower == IndexKey::from_bytes(b"ab".to_vec()));
+
+        let long = "x".repeat(2000);
+        let (lower, upper) = IndexKey::string_prefix_range(&long, true).unwrap();
+        assert_eq!(lower.len(), IsarIndex::MAX_STRING_INDEX_SIZE);
+        assert!(key(&long) >= lower && key(&long) <= upper);
+    }
 }
@@ -57,6 +57,7 @@ impl<'a> IndexKeyBuilder<'a> {
                         object.read_string(property.offset),
                         index_property.case_sensitive,
                     ),
+                    DataType::ByteList => key.add_blob(object.read_byte_list(property.offset)),
                     _ => unreachable!(),
                 }
             }
@@ -75,6 +76,19 @@ impl<'a> IndexKeyBuilder<'a> {
             return Ok(true);
         }
         match property.data_type {
+            DataType::String => {
+                let text = object.read_string(property.offset).unwrap();
+                let words = index_property
+                    .tokenizer
+                    .words(text, index_property.case_sensitive);
+                for word in words {
+                    key.truncate(0);
+                    key.add_string(Some(&word), true);
+                    if !callback(&key)? {
+                        return Ok(false);
+                    }
+                }
+            }
             DataType::BoolList | DataType::ByteList => {
                 for value in object.read_byte_list(property.offset).unwrap() {
                     key.truncate(0);
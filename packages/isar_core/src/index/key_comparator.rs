@@ -0,0 +1,95 @@
+use crate::mdbx::from_mdb_val;
+use libc::c_int;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Custom key order for index databases. The comparator is persisted in the schema because
+/// MDBX requires every open of the database to use the same order.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum KeyComparator {
+    /// Compares runs of digits numerically so "1.10" sorts after "1.9".
+    Version,
+}
+
+impl KeyComparator {
+    pub fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        match self {
+            KeyComparator::Version => compare_version(a, b),
+        }
+    }
+
+    pub(crate) fn as_mdbx_cmp(&self) -> ffi::MDBX_cmp_func {
+        match self {
+            KeyComparator::Version => Some(mdbx_version_cmp),
+        }
+    }
+}
+
+fn digit_run(bytes: &[u8]) -> &[u8] {
+    let len = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+    &bytes[..len]
+}
+
+fn skip_zeros(run: &[u8]) -> &[u8] {
+    let zeros = run.iter().take_while(|b| **b == b'0').count();
+    &run[zeros..]
+}
+
+fn compare_digit_runs(a: &[u8], b: &[u8]) -> Ordering {
+    let a = skip_zeros(a);
+    let b = skip_zeros(b);
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+fn compare_version(a: &[u8], b: &[u8]) -> Ordering {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let ord = if a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            let run_a = digit_run(&a[i..]);
+            let run_b = digit_run(&b[j..]);
+            i += run_a.len();
+            j += run_b.len();
+            compare_digit_runs(run_a, run_b)
+        } else {
+            i += 1;
+            j += 1;
+            a[i - 1].cmp(&b[j - 1])
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+
+    // Keys must only be equal if their bytes are equal, e.g. "01" and "1".
+    (a.len() - i).cmp(&(b.len() - j)).then_with(|| a.cmp(b))
+}
+
+unsafe extern "C" fn mdbx_version_cmp(a: *const ffi::MDBX_val, b: *const ffi::MDBX_val) -> c_int {
+    let a = from_mdb_val(&*a);
+    let b = from_mdb_val(&*b);
+    compare_version(a, b) as c_int
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_order() {
+        let cmp = |a: &str, b: &str| KeyComparator::Version.compare(a.as_bytes(), b.as_bytes());
+        assert_eq!(cmp("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(cmp("1.9", "1.10"), Ordering::Less);
+        assert_eq!(cmp("2.0", "10.0"), Ordering::Less);
+        assert_eq!(cmp("1.2", "1.2"), Ordering::Equal);
+        assert_eq!(cmp("1.2", "1.2.1"), Ordering::Less);
+        assert_eq!(cmp("1.2a", "1.2b"), Ordering::Less);
+        assert_ne!(cmp("01", "1"), Ordering::Equal);
+
+        let mut versions = vec!["1.10", "1.9", "1.2", "10.0", "2.0", "1.10.1", "1.0"];
+        versions.sort_by(|a, b| cmp(a, b));
+        assert_eq!(
+            versions,
+            vec!["1.0", "1.2", "1.9", "1.10", "1.10.1", "2.0", "10.0"]
+        );
+    }
+}
@@ -2,22 +2,34 @@ use crate::cursor::IsarCursors;
 use crate::error::{IsarError, Result};
 use crate::index::index_key::IndexKey;
 use crate::index::index_key_builder::IndexKeyBuilder;
+use crate::index::tokenizer::Tokenizer;
 use crate::mdbx::db::Db;
+use crate::mdbx::Key;
+use crate::object::data_type::DataType;
 use crate::object::id::{BytesToId, IdToBytes};
 use crate::object::isar_object::IsarObject;
+use crate::object::json_encode_decode::JsonEncodeDecode;
 use crate::object::property::Property;
-use crate::schema::index_schema::IndexType;
+use crate::query::scan::{IndexRange, Scan};
+use crate::query::Nulls;
+use crate::schema::index_schema::{IndexType, KeyOverflow};
 use intmap::IntMap;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::sync::atomic::AtomicBool;
 use xxhash_rust::xxh3::xxh3_64;
 
 pub mod index_key;
 pub(crate) mod index_key_builder;
+pub mod key_comparator;
+pub mod tokenizer;
 
 #[derive(Clone, Eq, PartialEq)]
 pub struct IndexProperty {
     pub property: Property,
     pub index_type: IndexType,
     pub case_sensitive: bool,
+    pub tokenizer: Tokenizer,
 }
 
 impl IndexProperty {
@@ -26,6 +38,7 @@ impl IndexProperty {
             property,
             index_type,
             case_sensitive,
+            tokenizer: Tokenizer::default(),
         }
     }
 
@@ -40,10 +53,20 @@ impl IndexProperty {
     }
 
     fn is_multi_entry(&self) -> bool {
-        self.property.data_type.get_element_type().is_some() && self.index_type != IndexType::Hash
+        self.index_type == IndexType::Words
+            || (self.property.data_type.get_element_type().is_some()
+                && self.index_type != IndexType::Hash
+                && self.index_type != IndexType::Blob)
     }
 }
 
+pub struct IndexStats {
+    pub entries: u64,
+    pub distinct_keys: u64,
+    pub min_key: Option<Vec<u8>>,
+    pub max_key: Option<Vec<u8>>,
+}
+
 #[derive(Clone, Eq, PartialEq)]
 pub(crate) struct IsarIndex {
     pub name: String,
@@ -52,21 +75,52 @@ pub(crate) struct IsarIndex {
     pub unique: bool,
     pub replace: bool,
     pub multi_entry: bool,
+    key_overflow: KeyOverflow,
+    null_key: Option<IndexKey>,
+    pub nulls: Option<Nulls>,
     db: Db,
 }
 
 impl IsarIndex {
     pub const MAX_STRING_INDEX_SIZE: usize = 1024;
 
+    /// Whether the value of the property at `offset` can be read from the index keys. Strings
+    /// may be truncated or lowercased and hashed values cannot be recovered.
+    pub fn covers_property(&self, offset: usize) -> bool {
+        self.properties.iter().any(|p| {
+            p.property.offset == offset
+                && p.index_type == IndexType::Value
+                && matches!(
+                    p.property.data_type,
+                    DataType::Bool
+                        | DataType::Byte
+                        | DataType::Int
+                        | DataType::Long
+                        | DataType::Float
+                        | DataType::Double
+                )
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: &str,
         db: Db,
         properties: Vec<IndexProperty>,
         unique: bool,
         replace: bool,
+        sparse: bool,
+        key_overflow: KeyOverflow,
+        nulls: Option<Nulls>,
     ) -> Self {
         let id = xxh3_64(name.as_bytes());
         let multi_entry = properties.first().unwrap().is_multi_entry();
+        let null_key = if sparse && !multi_entry {
+            let empty = IsarObject::from_bytes(&[0, 0]);
+            Some(IndexKeyBuilder::new(&properties).create_primitive_key(empty))
+        } else {
+            None
+        };
         IsarIndex {
             name: name.to_string(),
             id,
@@ -74,10 +128,95 @@ impl IsarIndex {
             unique,
             replace,
             multi_entry,
+            key_overflow,
+            null_key,
+            nulls,
             db,
         }
     }
 
+    /// Fails if the index uses [KeyOverflow::Error] and a string value, or a word of a words index,
+    /// of `object` does not fit into a key. Blobs that do not fit always fail because hashing them
+    /// would break the order of range queries.
+    fn check_key_size(&self, object: IsarObject) -> Result<()> {
+        let max = Self::MAX_STRING_INDEX_SIZE - 1;
+        for index_property in &self.properties {
+            let hashed = self.key_overflow != KeyOverflow::Error
+                && index_property.index_type != IndexType::Blob;
+            if hashed || index_property.index_type == IndexType::Hash {
+                continue;
+            }
+            let property = &index_property.property;
+            let len = match property.data_type {
+                DataType::String if index_property.index_type == IndexType::Words => {
+                    object.read_string(property.offset).map_or(0, |text| {
+                        index_property
+                            .tokenizer
+                            .words(text, index_property.case_sensitive)
+                            .iter()
+                            .map(|word| word.len())
+                            .max()
+                            .unwrap_or(0)
+                    })
+                }
+                DataType::String => object.read_string(property.offset).map_or(0, |s| s.len()),
+                DataType::ByteList if index_property.index_type == IndexType::Blob => object
+                    .read_byte_list(property.offset)
+                    .map_or(0, |b| b.len()),
+                DataType::StringList => object
+                    .read_string_list(property.offset)
+                    .into_iter()
+                    .flatten()
+                    .map(|s| s.map_or(0, |s| s.len()))
+                    .max()
+                    .unwrap_or(0),
+                _ => continue,
+            };
+            if len > max {
+                return Err(IsarError::KeyTooLong {
+                    index_name: self.name.clone(),
+                    len,
+                    max,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Key bounds that include every key of the index.
+    pub fn full_range(&self) -> (IndexKey, IndexKey) {
+        let max_len = self.properties.len() * (Self::MAX_STRING_INDEX_SIZE + 9);
+        (
+            IndexKey::new(),
+            IndexKey::from_bytes(vec![u8::MAX; max_len]),
+        )
+    }
+
+    /// The smallest key whose first property is not null. Keys with a null first property sort
+    /// before it. `None` if the keys are not ordered by the first property.
+    pub fn first_non_null_key(&self) -> Option<IndexKey> {
+        let first = self.properties.first()?;
+        if self.multi_entry || first.index_type == IndexType::Hash {
+            return None;
+        }
+        let empty = IsarObject::from_bytes(&[0, 0]);
+        let mut key = IndexKeyBuilder::new(std::slice::from_ref(first)).create_primitive_key(empty);
+        key.increase().then_some(key)
+    }
+
+    /// The key of objects that are not stored in a sparse index.
+    pub fn sparse_null_key(&self) -> Option<&IndexKey> {
+        self.null_key.as_ref()
+    }
+
+    pub fn compare_keys(&self, a: &IndexKey, b: &IndexKey) -> Ordering {
+        if let Some(comparator) = &self.db.comparator {
+            comparator.compare(&a.as_bytes(), &b.as_bytes())
+        } else {
+            a.cmp(b)
+        }
+    }
+
     pub fn create_for_object<F>(
         &self,
         cursors: &IsarCursors,
@@ -88,9 +227,13 @@ impl IsarIndex {
     where
         F: FnMut(i64) -> Result<()>,
     {
+        self.check_key_size(object)?;
         let mut cursor = cursors.get_cursor(self.db)?;
         let key_builder = IndexKeyBuilder::new(&self.properties);
         key_builder.create_keys(object, |key| {
+            if self.null_key.as_ref() == Some(key) {
+                return Ok(true);
+            }
             if self.unique {
                 let existing = cursor.move_to(key)?;
                 if let Some((_, existing_id_bytes)) = existing {
@@ -98,7 +241,7 @@ impl IsarIndex {
                     if self.replace && existing_id != id {
                         delete(existing_id)?;
                     } else {
-                        return Err(IsarError::UniqueViolated {});
+                        return Err(self.unique_violation(object));
                     }
                 }
             }
@@ -109,6 +252,18 @@ impl IsarIndex {
         Ok(())
     }
 
+    /// The error for `object` colliding with an existing entry. The key lists the values of all
+    /// indexed properties, e.g. `{"email":"a@b.c","tenantId":1}`.
+    fn unique_violation(&self, object: IsarObject) -> IsarError {
+        let properties: Vec<Property> =
+            self.properties.iter().map(|p| p.property.clone()).collect();
+        let key = JsonEncodeDecode::encode(&properties, &IntMap::new(), object, true);
+        IsarError::UniqueViolated {
+            index_name: self.name.clone(),
+            key: Value::Object(key).to_string(),
+        }
+    }
+
     pub fn delete_for_object(
         &self,
         cursors: &IsarCursors,
@@ -118,6 +273,9 @@ impl IsarIndex {
         let mut cursor = cursors.get_cursor(self.db)?;
         let key_builder = IndexKeyBuilder::new(&self.properties);
         key_builder.create_keys(object, |key| {
+            if self.null_key.as_ref() == Some(key) {
+                return Ok(true);
+            }
             let entry = if self.unique {
                 cursor.move_to(key)?
             } else {
@@ -139,6 +297,26 @@ impl IsarIndex {
         skip_duplicates: bool,
         ascending: bool,
         mut callback: impl FnMut(i64) -> Result<bool>,
+    ) -> Result<bool> {
+        self.iter_between_keys(
+            cursors,
+            lower_key,
+            upper_key,
+            skip_duplicates,
+            ascending,
+            |_, id| callback(id),
+        )
+    }
+
+    /// Like [IsarIndex::iter_between] but also passes the key of every entry.
+    pub fn iter_between_keys<'txn, 'env>(
+        &self,
+        cursors: &IsarCursors<'txn, 'env>,
+        lower_key: &IndexKey,
+        upper_key: &IndexKey,
+        skip_duplicates: bool,
+        ascending: bool,
+        mut callback: impl FnMut(&[u8], i64) -> Result<bool>,
     ) -> Result<bool> {
         let mut cursor = cursors.get_cursor(self.db)?;
         cursor.iter_between(
@@ -147,10 +325,65 @@ impl IsarIndex {
             !self.unique,
             skip_duplicates,
             ascending,
-            |_, _, id_bytes| callback(id_bytes.to_id()),
+            |_, key, id_bytes| callback(key, id_bytes.to_id()),
         )
     }
 
+    /// Reads the objects of `data_db` that the entries in `ranges` point to, see [Scan].
+    pub fn scan<'txn>(
+        &self,
+        cursors: &IsarCursors<'txn, '_>,
+        data_db: Db,
+        ranges: Vec<IndexRange>,
+        skip_duplicates: bool,
+        ascending: bool,
+    ) -> Result<Scan<'txn>> {
+        let duplicates = !self.unique;
+        Scan::index(
+            cursors,
+            data_db,
+            self.db,
+            duplicates,
+            ranges,
+            skip_duplicates,
+            ascending,
+        )
+    }
+
+    /// Calls `callback` with the id of the first object of every key in key order.
+    pub fn iter_distinct(
+        &self,
+        cursors: &IsarCursors,
+        mut callback: impl FnMut(i64) -> Result<bool>,
+    ) -> Result<bool> {
+        let mut cursor = cursors.get_cursor(self.db)?;
+        cursor.iter_all(true, true, |_, _, id_bytes| callback(id_bytes.to_id()))
+    }
+
+    /// Whether every distinct value of `property` has exactly one key in this index.
+    pub fn has_distinct_keys(&self, property: &Property) -> bool {
+        if let [index_property] = self.properties.as_slice() {
+            index_property.property == *property
+                && index_property.index_type == IndexType::Value
+                && (index_property.case_sensitive || property.data_type != DataType::String)
+                && !self.multi_entry
+                && self.null_key.is_none()
+        } else {
+            false
+        }
+    }
+
+    /// Estimates the number of entries between `lower_key` and `upper_key`.
+    pub fn estimate_between(
+        &self,
+        cursors: &IsarCursors,
+        lower_key: &IndexKey,
+        upper_key: &IndexKey,
+    ) -> Result<u64> {
+        let mut cursor = cursors.get_cursor(self.db)?;
+        cursor.estimate_between(lower_key, upper_key)
+    }
+
     pub fn get_id<'txn, 'env>(
         &self,
         cursors: &IsarCursors<'txn, 'env>,
@@ -164,10 +397,70 @@ impl IsarIndex {
         Ok(result)
     }
 
+    /// The number of objects stored for `key`.
+    pub fn count_key(&self, cursors: &IsarCursors, key: &IndexKey) -> Result<u64> {
+        let mut cursor = cursors.get_cursor(self.db)?;
+        if cursor.move_to(key)?.is_none() {
+            Ok(0)
+        } else if self.unique {
+            Ok(1)
+        } else {
+            cursor.count_duplicates()
+        }
+    }
+
     pub fn get_size(&self, cursors: &IsarCursors) -> Result<u64> {
         Ok(cursors.db_stat(self.db)?.1)
     }
 
+    pub fn warm(&self, cursors: &IsarCursors, cancel_token: Option<&AtomicBool>) -> Result<u64> {
+        cursors.warm_db(self.db, cancel_token)
+    }
+
+    pub fn get_entries(&self, cursors: &IsarCursors) -> Result<u64> {
+        Ok(cursors.db_stat(self.db)?.0)
+    }
+
+    pub fn get_stats(&self, cursors: &IsarCursors) -> Result<IndexStats> {
+        Ok(self.get_stats_capped(cursors, u64::MAX)?.0)
+    }
+
+    /// Like [IsarIndex::get_stats] but stops counting the distinct keys after
+    /// `max_distinct_keys`. Also returns whether counting stopped early, `distinct_keys` is only a
+    /// lower bound then.
+    pub fn get_stats_capped(
+        &self,
+        cursors: &IsarCursors,
+        max_distinct_keys: u64,
+    ) -> Result<(IndexStats, bool)> {
+        let entries = cursors.db_stat(self.db)?.0;
+        let mut cursor = cursors.get_cursor(self.db)?;
+        let min_key = cursor.move_to_first()?.map(|(key, _)| key.to_vec());
+        let max_key = cursor.move_to_last()?.map(|(key, _)| key.to_vec());
+
+        let (distinct_keys, capped) = if self.unique {
+            (entries, false)
+        } else {
+            let mut count = 0;
+            let complete = cursor.iter_all(true, true, |_, _, _| {
+                if count == max_distinct_keys {
+                    return Ok(false);
+                }
+                count += 1;
+                Ok(true)
+            })?;
+            (count, !complete)
+        };
+
+        let stats = IndexStats {
+            entries,
+            distinct_keys,
+            min_key,
+            max_key,
+        };
+        Ok((stats, capped))
+    }
+
     pub fn clear(&self, cursors: &IsarCursors) -> Result<()> {
         cursors.clear_db(self.db)
     }
@@ -203,3 +496,222 @@ impl IsarIndex {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::IsarInstance;
+    use crate::object::object_builder::ObjectBuilder;
+    use crate::query::Sort;
+
+    #[test]
+    fn test_check_key_size() {
+        let p = Property::debug(DataType::String, 2);
+        let props = vec![p.clone()];
+        let index = |key_overflow| {
+            let properties = vec![IndexProperty::new(p.clone(), IndexType::Value, true)];
+            IsarIndex::new(
+                "idx",
+                Db::gc(),
+                properties,
+                false,
+                false,
+                false,
+                key_overflow,
+                None,
+            )
+        };
+        let hash = index(KeyOverflow::Hash);
+        let error = index(KeyOverflow::Error);
+
+        let long = "a".repeat(IsarIndex::MAX_STRING_INDEX_SIZE + 10);
+        let mut b = ObjectBuilder::new(&props, None);
+        b.write_string(p.offset, Some(&long));
+        let object = b.finish();
+        assert!(hash.check_key_size(object).is_ok());
+        assert_eq!(
+            error.check_key_size(object),
+            Err(IsarError::KeyTooLong {
+                index_name: "idx".to_string(),
+                len: long.len(),
+                max: IsarIndex::MAX_STRING_INDEX_SIZE - 1,
+            })
+        );
+
+        let mut b = ObjectBuilder::new(&props, None);
+        b.write_string(p.offset, Some("short"));
+        assert!(error.check_key_size(b.finish()).is_ok());
+
+        let mut other = long.clone();
+        other.push('b');
+        let mut b = ObjectBuilder::new(&props, None);
+        b.write_string(p.offset, Some(&other));
+        let key_builder = IndexKeyBuilder::new(&hash.properties);
+        assert!(
+            key_builder.create_primitive_key(object)
+                != key_builder.create_primitive_key(b.finish())
+        );
+    }
+
+    #[test]
+    fn test_composite_unique_key() {
+        let tenant = Property::new("tenantId", DataType::Long, 2, None);
+        let email = Property::new("email", DataType::String, 10, None);
+        let props = vec![tenant.clone(), email.clone()];
+        let index = IsarIndex::new(
+            "tenant_email",
+            Db::gc(),
+            vec![
+                IndexProperty::new(tenant.clone(), IndexType::Value, true),
+                IndexProperty::new(email.clone(), IndexType::Hash, false),
+            ],
+            true,
+            false,
+            false,
+            KeyOverflow::Hash,
+            None,
+        );
+        let object = |tenant_id: i64, mail: &str| {
+            let mut b = ObjectBuilder::new(&props, None);
+            b.write_long(tenant.offset, tenant_id);
+            b.write_string(email.offset, Some(mail));
+            b.finish().as_bytes().to_vec()
+        };
+        let key = |bytes: &[u8]| {
+            IndexKeyBuilder::new(&index.properties)
+                .create_primitive_key(IsarObject::from_bytes(bytes))
+        };
+
+        let a = object(1, "a@b.c");
+        assert!(key(&a) == key(&object(1, "A@b.c")));
+        assert!(key(&a) != key(&object(2, "a@b.c")));
+        assert!(key(&a) != key(&object(1, "x@b.c")));
+
+        assert_eq!(
+            index.unique_violation(IsarObject::from_bytes(&a)),
+            IsarError::UniqueViolated {
+                index_name: "tenant_email".to_string(),
+                key: r#"{"email":"a@b.c","tenantId":1}"#.to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_rebuild_index() {
+        let schema = r#"[{
+            "name": "Item",
+            "properties": [{"name": "value", "type": "Long"}],
+            "indexes": [{
+                "name": "value",
+                "properties": [{"name": "value", "type": "Value", "caseSensitive": false}],
+                "unique": false
+            }]
+        }]"#;
+        let instance = IsarInstance::open_test("index_rebuild", schema);
+        let col = &instance.collections[0];
+        let index = &col.indexes[0];
+        let offset = col.properties[0].offset;
+        let object = |value: i64| {
+            let mut b = ObjectBuilder::new(&col.properties, None);
+            b.write_long(offset, value);
+            b.finish().as_bytes().to_vec()
+        };
+
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        for id in 1..=4 {
+            col.put(&mut txn, Some(id), IsarObject::from_bytes(&object(id % 2)))
+                .unwrap();
+        }
+        txn.commit().unwrap();
+
+        let find = |value: i64| {
+            let mut key = IndexKey::new();
+            key.add_long(value);
+            let mut qb = col.new_query_builder();
+            qb.add_index_where_clause(index.id, key.clone(), key, Sort::Ascending, false)
+                .unwrap();
+            let query = qb.build().unwrap();
+            let mut txn = instance.begin_txn(false, false).unwrap();
+            let mut ids = vec![];
+            query
+                .find_while(&mut txn, |id, _| {
+                    ids.push(id);
+                    true
+                })
+                .unwrap();
+            txn.abort();
+            ids
+        };
+        assert_eq!(find(1), vec![1, 3]);
+
+        // drop the entry of object 3 and point object 2 at the wrong value
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        txn.write(instance.instance_id, |cursors, _| {
+            index.delete_for_object(cursors, 3, IsarObject::from_bytes(&object(1)))?;
+            index.delete_for_object(cursors, 2, IsarObject::from_bytes(&object(0)))?;
+            index.create_for_object(cursors, 2, IsarObject::from_bytes(&object(1)), |_| Ok(()))
+        })
+        .unwrap();
+        txn.commit().unwrap();
+        assert_eq!(find(1), vec![1, 2]);
+        assert_eq!(find(0), vec![4]);
+
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        assert_eq!(col.rebuild_index(&mut txn, index.id).unwrap(), 4);
+        txn.commit().unwrap();
+        assert_eq!(find(1), vec![1, 3]);
+        assert_eq!(find(0), vec![2, 4]);
+        instance.close_test();
+    }
+
+    #[test]
+    fn test_count_duplicates() {
+        let schema = r#"[{
+            "name": "Item",
+            "properties": [{"name": "value", "type": "Long"}],
+            "indexes": [{
+                "name": "value",
+                "properties": [{"name": "value", "type": "Value", "caseSensitive": false}],
+                "unique": false
+            }]
+        }]"#;
+        let instance = IsarInstance::open_test("index_count_duplicates", schema);
+        let col = &instance.collections[0];
+        let index = &col.indexes[0];
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        for (id, value) in [(1, 7), (2, 7), (3, 5), (4, 7), (5, 7)] {
+            let mut b = ObjectBuilder::new(&col.properties, None);
+            b.write_long(col.properties[0].offset, value);
+            col.put(&mut txn, Some(id), b.finish()).unwrap();
+        }
+        txn.commit().unwrap();
+
+        let key = |value: i64| {
+            let mut key = IndexKey::new();
+            key.add_long(value);
+            key
+        };
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        txn.read(instance.instance_id, |cursors| {
+            let mut cursor = cursors.get_cursor(index.db)?;
+            cursor.move_to(&key(7))?.unwrap();
+            assert_eq!(cursor.count_duplicates()?, 4);
+            cursor.move_to(&key(5))?.unwrap();
+            assert_eq!(cursor.count_duplicates()?, 1);
+
+            assert_eq!(index.count_key(cursors, &key(7))?, 4);
+            assert_eq!(index.count_key(cursors, &key(6))?, 0);
+
+            let mut cursor = cursors.get_cursor(col.db)?;
+            cursor.move_to_first()?.unwrap();
+            assert!(matches!(
+                cursor.count_duplicates(),
+                Err(IsarError::IllegalArg { .. })
+            ));
+            Ok(())
+        })
+        .unwrap();
+        txn.abort();
+        instance.close_test();
+    }
+}
@@ -0,0 +1,132 @@
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// Splits strings into the words stored by [crate::schema::index_schema::IndexType::Words]
+/// indexes.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum Tokenizer {
+    /// Words are separated by whitespace. Punctuation stays part of the words.
+    Whitespace,
+    /// Words are runs of alphanumeric characters. Everything else separates words.
+    #[default]
+    Unicode,
+}
+
+/// A word of a full-text query. Prefix terms match every word that starts with them.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub(crate) struct Term {
+    pub word: String,
+    pub prefix: bool,
+}
+
+impl Tokenizer {
+    /// The distinct words of `text`, lowercased unless `case_sensitive` is set.
+    pub fn words(&self, text: &str, case_sensitive: bool) -> Vec<String> {
+        let words: Box<dyn Iterator<Item = &str>> = match self {
+            Tokenizer::Whitespace => Box::new(text.split_whitespace()),
+            Tokenizer::Unicode => Box::new(text.split(|c: char| !c.is_alphanumeric())),
+        };
+        words
+            .filter(|word| !word.is_empty())
+            .map(|word| fold_case(word, case_sensitive))
+            .sorted()
+            .dedup()
+            .collect()
+    }
+
+    /// Parses a query like `foo bar*` into terms that all need to match. A trailing `*` turns the
+    /// last word of a term into a prefix.
+    pub(crate) fn parse_query(&self, query: &str, case_sensitive: bool) -> Vec<Term> {
+        let mut terms = vec![];
+        for part in query.split_whitespace() {
+            let (part, prefix) = if let Some(part) = part.strip_suffix('*') {
+                (part, true)
+            } else {
+                (part, false)
+            };
+            let words = match self {
+                Tokenizer::Whitespace => vec![fold_case(part, case_sensitive)],
+                Tokenizer::Unicode => part
+                    .split(|c: char| !c.is_alphanumeric())
+                    .filter(|word| !word.is_empty())
+                    .map(|word| fold_case(word, case_sensitive))
+                    .collect(),
+            };
+            let last = words.len().saturating_sub(1);
+            for (i, word) in words.into_iter().enumerate() {
+                if !word.is_empty() {
+                    terms.push(Term {
+                        word,
+                        prefix: prefix && i == last,
+                    });
+                }
+            }
+        }
+        terms
+    }
+}
+
+impl Term {
+    pub fn matches(&self, word: &str) -> bool {
+        if self.prefix {
+            word.starts_with(&self.word)
+        } else {
+            word == self.word
+        }
+    }
+}
+
+fn fold_case(word: &str, case_sensitive: bool) -> String {
+    if case_sensitive {
+        word.to_string()
+    } else {
+        word.to_lowercase()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_words() {
+        let text = "The quick, brown fox-trot. THE end";
+        assert_eq!(
+            Tokenizer::Unicode.words(text, false),
+            vec!["brown", "end", "fox", "quick", "the", "trot"]
+        );
+        assert_eq!(
+            Tokenizer::Whitespace.words(text, true),
+            vec!["THE", "The", "brown", "end", "fox-trot.", "quick,"]
+        );
+        assert!(Tokenizer::Unicode.words(" ,. ", false).is_empty());
+        assert_eq!(
+            Tokenizer::Unicode.words("Straße über", false),
+            vec!["straße", "über"]
+        );
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let term = |word: &str, prefix: bool| Term {
+            word: word.to_string(),
+            prefix,
+        };
+        assert_eq!(
+            Tokenizer::Unicode.parse_query("Foo bar*", false),
+            vec![term("foo", false), term("bar", true)]
+        );
+        assert_eq!(
+            Tokenizer::Unicode.parse_query("fox-tr* * ", false),
+            vec![term("fox", false), term("tr", true)]
+        );
+        assert_eq!(
+            Tokenizer::Whitespace.parse_query("fox-tr*", true),
+            vec![term("fox-tr", true)]
+        );
+
+        let prefix = term("qu", true);
+        assert!(prefix.matches("quick") && prefix.matches("qu") && !prefix.matches("q"));
+        assert!(term("quick", false).matches("quick") && !term("quick", false).matches("quicker"));
+    }
+}
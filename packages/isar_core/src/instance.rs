@@ -1,22 +1,43 @@
+use crate::archive::{ArchiveReader, ArchiveWriter, CollectionManifest, ExportManifest, Frame};
 use crate::collection::IsarCollection;
+use crate::collection_diff::{CollectionDiff, DeltaStats};
+use crate::cursor::{CursorPool, IsarCursors};
 use crate::error::*;
+use crate::health::HealthStatus;
+use crate::index::index_key::IndexKey;
+use crate::mdbx::db::Db;
 use crate::mdbx::env::Env;
-use crate::query::Query;
+use crate::mdbx::retry_busy;
+use crate::object::id::BytesToId;
+use crate::object::isar_object::IsarObject;
+use crate::oplog::{self, OpLogEntry};
+use crate::pinning::{os_page_size, PageRanges, PinnedPages};
+use crate::query::aggregate_cache::CachedValue;
+use crate::query::{Query, SlowQueryLog};
+use crate::schema::migration_plan::MigrationPlan;
 use crate::schema::schema_manager::SchemaManager;
 use crate::schema::Schema;
-use crate::txn::IsarTxn;
-use crate::watch::change_set::ChangeSet;
+use crate::txn::{AbortLog, IsarTxn};
+use crate::txn_gate::TxnGate;
+use crate::watch::change_set::{ChangeSet, PendingChanges};
+use crate::watch::debouncer::Debouncer;
 use crate::watch::isar_watchers::{IsarWatchers, WatcherModifier};
-use crate::watch::watcher::WatcherCallback;
+use crate::watch::watcher::{IdsWatcherCallback, WatcherCallback};
 use crate::watch::WatchHandle;
+use crate::write_queue::{WriteOp, WriteQueue};
+use byteorder::{ByteOrder, LittleEndian};
 use crossbeam_channel::{unbounded, Sender};
 use intmap::IntMap;
 use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::fs::remove_file;
 use std::fs::{self, metadata};
+use std::io::{Read, Write};
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 use xxhash_rust::xxh3::xxh3_64;
 
 static INSTANCES: Lazy<RwLock<IntMap<Arc<IsarInstance>>>> =
@@ -24,46 +45,138 @@ static INSTANCES: Lazy<RwLock<IntMap<Arc<IsarInstance>>>> =
 
 static WATCHER_ID: AtomicU64 = AtomicU64::new(0);
 
+static MAX_INSTANCES: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+const MAX_RAW_DBS: u64 = if cfg!(feature = "raw-access") { 16 } else { 0 };
+
 pub struct CompactCondition {
     pub min_file_size: u64,
     pub min_bytes: u64,
     pub min_ratio: f64,
 }
 
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum CloseResult {
+    Closed,
+    /// The instance stays open because other handles still use it.
+    StillReferenced {
+        remaining: usize,
+    },
+    /// Pending data could not be flushed or the files could not be deleted.
+    Error(IsarError),
+}
+
+pub struct FreelistInfo {
+    pub free_pages: u64,
+    pub unused_pages: u64,
+    pub page_size: u32,
+    pub reclaimable_bytes: u64,
+}
+
+/// An environment that hosts multiple named instances in a single file. The instances share the
+/// page cache and reader slots but their databases are namespaced by the instance name.
+///
+/// The file is closed once the handle and all instances opened from it are gone.
+#[derive(Clone)]
+pub struct SharedEnv {
+    dir: String,
+    path: String,
+    env: Arc<Env>,
+}
+
+impl SharedEnv {
+    /// Opens the environment with room for the databases of one instance per schema in
+    /// `schemas`.
+    pub fn open(
+        name: &str,
+        dir: &str,
+        schemas: &[Schema],
+        max_size_mib: usize,
+        page_size: Option<usize>,
+        relaxed_durability: bool,
+    ) -> Result<Self> {
+        IsarInstance::check_page_size(page_size)?;
+        IsarInstance::prepare_dir(dir, true)?;
+        let isar_file = IsarInstance::get_isar_path(name, dir);
+        let max_dbs = schemas.iter().map(IsarInstance::max_dbs).sum();
+        let env = Env::create(
+            &isar_file,
+            max_dbs,
+            max_size_mib.max(1),
+            page_size,
+            relaxed_durability,
+        )
+        .map_err(|e| IsarError::EnvError { error: Box::new(e) })?;
+        Ok(SharedEnv {
+            dir: dir.to_string(),
+            path: isar_file,
+            env: Arc::new(env),
+        })
+    }
+
+    // instances of different environments may use the same name
+    fn instance_id(&self, name: &str) -> u64 {
+        xxh3_64(format!("{}/{}", self.path, name).as_bytes())
+    }
+}
+
 pub struct IsarInstance {
     pub name: String,
     pub dir: String,
     pub collections: Vec<IsarCollection>,
     pub(crate) instance_id: u64,
 
-    env: Env,
+    schema: Schema,
+    env: Arc<Env>,
+    db_prefix: String,
+    counter_db: Db,
+    oplog_db: Db,
+    slow_query_log: RwLock<Option<Arc<SlowQueryLog>>>,
+    strict_read_commit: AtomicBool,
+    cursor_pool_size: AtomicUsize,
+    auto_reader_check: AtomicBool,
+    abort_log: AbortLog,
+    pinned: Mutex<IntMap<PinnedPages>>,
+    txn_gate: TxnGate,
+    write_queue: WriteQueue,
+    pending_changes: Mutex<PendingChanges>,
     watchers: Mutex<IsarWatchers>,
     watcher_modifier_sender: Sender<WatcherModifier>,
 }
 
 impl IsarInstance {
+    /// Opening the environment is retried `attempts` times with exponential backoff starting at
+    /// the given delay if `open_retry` is set and another process holds it busy.
+    #[allow(clippy::too_many_arguments)]
     pub fn open(
         name: &str,
         dir: Option<&str>,
         schema: Schema,
         max_size_mib: usize,
+        page_size: Option<usize>,
         relaxed_durability: bool,
         compact_condition: Option<CompactCondition>,
+        create_dir: bool,
+        open_retry: Option<(u32, Duration)>,
     ) -> Result<Arc<Self>> {
         let mut lock = INSTANCES.write().unwrap();
         let instance_id = xxh3_64(name.as_bytes());
         if let Some(instance) = lock.get(instance_id) {
             Ok(instance.clone())
         } else {
+            Self::check_instance_limit(lock.len())?;
             if let Some(dir) = dir {
+                Self::prepare_dir(dir, create_dir)?;
                 let new_instance = Self::open_internal(
                     name,
                     dir,
                     instance_id,
                     schema,
                     max_size_mib,
+                    page_size,
                     relaxed_durability,
                     compact_condition,
+                    open_retry,
                 )?;
                 let new_instance = Arc::new(new_instance);
                 lock.insert(instance_id, new_instance.clone());
@@ -76,6 +189,74 @@ impl IsarInstance {
         }
     }
 
+    /// Opens the instance `name` inside a shared environment. Opening the same name in the same
+    /// environment again returns the existing instance.
+    pub fn open_named(shared_env: &SharedEnv, name: &str, schema: Schema) -> Result<Arc<Self>> {
+        if name.is_empty() || name.contains('/') {
+            illegal_arg("Instance name must not be empty or contain '/'.")?;
+        }
+        let mut lock = INSTANCES.write().unwrap();
+        let instance_id = shared_env.instance_id(name);
+        if let Some(instance) = lock.get(instance_id) {
+            Ok(instance.clone())
+        } else {
+            Self::check_instance_limit(lock.len())?;
+            let new_instance = Self::open_with_env(
+                name,
+                &shared_env.dir,
+                instance_id,
+                shared_env.env.clone(),
+                format!("{}/", name),
+                schema,
+            )?;
+            let new_instance = Arc::new(new_instance);
+            lock.insert(instance_id, new_instance.clone());
+            Ok(new_instance)
+        }
+    }
+
+    /// Limits how many instances can be open at the same time in this process. Opening another
+    /// instance fails with [IsarError::TooManyInstances] until one is closed. Instances that are
+    /// already open are not affected. `None` removes the limit.
+    pub fn set_max_instances(max: Option<usize>) {
+        MAX_INSTANCES.store(max.unwrap_or(usize::MAX), Ordering::Relaxed);
+    }
+
+    fn check_instance_limit(open_instances: usize) -> Result<()> {
+        let max = MAX_INSTANCES.load(Ordering::Relaxed);
+        if open_instances >= max {
+            Err(IsarError::TooManyInstances { max })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn prepare_dir(dir: &str, create_dir: bool) -> Result<()> {
+        let path_error = || IsarError::PathError {
+            path: dir.to_string(),
+        };
+        if create_dir {
+            fs::create_dir_all(dir).map_err(|_| path_error())
+        } else if fs::metadata(dir).is_ok_and(|m| m.is_dir()) {
+            Ok(())
+        } else {
+            Err(path_error())
+        }
+    }
+
+    fn check_page_size(page_size: Option<usize>) -> Result<()> {
+        if let Some(page_size) = page_size {
+            if !page_size.is_power_of_two() || !(256..=65536).contains(&page_size) {
+                illegal_arg("Page size must be a power of two between 256 and 65536.")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn max_dbs(schema: &Schema) -> u64 {
+        schema.count_dbs() as u64 + 5 + MAX_RAW_DBS
+    }
+
     fn get_isar_path(name: &str, dir: &str) -> String {
         let mut file_name = name.to_string();
         file_name.push_str(".isar");
@@ -99,30 +280,76 @@ impl IsarInstance {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn open_internal(
         name: &str,
         dir: &str,
         instance_id: u64,
-        mut schema: Schema,
+        schema: Schema,
         max_size_mib: usize,
+        page_size: Option<usize>,
         relaxed_durability: bool,
         compact_condition: Option<CompactCondition>,
+        open_retry: Option<(u32, Duration)>,
     ) -> Result<Self> {
+        Self::check_page_size(page_size)?;
+
         let isar_file = Self::get_isar_path(name, dir);
 
         Self::move_old_database(name, dir, &isar_file);
 
-        let db_count = schema.count_dbs() as u64 + 3;
-        let env = Env::create(
-            &isar_file,
-            db_count,
-            max_size_mib.max(1),
-            relaxed_durability,
-        )
+        let db_count = Self::max_dbs(&schema);
+        let env = retry_busy(open_retry, || {
+            Env::create(
+                &isar_file,
+                db_count,
+                max_size_mib.max(1),
+                page_size,
+                relaxed_durability,
+            )
+        })
         .map_err(|e| IsarError::EnvError { error: Box::new(e) })?;
 
+        let instance =
+            Self::open_with_env(name, dir, instance_id, Arc::new(env), String::new(), schema)?;
+
+        if let Some(compact_condition) = compact_condition {
+            let schema = instance.schema.clone();
+            let instance = instance.compact(compact_condition)?;
+            if let Some(instance) = instance {
+                Ok(instance)
+            } else {
+                Self::open_internal(
+                    name,
+                    dir,
+                    instance_id,
+                    schema,
+                    max_size_mib,
+                    page_size,
+                    relaxed_durability,
+                    None,
+                    open_retry,
+                )
+            }
+        } else {
+            Ok(instance)
+        }
+    }
+
+    fn open_with_env(
+        name: &str,
+        dir: &str,
+        instance_id: u64,
+        env: Arc<Env>,
+        db_prefix: String,
+        mut schema: Schema,
+    ) -> Result<Self> {
         let txn = env.txn(true)?;
-        let mut manager = SchemaManager::create(instance_id, &txn)?;
+        let mut manager = SchemaManager::create(instance_id, &db_prefix, &txn)?;
+        let counter_db_name = format!("{}_counters", db_prefix);
+        let counter_db = Db::open(&txn, Some(&counter_db_name), false, false, false)?;
+        let oplog_db_name = format!("{}_oplog", db_prefix);
+        let oplog_db = Db::open(&txn, Some(&oplog_db_name), true, false, false)?;
         txn.commit()?;
 
         let txn = env.txn(true)?;
@@ -137,7 +364,9 @@ impl IsarInstance {
                 .get(col_id)
                 .map(|v| v.as_slice())
                 .unwrap_or_default();
-            let col = manager.open_collection(&txn, col_schema, &schema, added_indexes)?;
+            let col =
+                manager.open_collection(&txn, col_schema, &schema, added_indexes, oplog_db)?;
+            col.init_reserved_ids(&IsarCursors::new(&txn, CursorPool::new(0)), counter_db)?;
             collections.push(col);
             txn.commit()?;
         }
@@ -156,28 +385,23 @@ impl IsarInstance {
             dir: dir.to_string(),
             collections,
             instance_id,
+            schema,
+            db_prefix,
+            counter_db,
+            oplog_db,
+            slow_query_log: RwLock::new(None),
+            strict_read_commit: AtomicBool::new(false),
+            cursor_pool_size: AtomicUsize::new(<CursorPool>::DEFAULT_CAPACITY),
+            auto_reader_check: AtomicBool::new(false),
+            abort_log: AbortLog::new(Self::MAX_RECENT_ABORTS),
+            pinned: Mutex::new(IntMap::new()),
+            txn_gate: TxnGate::new(),
+            write_queue: WriteQueue::new(),
+            pending_changes: Mutex::new(PendingChanges::default()),
             watchers: Mutex::new(IsarWatchers::new(rx)),
             watcher_modifier_sender: tx,
         };
-
-        if let Some(compact_condition) = compact_condition {
-            let instance = instance.compact(compact_condition)?;
-            if let Some(instance) = instance {
-                Ok(instance)
-            } else {
-                Self::open_internal(
-                    name,
-                    dir,
-                    instance_id,
-                    schema,
-                    max_size_mib,
-                    relaxed_durability,
-                    None,
-                )
-            }
-        } else {
-            Ok(instance)
-        }
+        Ok(instance)
     }
 
     fn compact(self, compact_condition: CompactCondition) -> Result<Option<Self>> {
@@ -187,7 +411,9 @@ impl IsarInstance {
 
         let isar_file = Self::get_isar_path(&self.name, &self.dir);
         let file_size = metadata(&isar_file)
-            .map_err(|_| IsarError::PathError {})?
+            .map_err(|_| IsarError::PathError {
+                path: isar_file.clone(),
+            })?
             .len();
 
         let compact_bytes = file_size.saturating_sub(instance_size);
@@ -227,8 +453,263 @@ impl IsarInstance {
             None
         };
 
-        let txn = self.env.txn(write)?;
-        IsarTxn::new(self.instance_id, txn, write, change_set)
+        let (txn, active_writer) = if write {
+            let waiting = self.txn_gate.enter_write();
+            let txn = self.env.txn(true)?;
+            (txn, Some(waiting.started()))
+        } else {
+            self.txn_gate.enter_read();
+            if self.auto_reader_check.load(Ordering::Relaxed) {
+                self.check_readers_if_scarce()?;
+            }
+            (self.env.txn(false)?, None)
+        };
+        let slow_query_log = self.slow_query_log.read().unwrap().clone();
+        let mut txn = IsarTxn::new(
+            self.instance_id,
+            txn,
+            write,
+            change_set,
+            slow_query_log,
+            self.cursor_pool_size.load(Ordering::Relaxed),
+        )?;
+        txn.set_strict_read_commit(self.strict_read_commit.load(Ordering::Relaxed));
+        txn.set_abort_log(&self.abort_log);
+        if let Some(active_writer) = active_writer {
+            txn.set_active_writer(active_writer);
+        }
+        Ok(txn)
+    }
+
+    /// Imports a JSON array and commits the transaction after every `commit_every` objects to
+    /// release dirty pages. Each object is written together with its index entries so a partial
+    /// import never leaves the indexes inconsistent. Returns the open transaction containing the
+    /// last batch and the number of imported objects.
+    pub fn import_json_batched<'a>(
+        &'a self,
+        mut txn: IsarTxn<'a>,
+        collection: &IsarCollection,
+        id_name: Option<&str>,
+        json: Value,
+        commit_every: Option<u32>,
+    ) -> Result<(IsarTxn<'a>, u32)> {
+        let array = json.as_array().ok_or(IsarError::InvalidJson {})?;
+        let batch_size = match commit_every {
+            Some(0) => illegal_arg("commit_every must be greater than zero.")?,
+            Some(n) => n as usize,
+            None => array.len().max(1),
+        };
+        let silent = txn.is_silent();
+        for (i, batch) in array.chunks(batch_size).enumerate() {
+            if i > 0 {
+                txn.commit()?;
+                txn = self.begin_txn(true, silent)?;
+            }
+            collection.import_json_values(&mut txn, id_name, batch)?;
+        }
+        Ok((txn, array.len() as u32))
+    }
+
+    /// Like [IsarInstance::begin_txn] but the watchers are not notified when a write transaction is
+    /// committed. The changes are collected until [IsarInstance::notify_all] is called.
+    pub fn begin_txn_deferred(&self, write: bool) -> Result<IsarTxn<'_>> {
+        let mut txn = self.begin_txn(write, false)?;
+        txn.defer_notifications(&self.pending_changes);
+        Ok(txn)
+    }
+
+    /// Notifies the watchers of all changes committed by deferred transactions.
+    pub fn notify_all(&self) {
+        let pending = std::mem::take(&mut *self.pending_changes.lock().unwrap());
+        if !pending.is_empty() {
+            let mut watchers = self.watchers.lock().unwrap();
+            watchers.sync();
+            pending.notify(watchers);
+        }
+    }
+
+    /// Runs `job` in a read transaction taken from a pool of reset transactions. Renewing a pooled
+    /// transaction is cheaper than beginning a new one because its reader slot is kept.
+    ///
+    /// Every call observes the latest committed data. Pooled transactions do not pin old
+    /// snapshots but they keep their reader slots until [IsarInstance::clear_read_pool].
+    pub fn read<T>(&self, job: impl FnOnce(&mut IsarTxn) -> Result<T>) -> Result<T> {
+        let txn = self.env.pooled_read_txn()?;
+        let slow_query_log = self.slow_query_log.read().unwrap().clone();
+        let mut txn = IsarTxn::new(
+            self.instance_id,
+            txn,
+            false,
+            None,
+            slow_query_log,
+            self.cursor_pool_size.load(Ordering::Relaxed),
+        )?;
+        txn.set_abort_log(&self.abort_log);
+        let result = job(&mut txn);
+        self.env.release_read_txn(txn.into_txn());
+        result
+    }
+
+    /// Frees the reader slots of all pooled read transactions.
+    pub fn clear_read_pool(&self) {
+        self.env.clear_read_pool();
+    }
+
+    /// The number of [IsarInstance::read] calls that reused a pooled transaction.
+    pub fn reused_read_count(&self) -> u64 {
+        self.env.reused_read_count()
+    }
+
+    /// Executes `op` in a write transaction that is shared with the writes queued by other
+    /// threads. Writes of a single thread are committed in the order they were submitted.
+    ///
+    /// Must not be called while the current thread holds a write transaction.
+    pub fn enqueue_write(&self, op: WriteOp) -> Result<()> {
+        self.write_queue.submit(self, op)
+    }
+
+    /// The number of write transactions committed by [IsarInstance::enqueue_write].
+    pub fn write_batch_count(&self) -> u64 {
+        self.write_queue.batch_count()
+    }
+
+    /// Enables or disables the slow query log. Transactions that are already running keep the
+    /// previous setting.
+    pub fn set_slow_query_log(&self, log: Option<SlowQueryLog>) {
+        *self.slow_query_log.write().unwrap() = log.map(Arc::new);
+    }
+
+    /// Whether committing a read transaction fails with [IsarError::ReadTxnCommit] instead of just
+    /// releasing it. Useful to find code that commits transactions it did not mean to write in.
+    pub fn set_strict_read_commit(&self, strict: bool) {
+        self.strict_read_commit.store(strict, Ordering::Relaxed);
+    }
+
+    /// How many unused cursors a transaction keeps for reuse. Cursors are preferably reused for
+    /// the database they were last bound to, so transactions that query many collections and
+    /// indexes benefit from a larger pool. Applies to transactions that begin afterwards.
+    pub fn set_cursor_pool_size(&self, size: usize) {
+        self.cursor_pool_size.store(size, Ordering::Relaxed);
+    }
+
+    /// Lets write transactions that wait for the write lock go ahead of read transactions that
+    /// begin later so writers cannot be starved under heavy read load. Beginning a read
+    /// transaction may then block until the waiting writers have started.
+    pub fn set_write_priority(&self, write_priority: bool) {
+        self.txn_gate.set_write_priority(write_priority);
+    }
+
+    const MAX_RECENT_ABORTS: usize = 32;
+
+    /// The most memory all pinned collections of an instance may lock.
+    pub const MAX_PINNED_BYTES: u64 = 64 << 20;
+
+    /// The ids and reasons of the most recent transactions aborted with
+    /// [IsarTxn::abort_with_reason], oldest first.
+    pub fn recent_aborts(&self) -> Vec<(u64, String)> {
+        self.abort_log.recent()
+    }
+
+    /// Frees the reader slots held by processes or threads that died without closing their read
+    /// transactions and returns the number of freed slots.
+    pub fn check_readers(&self) -> Result<u32> {
+        self.env.reader_check()
+    }
+
+    /// Whether [IsarInstance::check_readers] runs automatically before a read transaction begins
+    /// while less than an eighth of the reader slots are free.
+    pub fn set_auto_reader_check(&self, enabled: bool) {
+        self.auto_reader_check.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Locks the pages holding the objects of `collection` into RAM so they are not evicted under
+    /// memory pressure. Pinning is best effort: pages that do not fit into
+    /// [IsarInstance::MAX_PINNED_BYTES] or the `RLIMIT_MEMLOCK` limit stay unpinned and nothing
+    /// is pinned on platforms without `mlock`. Pinning again refreshes the pages after writes.
+    /// Returns the number of pinned bytes.
+    pub fn pin_collection(&self, txn: &mut IsarTxn, collection: &IsarCollection) -> Result<u64> {
+        self.unpin_collection(collection);
+        let mut ranges = PageRanges::new(os_page_size());
+        txn.read(self.instance_id, |cursors| {
+            let mut cursor = cursors.get_cursor(collection.db)?;
+            cursor.iter_all(false, true, |_, _, object| {
+                ranges.add(object);
+                Ok(true)
+            })
+        })?;
+
+        let mut pinned = self.pinned.lock().unwrap();
+        let used = pinned.values().map(|p| p.bytes()).sum::<u64>();
+        let pages = PinnedPages::lock(
+            ranges.into_ranges(),
+            Self::MAX_PINNED_BYTES.saturating_sub(used),
+        );
+        let bytes = pages.bytes();
+        pinned.insert(collection.id, pages);
+        Ok(bytes)
+    }
+
+    /// Releases the pages locked by [IsarInstance::pin_collection]. Does nothing if the
+    /// collection is not pinned.
+    pub fn unpin_collection(&self, collection: &IsarCollection) {
+        if let Some(pages) = self.pinned.lock().unwrap().remove(collection.id) {
+            pages.unlock();
+        }
+    }
+
+    /// A cheap liveness probe. Opens and aborts a read transaction and reports `Degraded` if the
+    /// map is more than 90% full or less than 10% of the reader slots are free. Never waits for
+    /// writers.
+    pub fn health_check(&self) -> Result<HealthStatus> {
+        self.env.txn(false)?.abort();
+        let info = self.env.info()?;
+        let used_bytes = (info.last_page + 1) * info.page_size as u64;
+        Ok(HealthStatus::from_usage(
+            used_bytes,
+            info.map_size,
+            info.num_readers,
+            info.max_readers,
+        ))
+    }
+
+    fn check_readers_if_scarce(&self) -> Result<()> {
+        let info = self.env.info()?;
+        let free = info.max_readers.saturating_sub(info.num_readers);
+        if free <= info.max_readers / 8 {
+            self.env.reader_check()?;
+        }
+        Ok(())
+    }
+
+    /// The schema version stored in the database. It is incremented by every migration that
+    /// changes the schema and is `0` for databases created before versions were tracked.
+    pub fn schema_version(&self) -> Result<u32> {
+        let txn = self.env.txn(false)?;
+        let version = SchemaManager::read_schema_version(&txn, &self.db_prefix);
+        txn.abort();
+        version
+    }
+
+    /// Lists the changes opening this instance with `new_schema` would apply, without modifying
+    /// the database.
+    pub fn plan_migration(&self, new_schema: &Schema) -> MigrationPlan {
+        MigrationPlan::create(&self.schema, new_schema)
+    }
+
+    /// Compares the schema stored in the database with `expected`. The returned plan lists the
+    /// differences and is empty if the schemas match.
+    pub fn schema_diff(&self, expected: &Schema) -> Result<MigrationPlan> {
+        let txn = self.env.txn(false)?;
+        let collections = SchemaManager::read_schemas(&txn, &self.db_prefix);
+        txn.abort();
+        let persisted = Schema {
+            collections: collections?,
+        };
+        Ok(MigrationPlan::create(&persisted, expected))
+    }
+
+    pub fn schema_matches(&self, expected: &Schema) -> Result<bool> {
+        Ok(self.schema_diff(expected)?.is_empty())
     }
 
     pub fn get_size(
@@ -246,10 +727,439 @@ impl IsarInstance {
         Ok(size)
     }
 
+    /// Returns the index and name of each collection in schema order. The index can be passed to
+    /// [IsarInstance::get_collection_by_index] and [IsarInstance::count].
+    pub fn collections_indexed(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.collections
+            .iter()
+            .enumerate()
+            .map(|(index, col)| (index as u16, col.name.as_str()))
+    }
+
+    pub fn get_collection_by_index(&self, collection_index: u16) -> Result<&IsarCollection> {
+        if let Some(col) = self.collections.get(collection_index as usize) {
+            Ok(col)
+        } else {
+            illegal_arg("Collection index is invalid.")
+        }
+    }
+
+    pub fn count(&self, txn: &mut IsarTxn, collection_index: u16) -> Result<u64> {
+        self.get_collection_by_index(collection_index)?.count(txn)
+    }
+
+    /// Reserves a block of `count` consecutive ids in a collection and returns the first one.
+    /// The auto increment never generates reserved ids so they can be used for objects that
+    /// reference each other before they are inserted.
+    pub fn reserve_ids(&self, txn: &mut IsarTxn, collection_index: u16, count: u32) -> Result<i64> {
+        let col = self.get_collection_by_index(collection_index)?;
+        txn.write(self.instance_id, |cursors, change_set| {
+            if let Some(change_set) = change_set {
+                change_set.mark_changed();
+            }
+            col.reserve_ids(cursors, self.counter_db, count)
+        })
+    }
+
+    const DIAGNOSTICS_MAX_KEY_BYTES: usize = 64;
+
+    const DIAGNOSTICS_MAX_DISTINCT_KEYS: u64 = 10_000;
+
+    /// Collects the schema, sizes and index statistics of all collections together with the
+    /// environment info into a JSON report. Index keys are truncated to keep the report small and
+    /// at most [Self::DIAGNOSTICS_MAX_DISTINCT_KEYS] distinct keys are counted per index, so the
+    /// report stays cheap for large databases.
+    pub fn diagnostics_report(&self, txn: &mut IsarTxn) -> Result<String> {
+        let format_key = |key: Option<Vec<u8>>| -> Value {
+            if let Some(key) = key {
+                let truncated = key.len() > Self::DIAGNOSTICS_MAX_KEY_BYTES;
+                let hex: String = key
+                    .iter()
+                    .take(Self::DIAGNOSTICS_MAX_KEY_BYTES)
+                    .map(|b| format!("{:02x}", b))
+                    .collect();
+                json!({ "hex": hex, "truncated": truncated })
+            } else {
+                Value::Null
+            }
+        };
+
+        let mut collections = vec![];
+        for col in &self.collections {
+            let index_stats = txn.read(self.instance_id, |cursors| {
+                col.indexes
+                    .iter()
+                    .map(|index| {
+                        index.get_stats_capped(cursors, Self::DIAGNOSTICS_MAX_DISTINCT_KEYS)
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })?;
+            let mut indexes = vec![];
+            for (index, (stats, capped)) in col.indexes.iter().zip(index_stats) {
+                indexes.push(json!({
+                    "name": index.name,
+                    "entries": stats.entries,
+                    "distinctKeys": stats.distinct_keys,
+                    "distinctKeysCapped": capped,
+                    "minKey": format_key(stats.min_key),
+                    "maxKey": format_key(stats.max_key),
+                }));
+            }
+
+            let schema = self.schema.get_collection(&col.name, false);
+            collections.push(json!({
+                "name": col.name,
+                "schema": schema,
+                "count": col.count(txn)?,
+                "size": col.get_size(txn, false, false)?,
+                "sizeWithIndexesAndLinks": col.get_size(txn, true, true)?,
+                "indexes": indexes,
+            }));
+        }
+
+        let embedded: Vec<_> = self
+            .schema
+            .collections
+            .iter()
+            .filter(|c| c.embedded)
+            .collect();
+
+        let info = self.env.info()?;
+        let report = json!({
+            "name": self.name,
+            "collections": collections,
+            "embedded": embedded,
+            "env": {
+                "mapSize": info.map_size,
+                "pageSize": info.page_size,
+                "lastPage": info.last_page,
+                "recentTxnId": info.recent_txn_id,
+                "numReaders": info.num_readers,
+                "maxReaders": info.max_readers,
+            },
+        });
+
+        Ok(report.to_string())
+    }
+
+    /// How many transactions have been committed since `txn` began.
+    pub fn txn_lag(&self, txn: &IsarTxn) -> Result<u64> {
+        let info = self.env.info()?;
+        Ok(info.recent_txn_id.saturating_sub(txn.id()))
+    }
+
+    /// Counts the pages on the MDBX free list and the unused pages at the end of the file. Their
+    /// size is an estimate of how much space compaction would reclaim.
+    pub fn freelist_info(&self, txn: &mut IsarTxn) -> Result<FreelistInfo> {
+        let info = self.env.info()?;
+        let free_pages = txn.read(self.instance_id, |cursors| {
+            let mut free_pages = 0;
+            let mut cursor = cursors.get_cursor(Db::gc())?;
+            cursor.iter_all(false, true, |_, _, page_list| {
+                // Each GC record is a list of page numbers prefixed with its length
+                if page_list.len() >= 4 {
+                    free_pages += LittleEndian::read_u32(page_list) as u64;
+                }
+                Ok(true)
+            })?;
+            Ok(free_pages)
+        })?;
+
+        let page_size = info.page_size.max(1);
+        let allocated_pages = info.file_size / page_size as u64;
+        let unused_pages = allocated_pages.saturating_sub(info.last_page + 1);
+        Ok(FreelistInfo {
+            free_pages,
+            unused_pages,
+            page_size,
+            reclaimable_bytes: (free_pages + unused_pages) * page_size as u64,
+        })
+    }
+
+    /// Adds `by` to the counter `name` and returns the new value. Counters that do not exist yet
+    /// start at 0.
+    pub fn counter_incr(&self, txn: &mut IsarTxn, name: &str, by: i64) -> Result<i64> {
+        let key = IndexKey::from_bytes(name.as_bytes().to_vec());
+        txn.write(self.instance_id, |cursors, change_set| {
+            let mut cursor = cursors.get_cursor(self.counter_db)?;
+            let value = if let Some((_, bytes)) = cursor.move_to(&key)? {
+                LittleEndian::read_i64(bytes)
+            } else {
+                0
+            };
+            let value = if let Some(value) = value.checked_add(by) {
+                value
+            } else {
+                return illegal_arg("Counter overflow");
+            };
+            cursor.put(&key, &value.to_le_bytes())?;
+            if let Some(change_set) = change_set {
+                change_set.mark_changed();
+            }
+            Ok(value)
+        })
+    }
+
+    pub fn counter_get(&self, txn: &mut IsarTxn, name: &str) -> Result<i64> {
+        let key = IndexKey::from_bytes(name.as_bytes().to_vec());
+        txn.read(self.instance_id, |cursors| {
+            let mut cursor = cursors.get_cursor(self.counter_db)?;
+            let value = cursor.move_to(&key)?;
+            Ok(value.map_or(0, |(_, bytes)| LittleEndian::read_i64(bytes)))
+        })
+    }
+
+    /// Calls `step` for every item in `0..total` and commits a write transaction after every
+    /// `batch_size` items together with the progress of the job. If a previous run with the same
+    /// `job_id` was interrupted, the items it already committed are skipped. The progress is
+    /// removed once the job completes. Returns the item the job was resumed at.
+    pub fn run_checkpointed<F>(
+        &self,
+        job_id: &str,
+        total: u32,
+        batch_size: u32,
+        mut step: F,
+    ) -> Result<u32>
+    where
+        F: FnMut(&mut IsarTxn, u32) -> Result<()>,
+    {
+        if batch_size == 0 {
+            illegal_arg("Batch size must be greater than 0.")?;
+        }
+        let key = IndexKey::from_bytes(format!("_isar_checkpoint_{}", job_id).into_bytes());
+
+        let mut txn = self.begin_txn(false, true)?;
+        let resumed_at = txn.read(self.instance_id, |cursors| {
+            let mut cursor = cursors.get_cursor(self.counter_db)?;
+            let value = cursor.move_to(&key)?;
+            Ok(value.map_or(0, |(_, bytes)| LittleEndian::read_u32(bytes)))
+        })?;
+        txn.abort();
+
+        let mut next = resumed_at;
+        while next < total {
+            let end = next.saturating_add(batch_size).min(total);
+            let mut txn = self.begin_txn(true, false)?;
+            for item in next..end {
+                step(&mut txn, item)?;
+            }
+            txn.write(self.instance_id, |cursors, change_set| {
+                let mut cursor = cursors.get_cursor(self.counter_db)?;
+                if end < total {
+                    cursor.put(&key, &end.to_le_bytes())?;
+                } else if cursor.move_to(&key)?.is_some() {
+                    cursor.delete_current()?;
+                }
+                if let Some(change_set) = change_set {
+                    change_set.mark_changed();
+                }
+                Ok(())
+            })?;
+            txn.commit()?;
+            next = end;
+        }
+        Ok(resumed_at)
+    }
+
+    /// Returns the operation log entries of `collection` with a sequence number greater than
+    /// `since_seq` in the order they were committed, at most `limit` of them. Sync engines store the
+    /// sequence number of the last entry they processed and pass it on the next call. Only
+    /// collections with `oplog` enabled in their schema are logged.
+    pub fn changes_since(
+        &self,
+        txn: &mut IsarTxn,
+        collection: &IsarCollection,
+        since_seq: i64,
+        limit: Option<u32>,
+    ) -> Result<Vec<OpLogEntry>> {
+        if !collection.has_oplog() {
+            illegal_arg("The collection does not have an operation log.")?;
+        }
+        let limit = limit.map_or(usize::MAX, |l| l as usize);
+        txn.read(self.instance_id, |cursors| {
+            oplog::read(cursors, self.oplog_db, collection.id, since_seq, limit)
+        })
+    }
+
+    /// Removes all operation log entries with a sequence number lower than `before_seq`. The newest
+    /// entry is kept so sequence numbers keep increasing. Returns the number of removed entries.
+    pub fn prune_changes(&self, txn: &mut IsarTxn, before_seq: i64) -> Result<u32> {
+        txn.write(self.instance_id, |cursors, change_set| {
+            let count = oplog::prune(cursors, self.oplog_db, before_seq)?;
+            if let (Some(change_set), true) = (change_set, count > 0) {
+                change_set.mark_changed();
+            }
+            Ok(count)
+        })
+    }
+
     pub fn copy_to_file(&self, path: &str) -> Result<()> {
         self.env.copy(path)
     }
 
+    /// Writes the schema and the objects and links of all collections to a single archive. All
+    /// collections are read in `txn` so the archive is a consistent snapshot.
+    pub fn export_all<W: Write>(
+        &self,
+        txn: &mut IsarTxn,
+        writer: &mut W,
+    ) -> Result<ExportManifest> {
+        let schema = serde_json::to_vec(&self.schema).map_err(|_| IsarError::InvalidJson {})?;
+        let txn_id = txn.id();
+        txn.read(self.instance_id, |cursors| {
+            let mut archive = ArchiveWriter::new(writer, txn_id)?;
+            archive.write(&Frame::Schema(schema))?;
+            let mut manifest = ExportManifest {
+                txn_id,
+                collections: vec![],
+            };
+            for col in &self.collections {
+                archive.write(&Frame::Collection(col.name.clone()))?;
+                let mut entry = CollectionManifest {
+                    name: col.name.clone(),
+                    ..Default::default()
+                };
+
+                let mut cursor = cursors.get_cursor(col.db)?;
+                cursor.iter_all(false, true, |_, id_bytes, bytes| {
+                    archive.write(&Frame::Object(id_bytes.to_id(), bytes.to_vec()))?;
+                    entry.objects += 1;
+                    Ok(true)
+                })?;
+                for link in &col.links {
+                    for (source_id, target_id) in link.get_all(cursors)? {
+                        archive.write(&Frame::Link(link.id, source_id, target_id))?;
+                        entry.links += 1;
+                    }
+                }
+                manifest.collections.push(entry);
+            }
+            archive.write(&Frame::End)?;
+            Ok(manifest)
+        })
+    }
+
+    /// Writes an archive with the objects of `collection` that are missing in `peer_hashes` or
+    /// differ from it, and delete frames for the ids of `peer_hashes` that no longer exist.
+    /// `peer_hashes` maps ids to the hashes returned by [IsarCollection::object_hashes] on the
+    /// peer. Links are not included. Importing the archive with [IsarInstance::import_all] on
+    /// the peer makes its collection equal to this one.
+    pub fn export_delta<W: Write>(
+        &self,
+        txn: &mut IsarTxn,
+        collection: &IsarCollection,
+        peer_hashes: &HashMap<i64, u64>,
+        writer: &mut W,
+    ) -> Result<DeltaStats> {
+        let schema = serde_json::to_vec(&self.schema).map_err(|_| IsarError::InvalidJson {})?;
+        let txn_id = txn.id();
+        let hashes = collection.object_hashes(txn)?;
+        let diff = CollectionDiff::from_peer_hashes(&hashes, peer_hashes);
+        let mut changed = diff.only_in_a;
+        changed.extend(diff.different);
+        changed.sort_unstable();
+
+        txn.read(self.instance_id, |cursors| {
+            let mut archive = ArchiveWriter::new(writer, txn_id)?;
+            archive.write(&Frame::Schema(schema))?;
+            archive.write(&Frame::Collection(collection.name.clone()))?;
+            let mut cursor = cursors.get_cursor(collection.db)?;
+            for id in &changed {
+                let (_, bytes) = cursor.move_to(id)?.ok_or(IsarError::DbCorrupted {
+                    message: "Object disappeared during export".to_string(),
+                })?;
+                archive.write(&Frame::Object(*id, bytes.to_vec()))?;
+            }
+            for id in &diff.only_in_b {
+                archive.write(&Frame::Delete(*id))?;
+            }
+            archive.write(&Frame::End)?;
+            Ok(DeltaStats {
+                objects: changed.len() as u64,
+                deleted: diff.only_in_b.len() as u64,
+                unchanged: (hashes.len() - changed.len()) as u64,
+            })
+        })
+    }
+
+    /// Restores an archive written by [IsarInstance::export_all]. It is meant for a fresh
+    /// instance: existing objects with the same id are replaced. Every archived collection must
+    /// exist with the same properties. Returns the manifest of the archive.
+    pub fn import_all<R: Read>(&self, txn: &mut IsarTxn, reader: &mut R) -> Result<ExportManifest> {
+        txn.write(self.instance_id, |cursors, mut change_set| {
+            let mut archive = ArchiveReader::new(reader)?;
+            let schema: Schema = match archive.read()? {
+                Frame::Schema(json) => {
+                    serde_json::from_slice(&json).map_err(|_| IsarError::InvalidArchive {})?
+                }
+                _ => return Err(IsarError::InvalidArchive {}),
+            };
+            let mut manifest = ExportManifest {
+                txn_id: archive.txn_id,
+                collections: vec![],
+            };
+
+            // Links are created last because their targets may be in a later collection.
+            let mut links = vec![];
+            let mut current: Option<&IsarCollection> = None;
+            loop {
+                match archive.read()? {
+                    Frame::Collection(name) => {
+                        let col_schema = schema
+                            .collections
+                            .iter()
+                            .find(|c| c.name == name && !c.embedded)
+                            .ok_or(IsarError::InvalidArchive {})?;
+                        let col = self
+                            .collections
+                            .iter()
+                            .find(|c| c.name == name)
+                            .ok_or(IsarError::SchemaMismatch {})?;
+                        if col_schema.get_properties() != col.properties {
+                            return Err(IsarError::SchemaMismatch {});
+                        }
+                        manifest.collections.push(CollectionManifest {
+                            name,
+                            ..Default::default()
+                        });
+                        current = Some(col);
+                    }
+                    Frame::Object(id, bytes) => {
+                        let col = current.ok_or(IsarError::InvalidArchive {})?;
+                        let object = IsarObject::from_bytes(&bytes);
+                        col.put_internal(cursors, change_set.as_deref_mut(), Some(id), object)?;
+                        manifest.collections.last_mut().unwrap().objects += 1;
+                    }
+                    Frame::Delete(id) => {
+                        let col = current.ok_or(IsarError::InvalidArchive {})?;
+                        col.delete_internal(cursors, true, change_set.as_deref_mut(), id)?;
+                        manifest.collections.last_mut().unwrap().deleted += 1;
+                    }
+                    Frame::Link(link_id, source_id, target_id) => {
+                        let col = current.ok_or(IsarError::InvalidArchive {})?;
+                        let link = col
+                            .links
+                            .iter()
+                            .find(|l| l.id == link_id)
+                            .ok_or(IsarError::SchemaMismatch {})?;
+                        links.push((link, source_id, target_id));
+                        manifest.collections.last_mut().unwrap().links += 1;
+                    }
+                    Frame::End => break,
+                    Frame::Schema(_) => return Err(IsarError::InvalidArchive {}),
+                }
+            }
+
+            for (link, source_id, target_id) in &links {
+                link.create(cursors, *source_id, *target_id)?;
+            }
+            if let (Some(change_set), false) = (change_set, links.is_empty()) {
+                change_set.mark_changed();
+            }
+            Ok(manifest)
+        })
+    }
+
     fn new_watcher(&self, start: WatcherModifier, stop: WatcherModifier) -> WatchHandle {
         self.watcher_modifier_sender.try_send(start).unwrap();
 
@@ -277,6 +1187,24 @@ impl IsarInstance {
         )
     }
 
+    pub fn watch_collection_ids(
+        &self,
+        collection: &IsarCollection,
+        callback: IdsWatcherCallback,
+    ) -> WatchHandle {
+        let watcher_id = WATCHER_ID.fetch_add(1, Ordering::SeqCst);
+        let col_id = collection.id;
+        self.new_watcher(
+            Box::new(move |iw| {
+                iw.get_col_watchers(col_id)
+                    .add_ids_watcher(watcher_id, callback);
+            }),
+            Box::new(move |iw| {
+                iw.get_col_watchers(col_id).remove_ids_watcher(watcher_id);
+            }),
+        )
+    }
+
     pub fn watch_object(
         &self,
         collection: &IsarCollection,
@@ -316,40 +1244,166 @@ impl IsarInstance {
         )
     }
 
-    fn close_internal(self: Arc<Self>, delete_from_disk: bool) -> bool {
+    /// Computes an aggregate of `query` with `aggregate` and passes it to `callback`, once right
+    /// away and again after every committed change of the collection. Changes within `debounce`
+    /// of each other are combined into one update. The values are computed in read
+    /// transactions on a background thread and failed computations are skipped.
+    pub fn watch_aggregate<A, F>(
+        &self,
+        collection: &IsarCollection,
+        query: Query,
+        debounce: Option<Duration>,
+        aggregate: A,
+        callback: F,
+    ) -> WatchHandle
+    where
+        A: Fn(&Query, &mut IsarTxn) -> Result<CachedValue> + Send + Sync + 'static,
+        F: Fn(CachedValue) + Send + Sync + 'static,
+    {
+        let instance_id = self.instance_id;
+        let debouncer = Debouncer::new(debounce.unwrap_or_default(), move || {
+            let instance = INSTANCES.read().unwrap().get(instance_id).cloned();
+            if let Some(instance) = instance {
+                let value = instance.begin_txn(false, true).and_then(|mut txn| {
+                    let value = aggregate(&query, &mut txn);
+                    txn.abort();
+                    value
+                });
+                if let Ok(value) = value {
+                    callback(value);
+                }
+            }
+        });
+        debouncer.trigger();
+        self.watch_collection(collection, Box::new(move || debouncer.trigger()))
+    }
+
+    fn close_internal(
+        self: Arc<Self>,
+        delete_from_disk: bool,
+        compact_condition: Option<CompactCondition>,
+    ) -> CloseResult {
+        // The handle of the caller and the registry entry
+        const OWN_REFERENCES: usize = 2;
+
         // Check whether all other references are gone
-        if Arc::strong_count(&self) == 2 {
+        if Arc::strong_count(&self) == OWN_REFERENCES {
             let mut lock = INSTANCES.write().unwrap();
             // Check again to make sure there are no new references
-            if Arc::strong_count(&self) == 2 {
+            if Arc::strong_count(&self) == OWN_REFERENCES {
+                self.notify_all();
+                if !delete_from_disk {
+                    if let Err(e) = self.env.sync() {
+                        return CloseResult::Error(e);
+                    }
+                }
                 lock.remove(self.instance_id);
 
-                if delete_from_disk {
+                if delete_from_disk && !self.db_prefix.is_empty() {
+                    if let Err(e) = self.drop_dbs() {
+                        return CloseResult::Error(e);
+                    }
+                } else if delete_from_disk {
                     let mut path = Self::get_isar_path(&self.name, &self.dir);
                     drop(self);
                     let _ = remove_file(&path);
                     path.push_str(".lock");
                     let _ = remove_file(&path);
+                } else if let Some(compact_condition) = compact_condition {
+                    // Instances sharing the environment still use the file.
+                    if self.db_prefix.is_empty() && Arc::strong_count(&self.env) == 1 {
+                        drop(lock);
+                        if let Ok(instance) = Arc::try_unwrap(self) {
+                            if let Err(e) = instance.compact(compact_condition) {
+                                return CloseResult::Error(e);
+                            }
+                        }
+                    }
                 }
-                return true;
+                return CloseResult::Closed;
             }
         }
-        false
+        let remaining = Arc::strong_count(&self).saturating_sub(OWN_REFERENCES);
+        CloseResult::StillReferenced { remaining }
     }
 
-    pub fn close(self: Arc<Self>) -> bool {
-        self.close_internal(false)
+    /// Flushes pending data and closes the instance if `self` is the last handle.
+    pub fn close(self: Arc<Self>) -> CloseResult {
+        self.close_internal(false, None)
     }
 
-    pub fn close_and_delete(self: Arc<Self>) -> bool {
-        self.close_internal(true)
+    /// Like [IsarInstance::close] but compacts the database file first if `compact_condition`
+    /// is met. Compaction is skipped for instances that share their environment.
+    pub fn close_and_compact(self: Arc<Self>, compact_condition: CompactCondition) -> CloseResult {
+        self.close_internal(false, Some(compact_condition))
     }
 
-    pub fn verify(&self, txn: &mut IsarTxn) -> Result<()> {
+    pub fn close_and_delete(self: Arc<Self>) -> CloseResult {
+        self.close_internal(true, None)
+    }
+
+    #[cfg(feature = "raw-access")]
+    fn open_raw_db(&self, txn: &IsarTxn, db_name: &str) -> Result<Option<Db>> {
+        txn.verify_instance_id(self.instance_id)?;
+        if db_name.is_empty() {
+            illegal_arg("Raw database name must not be empty.")?;
+        }
+        txn.open_db(&format!("{}_r_{}", self.db_prefix, db_name))
+    }
+
+    /// Stores a value in the auxiliary database `db_name` of this instance.
+    ///
+    /// Raw databases bypass the schema, indexes and watchers. Keeping their content consistent
+    /// is the responsibility of the caller.
+    #[cfg(feature = "raw-access")]
+    pub fn raw_put(
+        &self,
+        txn: &mut IsarTxn,
+        db_name: &str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<()> {
+        let db = self
+            .open_raw_db(txn, db_name)?
+            .ok_or(IsarError::WriteTxnRequired {})?;
+        txn.write(self.instance_id, |cursors, change_set| {
+            if let Some(change_set) = change_set {
+                change_set.mark_changed();
+            }
+            let mut cursor = cursors.get_cursor(db)?;
+            cursor.put(&IndexKey::from_bytes(key.to_vec()), value)
+        })
+    }
+
+    /// Reads a value previously written with [IsarInstance::raw_put].
+    #[cfg(feature = "raw-access")]
+    pub fn raw_get<'txn>(
+        &self,
+        txn: &'txn mut IsarTxn,
+        db_name: &str,
+        key: &[u8],
+    ) -> Result<Option<&'txn [u8]>> {
+        let db = match self.open_raw_db(txn, db_name)? {
+            Some(db) => db,
+            None => return Ok(None),
+        };
+        txn.read(self.instance_id, |cursors| {
+            let mut cursor = cursors.get_cursor(db)?;
+            let entry = cursor.move_to(&IndexKey::from_bytes(key.to_vec()))?;
+            Ok(entry.map(|(_, value)| value))
+        })
+    }
+
+    fn db_names(&self) -> Vec<String> {
         let mut db_names = vec![];
         db_names.push("_info".to_string());
+        db_names.push("_counters".to_string());
+        db_names.push("_oplog".to_string());
         for col in &self.collections {
             db_names.push(col.name.clone());
+            if col.version_db.is_some() {
+                db_names.push(format!("_v_{}", col.name));
+            }
             for index in &col.indexes {
                 db_names.push(format!("_i_{}_{}", col.name, index.name));
             }
@@ -359,7 +1413,65 @@ impl IsarInstance {
                 db_names.push(format!("_b_{}_{}", col.name, link.name));
             }
         }
+        db_names
+            .into_iter()
+            .map(|name| format!("{}{}", self.db_prefix, name))
+            .collect()
+    }
+
+    /// Lists all named databases of the environment, including those of other instances sharing
+    /// it.
+    pub fn list_dbs(&self, txn: &mut IsarTxn) -> Result<Vec<String>> {
+        txn.verify_instance_id(self.instance_id)?;
+        txn.db_names()
+    }
+
+    /// Drops the index, link and version databases of this instance that are no longer used by
+    /// its schema, for example after an index was removed. Databases named in `keep` are never
+    /// dropped. Returns the number of dropped databases.
+    pub fn drop_orphan_dbs(&self, txn: &mut IsarTxn, keep: &[&str]) -> Result<u32> {
+        let db_names = self.db_names();
+        let prefixes = ["_i_", "_l_", "_b_", "_v_"].map(|p| format!("{}{}", self.db_prefix, p));
+        let mut orphans = self.list_dbs(txn)?;
+        orphans.retain(|name| {
+            prefixes.iter().any(|prefix| name.starts_with(prefix))
+                && !db_names.contains(name)
+                && !keep.contains(&name.as_str())
+        });
+
+        txn.write(self.instance_id, |_, change_set| {
+            if let (Some(change_set), false) = (change_set, orphans.is_empty()) {
+                change_set.mark_changed();
+            }
+            Ok(())
+        })?;
+        for name in &orphans {
+            txn.drop_db(name)?;
+        }
+        Ok(orphans.len() as u32)
+    }
+
+    /// Removes all databases of a named instance from its shared environment.
+    fn drop_dbs(&self) -> Result<()> {
+        let mut txn = self.begin_txn(true, true)?;
+        let mut db_names = self.db_names();
+        for name in txn.db_names()? {
+            if name.starts_with(&format!("{}_r_", self.db_prefix)) {
+                db_names.push(name);
+            }
+        }
+        for name in db_names {
+            txn.drop_db(&name)?;
+        }
+        txn.commit()
+    }
+
+    pub fn verify(&self, txn: &mut IsarTxn) -> Result<()> {
+        let mut db_names = self.db_names();
+        let raw_prefix = format!("{}_r_", self.db_prefix);
         let mut actual_db_names = txn.db_names()?;
+        actual_db_names
+            .retain(|name| name.starts_with(&self.db_prefix) && !name.starts_with(&raw_prefix));
 
         db_names.sort();
         actual_db_names.sort();
@@ -373,3 +1485,117 @@ impl IsarInstance {
         }
     }
 }
+
+#[cfg(test)]
+impl IsarInstance {
+    /// Opens a new instance with the given schema in its own temporary directory.
+    pub(crate) fn open_test(prefix: &str, schema: &str) -> Arc<Self> {
+        let schema = Schema::from_json(schema.as_bytes()).unwrap();
+        let name = format!("{}_{}", prefix, std::process::id());
+        let dir = std::env::temp_dir().join("isar_tests").join(&name);
+        let dir = dir.to_str().unwrap();
+        IsarInstance::open(&name, Some(dir), schema, 64, None, true, None, true, None).unwrap()
+    }
+
+    pub(crate) fn close_test(self: Arc<Self>) {
+        let dir = self.dir.clone();
+        self.close_and_delete();
+        let _ = fs::remove_dir_all(dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepare_dir() {
+        let dir = std::env::temp_dir().join(format!("isar_prepare_dir_{}", std::process::id()));
+        let nested = dir.join("a").join("b");
+        let nested = nested.to_str().unwrap();
+
+        assert_eq!(
+            IsarInstance::prepare_dir(nested, false),
+            Err(IsarError::PathError {
+                path: nested.to_string()
+            })
+        );
+        assert!(IsarInstance::prepare_dir(nested, true).is_ok());
+        assert!(IsarInstance::prepare_dir(nested, false).is_ok());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_instance_limit() {
+        assert!(IsarInstance::check_instance_limit(1000).is_ok());
+        IsarInstance::set_max_instances(Some(2));
+        assert!(IsarInstance::check_instance_limit(0).is_ok());
+        assert!(IsarInstance::check_instance_limit(1).is_ok());
+        assert_eq!(
+            IsarInstance::check_instance_limit(2),
+            Err(IsarError::TooManyInstances { max: 2 })
+        );
+        IsarInstance::set_max_instances(None);
+        assert!(IsarInstance::check_instance_limit(2).is_ok());
+    }
+
+    #[test]
+    fn test_drop_orphan_dbs() {
+        let schema = r#"[{
+            "name": "Item",
+            "properties": [{"name": "value", "type": "Long"}],
+            "indexes": [{
+                "name": "value",
+                "properties": [{"name": "value", "type": "Value", "caseSensitive": false}],
+                "unique": false
+            }]
+        }]"#;
+        let instance = IsarInstance::open_test("drop_orphan_dbs", schema);
+        let txn = instance.begin_txn(true, false).unwrap().into_txn();
+        for name in ["_i_Item_old", "_i_Item_kept"] {
+            Db::open(&txn, Some(name), false, false, false).unwrap();
+        }
+        txn.commit().unwrap();
+
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        let names = instance.list_dbs(&mut txn).unwrap();
+        for name in ["Item", "_i_Item_value", "_i_Item_old", "_i_Item_kept"] {
+            assert!(names.contains(&name.to_string()));
+        }
+        assert_eq!(instance.drop_orphan_dbs(&mut txn, &["_i_Item_kept"]), Ok(1));
+        txn.commit().unwrap();
+
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        let names = instance.list_dbs(&mut txn).unwrap();
+        assert!(!names.contains(&"_i_Item_old".to_string()));
+        for name in ["Item", "_i_Item_value", "_i_Item_kept"] {
+            assert!(names.contains(&name.to_string()));
+        }
+        txn.abort();
+        instance.close_test();
+    }
+
+    #[test]
+    fn test_commit_read_txn() {
+        let schema = r#"[{"name": "Item", "properties": [{"name": "value", "type": "Long"}]}]"#;
+        let instance = IsarInstance::open_test("commit_read_txn", schema);
+        let readers = || instance.env.info().unwrap().num_readers;
+        let idle = readers();
+
+        let txn = instance.begin_txn(false, false).unwrap();
+        assert_eq!(readers(), idle + 1);
+        assert_eq!(txn.commit(), Ok(()));
+        assert_eq!(readers(), idle);
+
+        // strict mode reports the commit but still releases the reader slot
+        instance.set_strict_read_commit(true);
+        let txn = instance.begin_txn(false, false).unwrap();
+        assert_eq!(readers(), idle + 1);
+        assert_eq!(txn.commit(), Err(IsarError::ReadTxnCommit {}));
+        assert_eq!(readers(), idle);
+
+        let txn = instance.begin_txn(true, false).unwrap();
+        assert_eq!(txn.commit(), Ok(()));
+        instance.close_test();
+    }
+}
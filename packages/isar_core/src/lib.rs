@@ -3,16 +3,23 @@
 #[cfg(not(target_endian = "little"))]
 compile_error!("Only little endian systems are supported.");
 
+pub mod archive;
 pub mod collection;
+pub mod collection_diff;
 mod cursor;
 pub mod error;
+pub mod health;
 pub mod index;
 pub mod instance;
 mod legacy;
 mod link;
 mod mdbx;
 pub mod object;
+pub mod oplog;
+mod pinning;
 pub mod query;
 pub mod schema;
 pub mod txn;
+mod txn_gate;
 pub mod watch;
+pub mod write_queue;
@@ -4,6 +4,7 @@ use crate::mdbx::cursor::Cursor;
 use crate::mdbx::db::Db;
 use crate::object::id::{BytesToId, IdToBytes};
 use crate::object::isar_object::IsarObject;
+use crate::query::scan::Scan;
 use std::ops::Deref;
 use xxhash_rust::xxh3::xxh3_64_with_seed;
 
@@ -71,6 +72,15 @@ impl IsarLink {
         })
     }
 
+    /// Like [IsarLink::iter] but returns a [Scan] of the targets.
+    pub(crate) fn scan<'txn>(
+        &self,
+        cursors: &IsarCursors<'txn, '_>,
+        id: i64,
+    ) -> Result<Scan<'txn>> {
+        Scan::link(cursors, self.db, self.target_db, id)
+    }
+
     pub fn create(&self, cursors: &IsarCursors, source_id: i64, target_id: i64) -> Result<bool> {
         let mut source_cursor = cursors.get_cursor(self.source_db)?;
         let mut target_cursor = cursors.get_cursor(self.target_db)?;
@@ -135,6 +145,22 @@ impl IsarLink {
         Ok(())
     }
 
+    /// Whether source and target of the link are the same collection.
+    pub fn is_self_link(&self) -> bool {
+        self.source_db == self.target_db
+    }
+
+    /// Returns all `(source_id, target_id)` pairs of the link.
+    pub fn get_all(&self, cursors: &IsarCursors) -> Result<Vec<(i64, i64)>> {
+        let mut links = vec![];
+        let mut cursor = cursors.get_cursor(self.db)?;
+        cursor.iter_all(false, true, |_, id_bytes, target_id_bytes| {
+            links.push((id_bytes.to_id(), target_id_bytes.to_id()));
+            Ok(true)
+        })?;
+        Ok(links)
+    }
+
     pub fn get_size(&self, cursors: &IsarCursors) -> Result<u64> {
         Ok(cursors.db_stat(self.db)?.1)
     }
@@ -1,4 +1,5 @@
-use crate::error::Result;
+use crate::error::{illegal_arg, Result};
+use crate::index::key_comparator::KeyComparator;
 use crate::mdbx::db::Db;
 use crate::mdbx::txn::Txn;
 use crate::mdbx::{from_mdb_val, mdbx_result, to_mdb_val, Key, KeyVal, EMPTY_KEY, EMPTY_VAL};
@@ -24,6 +25,9 @@ impl UnboundCursor {
 
         Ok(Cursor {
             cursor: self,
+            comparator: db.comparator,
+            dup: db.dup,
+            prefetched: None,
             _marker: PhantomData::default(),
         })
     }
@@ -37,14 +41,63 @@ impl Drop for UnboundCursor {
 
 pub struct Cursor<'txn> {
     cursor: UnboundCursor,
+    comparator: Option<KeyComparator>,
+    dup: bool,
+    // address range that has been advised for prefetching, `None` if prefetching is disabled
+    prefetched: Option<(usize, usize)>,
     _marker: PhantomData<&'txn ()>,
 }
 
+const PREFETCH_ALIGN: usize = 1 << 16;
+const PREFETCH_WINDOW: usize = 1 << 20;
+
+#[cfg(unix)]
+fn advise_willneed(start: usize, len: usize) {
+    // The hint is best effort so errors (e.g. parts of the range not being mapped) are ignored.
+    unsafe {
+        libc::madvise(start as *mut libc::c_void, len, libc::MADV_WILLNEED);
+    }
+}
+
+#[cfg(not(unix))]
+fn advise_willneed(_start: usize, _len: usize) {}
+
 impl<'txn> Cursor<'txn> {
+    /// Enables read ahead hints for the pages following the values visited by range scans which
+    /// improves the throughput of large scans with a cold page cache.
+    pub fn set_prefetch(&mut self, prefetch: bool) {
+        if prefetch != self.prefetched.is_some() {
+            self.prefetched = prefetch.then_some((0, 0));
+        }
+    }
+
+    fn prefetch(&mut self, val: &[u8], ascending: bool) {
+        if let Some((start, end)) = self.prefetched {
+            let address = val.as_ptr() as usize;
+            if address < start || address >= end {
+                let start = if ascending {
+                    address & !(PREFETCH_ALIGN - 1)
+                } else {
+                    address.saturating_sub(PREFETCH_WINDOW) & !(PREFETCH_ALIGN - 1)
+                };
+                advise_willneed(start, PREFETCH_WINDOW);
+                self.prefetched = Some((start, start + PREFETCH_WINDOW));
+            }
+        }
+    }
+
     pub fn unbind(self) -> UnboundCursor {
         self.cursor
     }
 
+    fn cmp_key<K: Key>(&self, key: &K, other: &[u8]) -> Ordering {
+        if let Some(comparator) = &self.comparator {
+            comparator.compare(&key.as_bytes(), other)
+        } else {
+            key.cmp_bytes(other)
+        }
+    }
+
     #[allow(clippy::try_err)]
     fn op_get(
         &mut self,
@@ -71,6 +124,11 @@ impl<'txn> Cursor<'txn> {
         }
     }
 
+    /// Returns the entry at the current position without moving the cursor.
+    pub fn current(&mut self) -> Result<Option<KeyVal<'txn>>> {
+        self.op_get(ffi::MDBX_cursor_op::MDBX_GET_CURRENT, None, None)
+    }
+
     pub fn move_to<K: Key>(&mut self, key: &K) -> Result<Option<KeyVal<'txn>>> {
         self.op_get(
             ffi::MDBX_cursor_op::MDBX_SET_KEY,
@@ -95,7 +153,7 @@ impl<'txn> Cursor<'txn> {
         )
     }
 
-    fn move_to_next_dup(&mut self) -> Result<Option<KeyVal<'txn>>> {
+    pub fn move_to_next_dup(&mut self) -> Result<Option<KeyVal<'txn>>> {
         self.op_get(ffi::MDBX_cursor_op::MDBX_NEXT_DUP, None, None)
     }
 
@@ -130,6 +188,17 @@ impl<'txn> Cursor<'txn> {
         Ok(())
     }
 
+    /// Returns the number of values stored for the key at the current position. Only supported
+    /// for dup dbs and requires the cursor to have a valid position.
+    pub fn count_duplicates(&mut self) -> Result<u64> {
+        if !self.dup {
+            return illegal_arg("Duplicates can only be counted in dup dbs.");
+        }
+        let mut count: ffi::size_t = 0;
+        unsafe { mdbx_result(ffi::mdbx_cursor_count(self.cursor.cursor, &mut count))? };
+        Ok(count as u64)
+    }
+
     /// Requires the cursor to have a valid position
     pub fn delete_current(&mut self) -> Result<()> {
         unsafe { mdbx_result(ffi::mdbx_cursor_del(self.cursor.cursor, 0))? };
@@ -137,20 +206,113 @@ impl<'txn> Cursor<'txn> {
         Ok(())
     }
 
+    /// Creates a second cursor at the same position.
+    fn duplicate(&self) -> Result<Cursor<'txn>> {
+        let cursor = UnboundCursor::new();
+        unsafe { mdbx_result(ffi::mdbx_cursor_copy(self.cursor.cursor, cursor.cursor))? };
+        Ok(Cursor {
+            cursor,
+            comparator: self.comparator,
+            dup: self.dup,
+            prefetched: None,
+            _marker: PhantomData,
+        })
+    }
+
+    fn is_beyond<K: Key>(&self, end_key: &K, key: &[u8], ascending: bool) -> bool {
+        if ascending {
+            self.cmp_key(end_key, key) == Ordering::Less
+        } else {
+            self.cmp_key(end_key, key) == Ordering::Greater
+        }
+    }
+
+    /// Counts the entries after the current position up to and including `end_key` without
+    /// moving the cursor. Requires the cursor to have a valid position.
+    pub fn count_remaining<K: Key>(&self, end_key: &K, ascending: bool) -> Result<u64> {
+        let mut cursor = self.duplicate()?;
+        let mut count = 0;
+        cursor.iter(false, ascending, |cursor, key, _| {
+            if cursor.is_beyond(end_key, key, ascending) {
+                Ok(false)
+            } else {
+                count += 1;
+                Ok(true)
+            }
+        })?;
+        Ok(count)
+    }
+
+    /// Like [Cursor::count_remaining] but uses the MDBX range estimation which only visits the
+    /// b-tree pages on the path to both ends. The result is exact for small ranges.
+    pub fn estimate_remaining<K: Key>(&self, end_key: &K, ascending: bool) -> Result<u64> {
+        // move a second cursor to the first entry outside of the range or to the last entry
+        let mut end = self.duplicate()?;
+        let gte = end.move_to_gte(end_key)?;
+        let beyond = if ascending {
+            match gte {
+                Some((key, _)) if end.cmp_key(end_key, key) == Ordering::Equal => {
+                    end.op_get(ffi::MDBX_cursor_op::MDBX_NEXT_NODUP, None, None)?
+                }
+                gte => gte,
+            }
+        } else if gte.is_some() {
+            end.op_get(ffi::MDBX_cursor_op::MDBX_PREV, None, None)?
+        } else {
+            end.move_to_last()?
+        };
+        let reached_end = beyond.is_none();
+        if reached_end {
+            if ascending {
+                end.move_to_last()?;
+            } else {
+                end.move_to_first()?;
+            }
+        }
+
+        let mut distance: isize = 0;
+        unsafe {
+            mdbx_result(ffi::mdbx_estimate_distance(
+                self.cursor.cursor,
+                end.cursor.cursor,
+                &mut distance,
+            ))?
+        };
+        let distance = if ascending { distance } else { -distance };
+        let remaining = if reached_end { distance } else { distance - 1 };
+        Ok(remaining.max(0) as u64)
+    }
+
+    /// Estimates the number of entries between `lower` and `upper` (inclusive). Moves the
+    /// cursor to the first entry of the range.
+    pub fn estimate_between<K: Key>(&mut self, lower: &K, upper: &K) -> Result<u64> {
+        match self.move_to_gte(lower)? {
+            Some((key, _)) if self.cmp_key(upper, key) != Ordering::Less => {
+                Ok(1 + self.estimate_remaining(upper, true)?)
+            }
+            _ => Ok(0),
+        }
+    }
+
+    fn next_op(skip_duplicates: bool, ascending: bool) -> ffi::MDBX_cursor_op {
+        match (ascending, skip_duplicates) {
+            (true, true) => ffi::MDBX_cursor_op::MDBX_NEXT_NODUP,
+            (true, false) => ffi::MDBX_cursor_op::MDBX_NEXT,
+            (false, true) => ffi::MDBX_cursor_op::MDBX_PREV_NODUP,
+            (false, false) => ffi::MDBX_cursor_op::MDBX_PREV,
+        }
+    }
+
     fn iter(
         &mut self,
         skip_duplicates: bool,
         ascending: bool,
         mut callback: impl FnMut(&mut Self, &'txn [u8], &'txn [u8]) -> Result<bool>,
     ) -> Result<bool> {
-        let next = match (ascending, skip_duplicates) {
-            (true, true) => ffi::MDBX_cursor_op::MDBX_NEXT_NODUP,
-            (true, false) => ffi::MDBX_cursor_op::MDBX_NEXT,
-            (false, true) => ffi::MDBX_cursor_op::MDBX_PREV_NODUP,
-            (false, false) => ffi::MDBX_cursor_op::MDBX_PREV,
-        };
+        let next = Self::next_op(skip_duplicates, ascending);
         loop {
             if let Some((key, val)) = self.op_get(next, None, None)? {
+                self.prefetch(val, ascending);
                 if !callback(self, key, val)? {
                     return Ok(false);
                 }
@@ -192,30 +354,26 @@ impl<'txn> Cursor<'txn> {
     ) -> Result<Option<KeyVal<'txn>>> {
         let first_entry = if !ascending {
             if let Some(first_entry) = self.move_to_gte(upper_key)? {
-                if duplicates {
-                    self.move_to_last_dup()?.or(Some(first_entry))
+                if duplicates && self.move_to_last_dup()?.is_some() {
+                    // MDBX_LAST_DUP only returns the value
+                    self.current()?
                 } else {
                     Some(first_entry)
                 }
-            } else if let Some(last) = self.move_to_last()? {
-                // If some key between upper_key and lower_key happens to be the last key in the db
-                if lower_key.cmp_bytes(&last.0) != Ordering::Greater {
-                    Some(last)
-                } else {
-                    None
-                }
             } else {
-                None
+                // If some key between upper_key and lower_key happens to be the last key in the db
+                self.move_to_last()?
+                    .filter(|last| self.cmp_key(lower_key, last.0) != Ordering::Greater)
             }
         } else {
             self.move_to_gte(lower_key)?
         };
 
         if let Some(first_entry) = first_entry {
-            if upper_key.cmp_bytes(&first_entry.0) == Ordering::Less {
+            if self.cmp_key(upper_key, first_entry.0) == Ordering::Less {
                 if !ascending {
                     if let Some(prev) = self.move_to_prev_no_dup()? {
-                        if lower_key.cmp_bytes(&prev.0) != Ordering::Greater {
+                        if self.cmp_key(lower_key, prev.0) != Ordering::Greater {
                             return Ok(Some(prev));
                         }
                     }
@@ -238,13 +396,14 @@ impl<'txn> Cursor<'txn> {
         ascending: bool,
         mut callback: impl FnMut(&mut Self, &'txn [u8], &'txn [u8]) -> Result<bool>,
     ) -> Result<bool> {
-        if upper_key.cmp_bytes(&lower_key.as_bytes()) == Ordering::Less {
+        if self.cmp_key(upper_key, &lower_key.as_bytes()) == Ordering::Less {
             return Ok(true);
         }
 
         if let Some((key, val)) =
             self.iter_between_first(lower_key, upper_key, ascending, duplicates)?
         {
+            self.prefetch(val, ascending);
             if !callback(self, key, val)? {
                 return Ok(false);
             }
@@ -254,9 +413,9 @@ impl<'txn> Cursor<'txn> {
 
         self.iter(skip_duplicates, ascending, |cursor, key, val| {
             let abort = if ascending {
-                upper_key.cmp_bytes(&key) == Ordering::Less
+                cursor.cmp_key(upper_key, key) == Ordering::Less
             } else {
-                lower_key.cmp_bytes(&key) == Ordering::Greater
+                cursor.cmp_key(lower_key, key) == Ordering::Greater
             };
             if abort {
                 Ok(true)
@@ -266,6 +425,43 @@ impl<'txn> Cursor<'txn> {
         })
     }
 
+    /// Moves to the entry [Cursor::iter_between] starts with. Together with
+    /// [Cursor::move_to_next_between] the range can be read one entry at a time.
+    pub fn move_to_first_between<K: Key>(
+        &mut self,
+        lower_key: &K,
+        upper_key: &K,
+        duplicates: bool,
+        ascending: bool,
+    ) -> Result<Option<KeyVal<'txn>>> {
+        if self.cmp_key(upper_key, &lower_key.as_bytes()) == Ordering::Less {
+            return Ok(None);
+        }
+        let first = self.iter_between_first(lower_key, upper_key, ascending, duplicates)?;
+        if let Some((_, val)) = first {
+            self.prefetch(val, ascending);
+        }
+        Ok(first)
+    }
+
+    /// Moves to the next entry of the range. Returns `None` once the cursor leaves the range.
+    pub fn move_to_next_between<K: Key>(
+        &mut self,
+        lower_key: &K,
+        upper_key: &K,
+        skip_duplicates: bool,
+        ascending: bool,
+    ) -> Result<Option<KeyVal<'txn>>> {
+        let end_key = if ascending { upper_key } else { lower_key };
+        match self.op_get(Self::next_op(skip_duplicates, ascending), None, None)? {
+            Some((key, val)) if !self.is_beyond(end_key, key, ascending) => {
+                self.prefetch(val, ascending);
+                Ok(Some((key, val)))
+            }
+            _ => Ok(None),
+        }
+    }
+
     pub fn iter_dups<K: Key>(
         &mut self,
         key: &K,
@@ -1,4 +1,5 @@
 use crate::error::Result;
+use crate::index::key_comparator::KeyComparator;
 use crate::mdbx::mdbx_result;
 use crate::mdbx::txn::Txn;
 use std::ffi::CString;
@@ -9,9 +10,19 @@ use std::ptr;
 pub struct Db {
     pub(crate) dbi: ffi::MDBX_dbi,
     pub dup: bool,
+    pub(crate) comparator: Option<KeyComparator>,
 }
 
 impl Db {
+    /// The garbage collection database of MDBX which tracks free pages.
+    pub fn gc() -> Self {
+        Self {
+            dbi: 0,
+            dup: false,
+            comparator: None,
+        }
+    }
+
     pub fn runtime_id(&self) -> u64 {
         self.dbi as u64
     }
@@ -22,6 +33,17 @@ impl Db {
         int_key: bool,
         dup: bool,
         int_dup: bool,
+    ) -> Result<Self> {
+        Self::open_with_comparator(txn, name, int_key, dup, int_dup, None)
+    }
+
+    pub fn open_with_comparator(
+        txn: &Txn,
+        name: Option<&str>,
+        int_key: bool,
+        dup: bool,
+        int_dup: bool,
+        comparator: Option<KeyComparator>,
     ) -> Result<Self> {
         let mut flags = ffi::MDBX_CREATE;
         if int_key {
@@ -37,8 +59,21 @@ impl Db {
         let mut dbi: ffi::MDBX_dbi = 0;
         if let Some(name) = name {
             let name = CString::new(name.as_bytes()).unwrap();
-            unsafe {
-                mdbx_result(ffi::mdbx_dbi_open(txn.txn, name.as_ptr(), flags, &mut dbi))?;
+            if let Some(comparator) = comparator {
+                unsafe {
+                    mdbx_result(ffi::mdbx_dbi_open_ex(
+                        txn.txn,
+                        name.as_ptr(),
+                        flags,
+                        &mut dbi,
+                        comparator.as_mdbx_cmp(),
+                        None,
+                    ))?;
+                }
+            } else {
+                unsafe {
+                    mdbx_result(ffi::mdbx_dbi_open(txn.txn, name.as_ptr(), flags, &mut dbi))?;
+                }
             }
         } else {
             unsafe {
@@ -46,7 +81,45 @@ impl Db {
             }
         }
 
-        Ok(Self { dbi, dup })
+        Ok(Self {
+            dbi,
+            dup,
+            comparator,
+        })
+    }
+
+    /// Opens an existing database regardless of its flags.
+    pub fn open_existing(txn: &Txn, name: &str) -> Result<Self> {
+        let name = CString::new(name.as_bytes()).unwrap();
+        let mut dbi: ffi::MDBX_dbi = 0;
+        unsafe {
+            mdbx_result(ffi::mdbx_dbi_open(
+                txn.txn,
+                name.as_ptr(),
+                ffi::MDBX_DB_ACCEDE,
+                &mut dbi,
+            ))?;
+        }
+        Ok(Self {
+            dbi,
+            dup: false,
+            comparator: None,
+        })
+    }
+
+    /// Like [Db::open_existing] but returns `None` if the database does not exist.
+    #[cfg(feature = "raw-access")]
+    pub fn open_if_exists(txn: &Txn, name: &str) -> Result<Option<Self>> {
+        use crate::error::IsarError;
+
+        match Self::open_existing(txn, name) {
+            Ok(db) => Ok(Some(db)),
+            Err(IsarError::MdbxError {
+                code: ffi::MDBX_NOTFOUND,
+                ..
+            }) => Ok(None),
+            Err(e) => Err(e),
+        }
     }
 
     pub fn stat(&self, txn: &Txn) -> Result<(u64, u64)> {
@@ -3,9 +3,14 @@ use crate::error::{IsarError, Result};
 use crate::mdbx::mdbx_result;
 use crate::mdbx::txn::Txn;
 use core::ptr;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
 
 pub struct Env {
     env: *mut ffi::MDBX_env,
+    read_pool: Mutex<Vec<*mut ffi::MDBX_txn>>,
+    reused_reads: AtomicU64,
 }
 
 unsafe impl Sync for Env {}
@@ -13,14 +18,25 @@ unsafe impl Send for Env {}
 
 const MIB: isize = 1 << 20;
 
+pub struct EnvInfo {
+    pub map_size: u64,
+    pub file_size: u64,
+    pub page_size: u32,
+    pub last_page: u64,
+    pub recent_txn_id: u64,
+    pub num_readers: u32,
+    pub max_readers: u32,
+}
+
 impl Env {
     pub fn create(
         path: &str,
         max_dbs: u64,
         max_size_mib: usize,
+        page_size: Option<usize>,
         relaxed_durability: bool,
     ) -> Result<Env> {
-        let path = str_to_os(path)?;
+        let os_path = str_to_os(path)?;
         let mut env: *mut ffi::MDBX_env = ptr::null_mut();
         unsafe {
             mdbx_result(ffi::mdbx_env_create(&mut env))?;
@@ -36,6 +52,7 @@ impl Env {
             }
 
             let max_size = (max_size_mib as isize).saturating_mul(MIB);
+            let page_size = page_size.map_or(-1, |p| p as isize);
 
             let mut err_code = 0;
             for i in 0..9 {
@@ -47,18 +64,24 @@ impl Env {
                     max_size_i,
                     5 * MIB,
                     20 * MIB,
-                    -1,
+                    page_size,
                 ))?;
 
-                err_code = ENV_OPEN(env, path.as_ptr(), flags, 0o600);
+                err_code = ENV_OPEN(env, os_path.as_ptr(), flags, 0o600);
                 if err_code == ffi::MDBX_SUCCESS {
                     break;
                 }
             }
 
             match err_code {
-                ffi::MDBX_SUCCESS => Ok(Env { env }),
-                ffi::MDBX_EPERM | ffi::MDBX_ENOFILE => Err(IsarError::PathError {}),
+                ffi::MDBX_SUCCESS => Ok(Env {
+                    env,
+                    read_pool: Mutex::new(vec![]),
+                    reused_reads: AtomicU64::new(0),
+                }),
+                ffi::MDBX_EPERM | ffi::MDBX_ENOFILE => Err(IsarError::PathError {
+                    path: path.to_string(),
+                }),
                 e => {
                     mdbx_result(e)?;
                     unreachable!()
@@ -82,6 +105,85 @@ impl Env {
         Ok(Txn::new(txn, write))
     }
 
+    const MAX_POOLED_READS: usize = 16;
+
+    /// Returns a read transaction, renewing a pooled handle if one is available. The env is
+    /// opened with `MDBX_NOTLS` so handles are not bound to the thread that created them.
+    pub fn pooled_read_txn(&self) -> Result<Txn<'_>> {
+        loop {
+            let pooled = self.read_pool.lock().unwrap().pop();
+            if let Some(txn) = pooled {
+                if unsafe { ffi::mdbx_txn_renew(txn) } == ffi::MDBX_SUCCESS {
+                    self.reused_reads.fetch_add(1, Ordering::Relaxed);
+                    return Ok(Txn::new(txn, false));
+                } else {
+                    unsafe { ffi::mdbx_txn_abort(txn) };
+                }
+            } else {
+                return self.txn(false);
+            }
+        }
+    }
+
+    /// Returns a read transaction to the pool. Its snapshot is released immediately.
+    pub fn release_read_txn(&self, txn: Txn) {
+        let txn = txn.reset();
+        if !txn.is_null() {
+            let mut pool = self.read_pool.lock().unwrap();
+            if pool.len() < Self::MAX_POOLED_READS {
+                pool.push(txn);
+            } else {
+                unsafe { ffi::mdbx_txn_abort(txn) };
+            }
+        }
+    }
+
+    /// Aborts all pooled read transactions and frees their reader slots.
+    pub fn clear_read_pool(&self) {
+        for txn in self.read_pool.lock().unwrap().drain(..) {
+            unsafe { ffi::mdbx_txn_abort(txn) };
+        }
+    }
+
+    /// The number of read transactions that were served from the pool.
+    pub fn reused_read_count(&self) -> u64 {
+        self.reused_reads.load(Ordering::Relaxed)
+    }
+
+    pub fn info(&self) -> Result<EnvInfo> {
+        unsafe {
+            let mut info: ffi::MDBX_envinfo = mem::zeroed();
+            mdbx_result(ffi::mdbx_env_info_ex(
+                self.env,
+                ptr::null(),
+                &mut info,
+                mem::size_of::<ffi::MDBX_envinfo>() as ffi::size_t,
+            ))?;
+            Ok(EnvInfo {
+                map_size: info.mi_mapsize,
+                file_size: info.mi_geo.current,
+                page_size: info.mi_dxb_pagesize,
+                last_page: info.mi_last_pgno,
+                recent_txn_id: info.mi_recent_txnid,
+                num_readers: info.mi_numreaders,
+                max_readers: info.mi_maxreaders,
+            })
+        }
+    }
+
+    /// Clears the reader slots of processes and threads that exited without closing their read
+    /// transactions. Returns the number of cleared slots.
+    pub fn reader_check(&self) -> Result<u32> {
+        let mut dead = 0;
+        unsafe { mdbx_result(ffi::mdbx_reader_check(self.env, &mut dead))? };
+        Ok(dead.max(0) as u32)
+    }
+
+    /// Flushes all committed data to disk, even with relaxed durability.
+    pub fn sync(&self) -> Result<()> {
+        unsafe { mdbx_result(ffi::mdbx_env_sync_ex(self.env, true, false)) }
+    }
+
     pub fn copy(&self, path: &str) -> Result<()> {
         let path = str_to_os(path)?;
         unsafe { mdbx_result(ENV_COPY(self.env, path.as_ptr(), ffi::MDBX_CP_COMPACT)) }
@@ -91,6 +193,7 @@ impl Env {
 impl Drop for Env {
     fn drop(&mut self) {
         if !self.env.is_null() {
+            self.clear_read_pool();
             unsafe {
                 ffi::mdbx_env_close_ex(self.env, false);
             }
@@ -6,6 +6,8 @@ use libc::c_int;
 use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::ffi::{c_void, CStr};
+use std::thread;
+use std::time::Duration;
 
 pub mod cursor;
 pub mod db;
@@ -56,6 +58,28 @@ pub fn mdbx_result(err_code: c_int) -> Result<()> {
     }
 }
 
+/// Runs `op` again while it fails with `MDBX_BUSY`, at most `attempts` more times. The delay
+/// between attempts starts at `delay` and doubles after every attempt.
+pub fn retry_busy<T>(
+    retry: Option<(u32, Duration)>,
+    mut op: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let (attempts, mut delay) = retry.unwrap_or((0, Duration::ZERO));
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(IsarError::MdbxError { code, .. })
+                if code == ffi::MDBX_BUSY && attempt < attempts =>
+            {
+                thread::sleep(delay);
+                delay = delay.saturating_mul(2);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
 pub trait Key {
     fn as_bytes(&self) -> Cow<[u8]>;
 
@@ -111,3 +135,49 @@ pub(crate) mod osal {
         ffi::MDBX_copy_flags_t,
     ) -> i32 = ffi::mdbx_env_copy;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn busy() -> IsarError {
+        IsarError::MdbxError {
+            code: ffi::MDBX_BUSY,
+            message: "busy".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_retry_busy() {
+        let available_at = Instant::now() + Duration::from_millis(20);
+        let open = || {
+            if Instant::now() < available_at {
+                Err(busy())
+            } else {
+                Ok(42)
+            }
+        };
+        assert_eq!(retry_busy(None, open), Err(busy()));
+        assert_eq!(
+            retry_busy(Some((10, Duration::from_millis(2))), open),
+            Ok(42)
+        );
+
+        let mut calls = 0;
+        let result = retry_busy(Some((5, Duration::from_millis(1))), || {
+            calls += 1;
+            Err::<(), _>(busy())
+        });
+        assert_eq!(result, Err(busy()));
+        assert_eq!(calls, 6);
+
+        let mut calls = 0;
+        let result = retry_busy(Some((5, Duration::from_millis(1))), || {
+            calls += 1;
+            Err::<(), _>(IsarError::DbFull {})
+        });
+        assert_eq!(result, Err(IsarError::DbFull {}));
+        assert_eq!(calls, 1);
+    }
+}
@@ -2,6 +2,7 @@ use crate::error::Result;
 use crate::mdbx::mdbx_result;
 use core::ptr;
 use std::marker::PhantomData;
+use std::mem;
 
 pub struct Txn<'env> {
     pub(crate) txn: *mut ffi::MDBX_txn,
@@ -18,6 +19,41 @@ impl<'env> Txn<'env> {
         }
     }
 
+    pub fn id(&self) -> u64 {
+        unsafe { ffi::mdbx_txn_id(self.txn) }
+    }
+
+    /// The page size of the environment in bytes.
+    pub fn page_size(&self) -> Result<u32> {
+        unsafe {
+            let mut info: ffi::MDBX_envinfo = mem::zeroed();
+            mdbx_result(ffi::mdbx_env_info_ex(
+                ffi::mdbx_txn_env(self.txn),
+                self.txn,
+                &mut info,
+                mem::size_of::<ffi::MDBX_envinfo>() as ffi::size_t,
+            ))?;
+            Ok(info.mi_dxb_pagesize)
+        }
+    }
+
+    /// Begins a nested write transaction. The parent must not be used until the child is
+    /// committed or aborted.
+    pub(crate) fn begin_nested(&self) -> Result<Txn<'env>> {
+        let mut txn: *mut ffi::MDBX_txn = ptr::null_mut();
+        unsafe {
+            let env = ffi::mdbx_txn_env(self.txn);
+            mdbx_result(ffi::mdbx_txn_begin_ex(
+                env,
+                self.txn,
+                0,
+                &mut txn,
+                ptr::null_mut(),
+            ))?;
+        }
+        Ok(Txn::new(txn, true))
+    }
+
     pub fn commit(mut self) -> Result<()> {
         let result = unsafe { mdbx_result(ffi::mdbx_txn_commit_ex(self.txn, ptr::null_mut())) };
         self.txn = ptr::null_mut();
@@ -26,6 +62,19 @@ impl<'env> Txn<'env> {
     }
 
     pub fn abort(self) {}
+
+    /// Releases the snapshot of a read transaction but keeps its handle so it can be renewed.
+    /// Returns a null pointer if the transaction could not be reset.
+    pub(crate) fn reset(mut self) -> *mut ffi::MDBX_txn {
+        let txn = self.txn;
+        self.txn = ptr::null_mut();
+        if self.write || unsafe { ffi::mdbx_txn_reset(txn) } != ffi::MDBX_SUCCESS {
+            unsafe { ffi::mdbx_txn_abort(txn) };
+            ptr::null_mut()
+        } else {
+            txn
+        }
+    }
 }
 
 impl<'a> Drop for Txn<'a> {
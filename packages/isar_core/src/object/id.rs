@@ -14,6 +14,9 @@ impl BytesToId for &[u8] {
     }
 }
 
+/// Ids are stored with the sign bit flipped so that their unsigned value orders like the signed
+/// id. Databases keyed by id use `MDBX_INTEGERKEY` which compares keys as native unsigned
+/// integers, so iterating them yields ascending ids across the full `i64` range.
 pub trait IdToBytes {
     fn to_id_bytes(&self) -> [u8; 8];
 }
@@ -68,4 +71,31 @@ mod tests {
             i64::MAX
         );
     }
+
+    #[test]
+    fn test_id_order() {
+        let mut ids = vec![
+            i64::MIN,
+            i64::MIN + 1,
+            -1000,
+            -256,
+            -255,
+            -1,
+            0,
+            1,
+            255,
+            256,
+            1000,
+            i64::MAX - 1,
+            i64::MAX,
+        ];
+        ids.reverse();
+        // Sort as MDBX_INTEGERKEY compares the stored keys.
+        ids.sort_by_key(|id| u64::from_le_bytes(id.to_id_bytes()));
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        for id in ids {
+            assert_eq!(id.to_id_bytes().as_ref().to_id(), id);
+            assert_eq!(id.cmp_bytes(&id.to_id_bytes()), Ordering::Equal);
+        }
+    }
 }
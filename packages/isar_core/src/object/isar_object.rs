@@ -1,6 +1,8 @@
 use crate::object::data_type::DataType;
 use crate::object::object_builder::ObjectBuilder;
+use crate::object::property::Property;
 use byteorder::{ByteOrder, LittleEndian};
+use intmap::IntMap;
 use std::{cmp::Ordering, str::from_utf8_unchecked};
 use xxhash_rust::xxh3::xxh3_64_with_seed;
 
@@ -26,6 +28,94 @@ impl<'a> IsarObject<'a> {
         IsarObject { bytes, static_size }
     }
 
+    /// Checks that `bytes` can be read with `properties` without reading out of bounds and that
+    /// all strings are valid UTF-8.
+    pub fn is_valid(
+        bytes: &[u8],
+        properties: &[Property],
+        embedded_properties: &IntMap<Vec<Property>>,
+    ) -> bool {
+        if bytes.len() < 2 {
+            return false;
+        }
+        let object = IsarObject::from_bytes(bytes);
+        if object.static_size < 2 || object.static_size > bytes.len() {
+            return false;
+        }
+        properties.iter().all(|p| {
+            if !object.contains_offset(p.offset) {
+                true
+            } else if p.offset + p.data_type.get_static_size() > object.static_size {
+                false
+            } else if p.data_type.is_static() {
+                true
+            } else {
+                object.is_valid_dynamic(p, embedded_properties)
+            }
+        })
+    }
+
+    fn is_valid_dynamic(
+        &self,
+        property: &Property,
+        embedded_properties: &IntMap<Vec<Property>>,
+    ) -> bool {
+        let length_offset = self.read_u24(property.offset);
+        if length_offset == 0 {
+            return true;
+        }
+        if length_offset < self.static_size || length_offset + 3 > self.bytes.len() {
+            return false;
+        }
+        let offset = length_offset + 3;
+        let length = self.read_u24(length_offset);
+        let embedded = property
+            .target_id
+            .and_then(|id| embedded_properties.get(id))
+            .map_or(&[][..], |p| p.as_slice());
+        let is_valid_item = |bytes: &[u8]| match property.data_type {
+            DataType::String | DataType::StringList => std::str::from_utf8(bytes).is_ok(),
+            DataType::Object | DataType::ObjectList => {
+                IsarObject::is_valid(bytes, embedded, embedded_properties)
+            }
+            _ => true,
+        };
+
+        match property.data_type {
+            DataType::StringList | DataType::ObjectList => {
+                let mut content_offset = offset + length * 3;
+                if content_offset > self.bytes.len() {
+                    return false;
+                }
+                for i in 0..length {
+                    let item_size = self.read_u24(offset + i * 3);
+                    if item_size != 0 {
+                        let item_end = content_offset + item_size - 1;
+                        if item_end > self.bytes.len()
+                            || !is_valid_item(&self.bytes[content_offset..item_end])
+                        {
+                            return false;
+                        }
+                        content_offset = item_end;
+                    }
+                }
+                true
+            }
+            DataType::String | DataType::Object => {
+                offset + length <= self.bytes.len()
+                    && is_valid_item(&self.bytes[offset..][..length])
+            }
+            _ => {
+                let element_size = property
+                    .data_type
+                    .get_element_type()
+                    .unwrap()
+                    .get_static_size();
+                offset + length * element_size <= self.bytes.len()
+            }
+        }
+    }
+
     pub fn as_bytes(&self) -> &'a [u8] {
         self.bytes
     }
@@ -124,6 +214,24 @@ impl<'a> IsarObject<'a> {
         None
     }
 
+    /// The number of bytes a value takes up in the dynamic section. Static and null values
+    /// return `0`.
+    pub(crate) fn read_value_size(&self, offset: usize, data_type: DataType) -> usize {
+        match data_type {
+            DataType::StringList | DataType::ObjectList => self
+                .read_dynamic_list(offset, |bytes| bytes.len())
+                .map_or(0, |list| list.iter().flatten().sum()),
+            _ if data_type.is_dynamic() => {
+                let length = self.read_length(offset).unwrap_or(0);
+                let element_size = data_type
+                    .get_element_type()
+                    .map_or(1, |element| element.get_static_size());
+                length * element_size
+            }
+            _ => 0,
+        }
+    }
+
     pub fn read_length(&self, offset: usize) -> Option<usize> {
         let (_, length) = self.get_offset_length(offset)?;
         Some(length)
@@ -369,6 +477,32 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_is_valid() {
+        let props = vec![
+            Property::debug(Long, 2),
+            Property::debug(String, 10),
+            Property::debug(StringList, 13),
+        ];
+        let embedded = intmap::IntMap::new();
+        let mut b = ObjectBuilder::new(&props, None);
+        b.write_long(2, 42);
+        b.write_string(10, Some("hello"));
+        b.write_string_list(13, Some(&[Some("a"), None, Some("bc")]));
+        let bytes = b.finish().as_bytes().to_vec();
+        assert!(IsarObject::is_valid(&bytes, &props, &embedded));
+        assert!(IsarObject::is_valid(&[2, 0], &props, &embedded));
+
+        for len in 0..bytes.len() {
+            assert!(!IsarObject::is_valid(&bytes[..len], &props, &embedded));
+        }
+
+        let mut invalid_utf8 = bytes.clone();
+        let pos = invalid_utf8.windows(5).position(|w| w == b"hello").unwrap();
+        invalid_utf8[pos] = 0xff;
+        assert!(!IsarObject::is_valid(&invalid_utf8, &props, &embedded));
+    }
+
     #[test]
     fn test_read_non_contained_property() {
         let data_types = vec![
@@ -47,8 +47,8 @@ impl<'a> JsonEncodeDecode {
                             primitive_null,
                         )
                     }
-                    DataType::BoolList => json!(object.read_bool_list(property.offset).unwrap()),
-                    DataType::ByteList => json!(object.read_byte_list(property.offset).unwrap()),
+                    DataType::BoolList => json!(object.read_bool_list(property.offset)),
+                    DataType::ByteList => json!(object.read_byte_list(property.offset)),
                     DataType::IntList => {
                         if primitive_null {
                             json!(object.read_int_or_null_list(property.offset))
@@ -325,3 +325,92 @@ impl<'a> JsonEncodeDecode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xxhash_rust::xxh3::xxh3_64;
+
+    // Embedded objects of every property are of the type `target`.
+    fn properties(types: &[DataType], target: Option<&str>) -> Vec<Property> {
+        let mut offset = 2;
+        types
+            .iter()
+            .enumerate()
+            .map(|(i, data_type)| {
+                let property = Property::new(&format!("p{}", i), *data_type, offset, target);
+                offset += data_type.get_static_size();
+                property
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let types = [
+            DataType::Bool,
+            DataType::Byte,
+            DataType::Int,
+            DataType::Float,
+            DataType::Long,
+            DataType::Double,
+            DataType::String,
+            DataType::Object,
+            DataType::BoolList,
+            DataType::ByteList,
+            DataType::IntList,
+            DataType::FloatList,
+            DataType::LongList,
+            DataType::DoubleList,
+            DataType::StringList,
+            DataType::ObjectList,
+        ];
+        let props = properties(&types, Some("Embedded"));
+        let embedded_props = properties(&[DataType::String, DataType::IntList], None);
+        let mut embedded = IntMap::new();
+        embedded.insert(xxh3_64(b"Embedded"), embedded_props.clone());
+
+        let mut b = ObjectBuilder::new(&embedded_props, None);
+        b.write_string(embedded_props[0].offset, Some("nested"));
+        b.write_int_list(embedded_props[1].offset, Some(&[1, IsarObject::NULL_INT]));
+        let nested = b.finish().as_bytes().to_vec();
+        let nested = IsarObject::from_bytes(&nested);
+
+        let mut full = ObjectBuilder::new(&props, None);
+        full.write_bool(props[0].offset, Some(true));
+        full.write_byte(props[1].offset, 7);
+        full.write_int(props[2].offset, -3);
+        full.write_float(props[3].offset, 1.5);
+        full.write_long(props[4].offset, i64::MAX);
+        full.write_double(props[5].offset, -0.25);
+        full.write_string(props[6].offset, Some("isar"));
+        full.write_object(props[7].offset, Some(nested));
+        full.write_bool_list(props[8].offset, Some(&[Some(false), None]));
+        full.write_byte_list(props[9].offset, Some(&[0, 255]));
+        full.write_int_list(props[10].offset, Some(&[IsarObject::NULL_INT, 5]));
+        full.write_float_list(props[11].offset, Some(&[IsarObject::NULL_FLOAT, 2.5]));
+        full.write_long_list(props[12].offset, Some(&[IsarObject::NULL_LONG, 9]));
+        full.write_double_list(props[13].offset, Some(&[IsarObject::NULL_DOUBLE, 0.5]));
+        full.write_string_list(props[14].offset, Some(&[None, Some("")]));
+        full.write_object_list(props[15].offset, Some(&[None, Some(nested)]));
+        let full = full.finish().as_bytes().to_vec();
+
+        let mut empty = ObjectBuilder::new(&props, None);
+        for p in &props {
+            empty.write_null(p.offset, p.data_type);
+        }
+        let empty = empty.finish().as_bytes().to_vec();
+
+        for bytes in [full, empty] {
+            let object = IsarObject::from_bytes(&bytes);
+            let json = JsonEncodeDecode::encode(&props, &embedded, object, true);
+            assert_eq!(json.len(), props.len());
+            let text = serde_json::to_string(&json).unwrap();
+
+            let mut b = ObjectBuilder::new(&props, None);
+            let value = serde_json::from_str(&text).unwrap();
+            JsonEncodeDecode::decode(&props, &embedded, &mut b, &value).unwrap();
+            assert_eq!(b.finish().as_bytes(), object.as_bytes());
+        }
+    }
+}
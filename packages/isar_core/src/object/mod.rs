@@ -2,5 +2,8 @@ pub mod data_type;
 pub mod id;
 pub mod isar_object;
 pub mod json_encode_decode;
+pub(crate) mod msgpack;
 pub mod object_builder;
+pub mod object_reader;
+pub mod projected_object;
 pub mod property;
@@ -0,0 +1,290 @@
+use crate::error::{IsarError, Result};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde_json::{Map, Number, Value};
+use std::io::{self, Read, Write};
+
+/// Nested arrays and objects deeper than this are rejected when decoding.
+const MAX_DEPTH: usize = 128;
+
+/// Encodes a JSON value as MessagePack. Numbers use the smallest integer representation and all
+/// floating point numbers are written as 64-bit floats.
+pub(crate) fn write_value<W: Write>(writer: &mut W, value: &Value) -> io::Result<()> {
+    match value {
+        Value::Null => writer.write_u8(0xc0),
+        Value::Bool(value) => writer.write_u8(if *value { 0xc3 } else { 0xc2 }),
+        Value::Number(number) => {
+            if let Some(value) = number.as_u64() {
+                write_uint(writer, value)
+            } else if let Some(value) = number.as_i64() {
+                write_int(writer, value)
+            } else {
+                writer.write_u8(0xcb)?;
+                writer.write_f64::<BigEndian>(number.as_f64().unwrap())
+            }
+        }
+        Value::String(value) => {
+            let len = value.len();
+            if len < 32 {
+                writer.write_u8(0xa0 | len as u8)?;
+            } else if len <= u8::MAX as usize {
+                writer.write_u8(0xd9)?;
+                writer.write_u8(len as u8)?;
+            } else if len <= u16::MAX as usize {
+                writer.write_u8(0xda)?;
+                writer.write_u16::<BigEndian>(len as u16)?;
+            } else {
+                writer.write_u8(0xdb)?;
+                writer.write_u32::<BigEndian>(len as u32)?;
+            }
+            writer.write_all(value.as_bytes())
+        }
+        Value::Array(values) => {
+            write_container_len(writer, values.len(), 0x90, 0xdc)?;
+            for value in values {
+                write_value(writer, value)?;
+            }
+            Ok(())
+        }
+        Value::Object(map) => {
+            write_container_len(writer, map.len(), 0x80, 0xde)?;
+            for (key, value) in map {
+                write_value(writer, &Value::String(key.clone()))?;
+                write_value(writer, value)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn write_uint<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    if value < 128 {
+        writer.write_u8(value as u8)
+    } else if value <= u8::MAX as u64 {
+        writer.write_u8(0xcc)?;
+        writer.write_u8(value as u8)
+    } else if value <= u16::MAX as u64 {
+        writer.write_u8(0xcd)?;
+        writer.write_u16::<BigEndian>(value as u16)
+    } else if value <= u32::MAX as u64 {
+        writer.write_u8(0xce)?;
+        writer.write_u32::<BigEndian>(value as u32)
+    } else {
+        writer.write_u8(0xcf)?;
+        writer.write_u64::<BigEndian>(value)
+    }
+}
+
+fn write_int<W: Write>(writer: &mut W, value: i64) -> io::Result<()> {
+    if value >= -32 {
+        writer.write_i8(value as i8)
+    } else if value >= i8::MIN as i64 {
+        writer.write_u8(0xd0)?;
+        writer.write_i8(value as i8)
+    } else if value >= i16::MIN as i64 {
+        writer.write_u8(0xd1)?;
+        writer.write_i16::<BigEndian>(value as i16)
+    } else if value >= i32::MIN as i64 {
+        writer.write_u8(0xd2)?;
+        writer.write_i32::<BigEndian>(value as i32)
+    } else {
+        writer.write_u8(0xd3)?;
+        writer.write_i64::<BigEndian>(value)
+    }
+}
+
+fn write_container_len<W: Write>(
+    writer: &mut W,
+    len: usize,
+    fix: u8,
+    marker: u8,
+) -> io::Result<()> {
+    if len < 16 {
+        writer.write_u8(fix | len as u8)
+    } else if len <= u16::MAX as usize {
+        writer.write_u8(marker)?;
+        writer.write_u16::<BigEndian>(len as u16)
+    } else {
+        writer.write_u8(marker + 1)?;
+        writer.write_u32::<BigEndian>(len as u32)
+    }
+}
+
+/// Decodes the next value of a stream of concatenated MessagePack values. Returns `None` at the
+/// end of the stream.
+pub(crate) fn read_value<R: Read>(reader: &mut R) -> Result<Option<Value>> {
+    let mut marker = [0u8];
+    loop {
+        match reader.read(&mut marker) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return Err(IsarError::InvalidMsgPack {}),
+        }
+    }
+    read_value_with_marker(reader, marker[0], 0).map(Some)
+}
+
+fn read_value_with_marker<R: Read>(reader: &mut R, marker: u8, depth: usize) -> Result<Value> {
+    if depth > MAX_DEPTH {
+        return Err(IsarError::InvalidMsgPack {});
+    }
+    let value = match marker {
+        0x00..=0x7f => Value::from(marker),
+        0xe0..=0xff => Value::from(marker as i8),
+        0xc0 => Value::Null,
+        0xc2 => Value::Bool(false),
+        0xc3 => Value::Bool(true),
+        0xcc => Value::from(reader.read_u8().map_err(invalid)?),
+        0xcd => Value::from(reader.read_u16::<BigEndian>().map_err(invalid)?),
+        0xce => Value::from(reader.read_u32::<BigEndian>().map_err(invalid)?),
+        0xcf => Value::from(reader.read_u64::<BigEndian>().map_err(invalid)?),
+        0xd0 => Value::from(reader.read_i8().map_err(invalid)?),
+        0xd1 => Value::from(reader.read_i16::<BigEndian>().map_err(invalid)?),
+        0xd2 => Value::from(reader.read_i32::<BigEndian>().map_err(invalid)?),
+        0xd3 => Value::from(reader.read_i64::<BigEndian>().map_err(invalid)?),
+        0xca => float_value(reader.read_f32::<BigEndian>().map_err(invalid)? as f64),
+        0xcb => float_value(reader.read_f64::<BigEndian>().map_err(invalid)?),
+        0xa0..=0xbf => Value::String(read_string(reader, (marker & 0x1f) as usize)?),
+        0xd9 => {
+            let len = reader.read_u8().map_err(invalid)? as usize;
+            Value::String(read_string(reader, len)?)
+        }
+        0xda => {
+            let len = reader.read_u16::<BigEndian>().map_err(invalid)? as usize;
+            Value::String(read_string(reader, len)?)
+        }
+        0xdb => {
+            let len = reader.read_u32::<BigEndian>().map_err(invalid)? as usize;
+            Value::String(read_string(reader, len)?)
+        }
+        0x90..=0x9f => read_array(reader, (marker & 0x0f) as usize, depth)?,
+        0xdc => {
+            let len = reader.read_u16::<BigEndian>().map_err(invalid)? as usize;
+            read_array(reader, len, depth)?
+        }
+        0xdd => {
+            let len = reader.read_u32::<BigEndian>().map_err(invalid)? as usize;
+            read_array(reader, len, depth)?
+        }
+        0x80..=0x8f => read_map(reader, (marker & 0x0f) as usize, depth)?,
+        0xde => {
+            let len = reader.read_u16::<BigEndian>().map_err(invalid)? as usize;
+            read_map(reader, len, depth)?
+        }
+        0xdf => {
+            let len = reader.read_u32::<BigEndian>().map_err(invalid)? as usize;
+            read_map(reader, len, depth)?
+        }
+        _ => return Err(IsarError::InvalidMsgPack {}),
+    };
+    Ok(value)
+}
+
+fn invalid(_: io::Error) -> IsarError {
+    IsarError::InvalidMsgPack {}
+}
+
+fn float_value(value: f64) -> Value {
+    Number::from_f64(value).map_or(Value::Null, Value::Number)
+}
+
+fn read_string<R: Read>(reader: &mut R, len: usize) -> Result<String> {
+    let mut bytes = vec![];
+    reader
+        .take(len as u64)
+        .read_to_end(&mut bytes)
+        .map_err(invalid)?;
+    if bytes.len() != len {
+        return Err(IsarError::InvalidMsgPack {});
+    }
+    String::from_utf8(bytes).map_err(|_| IsarError::InvalidMsgPack {})
+}
+
+fn read_array<R: Read>(reader: &mut R, len: usize, depth: usize) -> Result<Value> {
+    let mut values = Vec::with_capacity(len.min(1024));
+    for _ in 0..len {
+        let marker = reader.read_u8().map_err(invalid)?;
+        values.push(read_value_with_marker(reader, marker, depth + 1)?);
+    }
+    Ok(Value::Array(values))
+}
+
+fn read_map<R: Read>(reader: &mut R, len: usize, depth: usize) -> Result<Value> {
+    let mut map = Map::new();
+    for _ in 0..len {
+        let marker = reader.read_u8().map_err(invalid)?;
+        let key = match read_value_with_marker(reader, marker, depth + 1)? {
+            Value::String(key) => key,
+            _ => return Err(IsarError::InvalidMsgPack {}),
+        };
+        let marker = reader.read_u8().map_err(invalid)?;
+        map.insert(key, read_value_with_marker(reader, marker, depth + 1)?);
+    }
+    Ok(Value::Object(map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trip() {
+        let values = vec![
+            json!(null),
+            json!(true),
+            json!(0),
+            json!(127),
+            json!(128),
+            json!(u64::MAX),
+            json!(-1),
+            json!(-33),
+            json!(i64::MIN),
+            json!(1.5),
+            json!(""),
+            json!("a".repeat(40)),
+            json!("ä".repeat(200)),
+            json!((0..20).collect::<Vec<i32>>()),
+            json!({"id": 1, "name": "isar", "tags": ["a", null], "nested": {"x": -2.25}}),
+        ];
+        let mut bytes = vec![];
+        for value in &values {
+            write_value(&mut bytes, value).unwrap();
+        }
+
+        let mut reader = bytes.as_slice();
+        for value in &values {
+            assert_eq!(read_value(&mut reader).unwrap().as_ref(), Some(value));
+        }
+        assert_eq!(read_value(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_smaller_than_json() {
+        let objects = (0..100)
+            .map(|i| json!({"id": i, "price": i * 3, "active": i % 2 == 0, "name": "product"}))
+            .collect::<Vec<_>>();
+        let mut bytes = vec![];
+        for object in &objects {
+            write_value(&mut bytes, object).unwrap();
+        }
+        let json = serde_json::to_vec(&objects).unwrap();
+        assert!(bytes.len() < json.len());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut truncated: &[u8] = &[0xa5, b'i', b's'];
+        assert!(read_value(&mut truncated).is_err());
+
+        let mut unsupported: &[u8] = &[0xc4, 0x00];
+        assert!(read_value(&mut unsupported).is_err());
+
+        let mut non_string_key: &[u8] = &[0x81, 0x01, 0x02];
+        assert!(read_value(&mut non_string_key).is_err());
+
+        let mut deep = vec![0x91; MAX_DEPTH + 2];
+        deep.push(0xc0);
+        assert!(read_value(&mut deep.as_slice()).is_err());
+    }
+}
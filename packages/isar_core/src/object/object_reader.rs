@@ -0,0 +1,129 @@
+use crate::error::{illegal_arg, IsarError, Result};
+use crate::object::data_type::DataType;
+use crate::object::isar_object::IsarObject;
+use crate::object::property::Property;
+use intmap::IntMap;
+
+/// Reads the properties of an object by their index. Unlike the accessors of [IsarObject], every
+/// read checks the index and the type of the property so bindings can pass arbitrary indices
+/// without reading garbage.
+pub struct ObjectReader<'a> {
+    object: IsarObject<'a>,
+    properties: &'a [Property],
+}
+
+impl<'a> ObjectReader<'a> {
+    /// Fails with `IsarError::DbCorrupted` if `bytes` cannot be read with `properties`.
+    pub fn new(
+        bytes: &'a [u8],
+        properties: &'a [Property],
+        embedded_properties: &IntMap<Vec<Property>>,
+    ) -> Result<Self> {
+        if !IsarObject::is_valid(bytes, properties, embedded_properties) {
+            return Err(IsarError::DbCorrupted {
+                message: "Object does not match the schema.".to_string(),
+            });
+        }
+        Ok(ObjectReader {
+            object: IsarObject::from_bytes(bytes),
+            properties,
+        })
+    }
+
+    fn property(&self, index: usize, data_type: DataType) -> Result<&Property> {
+        if let Some(property) = self.properties.get(index) {
+            if property.data_type == data_type {
+                Ok(property)
+            } else {
+                illegal_arg(&format!(
+                    "Property {} has type {:?} instead of {:?}.",
+                    index, property.data_type, data_type
+                ))
+            }
+        } else {
+            illegal_arg("Property does not exist.")
+        }
+    }
+
+    pub fn try_read_bool(&self, index: usize) -> Result<Option<bool>> {
+        let property = self.property(index, DataType::Bool)?;
+        Ok(self.object.read_bool(property.offset))
+    }
+
+    pub fn try_read_byte(&self, index: usize) -> Result<u8> {
+        let property = self.property(index, DataType::Byte)?;
+        Ok(self.object.read_byte(property.offset))
+    }
+
+    pub fn try_read_int(&self, index: usize) -> Result<Option<i32>> {
+        let property = self.property(index, DataType::Int)?;
+        let value = self.object.read_int(property.offset);
+        Ok(Some(value).filter(|v| *v != IsarObject::NULL_INT))
+    }
+
+    pub fn try_read_long(&self, index: usize) -> Result<Option<i64>> {
+        let property = self.property(index, DataType::Long)?;
+        let value = self.object.read_long(property.offset);
+        Ok(Some(value).filter(|v| *v != IsarObject::NULL_LONG))
+    }
+
+    pub fn try_read_float(&self, index: usize) -> Result<Option<f32>> {
+        let property = self.property(index, DataType::Float)?;
+        let value = self.object.read_float(property.offset);
+        Ok(Some(value).filter(|v| !v.is_nan()))
+    }
+
+    pub fn try_read_double(&self, index: usize) -> Result<Option<f64>> {
+        let property = self.property(index, DataType::Double)?;
+        let value = self.object.read_double(property.offset);
+        Ok(Some(value).filter(|v| !v.is_nan()))
+    }
+
+    pub fn try_read_string(&self, index: usize) -> Result<Option<&'a str>> {
+        let property = self.property(index, DataType::String)?;
+        if let Some(bytes) = self.object.read_byte_list(property.offset) {
+            // validated in new()
+            Ok(std::str::from_utf8(bytes).ok())
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn try_read_byte_list(&self, index: usize) -> Result<Option<&'a [u8]>> {
+        let property = self.property(index, DataType::ByteList)?;
+        Ok(self.object.read_byte_list(property.offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::object_builder::ObjectBuilder;
+
+    #[test]
+    fn test_try_read() {
+        let properties = vec![
+            Property::debug(DataType::Long, 2),
+            Property::debug(DataType::Int, 10),
+            Property::debug(DataType::String, 14),
+        ];
+        let embedded = IntMap::new();
+        let mut builder = ObjectBuilder::new(&properties, None);
+        builder.write_long(2, 42);
+        builder.write_null(10, DataType::Int);
+        builder.write_string(14, Some("isar"));
+        let bytes = builder.finish().as_bytes().to_vec();
+
+        let reader = ObjectReader::new(&bytes, &properties, &embedded).unwrap();
+        assert_eq!(reader.try_read_long(0).unwrap(), Some(42));
+        assert_eq!(reader.try_read_int(1).unwrap(), None);
+        assert_eq!(reader.try_read_string(2).unwrap(), Some("isar"));
+
+        assert!(reader.try_read_long(3).is_err());
+        assert!(reader.try_read_int(0).is_err());
+        assert!(reader.try_read_double(0).is_err());
+        assert!(reader.try_read_byte_list(2).is_err());
+
+        assert!(ObjectReader::new(&bytes[..8], &properties, &embedded).is_err());
+    }
+}
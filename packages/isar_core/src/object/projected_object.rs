@@ -0,0 +1,218 @@
+use crate::error::{illegal_arg, Result};
+use crate::object::isar_object::IsarObject;
+use crate::object::json_encode_decode::JsonEncodeDecode;
+use crate::object::property::Property;
+use intmap::IntMap;
+use serde_json::{Map, Value};
+
+/// An object that only exposes a subset of its properties. Properties outside of the projection
+/// are never read.
+pub struct ProjectedObject<'a> {
+    object: IsarObject<'a>,
+    indices: Vec<usize>,
+    properties: Vec<Property>,
+}
+
+impl<'a> ProjectedObject<'a> {
+    pub(crate) fn new(
+        object: IsarObject<'a>,
+        all_properties: &[Property],
+        indices: &[usize],
+    ) -> Result<Self> {
+        let mut projected_indices = vec![];
+        let mut properties = vec![];
+        for index in indices {
+            if projected_indices.contains(index) {
+                continue;
+            }
+            if let Some(property) = all_properties.get(*index) {
+                projected_indices.push(*index);
+                properties.push(property.clone());
+            } else {
+                illegal_arg("Property does not exist.")?;
+            }
+        }
+        Ok(ProjectedObject {
+            object,
+            indices: projected_indices,
+            properties,
+        })
+    }
+
+    pub fn object(&self) -> IsarObject<'a> {
+        self.object
+    }
+
+    pub fn property(&self, index: usize) -> Result<&Property> {
+        if let Some(pos) = self.indices.iter().position(|i| *i == index) {
+            Ok(&self.properties[pos])
+        } else {
+            illegal_arg("Property is not part of the projection.")
+        }
+    }
+
+    pub fn read_json(
+        &self,
+        index: usize,
+        embedded_properties: &IntMap<Vec<Property>>,
+        primitive_null: bool,
+    ) -> Result<Value> {
+        let property = self.property(index)?;
+        let mut json = JsonEncodeDecode::encode(
+            std::slice::from_ref(property),
+            embedded_properties,
+            self.object,
+            primitive_null,
+        );
+        Ok(json.remove(&property.name).unwrap_or(Value::Null))
+    }
+
+    pub fn to_json(
+        &self,
+        embedded_properties: &IntMap<Vec<Property>>,
+        primitive_null: bool,
+    ) -> Map<String, Value> {
+        JsonEncodeDecode::encode(
+            &self.properties,
+            embedded_properties,
+            self.object,
+            primitive_null,
+        )
+    }
+}
+
+/// Replaces the values of redacted properties in exports.
+pub const REDACTED_VALUE: &str = "<redacted>";
+
+/// Selects the properties of an export. Redacted properties are included with
+/// [REDACTED_VALUE] instead of their value.
+pub(crate) struct ExportProjection {
+    properties: Vec<Property>,
+    redacted: Vec<String>,
+}
+
+impl ExportProjection {
+    /// `properties` and `redact` are indices into `all_properties`. All properties are included
+    /// if `properties` is `None`.
+    pub fn new(
+        all_properties: &[Property],
+        properties: Option<&[u16]>,
+        redact: &[u16],
+    ) -> Result<Self> {
+        let get = |index: &u16| match all_properties.get(*index as usize) {
+            Some(property) => Ok(property),
+            None => illegal_arg("Property does not exist."),
+        };
+        let redacted = redact
+            .iter()
+            .map(|i| get(i).map(|p| p.name.clone()))
+            .collect::<Result<Vec<_>>>()?;
+        let included = match properties {
+            Some(indices) => indices.iter().map(get).collect::<Result<Vec<_>>>()?,
+            None => all_properties.iter().collect(),
+        };
+        let mut properties: Vec<Property> = vec![];
+        for property in included {
+            if !properties.contains(property) {
+                properties.push(property.clone());
+            }
+        }
+        let redacted: Vec<String> = redacted
+            .into_iter()
+            .filter(|name| properties.iter().any(|p| &p.name == name))
+            .collect();
+        properties.retain(|p| !redacted.contains(&p.name));
+        Ok(ExportProjection {
+            properties,
+            redacted,
+        })
+    }
+
+    pub fn encode(
+        &self,
+        embedded_properties: &IntMap<Vec<Property>>,
+        object: IsarObject,
+        primitive_null: bool,
+    ) -> Map<String, Value> {
+        let mut json = JsonEncodeDecode::encode(
+            &self.properties,
+            embedded_properties,
+            object,
+            primitive_null,
+        );
+        for name in &self.redacted {
+            json.insert(name.clone(), Value::from(REDACTED_VALUE));
+        }
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::data_type::DataType;
+    use crate::object::object_builder::ObjectBuilder;
+    use serde_json::json;
+
+    #[test]
+    fn test_projection() {
+        let props = vec![
+            Property::new("a", DataType::Long, 2, None),
+            Property::new("blob", DataType::ByteList, 10, None),
+            Property::new("c", DataType::String, 18, None),
+        ];
+        let mut builder = ObjectBuilder::new(&props, None);
+        builder.write_long(2, 42);
+        builder.write_byte_list(10, Some(&[1; 1000]));
+        builder.write_string(18, Some("hello"));
+        let object = builder.finish();
+
+        let embedded = IntMap::new();
+        let full = JsonEncodeDecode::encode(&props, &embedded, object, false);
+        let projected = ProjectedObject::new(object, &props, &[2, 0, 2]).unwrap();
+
+        let json = projected.to_json(&embedded, false);
+        assert_eq!(json.len(), 2);
+        assert_eq!(json.get("a"), full.get("a"));
+        assert_eq!(json.get("c"), full.get("c"));
+        assert!(json.get("blob").is_none());
+
+        assert_eq!(projected.read_json(0, &embedded, false).unwrap(), json!(42));
+        assert!(projected.read_json(1, &embedded, false).is_err());
+        assert!(projected.property(1).is_err());
+        assert!(ProjectedObject::new(object, &props, &[3]).is_err());
+    }
+
+    #[test]
+    fn test_export_projection() {
+        let props = vec![
+            Property::new("a", DataType::Long, 2, None),
+            Property::new("blob", DataType::ByteList, 10, None),
+            Property::new("secret", DataType::String, 18, None),
+        ];
+        let mut builder = ObjectBuilder::new(&props, None);
+        builder.write_long(2, 42);
+        builder.write_byte_list(10, Some(&[1; 100]));
+        builder.write_string(18, Some("hunter2"));
+        let object = builder.finish();
+        let embedded = IntMap::new();
+
+        let projection = ExportProjection::new(&props, Some(&[0, 2]), &[2]).unwrap();
+        let json = projection.encode(&embedded, object, false);
+        assert_eq!(json.len(), 2);
+        assert_eq!(json.get("a"), Some(&json!(42)));
+        assert_eq!(json.get("secret"), Some(&json!(REDACTED_VALUE)));
+        assert!(json.get("blob").is_none());
+
+        let projection = ExportProjection::new(&props, None, &[1]).unwrap();
+        let json = projection.encode(&embedded, object, false);
+        assert_eq!(json.len(), 3);
+        assert_eq!(json.get("blob"), Some(&json!(REDACTED_VALUE)));
+        assert_eq!(json.get("secret"), Some(&json!("hunter2")));
+
+        let projection = ExportProjection::new(&props, Some(&[0]), &[2]).unwrap();
+        assert_eq!(projection.encode(&embedded, object, false).len(), 1);
+        assert!(ExportProjection::new(&props, Some(&[3]), &[]).is_err());
+        assert!(ExportProjection::new(&props, None, &[3]).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::cursor::IsarCursors;
+use crate::error::{IsarError, Result};
+use crate::mdbx::db::Db;
+use crate::object::id::BytesToId;
+use byteorder::{ByteOrder, LittleEndian};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum OpType {
+    Insert,
+    Update,
+    Delete,
+    Clear,
+}
+
+impl OpType {
+    fn to_byte(self) -> u8 {
+        match self {
+            OpType::Insert => 0,
+            OpType::Update => 1,
+            OpType::Delete => 2,
+            OpType::Clear => 3,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(OpType::Insert),
+            1 => Some(OpType::Update),
+            2 => Some(OpType::Delete),
+            3 => Some(OpType::Clear),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct OpLogEntry {
+    pub seq: i64,
+    pub txn_id: u64,
+    pub collection_id: u64,
+    pub id: i64,
+    pub op: OpType,
+}
+
+impl OpLogEntry {
+    const SIZE: usize = 25;
+
+    fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        LittleEndian::write_u64(&mut bytes[0..8], self.txn_id);
+        LittleEndian::write_u64(&mut bytes[8..16], self.collection_id);
+        LittleEndian::write_i64(&mut bytes[16..24], self.id);
+        bytes[24] = self.op.to_byte();
+        bytes
+    }
+
+    fn from_bytes(seq: i64, bytes: &[u8]) -> Result<Self> {
+        let op = if bytes.len() == Self::SIZE {
+            OpType::from_byte(bytes[24])
+        } else {
+            None
+        };
+        if let Some(op) = op {
+            Ok(OpLogEntry {
+                seq,
+                txn_id: LittleEndian::read_u64(&bytes[0..8]),
+                collection_id: LittleEndian::read_u64(&bytes[8..16]),
+                id: LittleEndian::read_i64(&bytes[16..24]),
+                op,
+            })
+        } else {
+            Err(IsarError::DbCorrupted {
+                message: "Invalid operation log entry.".to_string(),
+            })
+        }
+    }
+}
+
+/// Appends an entry to the operation log. Entries are keyed by a sequence number so they are
+/// read back in the order they were written.
+pub(crate) fn append(
+    cursors: &IsarCursors,
+    db: Db,
+    collection_id: u64,
+    id: i64,
+    op: OpType,
+) -> Result<()> {
+    let mut cursor = cursors.get_cursor(db)?;
+    let seq = cursor
+        .move_to_last()?
+        .map_or(0, |(key, _)| key.to_id().saturating_add(1));
+    let entry = OpLogEntry {
+        seq,
+        txn_id: cursors.txn_id(),
+        collection_id,
+        id,
+        op,
+    };
+    cursor.put(&seq, &entry.to_bytes())
+}
+
+/// Returns the entries of `collection_id` with a sequence number greater than `since_seq`, at
+/// most `limit` of them.
+pub(crate) fn read(
+    cursors: &IsarCursors,
+    db: Db,
+    collection_id: u64,
+    since_seq: i64,
+    limit: usize,
+) -> Result<Vec<OpLogEntry>> {
+    let mut entries = vec![];
+    if since_seq == i64::MAX || limit == 0 {
+        return Ok(entries);
+    }
+    let mut cursor = cursors.get_cursor(db)?;
+    cursor.iter_between(
+        &(since_seq + 1),
+        &i64::MAX,
+        false,
+        false,
+        true,
+        |_, key, bytes| {
+            let entry = OpLogEntry::from_bytes(key.to_id(), bytes)?;
+            if entry.collection_id == collection_id {
+                entries.push(entry);
+            }
+            Ok(entries.len() < limit)
+        },
+    )?;
+    Ok(entries)
+}
+
+/// Removes the entries with a sequence number lower than `before_seq`. The newest entry is always
+/// kept because the next sequence number is derived from it, so sequence numbers are never reused.
+pub(crate) fn prune(cursors: &IsarCursors, db: Db, before_seq: i64) -> Result<u32> {
+    let mut count = 0;
+    let mut cursor = cursors.get_cursor(db)?;
+    let last_seq = cursor.move_to_last()?.map(|(key, _)| key.to_id());
+    while let Some((key, _)) = cursor.move_to_first()? {
+        let seq = key.to_id();
+        if Some(seq) == last_seq || seq >= before_seq {
+            break;
+        }
+        cursor.delete_current()?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_bytes() {
+        let entry = OpLogEntry {
+            seq: 7,
+            txn_id: 42,
+            collection_id: u64::MAX,
+            id: -3,
+            op: OpType::Update,
+        };
+        let bytes = entry.to_bytes();
+        assert_eq!(OpLogEntry::from_bytes(7, &bytes).unwrap(), entry);
+
+        let mut invalid = bytes;
+        invalid[24] = 9;
+        assert!(OpLogEntry::from_bytes(7, &invalid).is_err());
+        assert!(OpLogEntry::from_bytes(7, &bytes[..10]).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+/// Page aligned address ranges covering a set of memory slices. Slices are expected in roughly
+/// ascending address order, which is the order of the pages of an MDBX scan.
+pub(crate) struct PageRanges {
+    page_size: usize,
+    ranges: Vec<(usize, usize)>,
+}
+
+impl PageRanges {
+    pub fn new(page_size: usize) -> Self {
+        PageRanges {
+            page_size,
+            ranges: vec![],
+        }
+    }
+
+    pub fn add(&mut self, slice: &[u8]) {
+        if slice.is_empty() {
+            return;
+        }
+        let start = slice.as_ptr() as usize / self.page_size * self.page_size;
+        let end = (slice.as_ptr() as usize + slice.len()).div_ceil(self.page_size) * self.page_size;
+        if let Some(last) = self.ranges.last_mut() {
+            if start <= last.1 && end >= last.0 {
+                *last = (last.0.min(start), last.1.max(end));
+                return;
+            }
+        }
+        self.ranges.push((start, end));
+    }
+
+    pub fn into_ranges(self) -> Vec<(usize, usize)> {
+        self.ranges
+    }
+}
+
+/// Memory ranges locked into RAM with `mlock`.
+#[derive(Default)]
+pub(crate) struct PinnedPages {
+    ranges: Vec<(usize, usize)>,
+}
+
+impl PinnedPages {
+    /// Locks the ranges until `max_bytes` are locked. Ranges that cannot be locked, e.g. because
+    /// of the `RLIMIT_MEMLOCK` limit, are skipped. Nothing is locked on platforms without
+    /// `mlock`.
+    pub fn lock(ranges: Vec<(usize, usize)>, max_bytes: u64) -> Self {
+        let mut pinned = PinnedPages::default();
+        let mut budget = max_bytes;
+        for (start, end) in ranges {
+            let len = (end - start) as u64;
+            if len > budget {
+                break;
+            }
+            if mlock(start, end - start) {
+                budget -= len;
+                pinned.ranges.push((start, end));
+            }
+        }
+        pinned
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.ranges
+            .iter()
+            .map(|(start, end)| (end - start) as u64)
+            .sum()
+    }
+
+    pub fn unlock(self) {
+        for (start, end) in self.ranges {
+            munlock(start, end - start);
+        }
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn os_page_size() -> usize {
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if size > 0 {
+        size as usize
+    } else {
+        4096
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn os_page_size() -> usize {
+    4096
+}
+
+#[cfg(unix)]
+fn mlock(start: usize, len: usize) -> bool {
+    unsafe { libc::mlock(start as *const libc::c_void, len) == 0 }
+}
+
+#[cfg(not(unix))]
+fn mlock(_start: usize, _len: usize) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn munlock(start: usize, len: usize) {
+    // the pages may have been unmapped already if the map was resized
+    unsafe {
+        libc::munlock(start as *const libc::c_void, len);
+    }
+}
+
+#[cfg(not(unix))]
+fn munlock(_start: usize, _len: usize) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_ranges() {
+        let buffer = vec![0u8; 4 * 4096 + 100];
+        let base = buffer.as_ptr() as usize;
+        let aligned = (base.div_ceil(4096)) * 4096 - base;
+        let mut ranges = PageRanges::new(4096);
+        ranges.add(&buffer[aligned..aligned + 10]);
+        ranges.add(&buffer[aligned + 100..aligned + 4097]);
+        ranges.add(&buffer[aligned..aligned]);
+        let start = base + aligned;
+        assert_eq!(ranges.into_ranges(), vec![(start, start + 2 * 4096)]);
+
+        let mut ranges = PageRanges::new(4096);
+        ranges.add(&buffer[aligned..aligned + 1]);
+        ranges.add(&buffer[aligned + 3 * 4096..aligned + 3 * 4096 + 1]);
+        assert_eq!(
+            ranges.into_ranges(),
+            vec![(start, start + 4096), (start + 3 * 4096, start + 4 * 4096)]
+        );
+    }
+
+    #[test]
+    fn test_pin_unpin() {
+        let page_size = os_page_size();
+        let buffer = vec![1u8; 4 * page_size];
+        let mut ranges = PageRanges::new(page_size);
+        ranges.add(&buffer);
+        let ranges = ranges.into_ranges();
+
+        // locking fails cleanly without permission or on platforms without mlock
+        let pinned = PinnedPages::lock(ranges.clone(), u64::MAX);
+        assert!(pinned.bytes() == 0 || pinned.bytes() >= buffer.len() as u64);
+        pinned.unlock();
+
+        let pinned = PinnedPages::lock(ranges, page_size as u64);
+        assert_eq!(pinned.bytes(), 0);
+        pinned.unlock();
+    }
+}
@@ -0,0 +1,172 @@
+use crate::error::{illegal_arg, IsarError, Result};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CachedValue {
+    Long(i64),
+    Double(f64),
+    Null,
+}
+
+impl CachedValue {
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            CachedValue::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            CachedValue::Double(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == CachedValue::Null
+    }
+
+    /// Converts the value to `T` or fails with `IsarError::IllegalArg` if it has a different
+    /// type.
+    pub fn try_into_t<T: TryFrom<CachedValue, Error = IsarError>>(self) -> Result<T> {
+        T::try_from(self)
+    }
+}
+
+impl TryFrom<CachedValue> for i64 {
+    type Error = IsarError;
+
+    fn try_from(value: CachedValue) -> Result<Self> {
+        value
+            .as_i64()
+            .map_or_else(|| illegal_arg("The value is not a Long."), Ok)
+    }
+}
+
+impl TryFrom<CachedValue> for f64 {
+    type Error = IsarError;
+
+    fn try_from(value: CachedValue) -> Result<Self> {
+        value
+            .as_f64()
+            .map_or_else(|| illegal_arg("The value is not a Double."), Ok)
+    }
+}
+
+impl TryFrom<CachedValue> for Option<i64> {
+    type Error = IsarError;
+
+    fn try_from(value: CachedValue) -> Result<Self> {
+        if value.is_null() {
+            Ok(None)
+        } else {
+            i64::try_from(value).map(Some)
+        }
+    }
+}
+
+impl TryFrom<CachedValue> for Option<f64> {
+    type Error = IsarError;
+
+    fn try_from(value: CachedValue) -> Result<Self> {
+        if value.is_null() {
+            Ok(None)
+        } else {
+            f64::try_from(value).map(Some)
+        }
+    }
+}
+
+/// Caches aggregation results of a query together with the transaction id they were computed in.
+/// Entries are only returned for the same id so any committed write invalidates them. The least
+/// recently used entry is evicted once `capacity` entries are stored.
+pub(crate) struct AggregateCache {
+    capacity: usize,
+    // most recently used last
+    entries: Vec<(u64, u64, CachedValue)>,
+}
+
+impl AggregateCache {
+    pub fn new(capacity: usize) -> Self {
+        AggregateCache {
+            capacity,
+            entries: Vec::with_capacity(capacity.min(64)),
+        }
+    }
+
+    pub fn get(&mut self, key: u64, watermark: u64) -> Option<CachedValue> {
+        let index = self.entries.iter().position(|(k, _, _)| *k == key)?;
+        let entry = self.entries.remove(index);
+        if entry.1 == watermark {
+            self.entries.push(entry);
+            Some(entry.2)
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(&mut self, key: u64, watermark: u64, value: CachedValue) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.entries.retain(|(k, _, _)| *k != key);
+        if self.entries.len() >= self.capacity {
+            self.entries.remove(0);
+        }
+        self.entries.push((key, watermark, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregate_cache() {
+        let mut cache = AggregateCache::new(2);
+        assert_eq!(cache.get(1, 10), None);
+
+        cache.insert(1, 10, CachedValue::Long(5));
+        assert_eq!(cache.get(1, 10), Some(CachedValue::Long(5)));
+        assert_eq!(cache.get(1, 11), None);
+        assert_eq!(cache.get(1, 10), None);
+
+        cache.insert(1, 11, CachedValue::Long(6));
+        cache.insert(2, 11, CachedValue::Double(1.5));
+        cache.get(1, 11);
+        cache.insert(3, 11, CachedValue::Null);
+        assert_eq!(cache.get(2, 11), None);
+        assert_eq!(cache.get(1, 11), Some(CachedValue::Long(6)));
+        assert_eq!(cache.get(3, 11), Some(CachedValue::Null));
+
+        let mut disabled = AggregateCache::new(0);
+        disabled.insert(1, 10, CachedValue::Long(5));
+        assert_eq!(disabled.get(1, 10), None);
+    }
+
+    #[test]
+    fn test_cached_value_accessors() {
+        let long = CachedValue::Long(5);
+        let double = CachedValue::Double(1.5);
+        let null = CachedValue::Null;
+
+        assert_eq!(long.as_i64(), Some(5));
+        assert_eq!(double.as_i64(), None);
+        assert_eq!(null.as_i64(), None);
+        assert_eq!(double.as_f64(), Some(1.5));
+        assert_eq!(long.as_f64(), None);
+        assert_eq!(null.as_f64(), None);
+        assert!(null.is_null());
+        assert!(!long.is_null());
+
+        assert_eq!(long.try_into_t::<i64>().unwrap(), 5);
+        assert!(long.try_into_t::<f64>().is_err());
+        assert_eq!(double.try_into_t::<f64>().unwrap(), 1.5);
+        assert!(double.try_into_t::<i64>().is_err());
+        assert!(null.try_into_t::<i64>().is_err());
+        assert_eq!(null.try_into_t::<Option<i64>>().unwrap(), None);
+        assert_eq!(long.try_into_t::<Option<i64>>().unwrap(), Some(5));
+        assert_eq!(null.try_into_t::<Option<f64>>().unwrap(), None);
+        assert!(long.try_into_t::<Option<f64>>().is_err());
+    }
+}
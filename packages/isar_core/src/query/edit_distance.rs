@@ -0,0 +1,50 @@
+/// Whether the Levenshtein distance between `a` and `b` is at most `max_distance`.
+///
+/// Strings whose length differs by more than `max_distance` are rejected without computing the
+/// distance. Otherwise the cost is O(len(a) * len(b)) but the computation stops as soon as a row
+/// exceeds `max_distance`.
+pub(crate) fn within_edit_distance(a: &[char], b: &str, max_distance: usize) -> bool {
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max_distance {
+        return false;
+    }
+
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.iter().enumerate() {
+        current[0] = i + 1;
+        let mut row_min = current[0];
+        for (j, b_char) in b.iter().enumerate() {
+            let cost = if a_char == b_char { 0 } else { 1 };
+            current[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(current[j] + 1);
+            row_min = row_min.min(current[j + 1]);
+        }
+        if row_min > max_distance {
+            return false;
+        }
+        std::mem::swap(&mut prev, &mut current);
+    }
+    prev[b.len()] <= max_distance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn within(a: &str, b: &str, max_distance: usize) -> bool {
+        let a: Vec<char> = a.chars().collect();
+        within_edit_distance(&a, b, max_distance)
+    }
+
+    #[test]
+    fn test_within_edit_distance() {
+        assert!(within("colour", "color", 1));
+        assert!(!within("colour", "color", 0));
+        assert!(within("color", "color", 0));
+        assert!(within("kitten", "sitting", 3));
+        assert!(!within("kitten", "sitting", 2));
+        assert!(within("", "ab", 2));
+        assert!(!within("", "abc", 2));
+        assert!(within("straße", "strasse", 2));
+    }
+}
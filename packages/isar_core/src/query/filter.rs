@@ -1,14 +1,19 @@
 use crate::collection::IsarCollection;
 use crate::cursor::IsarCursors;
 use crate::error::{illegal_arg, Result};
+use crate::index::tokenizer::{Term, Tokenizer};
 use crate::link::IsarLink;
 use crate::object::data_type::DataType;
 use crate::object::isar_object::IsarObject;
 use crate::object::property::Property;
+use crate::query::edit_distance::within_edit_distance;
 use crate::query::fast_wild_match::fast_wild_match;
+use crate::query::time_where_clause::{now_micros, RelativeTime};
 use enum_dispatch::enum_dispatch;
 use itertools::Itertools;
 use paste::paste;
+use std::cmp::Ordering;
+use std::time::Duration;
 
 #[macro_export]
 macro_rules! primitive_create {
@@ -68,6 +73,29 @@ macro_rules! string_filter_create {
     };
 }
 
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CmpOp {
+    Eq,
+    NotEq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+}
+
+impl CmpOp {
+    fn matches(self, ordering: Ordering) -> bool {
+        match self {
+            CmpOp::Eq => ordering == Ordering::Equal,
+            CmpOp::NotEq => ordering != Ordering::Equal,
+            CmpOp::Lt => ordering == Ordering::Less,
+            CmpOp::Lte => ordering != Ordering::Greater,
+            CmpOp::Gt => ordering == Ordering::Greater,
+            CmpOp::Gte => ordering != Ordering::Less,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Filter(FilterCond);
 
@@ -97,6 +125,45 @@ impl Filter {
         primitive_create!(Double, property, lower, upper)
     }
 
+    /// Matches timestamps (microseconds since the Unix epoch) of at most `duration` ago. The time
+    /// is taken once when the query is executed.
+    pub fn newer_than(property: &Property, duration: Duration) -> Result<Filter> {
+        Self::relative_time(property, RelativeTime::newer_than(duration))
+    }
+
+    /// Matches timestamps of more than `duration` ago. Null timestamps never match.
+    pub fn older_than(property: &Property, duration: Duration) -> Result<Filter> {
+        Self::relative_time(property, RelativeTime::older_than(duration))
+    }
+
+    fn relative_time(property: &Property, time: RelativeTime) -> Result<Filter> {
+        if property.data_type != DataType::Long {
+            illegal_arg("Property does not support this filter.")?;
+        }
+        Ok(Filter(FilterCond::RelativeTime(RelativeTimeCond {
+            offset: property.offset,
+            time,
+        })))
+    }
+
+    /// Matches objects whose enum property has the value `name`.
+    pub fn enum_eq(collection: &IsarCollection, property: &Property, name: &str) -> Result<Filter> {
+        let mapping = collection
+            .enum_mappings
+            .iter()
+            .find(|m| m.property == *property);
+        let value = if let Some(mapping) = mapping {
+            mapping.value_of(name)?
+        } else {
+            return illegal_arg("Property is not an enum.");
+        };
+        match property.data_type {
+            DataType::Byte => Filter::byte(property, value as u8, value as u8),
+            DataType::Int => Filter::int(property, value as i32, value as i32),
+            _ => Filter::long(property, value, value),
+        }
+    }
+
     pub fn string_to_bytes(str: Option<&str>, case_sensitive: bool) -> Option<Vec<u8>> {
         if case_sensitive {
             str.map(|s| s.as_bytes().to_vec())
@@ -161,6 +228,8 @@ impl Filter {
         string_filter_create!(EndsWith, property, value, case_sensitive)
     }
 
+    /// Matches strings containing `value` anywhere. Indexes only support prefix scans so this
+    /// filter is always evaluated on the objects returned by the where clauses.
     pub fn string_contains(
         property: &Property,
         value: &str,
@@ -177,6 +246,105 @@ impl Filter {
         string_filter_create!(Matches, property, value, case_sensitive)
     }
 
+    /// Matches strings within a Levenshtein distance of `max_distance` from `value`. Like
+    /// [Filter::string_contains] it is evaluated on every object returned by the where clauses.
+    /// Strings whose length differs by more than `max_distance` are skipped cheaply, the others
+    /// cost O(n * m) so combine it with a prefix where clause for large collections.
+    pub fn string_fuzzy(
+        property: &Property,
+        value: &str,
+        max_distance: u32,
+        case_sensitive: bool,
+    ) -> Result<Filter> {
+        let value = if case_sensitive {
+            value.chars().collect()
+        } else {
+            value.to_lowercase().chars().collect()
+        };
+        let cond = StringFuzzyCond {
+            offset: property.offset,
+            value,
+            max_distance: max_distance as usize,
+            case_sensitive,
+        };
+        let filter_cond = if property.data_type == DataType::String {
+            Ok(FilterCond::StringFuzzy(cond))
+        } else if property.data_type == DataType::StringList {
+            Ok(FilterCond::AnyStringFuzzy(AnyStringFuzzyCond(cond)))
+        } else {
+            illegal_arg("Property does not support this filter.")
+        }?;
+        Ok(Filter(filter_cond))
+    }
+
+    /// Matches strings containing every word of `query`. The string and the query are split into
+    /// words by `tokenizer` and a trailing `*` matches every word starting with the term. An empty
+    /// query matches nothing.
+    pub fn words_match(
+        property: &Property,
+        tokenizer: Tokenizer,
+        query: &str,
+        case_sensitive: bool,
+    ) -> Result<Filter> {
+        if property.data_type != DataType::String {
+            illegal_arg("Property does not support this filter.")?;
+        }
+        Ok(Filter(FilterCond::WordsMatch(WordsMatchCond {
+            offset: property.offset,
+            tokenizer,
+            terms: tokenizer.parse_query(query, case_sensitive),
+            case_sensitive,
+        })))
+    }
+
+    /// Compares two properties of the same object, for example `discountPrice < price`. Both
+    /// properties need to have the same scalar type. Objects where either value is null never match.
+    pub fn property_cmp(lhs: &Property, op: CmpOp, rhs: &Property) -> Result<Filter> {
+        if lhs.data_type != rhs.data_type {
+            illegal_arg("Compared properties need to have the same type.")?;
+        }
+        match lhs.data_type {
+            DataType::Bool
+            | DataType::Byte
+            | DataType::Int
+            | DataType::Long
+            | DataType::Float
+            | DataType::Double
+            | DataType::String => {}
+            _ => illegal_arg("Property does not support this filter.")?,
+        }
+        Ok(Filter(FilterCond::PropertyCmp(PropertyCmpCond {
+            lhs_offset: lhs.offset,
+            rhs_offset: rhs.offset,
+            data_type: lhs.data_type,
+            op,
+        })))
+    }
+
+    /// Matches lists with exactly `length` elements. Null lists never match the list length
+    /// filters.
+    pub fn list_length_eq(property: &Property, length: usize) -> Result<Filter> {
+        Self::list_length(property, length, length)
+    }
+
+    pub fn list_length_gt(property: &Property, length: usize) -> Result<Filter> {
+        let filter = Self::list_length(property, length.saturating_add(1), usize::MAX)?;
+        if length == usize::MAX {
+            Ok(Filter::stat(false))
+        } else {
+            Ok(filter)
+        }
+    }
+
+    pub fn list_length_lt(property: &Property, length: usize) -> Result<Filter> {
+        let filter = Self::list_length(property, 0, length.saturating_sub(1))?;
+        if length == 0 {
+            Ok(Filter::stat(false))
+        } else {
+            Ok(filter)
+        }
+    }
+
     pub fn list_length(property: &Property, lower: usize, upper: usize) -> Result<Filter> {
         let filter_cond = if property.data_type.get_element_type().is_some() {
             Ok(FilterCond::ListLength(ListLengthCond {
@@ -217,9 +385,13 @@ impl Filter {
     }
 
     pub fn not(filter: Filter) -> Filter {
-        let filter_cond = FilterCond::Not(NotCond {
-            filter: Box::new(filter.0),
-        });
+        let filter_cond = match filter.0 {
+            FilterCond::Not(not) => *not.filter,
+            FilterCond::Static(stat) => FilterCond::Static(StaticCond { value: !stat.value }),
+            filter => FilterCond::Not(NotCond {
+                filter: Box::new(filter),
+            }),
+        };
         Filter(filter_cond)
     }
 
@@ -252,6 +424,22 @@ impl Filter {
         Ok(Filter(filter_cond))
     }
 
+    /// Applies `filter` to a nested embedded object. `path` lists the object properties from the
+    /// outermost to the innermost one.
+    pub fn object_path(path: &[&Property], filter: Filter) -> Result<Filter> {
+        if path.is_empty() {
+            illegal_arg("Object path must not be empty.")?;
+        }
+        let mut filter = filter;
+        for property in path.iter().rev() {
+            if property.data_type != DataType::Object {
+                illegal_arg("Object paths may only contain object properties.")?;
+            }
+            filter = Self::object(property, Some(filter))?;
+        }
+        Ok(filter)
+    }
+
     pub fn link(collection: &IsarCollection, link_id: u64, filter: Filter) -> Result<Filter> {
         let link = collection.get_link_backlink(link_id)?.clone();
         let filter_cond = FilterCond::AnyLink(AnyLinkCond {
@@ -261,6 +449,19 @@ impl Filter {
         Ok(Filter(filter_cond))
     }
 
+    /// Matches objects with at least one object linked by `link_name` that matches `filter`, for
+    /// example users that have an order over a certain amount. The linked objects of each
+    /// candidate are read through the link index so no full scan of the target collection is
+    /// required.
+    pub fn link_any(
+        collection: &IsarCollection,
+        link_name: &str,
+        filter: Filter,
+    ) -> Result<Filter> {
+        let link_id = collection.get_link_by_name(link_name)?.id;
+        Self::link(collection, link_id, filter)
+    }
+
     pub fn link_length(
         collection: &IsarCollection,
         link_id: u64,
@@ -280,6 +481,17 @@ impl Filter {
     ) -> Result<bool> {
         self.0.evaluate(id, object, cursors)
     }
+
+    /// The offsets of the properties read by this filter or `None` if it also reads lists,
+    /// strings, embedded objects or links.
+    pub(crate) fn scalar_offsets(&self) -> Option<Vec<usize>> {
+        let mut offsets = vec![];
+        if self.0.collect_offsets(&mut offsets) {
+            Some(offsets)
+        } else {
+            None
+        }
+    }
 }
 
 #[enum_dispatch]
@@ -297,6 +509,8 @@ enum FilterCond {
     StringEndsWith(StringEndsWithCond),
     StringContains(StringContainsCond),
     StringMatches(StringMatchesCond),
+    StringFuzzy(StringFuzzyCond),
+    WordsMatch(WordsMatchCond),
 
     AnyByteBetween(AnyByteBetweenCond),
     AnyIntBetween(AnyIntBetweenCond),
@@ -309,8 +523,11 @@ enum FilterCond {
     AnyStringEndsWith(AnyStringEndsWithCond),
     AnyStringContains(AnyStringContainsCond),
     AnyStringMatches(AnyStringMatchesCond),
+    AnyStringFuzzy(AnyStringFuzzyCond),
 
     ListLength(ListLengthCond),
+    RelativeTime(RelativeTimeCond),
+    PropertyCmp(PropertyCmpCond),
 
     Null(NullCond),
     And(AndCond),
@@ -329,6 +546,16 @@ enum FilterCond {
 #[enum_dispatch(FilterCond)]
 trait Condition {
     fn evaluate(&self, id: i64, object: IsarObject, cursors: Option<&IsarCursors>) -> Result<bool>;
+
+    fn needs_cursors(&self) -> bool {
+        false
+    }
+
+    /// Adds the offsets of the scalar properties read by this condition. Returns `false` if the
+    /// condition reads anything else.
+    fn collect_offsets(&self, _offsets: &mut Vec<usize>) -> bool {
+        false
+    }
 }
 
 #[derive(Clone)]
@@ -341,6 +568,10 @@ impl Condition for IdBetweenCond {
     fn evaluate(&self, id: i64, _object: IsarObject, _: Option<&IsarCursors>) -> Result<bool> {
         Ok(self.lower <= id && self.upper >= id)
     }
+
+    fn collect_offsets(&self, _offsets: &mut Vec<usize>) -> bool {
+        true
+    }
 }
 
 #[macro_export]
@@ -368,6 +599,11 @@ macro_rules! primitive_filter_between {
                 let val = object.$prop_accessor(self.offset);
                 Ok(self.lower <= val && self.upper >= val)
             }
+
+            fn collect_offsets(&self, offsets: &mut Vec<usize>) -> bool {
+                offsets.push(self.offset);
+                true
+            }
         }
     };
 }
@@ -432,6 +668,11 @@ macro_rules! float_filter_between {
                 let val = object.$prop_accessor(self.offset);
                 Ok(float_filter_between!(eval val, self.lower, self.upper))
             }
+
+            fn collect_offsets(&self, offsets: &mut Vec<usize>) -> bool {
+                offsets.push(self.offset);
+                true
+            }
         }
     };
 
@@ -633,6 +874,100 @@ string_filter!(StringEndsWith);
 string_filter!(StringContains);
 string_filter!(StringMatches);
 
+#[derive(Clone)]
+struct StringFuzzyCond {
+    offset: usize,
+    value: Vec<char>,
+    max_distance: usize,
+    case_sensitive: bool,
+}
+
+impl StringFuzzyCond {
+    fn matches(&self, value: Option<&str>) -> bool {
+        if let Some(value) = value {
+            if self.case_sensitive {
+                within_edit_distance(&self.value, value, self.max_distance)
+            } else {
+                within_edit_distance(&self.value, &value.to_lowercase(), self.max_distance)
+            }
+        } else {
+            false
+        }
+    }
+}
+
+impl Condition for StringFuzzyCond {
+    fn evaluate(&self, _id: i64, object: IsarObject, _: Option<&IsarCursors>) -> Result<bool> {
+        Ok(self.matches(object.read_string(self.offset)))
+    }
+}
+
+#[derive(Clone)]
+struct AnyStringFuzzyCond(StringFuzzyCond);
+
+impl Condition for AnyStringFuzzyCond {
+    fn evaluate(&self, _id: i64, object: IsarObject, _: Option<&IsarCursors>) -> Result<bool> {
+        let list = object.read_string_list(self.0.offset);
+        if let Some(list) = list {
+            Ok(list.into_iter().any(|value| self.0.matches(value)))
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+#[derive(Clone)]
+struct WordsMatchCond {
+    offset: usize,
+    tokenizer: Tokenizer,
+    terms: Vec<Term>,
+    case_sensitive: bool,
+}
+
+impl Condition for WordsMatchCond {
+    fn evaluate(&self, _id: i64, object: IsarObject, _: Option<&IsarCursors>) -> Result<bool> {
+        if let (Some(text), false) = (object.read_string(self.offset), self.terms.is_empty()) {
+            let words = self.tokenizer.words(text, self.case_sensitive);
+            Ok(self
+                .terms
+                .iter()
+                .all(|term| words.iter().any(|word| term.matches(word))))
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn collect_offsets(&self, offsets: &mut Vec<usize>) -> bool {
+        offsets.push(self.offset);
+        true
+    }
+}
+
+#[derive(Clone)]
+struct RelativeTimeCond {
+    offset: usize,
+    time: RelativeTime,
+}
+
+impl Condition for RelativeTimeCond {
+    fn evaluate(
+        &self,
+        _id: i64,
+        object: IsarObject,
+        cursors: Option<&IsarCursors>,
+    ) -> Result<bool> {
+        let now = cursors.map_or_else(now_micros, |c| c.now_micros());
+        let (lower, upper) = self.time.bounds(now);
+        let value = object.read_long(self.offset);
+        Ok(value != IsarObject::NULL_LONG && lower <= value && value <= upper)
+    }
+
+    fn collect_offsets(&self, offsets: &mut Vec<usize>) -> bool {
+        offsets.push(self.offset);
+        true
+    }
+}
+
 #[derive(Clone)]
 struct ListLengthCond {
     offset: usize,
@@ -670,6 +1005,11 @@ impl Condition for NullCond {
     ) -> Result<bool> {
         Ok(object.is_null(self.offset, self.data_type))
     }
+
+    fn collect_offsets(&self, offsets: &mut Vec<usize>) -> bool {
+        offsets.push(self.offset);
+        true
+    }
 }
 
 #[derive(Clone)]
@@ -678,6 +1018,14 @@ struct AndCond {
 }
 
 impl Condition for AndCond {
+    fn needs_cursors(&self) -> bool {
+        self.filters.iter().any(|f| f.needs_cursors())
+    }
+
+    fn collect_offsets(&self, offsets: &mut Vec<usize>) -> bool {
+        self.filters.iter().all(|f| f.collect_offsets(offsets))
+    }
+
     fn evaluate(&self, id: i64, object: IsarObject, cursors: Option<&IsarCursors>) -> Result<bool> {
         for filter in &self.filters {
             if !filter.evaluate(id, object, cursors)? {
@@ -694,6 +1042,14 @@ struct OrCond {
 }
 
 impl Condition for OrCond {
+    fn needs_cursors(&self) -> bool {
+        self.filters.iter().any(|f| f.needs_cursors())
+    }
+
+    fn collect_offsets(&self, offsets: &mut Vec<usize>) -> bool {
+        self.filters.iter().all(|f| f.collect_offsets(offsets))
+    }
+
     fn evaluate(&self, id: i64, object: IsarObject, cursors: Option<&IsarCursors>) -> Result<bool> {
         for filter in &self.filters {
             if filter.evaluate(id, object, cursors)? {
@@ -710,6 +1066,14 @@ struct XorCond {
 }
 
 impl Condition for XorCond {
+    fn needs_cursors(&self) -> bool {
+        self.filters.iter().any(|f| f.needs_cursors())
+    }
+
+    fn collect_offsets(&self, offsets: &mut Vec<usize>) -> bool {
+        self.filters.iter().all(|f| f.collect_offsets(offsets))
+    }
+
     fn evaluate(&self, id: i64, object: IsarObject, cursors: Option<&IsarCursors>) -> Result<bool> {
         let mut any = false;
         for filter in &self.filters {
@@ -731,9 +1095,21 @@ struct NotCond {
 }
 
 impl Condition for NotCond {
+    fn needs_cursors(&self) -> bool {
+        self.filter.needs_cursors()
+    }
+
     fn evaluate(&self, id: i64, object: IsarObject, cursors: Option<&IsarCursors>) -> Result<bool> {
+        // Without cursors link conditions can only tell whether an object may match
+        if cursors.is_none() && self.filter.needs_cursors() {
+            return Ok(true);
+        }
         Ok(!self.filter.evaluate(id, object, cursors)?)
     }
+
+    fn collect_offsets(&self, offsets: &mut Vec<usize>) -> bool {
+        self.filter.collect_offsets(offsets)
+    }
 }
 
 #[derive(Clone)]
@@ -745,6 +1121,10 @@ impl Condition for StaticCond {
     fn evaluate(&self, _id: i64, _: IsarObject, _: Option<&IsarCursors>) -> Result<bool> {
         Ok(self.value)
     }
+
+    fn collect_offsets(&self, _offsets: &mut Vec<usize>) -> bool {
+        true
+    }
 }
 
 #[derive(Clone)]
@@ -810,6 +1190,10 @@ struct AnyLinkCond {
 }
 
 impl Condition for AnyLinkCond {
+    fn needs_cursors(&self) -> bool {
+        true
+    }
+
     fn evaluate(
         &self,
         id: i64,
@@ -838,6 +1222,10 @@ struct LinkLengthCond {
 }
 
 impl Condition for LinkLengthCond {
+    fn needs_cursors(&self) -> bool {
+        true
+    }
+
     fn evaluate(
         &self,
         id: i64,
@@ -857,3 +1245,329 @@ impl Condition for LinkLengthCond {
         }
     }
 }
+
+#[derive(Clone)]
+struct PropertyCmpCond {
+    lhs_offset: usize,
+    rhs_offset: usize,
+    data_type: DataType,
+    op: CmpOp,
+}
+
+impl Condition for PropertyCmpCond {
+    fn evaluate(&self, _id: i64, object: IsarObject, _: Option<&IsarCursors>) -> Result<bool> {
+        if object.is_null(self.lhs_offset, self.data_type)
+            || object.is_null(self.rhs_offset, self.data_type)
+        {
+            return Ok(false);
+        }
+        let (lhs, rhs) = (self.lhs_offset, self.rhs_offset);
+        let ordering = match self.data_type {
+            DataType::Bool | DataType::Byte => object.read_byte(lhs).cmp(&object.read_byte(rhs)),
+            DataType::Int => object.read_int(lhs).cmp(&object.read_int(rhs)),
+            DataType::Long => object.read_long(lhs).cmp(&object.read_long(rhs)),
+            DataType::Float => object.read_float(lhs).total_cmp(&object.read_float(rhs)),
+            DataType::Double => object.read_double(lhs).total_cmp(&object.read_double(rhs)),
+            DataType::String => object.read_string(lhs).cmp(&object.read_string(rhs)),
+            _ => unreachable!(),
+        };
+        Ok(self.op.matches(ordering))
+    }
+
+    fn collect_offsets(&self, offsets: &mut Vec<usize>) -> bool {
+        offsets.push(self.lhs_offset);
+        offsets.push(self.rhs_offset);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::object_builder::ObjectBuilder;
+
+    #[test]
+    fn test_not_is_complement() {
+        let p = Property::debug(DataType::Long, 2);
+        let props = vec![p.clone()];
+        let eq = Filter::long(&p, 5, 5).unwrap();
+        let not_eq = Filter::not(eq.clone());
+
+        for value in [i64::MIN, 1, 5, 6] {
+            let mut b = ObjectBuilder::new(&props, None);
+            b.write_long(p.offset, value);
+            let object = b.finish();
+            let matches = eq.evaluate(0, object, None).unwrap();
+            assert_eq!(not_eq.evaluate(0, object, None).unwrap(), !matches);
+        }
+    }
+
+    #[test]
+    fn test_not_null() {
+        let p = Property::debug(DataType::String, 2);
+        let props = vec![p.clone()];
+        let not_null = Filter::not(Filter::null(&p));
+
+        let mut b = ObjectBuilder::new(&props, None);
+        b.write_string(p.offset, None);
+        assert!(!not_null.evaluate(0, b.finish(), None).unwrap());
+
+        let mut b = ObjectBuilder::new(&props, None);
+        b.write_string(p.offset, Some("isar"));
+        assert!(not_null.evaluate(0, b.finish(), None).unwrap());
+    }
+
+    #[test]
+    fn test_not_simplifies() {
+        let p = Property::debug(DataType::Int, 2);
+        let filter = Filter::not(Filter::not(Filter::int(&p, 1, 2).unwrap()));
+        assert!(matches!(filter.0, FilterCond::IntBetween(_)));
+
+        let filter = Filter::not(Filter::stat(true));
+        assert!(matches!(
+            filter.0,
+            FilterCond::Static(StaticCond { value: false })
+        ));
+    }
+
+    #[test]
+    fn test_string_contains() {
+        let p = Property::debug(DataType::String, 2);
+        let props = vec![p.clone()];
+        let object = |value: &str| {
+            let mut b = ObjectBuilder::new(&props, None);
+            b.write_string(p.offset, Some(value));
+            b.finish().as_bytes().to_vec()
+        };
+        let evaluate = |filter: &Filter, value: &str| {
+            let bytes = object(value);
+            filter
+                .evaluate(0, IsarObject::from_bytes(&bytes), None)
+                .unwrap()
+        };
+
+        let sensitive = Filter::string_contains(&p, "SAR", true).unwrap();
+        assert!(evaluate(&sensitive, "iSARdb"));
+        assert!(!evaluate(&sensitive, "isardb"));
+        assert!(!evaluate(&sensitive, "database"));
+
+        let insensitive = Filter::string_contains(&p, "SAR", false).unwrap();
+        assert!(evaluate(&insensitive, "isardb"));
+        assert!(!evaluate(&insensitive, "database"));
+    }
+
+    #[test]
+    fn test_string_fuzzy() {
+        let p = Property::debug(DataType::String, 2);
+        let props = vec![p.clone()];
+        let mut b = ObjectBuilder::new(&props, None);
+        b.write_string(p.offset, Some("Color"));
+        let object = b.finish();
+
+        let fuzzy = |term: &str, max_distance: u32, case_sensitive: bool| {
+            Filter::string_fuzzy(&p, term, max_distance, case_sensitive)
+                .unwrap()
+                .evaluate(0, object, None)
+                .unwrap()
+        };
+        assert!(fuzzy("colour", 1, false));
+        assert!(!fuzzy("colour", 0, false));
+        assert!(!fuzzy("colour", 1, true));
+        assert!(fuzzy("Colour", 1, true));
+    }
+
+    #[test]
+    fn test_words_match() {
+        let p = Property::debug(DataType::String, 2);
+        let props = vec![p.clone()];
+        let mut b = ObjectBuilder::new(&props, None);
+        b.write_string(p.offset, Some("The quick brown Fox"));
+        let object = b.finish();
+
+        let matches = |query: &str, case_sensitive: bool| {
+            Filter::words_match(&p, Tokenizer::Unicode, query, case_sensitive)
+                .unwrap()
+                .evaluate(0, object, None)
+                .unwrap()
+        };
+        assert!(matches("fox quick", false));
+        assert!(matches("qui* BRO*", false));
+        assert!(!matches("fox lazy", false));
+        assert!(!matches("qui", false));
+        assert!(!matches("fox", true));
+        assert!(matches("Fox", true));
+        assert!(!matches("  ", false));
+        let mut b = ObjectBuilder::new(&props, None);
+        b.write_string(p.offset, None);
+        let null = b.finish();
+        let filter = Filter::words_match(&p, Tokenizer::Unicode, "fox", false).unwrap();
+        assert!(!filter.evaluate(0, null, None).unwrap());
+
+        let long = Property::debug(DataType::Long, 2);
+        assert!(Filter::words_match(&long, Tokenizer::Unicode, "fox", false).is_err());
+    }
+
+    #[test]
+    fn test_relative_time() {
+        let p = Property::debug(DataType::Long, 2);
+        let props = vec![p.clone()];
+        let evaluate = |filter: &Filter, value: i64| {
+            let mut b = ObjectBuilder::new(&props, None);
+            b.write_long(p.offset, value);
+            filter.evaluate(0, b.finish(), None).unwrap()
+        };
+
+        let hour = Duration::from_secs(3600);
+        let now = now_micros();
+        let newer = Filter::newer_than(&p, hour).unwrap();
+        let older = Filter::older_than(&p, hour).unwrap();
+        for (value, is_newer) in [
+            (now, true),
+            (now - 60_000_000, true),
+            (now - 7_200_000_000, false),
+        ] {
+            assert_eq!(evaluate(&newer, value), is_newer);
+            assert_eq!(evaluate(&older, value), !is_newer);
+        }
+        assert!(!evaluate(&newer, IsarObject::NULL_LONG));
+        assert!(!evaluate(&older, IsarObject::NULL_LONG));
+
+        let p = Property::debug(DataType::Int, 2);
+        assert!(Filter::newer_than(&p, hour).is_err());
+    }
+
+    #[test]
+    fn test_list_length() {
+        let p = Property::debug(DataType::LongList, 2);
+        let props = vec![p.clone()];
+        let object = |list: Option<&[i64]>| {
+            let mut b = ObjectBuilder::new(&props, None);
+            b.write_long_list(p.offset, list);
+            b.finish().as_bytes().to_vec()
+        };
+        let evaluate = |filter: &Filter, list: Option<&[i64]>| {
+            let bytes = object(list);
+            filter
+                .evaluate(0, IsarObject::from_bytes(&bytes), None)
+                .unwrap()
+        };
+
+        let eq = Filter::list_length_eq(&p, 2).unwrap();
+        let gt = Filter::list_length_gt(&p, 2).unwrap();
+        let lt = Filter::list_length_lt(&p, 2).unwrap();
+        let lt_zero = Filter::list_length_lt(&p, 0).unwrap();
+
+        let lists: [(Option<&[i64]>, bool, bool, bool); 5] = [
+            (None, false, false, false),
+            (Some(&[]), false, false, true),
+            (Some(&[1]), false, false, true),
+            (Some(&[1, 2]), true, false, false),
+            (Some(&[1, 2, 3]), false, true, false),
+        ];
+        for (list, is_eq, is_gt, is_lt) in lists {
+            assert_eq!(evaluate(&eq, list), is_eq);
+            assert_eq!(evaluate(&gt, list), is_gt);
+            assert_eq!(evaluate(&lt, list), is_lt);
+            assert!(!evaluate(&lt_zero, list));
+        }
+
+        let p = Property::debug(DataType::Long, 2);
+        assert!(Filter::list_length_eq(&p, 1).is_err());
+    }
+
+    #[test]
+    fn test_object_path() {
+        let city = Property::debug(DataType::String, 2);
+        let zip = Property::debug(DataType::Int, 10);
+        let address_props = vec![city.clone(), zip.clone()];
+        let address = Property::debug(DataType::Object, 2);
+        let props = vec![address.clone()];
+        let object = |c: &str, z: i32| {
+            let mut b = ObjectBuilder::new(&address_props, None);
+            b.write_string(city.offset, Some(c));
+            b.write_int(zip.offset, z);
+            let address_bytes = b.finish().as_bytes().to_vec();
+
+            let mut b = ObjectBuilder::new(&props, None);
+            b.write_object(address.offset, Some(IsarObject::from_bytes(&address_bytes)));
+            b.finish().as_bytes().to_vec()
+        };
+        let evaluate = |filter: &Filter, c: &str, z: i32| {
+            let bytes = object(c, z);
+            filter
+                .evaluate(0, IsarObject::from_bytes(&bytes), None)
+                .unwrap()
+        };
+
+        let city_eq = Filter::string(&city, Some("Berlin"), Some("Berlin"), true).unwrap();
+        let city_eq = Filter::object_path(&[&address], city_eq).unwrap();
+        assert!(evaluate(&city_eq, "Berlin", 10115));
+        assert!(!evaluate(&city_eq, "Paris", 75001));
+
+        let zip_between = Filter::int(&zip, 10000, 19999).unwrap();
+        let zip_between = Filter::object_path(&[&address], zip_between).unwrap();
+        assert!(evaluate(&zip_between, "Berlin", 10115));
+        assert!(!evaluate(&zip_between, "Paris", 75001));
+
+        assert!(Filter::object_path(&[], Filter::stat(true)).is_err());
+        assert!(Filter::object_path(&[&city], Filter::stat(true)).is_err());
+    }
+
+    #[test]
+    fn test_property_cmp() {
+        let price = Property::debug(DataType::Double, 2);
+        let discount = Property::debug(DataType::Double, 10);
+        let props = vec![price.clone(), discount.clone()];
+        let lt = Filter::property_cmp(&discount, CmpOp::Lt, &price).unwrap();
+        let gte = Filter::property_cmp(&discount, CmpOp::Gte, &price).unwrap();
+
+        let rows = [
+            (10.0, Some(8.0), true),
+            (10.0, Some(10.0), false),
+            (5.0, Some(7.5), false),
+            (-1.0, Some(-2.0), true),
+            (10.0, None, false),
+        ];
+        for (price_value, discount_value, expected) in rows {
+            let mut b = ObjectBuilder::new(&props, None);
+            b.write_double(price.offset, price_value);
+            if let Some(discount_value) = discount_value {
+                b.write_double(discount.offset, discount_value);
+            } else {
+                b.write_null(discount.offset, DataType::Double);
+            }
+            let object = b.finish();
+            assert_eq!(lt.evaluate(0, object, None).unwrap(), expected);
+            assert_eq!(
+                gte.evaluate(0, object, None).unwrap(),
+                !expected && discount_value.is_some()
+            );
+        }
+
+        let count = Property::debug(DataType::Long, 18);
+        assert!(Filter::property_cmp(&price, CmpOp::Eq, &count).is_err());
+        let list = Property::debug(DataType::LongList, 2);
+        assert!(Filter::property_cmp(&list, CmpOp::Eq, &list).is_err());
+    }
+
+    #[test]
+    fn test_scalar_offsets() {
+        let a = Property::debug(DataType::Long, 2);
+        let b = Property::debug(DataType::Double, 10);
+        let name = Property::debug(DataType::String, 18);
+        let filter = Filter::or(vec![
+            Filter::long(&a, 1, 5).unwrap(),
+            Filter::not(Filter::and(vec![
+                Filter::double(&b, 0.0, 1.0).unwrap(),
+                Filter::id(0, 10),
+            ])),
+        ]);
+        assert_eq!(filter.scalar_offsets(), Some(vec![2, 10]));
+
+        let filter = Filter::and(vec![
+            Filter::long(&a, 1, 5).unwrap(),
+            Filter::string_contains(&name, "isar", true).unwrap(),
+        ]);
+        assert_eq!(filter.scalar_offsets(), None);
+    }
+}
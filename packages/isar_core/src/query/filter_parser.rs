@@ -0,0 +1,477 @@
+use crate::error::{IsarError, Result};
+use crate::object::data_type::DataType;
+use crate::object::isar_object::IsarObject;
+use crate::object::property::Property;
+use crate::query::filter::Filter;
+
+/// Parses a filter expression like `age > 18 AND (name startsWith "A" OR NOT active == true)`.
+///
+/// Comparisons always have a property on the left and a literal on the right. Supported operators
+/// are `==`, `!=`, `<`, `<=`, `>`, `>=` and for strings `startsWith`, `endsWith` and `contains`.
+/// Keywords are case-insensitive, property names and string comparisons are case-sensitive.
+/// Comparisons other than `== null` and `!= null` never match null values.
+pub(crate) fn parse_filter(properties: &[Property], expr: &str) -> Result<Filter> {
+    let tokens = tokenize(expr)?;
+    let mut parser = Parser {
+        properties,
+        tokens,
+        index: 0,
+        end: expr.len(),
+    };
+    let filter = parser.parse_or()?;
+    if let Some((position, _)) = parser.peek() {
+        return parser_error(*position, "Expected end of expression.");
+    }
+    Ok(filter)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Op(Op),
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Float(f64),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Op {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+enum Literal {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+fn parser_error<T>(position: usize, message: &str) -> Result<T> {
+    Err(IsarError::InvalidFilterExpression {
+        position,
+        message: message.to_string(),
+    })
+}
+
+fn tokenize(expr: &str) -> Result<Vec<(usize, Token)>> {
+    let bytes = expr.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let token = match bytes[i] {
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                i += 1;
+                continue;
+            }
+            b'(' => {
+                i += 1;
+                Token::LParen
+            }
+            b')' => {
+                i += 1;
+                Token::RParen
+            }
+            b'=' | b'!' | b'<' | b'>' => {
+                let next_eq = bytes.get(i + 1) == Some(&b'=');
+                let op = match (bytes[i], next_eq) {
+                    (b'=', _) => Op::Eq,
+                    (b'!', true) => Op::NotEq,
+                    (b'<', true) => Op::LtEq,
+                    (b'<', false) => Op::Lt,
+                    (b'>', true) => Op::GtEq,
+                    (b'>', false) => Op::Gt,
+                    _ => return parser_error(start, "Expected '!='."),
+                };
+                i += if next_eq { 2 } else { 1 };
+                Token::Op(op)
+            }
+            b'"' => {
+                i += 1;
+                let mut value = String::new();
+                loop {
+                    let rest = &expr[i..];
+                    let c = match rest.chars().next() {
+                        Some(c) => c,
+                        None => return parser_error(start, "Unterminated string."),
+                    };
+                    i += c.len_utf8();
+                    match c {
+                        '"' => break,
+                        '\\' => match bytes.get(i) {
+                            Some(b'"') => value.push('"'),
+                            Some(b'\\') => value.push('\\'),
+                            _ => return parser_error(i - 1, "Invalid escape sequence."),
+                        },
+                        c => {
+                            value.push(c);
+                            continue;
+                        }
+                    }
+                    i += 1;
+                }
+                Token::Str(value)
+            }
+            b'-' | b'0'..=b'9' => {
+                i += 1;
+                let mut is_float = false;
+                while i < bytes.len() {
+                    match bytes[i] {
+                        b'0'..=b'9' => {}
+                        b'.' | b'e' | b'E' => is_float = true,
+                        b'+' | b'-' if matches!(bytes[i - 1], b'e' | b'E') => {}
+                        _ => break,
+                    }
+                    i += 1;
+                }
+                let number = &expr[start..i];
+                if is_float {
+                    match number.parse() {
+                        Ok(value) => Token::Float(value),
+                        Err(_) => return parser_error(start, "Invalid number."),
+                    }
+                } else {
+                    match number.parse() {
+                        Ok(value) => Token::Int(value),
+                        Err(_) => return parser_error(start, "Invalid number."),
+                    }
+                }
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                Token::Ident(expr[start..i].to_string())
+            }
+            _ => return parser_error(start, "Unexpected character."),
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    properties: &'a [Property],
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    end: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&(usize, Token)> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<(usize, Token)> {
+        if let Some(token) = self.tokens.get(self.index) {
+            self.index += 1;
+            Ok(token.clone())
+        } else {
+            parser_error(self.end, &format!("Expected {}.", expected))
+        }
+    }
+
+    fn consume_keyword(&mut self, keyword: &str) -> bool {
+        if let Some((_, Token::Ident(ident))) = self.peek() {
+            if ident.eq_ignore_ascii_case(keyword) {
+                self.index += 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    fn parse_or(&mut self) -> Result<Filter> {
+        let mut filters = vec![self.parse_and()?];
+        while self.consume_keyword("OR") {
+            filters.push(self.parse_and()?);
+        }
+        if filters.len() == 1 {
+            Ok(filters.pop().unwrap())
+        } else {
+            Ok(Filter::or(filters))
+        }
+    }
+
+    fn parse_and(&mut self) -> Result<Filter> {
+        let mut filters = vec![self.parse_unary()?];
+        while self.consume_keyword("AND") {
+            filters.push(self.parse_unary()?);
+        }
+        if filters.len() == 1 {
+            Ok(filters.pop().unwrap())
+        } else {
+            Ok(Filter::and(filters))
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter> {
+        if self.consume_keyword("NOT") {
+            return Ok(Filter::not(self.parse_unary()?));
+        }
+        if let Some((_, Token::LParen)) = self.peek() {
+            self.index += 1;
+            let filter = self.parse_or()?;
+            match self.next("')'")? {
+                (_, Token::RParen) => return Ok(filter),
+                (position, _) => return parser_error(position, "Expected ')'."),
+            }
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Filter> {
+        let (position, name) = match self.next("property")? {
+            (position, Token::Ident(name)) => (position, name),
+            (position, _) => return parser_error(position, "Expected property."),
+        };
+        let property = if let Some(property) = self.properties.iter().find(|p| p.name == name) {
+            property
+        } else {
+            return parser_error(position, &format!("Unknown property '{}'.", name));
+        };
+
+        let (op_position, op) = self.next("operator")?;
+        let (literal_position, literal) = match self.next("value")? {
+            (position, Token::Str(value)) => (position, Literal::Str(value)),
+            (position, Token::Int(value)) => (position, Literal::Int(value)),
+            (position, Token::Float(value)) => (position, Literal::Float(value)),
+            (position, Token::Ident(ident)) => {
+                let literal = match ident.to_ascii_lowercase().as_str() {
+                    "null" => Literal::Null,
+                    "true" => Literal::Bool(true),
+                    "false" => Literal::Bool(false),
+                    _ => return parser_error(position, "Expected value."),
+                };
+                (position, literal)
+            }
+            (position, _) => return parser_error(position, "Expected value."),
+        };
+
+        let filter = match op {
+            Token::Op(op) => compare(property, op, literal),
+            Token::Ident(ident) => {
+                let value = if let Literal::Str(value) = literal {
+                    value
+                } else {
+                    return parser_error(literal_position, "Expected string.");
+                };
+                match ident.as_str() {
+                    "startsWith" => Filter::string_starts_with(property, &value, true),
+                    "endsWith" => Filter::string_ends_with(property, &value, true),
+                    "contains" => Filter::string_contains(property, &value, true),
+                    _ => return parser_error(op_position, "Expected operator."),
+                }
+                .ok()
+            }
+            _ => return parser_error(op_position, "Expected operator."),
+        };
+        if let Some(filter) = filter {
+            Ok(filter)
+        } else {
+            parser_error(
+                literal_position,
+                &format!("Invalid comparison for property '{}'.", name),
+            )
+        }
+    }
+}
+
+/// Builds a comparison filter or returns `None` if the literal does not match the property type.
+fn compare(property: &Property, op: Op, literal: Literal) -> Option<Filter> {
+    let negate = |filter: Filter| match op {
+        Op::Eq => Some(filter),
+        Op::NotEq => Some(Filter::not(filter)),
+        _ => None,
+    };
+    match (property.data_type, literal) {
+        (_, Literal::Null) => negate(Filter::null(property)),
+        (DataType::Bool, Literal::Bool(value)) => {
+            let value = if value {
+                IsarObject::TRUE_BOOL
+            } else {
+                IsarObject::FALSE_BOOL
+            };
+            negate(Filter::byte(property, value, value).ok()?)
+        }
+        (DataType::Byte, Literal::Int(value)) => {
+            let filter = |lower, upper| Filter::byte(property, lower as u8, upper as u8);
+            compare_int(op, value, 0, u8::MAX as i64, filter)
+        }
+        (DataType::Int, Literal::Int(value)) => {
+            let filter = |lower, upper| Filter::int(property, lower as i32, upper as i32);
+            compare_int(op, value, i32::MIN as i64 + 1, i32::MAX as i64, filter)
+        }
+        (DataType::Long, Literal::Int(value)) => {
+            let filter = |lower, upper| Filter::long(property, lower, upper);
+            compare_int(op, value, i64::MIN + 1, i64::MAX, filter)
+        }
+        (DataType::Float, Literal::Int(value)) => {
+            let filter = |lower, upper| Filter::float(property, lower as f32, upper as f32);
+            compare_float(op, value as f64, filter)
+        }
+        (DataType::Float, Literal::Float(value)) => {
+            let filter = |lower, upper| Filter::float(property, lower as f32, upper as f32);
+            compare_float(op, value, filter)
+        }
+        (DataType::Double, Literal::Int(value)) => {
+            compare_float(op, value as f64, |l, u| Filter::double(property, l, u))
+        }
+        (DataType::Double, Literal::Float(value)) => {
+            compare_float(op, value, |l, u| Filter::double(property, l, u))
+        }
+        (DataType::String, Literal::Str(value)) => {
+            let value = Some(value.as_str());
+            let eq = Filter::string(property, value, value, true).ok()?;
+            // A missing lower bound matches null and a missing upper bound never matches.
+            let at_most = Filter::string(property, Some(""), value, true).ok()?;
+            let filter = match op {
+                Op::Eq => eq,
+                Op::NotEq => Filter::not(eq),
+                Op::Lt => Filter::and(vec![at_most, Filter::not(eq)]),
+                Op::LtEq => at_most,
+                Op::Gt => Filter::not(Filter::string(property, None, value, true).ok()?),
+                Op::GtEq => Filter::or(vec![
+                    eq,
+                    Filter::not(Filter::string(property, None, value, true).ok()?),
+                ]),
+            };
+            Some(filter)
+        }
+        _ => None,
+    }
+}
+
+fn compare_int(
+    op: Op,
+    value: i64,
+    min: i64,
+    max: i64,
+    filter: impl Fn(i64, i64) -> Result<Filter>,
+) -> Option<Filter> {
+    let (lower, upper) = match op {
+        Op::Eq | Op::NotEq => (value, value),
+        Op::Lt => (min, value.saturating_sub(1)),
+        Op::LtEq => (min, value),
+        Op::Gt => (value.saturating_add(1), max),
+        Op::GtEq => (value, max),
+    };
+    let (lower, upper) = (lower.max(min), upper.min(max));
+    let filter = if lower > upper || (op == Op::Gt && value == i64::MAX) {
+        Filter::stat(false)
+    } else {
+        filter(lower, upper).ok()?
+    };
+    if op == Op::NotEq {
+        Some(Filter::not(filter))
+    } else {
+        Some(filter)
+    }
+}
+
+fn compare_float(
+    op: Op,
+    value: f64,
+    filter: impl Fn(f64, f64) -> Result<Filter>,
+) -> Option<Filter> {
+    let eq = filter(value, value).ok()?;
+    let filter = match op {
+        Op::Eq => eq,
+        Op::NotEq => Filter::not(eq),
+        Op::Lt => Filter::and(vec![
+            filter(f64::NEG_INFINITY, value).ok()?,
+            Filter::not(eq),
+        ]),
+        Op::LtEq => filter(f64::NEG_INFINITY, value).ok()?,
+        Op::Gt => Filter::and(vec![filter(value, f64::INFINITY).ok()?, Filter::not(eq)]),
+        Op::GtEq => filter(value, f64::INFINITY).ok()?,
+    };
+    Some(filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::object_builder::ObjectBuilder;
+
+    fn properties() -> Vec<Property> {
+        vec![
+            Property::new("active", DataType::Bool, 2, None),
+            Property::new("age", DataType::Int, 3, None),
+            Property::new("score", DataType::Double, 7, None),
+            Property::new("name", DataType::String, 15, None),
+        ]
+    }
+
+    fn matches(expr: &str, active: Option<bool>, age: i32, score: f64, name: Option<&str>) -> bool {
+        let properties = properties();
+        let mut b = ObjectBuilder::new(&properties, None);
+        b.write_bool(2, active);
+        b.write_int(3, age);
+        b.write_double(7, score);
+        b.write_string(15, name);
+        let filter = parse_filter(&properties, expr).unwrap();
+        filter.evaluate(0, b.finish(), None).unwrap()
+    }
+
+    fn error_position(expr: &str) -> usize {
+        match parse_filter(&properties(), expr) {
+            Err(IsarError::InvalidFilterExpression { position, .. }) => position,
+            _ => panic!("expected an error for {}", expr),
+        }
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        let expr = "age > 18 AND name startsWith \"A\"";
+        assert!(matches(expr, None, 19, 0.0, Some("Alice")));
+        assert!(!matches(expr, None, 18, 0.0, Some("Alice")));
+        assert!(!matches(expr, None, 30, 0.0, Some("Bob")));
+
+        let expr = "NOT (active == true OR score >= 2.5) and age != null";
+        assert!(matches(expr, Some(false), 1, 2.0, None));
+        assert!(!matches(expr, Some(true), 1, 2.0, None));
+        assert!(!matches(expr, Some(false), 1, 2.5, None));
+        assert!(!matches(expr, Some(false), IsarObject::NULL_INT, 2.0, None));
+
+        assert!(matches("age < 0", None, -5, 0.0, None));
+        assert!(!matches("age < 0", None, IsarObject::NULL_INT, 0.0, None));
+        assert!(!matches("age > 2147483647", None, i32::MAX, 0.0, None));
+        assert!(matches("score < 1", None, 0, 0.5, None));
+        assert!(!matches("score < 1", None, 0, f64::NAN, None));
+        assert!(matches("name == null", None, 0, 0.0, None));
+        assert!(matches("name > \"b\"", None, 0, 0.0, Some("c")));
+        assert!(!matches("name > \"b\"", None, 0, 0.0, Some("b")));
+        assert!(!matches("name > \"b\"", None, 0, 0.0, None));
+        assert!(matches("name <= \"b\"", None, 0, 0.0, Some("")));
+        assert!(!matches("name <= \"b\"", None, 0, 0.0, None));
+        assert!(matches(
+            "name == \"a \\\"b\\\"\"",
+            None,
+            0,
+            0.0,
+            Some("a \"b\"")
+        ));
+        assert!(matches("name contains \"li\"", None, 0, 0.0, Some("Alice")));
+    }
+
+    #[test]
+    fn test_parse_filter_errors() {
+        assert_eq!(error_position("age >"), 5);
+        assert_eq!(error_position("height > 3"), 0);
+        assert_eq!(error_position("age > 3 name == \"a\""), 8);
+        assert_eq!(error_position("(age > 3"), 8);
+        assert_eq!(error_position("age > 1.5"), 6);
+        assert_eq!(error_position("name == \"abc"), 8);
+        assert_eq!(error_position("active < true"), 9);
+        assert_eq!(error_position("age ~ 3"), 4);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::error::{illegal_arg, Result};
+use crate::object::data_type::DataType;
+use crate::object::isar_object::IsarObject;
+use crate::object::property::Property;
+use intmap::IntMap;
+
+/// Counts numeric values in fixed-width buckets. Buckets are identified by their lower bound
+/// `floor(value / width) * width` so negative values fall into the bucket below zero.
+pub(crate) struct Histogram {
+    width: f64,
+    buckets: IntMap<u64>,
+}
+
+impl Histogram {
+    pub fn new(width: f64) -> Result<Self> {
+        if !width.is_finite() || width <= 0.0 {
+            illegal_arg("Bucket width must be a positive number.")?;
+        }
+        Ok(Histogram {
+            width,
+            buckets: IntMap::new(),
+        })
+    }
+
+    pub fn add_property(&mut self, object: IsarObject, property: &Property) -> Result<()> {
+        if object.is_null(property.offset, property.data_type) {
+            return Ok(());
+        }
+        let value = match property.data_type {
+            DataType::Byte => object.read_byte(property.offset) as f64,
+            DataType::Int => object.read_int(property.offset) as f64,
+            DataType::Long => object.read_long(property.offset) as f64,
+            DataType::Float => object.read_float(property.offset) as f64,
+            DataType::Double => object.read_double(property.offset),
+            _ => return illegal_arg("Property does not support histograms."),
+        };
+        self.add(value);
+        Ok(())
+    }
+
+    /// Non-finite values are ignored.
+    pub fn add(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        let bucket = (value / self.width).floor() as i64;
+        let key = u64::from_le_bytes(bucket.to_le_bytes());
+        let count = self.buckets.get(key).copied().unwrap_or(0);
+        self.buckets.insert(key, count + 1);
+    }
+
+    /// Returns the lower bound and count of each non-empty bucket ordered by lower bound.
+    pub fn finish(self) -> Vec<(f64, u64)> {
+        let mut buckets: Vec<(i64, u64)> = self
+            .buckets
+            .iter()
+            .map(|(key, count)| (i64::from_le_bytes(key.to_le_bytes()), *count))
+            .collect();
+        buckets.sort_unstable_by_key(|(bucket, _)| *bucket);
+        buckets
+            .into_iter()
+            .map(|(bucket, count)| (bucket as f64 * self.width, count))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::new(10.0).unwrap();
+        for value in [
+            -15.0,
+            -0.5,
+            0.0,
+            3.0,
+            9.9,
+            10.0,
+            25.0,
+            29.0,
+            f64::NAN,
+            f64::INFINITY,
+        ] {
+            histogram.add(value);
+        }
+        assert_eq!(
+            histogram.finish(),
+            vec![(-20.0, 1), (-10.0, 1), (0.0, 3), (10.0, 1), (20.0, 2)]
+        );
+
+        assert!(Histogram::new(0.0).is_err());
+        assert!(Histogram::new(-1.0).is_err());
+        assert!(Histogram::new(f64::NAN).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use crate::cursor::IsarCursors;
+use crate::error::Result;
+use crate::object::isar_object::IsarObject;
+use crate::query::id_where_clause::IdWhereClause;
+use crate::query::index_where_clause::IndexWhereClause;
+use crate::query::scan::Scan;
+use crate::query::where_clause::WhereClause;
+use intmap::IntMap;
+
+/// Matches the objects that are in both an id range and an index range. The side with fewer
+/// estimated entries is scanned and the other side is checked for every scanned object, so the
+/// results are in id or in index order depending on the data.
+#[derive(Clone)]
+pub(crate) struct IdIndexWhereClause {
+    id: IdWhereClause,
+    index: IndexWhereClause,
+}
+
+impl IdIndexWhereClause {
+    pub fn new(id: IdWhereClause, index: IndexWhereClause) -> Self {
+        IdIndexWhereClause { id, index }
+    }
+
+    pub fn object_matches(&self, id: i64, object: IsarObject) -> bool {
+        self.id.id_matches(id) && self.index.object_matches(object)
+    }
+
+    /// The smaller of the two estimates, an upper bound of the matching objects.
+    pub fn estimate_count(&self, cursors: &IsarCursors) -> Result<u64> {
+        let index = self.index.estimate_count(cursors)?;
+        Ok(index.min(self.id.estimate_count(cursors)?))
+    }
+
+    /// Whether the index range is scanned and the id range is checked.
+    pub fn index_drives(&self, cursors: &IsarCursors) -> Result<bool> {
+        Ok(self.index.estimate_count(cursors)? < self.id.estimate_count(cursors)?)
+    }
+
+    pub(crate) fn reverse(&mut self) {
+        self.id.reverse();
+        self.index.reverse();
+    }
+
+    pub(crate) fn index_name(&self) -> &str {
+        self.index.index_name()
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!("{} & {}", self.id.describe(), self.index.describe())
+    }
+
+    pub(crate) fn describe_plan(&self, cursors: &IsarCursors) -> Result<String> {
+        let plan = if self.index_drives(cursors)? {
+            format!("{} > {}", self.index.describe(), self.id.describe())
+        } else {
+            format!("{} > {}", self.id.describe(), self.index.describe())
+        };
+        Ok(plan)
+    }
+
+    pub fn iter<'txn, 'env, F>(
+        &self,
+        cursors: &IsarCursors<'txn, 'env>,
+        result_ids: Option<&mut IntMap<()>>,
+        mut callback: F,
+    ) -> Result<bool>
+    where
+        F: FnMut(i64, IsarObject<'txn>) -> Result<bool>,
+    {
+        if self.index_drives(cursors)? {
+            self.index.iter(cursors, result_ids, |id, object| {
+                if self.id.id_matches(id) {
+                    callback(id, object)
+                } else {
+                    Ok(true)
+                }
+            })
+        } else {
+            self.id.iter(cursors, result_ids, |id, object| {
+                if self.index.object_matches(object) {
+                    callback(id, object)
+                } else {
+                    Ok(true)
+                }
+            })
+        }
+    }
+
+    pub(crate) fn scan<'txn>(&self, cursors: &IsarCursors<'txn, '_>) -> Result<Scan<'txn>> {
+        if self.index_drives(cursors)? {
+            let scan = self.index.scan(cursors)?;
+            Ok(scan.checked(WhereClause::Id(self.id.clone())))
+        } else {
+            let scan = self.id.scan(cursors)?;
+            Ok(scan.checked(WhereClause::Index(self.index.clone())))
+        }
+    }
+
+    pub fn has_duplicates(&self) -> bool {
+        self.index.has_duplicates()
+    }
+}
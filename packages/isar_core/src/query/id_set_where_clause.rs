@@ -0,0 +1,86 @@
+use crate::cursor::IsarCursors;
+use crate::error::Result;
+use crate::mdbx::db::Db;
+use crate::object::isar_object::IsarObject;
+use crate::query::scan::Scan;
+use crate::query::Sort;
+use intmap::IntMap;
+
+#[derive(Clone)]
+pub(crate) struct IdSetWhereClause {
+    db: Db,
+    ids: Vec<i64>,
+    sort: Sort,
+}
+
+impl IdSetWhereClause {
+    pub(crate) fn new(db: Db, ids: &[i64], sort: Sort) -> Self {
+        let mut ids = ids.to_vec();
+        ids.sort_unstable();
+        ids.dedup();
+        IdSetWhereClause { db, ids, sort }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub(crate) fn id_matches(&self, id: i64) -> bool {
+        self.ids.binary_search(&id).is_ok()
+    }
+
+    pub(crate) fn reverse(&mut self) {
+        self.sort = self.sort.reversed();
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!("ids[{}]", self.ids.len())
+    }
+
+    pub(crate) fn iter<'txn, 'env, F>(
+        &self,
+        cursors: &IsarCursors<'txn, 'env>,
+        mut result_ids: Option<&mut IntMap<()>>,
+        mut callback: F,
+    ) -> Result<bool>
+    where
+        F: FnMut(i64, IsarObject<'txn>) -> Result<bool>,
+    {
+        let mut cursor = cursors.get_cursor(self.db)?;
+        let mut visit = |id: i64| -> Result<bool> {
+            if let Some(result_ids) = result_ids.as_deref_mut() {
+                if !result_ids.insert_checked(id as u64, ()) {
+                    return Ok(true);
+                }
+            }
+            if let Some((_, object)) = cursor.move_to(&id)? {
+                callback(id, IsarObject::from_bytes(object))
+            } else {
+                Ok(true)
+            }
+        };
+
+        if self.sort == Sort::Ascending {
+            for id in &self.ids {
+                if !visit(*id)? {
+                    return Ok(false);
+                }
+            }
+        } else {
+            for id in self.ids.iter().rev() {
+                if !visit(*id)? {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    pub(crate) fn scan<'txn>(&self, cursors: &IsarCursors<'txn, '_>) -> Result<Scan<'txn>> {
+        let mut ids = self.ids.clone();
+        if self.sort == Sort::Descending {
+            ids.reverse();
+        }
+        Scan::id_list(cursors, self.db, ids)
+    }
+}
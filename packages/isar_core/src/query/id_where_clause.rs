@@ -3,6 +3,7 @@ use crate::error::Result;
 use crate::mdbx::db::Db;
 use crate::object::id::BytesToId;
 use crate::object::isar_object::IsarObject;
+use crate::query::scan::Scan;
 use crate::query::Sort;
 use intmap::IntMap;
 
@@ -28,10 +29,30 @@ impl IdWhereClause {
         self.upper < self.lower
     }
 
+    pub(crate) fn bounds(&self) -> (i64, i64, Sort) {
+        (self.lower, self.upper, self.sort)
+    }
+
     pub(crate) fn id_matches(&self, id: i64) -> bool {
         self.lower <= id && self.upper >= id
     }
 
+    pub(crate) fn reverse(&mut self) {
+        self.sort = self.sort.reversed();
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!("id[{}..{}]", self.lower, self.upper)
+    }
+
+    pub(crate) fn estimate_count(&self, cursors: &IsarCursors) -> Result<u64> {
+        if self.is_empty() {
+            return Ok(0);
+        }
+        let mut cursor = cursors.get_cursor(self.db)?;
+        cursor.estimate_between(&self.lower, &self.upper)
+    }
+
     pub(crate) fn iter<'txn, 'env, F>(
         &self,
         cursors: &IsarCursors<'txn, 'env>,
@@ -61,6 +82,11 @@ impl IdWhereClause {
         )
     }
 
+    pub(crate) fn scan<'txn>(&self, cursors: &IsarCursors<'txn, '_>) -> Result<Scan<'txn>> {
+        let ascending = self.sort == Sort::Ascending;
+        Scan::ids(cursors, self.db, self.lower, self.upper, ascending)
+    }
+
     pub(crate) fn is_overlapping(&self, other: &Self) -> bool {
         (self.lower <= other.lower && self.upper >= other.upper)
             || (other.lower <= self.lower && other.upper >= self.upper)
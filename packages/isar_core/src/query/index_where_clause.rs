@@ -1,12 +1,15 @@
 use crate::cursor::IsarCursors;
-use crate::error::{IsarError, Result};
+use crate::error::{illegal_arg, IsarError, Result};
 use crate::index::index_key::IndexKey;
 use crate::index::index_key_builder::IndexKeyBuilder;
 use crate::index::IsarIndex;
 use crate::mdbx::db::Db;
+use crate::mdbx::Key;
 use crate::object::isar_object::IsarObject;
-use crate::query::Sort;
+use crate::query::scan::{IndexRange, Scan};
+use crate::query::{Nulls, Sort};
 use intmap::IntMap;
+use std::cmp::Ordering;
 
 #[derive(Clone)]
 pub(crate) struct IndexWhereClause {
@@ -27,6 +30,13 @@ impl IndexWhereClause {
         skip_duplicates: bool,
         sort: Sort,
     ) -> Result<Self> {
+        if let Some(null_key) = index.sparse_null_key() {
+            if index.compare_keys(null_key, &lower_key) != Ordering::Less
+                && index.compare_keys(null_key, &upper_key) != Ordering::Greater
+            {
+                illegal_arg("Sparse indexes do not contain null values. Use a filter instead.")?;
+            }
+        }
         Ok(IndexWhereClause {
             db,
             index,
@@ -42,28 +52,133 @@ impl IndexWhereClause {
         let key_builder = IndexKeyBuilder::new(&self.index.properties);
         key_builder
             .create_keys(object, |key| {
-                key_matches = key >= &self.lower_key && key <= &self.upper_key;
+                key_matches = self.index.compare_keys(key, &self.lower_key) != Ordering::Less
+                    && self.index.compare_keys(key, &self.upper_key) != Ordering::Greater;
                 Ok(!key_matches)
             })
             .unwrap();
         key_matches
     }
 
+    pub(crate) fn covers_property(&self, offset: usize) -> bool {
+        self.index.covers_property(offset)
+    }
+
+    pub(crate) fn uses_index(&self, index: &IsarIndex) -> bool {
+        self.index == *index
+    }
+
+    pub(crate) fn reverse(&mut self) {
+        self.sort = self.sort.reversed();
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!("index[{}]", self.index.name)
+    }
+
+    pub(crate) fn index_name(&self) -> &str {
+        &self.index.name
+    }
+
+    pub fn estimate_count(&self, cursors: &IsarCursors) -> Result<u64> {
+        self.index
+            .estimate_between(cursors, &self.lower_key, &self.upper_key)
+    }
+
+    /// The key ranges in scan order. Nulls that are placed after the other keys are scanned in
+    /// a range of their own because they are stored before all other keys.
+    fn ranges(&self) -> Vec<IndexRange> {
+        let first_non_null = if Nulls::overrides(self.index.nulls, self.sort) {
+            self.index.first_non_null_key()
+        } else {
+            None
+        };
+        let first_non_null = match first_non_null {
+            Some(key) => key,
+            None => {
+                return vec![IndexRange {
+                    lower: self.lower_key.clone(),
+                    upper: self.upper_key.clone(),
+                    exclude: None,
+                }]
+            }
+        };
+
+        let cmp = |a, b| self.index.compare_keys(a, b);
+        let non_null_lower = if cmp(&self.lower_key, &first_non_null) == Ordering::Less {
+            &first_non_null
+        } else {
+            &self.lower_key
+        };
+        let null_upper = if cmp(&self.upper_key, &first_non_null) == Ordering::Less {
+            &self.upper_key
+        } else {
+            &first_non_null
+        };
+
+        let mut ranges = vec![];
+        if cmp(non_null_lower, &self.upper_key) != Ordering::Greater {
+            ranges.push(IndexRange {
+                lower: non_null_lower.clone(),
+                upper: self.upper_key.clone(),
+                exclude: None,
+            });
+        }
+        if cmp(&self.lower_key, null_upper) != Ordering::Greater {
+            let nulls = IndexRange {
+                lower: self.lower_key.clone(),
+                upper: null_upper.clone(),
+                exclude: Some(first_non_null.as_bytes().to_vec()),
+            };
+            // Nulls last in ascending and nulls first in descending order.
+            if self.sort == Sort::Ascending {
+                ranges.push(nulls);
+            } else {
+                ranges.insert(0, nulls);
+            }
+        }
+        ranges
+    }
+
     pub fn iter_ids<'txn, 'env, F>(
         &self,
         cursors: &IsarCursors<'txn, 'env>,
-        callback: F,
+        mut callback: F,
     ) -> Result<bool>
     where
         F: FnMut(i64) -> Result<bool>,
     {
-        self.index.iter_between(
+        let ascending = self.sort == Sort::Ascending;
+        for range in self.ranges() {
+            let cont = self.index.iter_between_keys(
+                cursors,
+                &range.lower,
+                &range.upper,
+                self.skip_duplicates,
+                ascending,
+                |key, id| {
+                    if range.exclude.as_deref() == Some(key) {
+                        Ok(true)
+                    } else {
+                        callback(id)
+                    }
+                },
+            )?;
+            if !cont {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    pub(crate) fn scan<'txn>(&self, cursors: &IsarCursors<'txn, '_>) -> Result<Scan<'txn>> {
+        let ascending = self.sort == Sort::Ascending;
+        self.index.scan(
             cursors,
-            &self.lower_key,
-            &self.upper_key,
+            self.db,
+            self.ranges(),
             self.skip_duplicates,
-            self.sort == Sort::Ascending,
-            callback,
+            ascending,
         )
     }
 
@@ -95,9 +210,12 @@ impl IndexWhereClause {
     }
 
     pub fn is_overlapping(&self, other: &Self) -> bool {
+        let cmp = |a, b| self.index.compare_keys(a, b);
         self.index != other.index
-            || ((self.lower_key <= other.lower_key && self.upper_key >= other.upper_key)
-                || (other.lower_key <= self.lower_key && other.upper_key >= self.upper_key))
+            || ((cmp(&self.lower_key, &other.lower_key) != Ordering::Greater
+                && cmp(&self.upper_key, &other.upper_key) != Ordering::Less)
+                || (cmp(&other.lower_key, &self.lower_key) != Ordering::Greater
+                    && cmp(&other.upper_key, &self.upper_key) != Ordering::Less))
     }
 
     pub fn has_duplicates(&self) -> bool {
@@ -0,0 +1,142 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// The distance function of a nearest neighbor search.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Metric {
+    /// `1 - cos(a, b)`. Zero vectors have a distance of 1 to every vector.
+    Cosine,
+    /// The euclidean distance.
+    L2,
+}
+
+impl Metric {
+    pub fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            Metric::Cosine => {
+                let (mut dot, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+                for (x, y) in a.iter().zip(b) {
+                    dot += x * y;
+                    norm_a += x * x;
+                    norm_b += y * y;
+                }
+                if norm_a == 0.0 || norm_b == 0.0 {
+                    1.0
+                } else {
+                    1.0 - dot / (norm_a.sqrt() * norm_b.sqrt())
+                }
+            }
+            Metric::L2 => a
+                .iter()
+                .zip(b)
+                .map(|(x, y)| (x - y) * (x - y))
+                .sum::<f32>()
+                .sqrt(),
+        }
+    }
+}
+
+struct Neighbor {
+    distance: f32,
+    id: i64,
+}
+
+impl PartialEq for Neighbor {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Neighbor {}
+
+impl PartialOrd for Neighbor {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Neighbor {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.id.cmp(&other.id))
+    }
+}
+
+/// Keeps the `k` nearest ids seen so far. Ties are broken by the smaller id.
+pub(crate) struct NearestNeighbors {
+    k: usize,
+    // farthest neighbor on top
+    heap: BinaryHeap<Neighbor>,
+}
+
+impl NearestNeighbors {
+    pub fn new(k: usize) -> Self {
+        NearestNeighbors {
+            k,
+            heap: BinaryHeap::with_capacity(k.min(1024) + 1),
+        }
+    }
+
+    pub fn add(&mut self, id: i64, distance: f32) {
+        if self.k == 0 || distance.is_nan() {
+            return;
+        }
+        self.heap.push(Neighbor { distance, id });
+        if self.heap.len() > self.k {
+            self.heap.pop();
+        }
+    }
+
+    /// The ids and distances of the neighbors, nearest first.
+    pub fn into_sorted(self) -> Vec<(i64, f32)> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|n| (n.id, n.distance))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn knn(
+        vectors: &[(i64, Vec<f32>)],
+        query: &[f32],
+        k: usize,
+        metric: Metric,
+    ) -> Vec<(i64, f32)> {
+        let mut neighbors = NearestNeighbors::new(k);
+        for (id, vector) in vectors {
+            neighbors.add(*id, metric.distance(query, vector));
+        }
+        neighbors.into_sorted()
+    }
+
+    #[test]
+    fn test_knn() {
+        let vectors = vec![
+            (1, vec![1.0, 0.0]),
+            (2, vec![0.0, 1.0]),
+            (3, vec![3.0, 4.0]),
+            (4, vec![-1.0, 0.0]),
+            (5, vec![2.0, 0.0]),
+        ];
+
+        let l2 = knn(&vectors, &[0.0, 0.0], 3, Metric::L2);
+        assert_eq!(l2, vec![(1, 1.0), (2, 1.0), (4, 1.0)]);
+        let l2 = knn(&vectors, &[3.0, 3.0], 2, Metric::L2);
+        assert_eq!(l2, vec![(3, 1.0), (5, 10f32.sqrt())]);
+
+        let cosine = knn(&vectors, &[4.0, 0.0], 3, Metric::Cosine);
+        assert_eq!(cosine, vec![(1, 0.0), (5, 0.0), (3, 1.0 - 3.0 / 5.0)]);
+        let cosine = knn(&vectors, &[1.0, 0.0], 10, Metric::Cosine);
+        assert_eq!(cosine.len(), 5);
+        assert_eq!(cosine.last(), Some(&(4, 2.0)));
+
+        assert_eq!(Metric::Cosine.distance(&[0.0, 0.0], &[1.0, 0.0]), 1.0);
+        assert!(knn(&vectors, &[0.0, 0.0], 0, Metric::L2).is_empty());
+    }
+}
@@ -2,6 +2,7 @@ use crate::cursor::IsarCursors;
 use crate::error::Result;
 use crate::link::IsarLink;
 use crate::object::isar_object::IsarObject;
+use crate::query::scan::Scan;
 use intmap::IntMap;
 
 #[derive(Clone)]
@@ -15,6 +16,10 @@ impl LinkWhereClause {
         Ok(LinkWhereClause { link, id })
     }
 
+    pub(crate) fn describe(&self) -> String {
+        format!("link[{}]", self.link.name)
+    }
+
     pub fn iter<'txn, 'env, F>(
         &self,
         cursors: &IsarCursors<'txn, 'env>,
@@ -33,4 +38,8 @@ impl LinkWhereClause {
             callback(id, object)
         })
     }
+
+    pub(crate) fn scan<'txn>(&self, cursors: &IsarCursors<'txn, '_>) -> Result<Scan<'txn>> {
+        self.link.scan(cursors, self.id)
+    }
 }
@@ -1,23 +1,49 @@
 use intmap::IntMap;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::cmp::Ordering;
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::collection::IsarCollection;
 use crate::cursor::IsarCursors;
-use crate::error::Result;
+use crate::error::{IsarError, Result};
 use crate::object::isar_object::IsarObject;
 use crate::object::json_encode_decode::JsonEncodeDecode;
+use crate::object::msgpack;
+use crate::object::projected_object::ExportProjection;
 use crate::object::property::Property;
+use crate::query::aggregate_cache::{AggregateCache, CachedValue};
 use crate::query::filter::Filter;
+use crate::query::histogram::Histogram;
+use crate::query::query_iter::QueryIter;
+use crate::query::sample::Reservoir;
+use crate::query::stats::{QueryStats, ScanProfile, WhereClauseStats};
 use crate::query::where_clause::WhereClause;
+use crate::schema::property_schema::EnumMapping;
 use crate::txn::IsarTxn;
 
+pub mod aggregate_cache;
+mod edit_distance;
 mod fast_wild_match;
 pub mod filter;
+mod filter_parser;
+mod histogram;
+mod id_index_where_clause;
+mod id_set_where_clause;
 mod id_where_clause;
 mod index_where_clause;
+pub mod knn;
 mod link_where_clause;
 pub mod query_builder;
+pub mod query_iter;
+mod sample;
+pub(crate) mod scan;
+pub mod stats;
+pub(crate) mod time_where_clause;
 mod where_clause;
 
 #[derive(Copy, Clone, Eq, PartialEq)]
@@ -26,33 +52,112 @@ pub enum Sort {
     Descending,
 }
 
+impl Sort {
+    pub fn reversed(self) -> Sort {
+        match self {
+            Sort::Ascending => Sort::Descending,
+            Sort::Descending => Sort::Ascending,
+        }
+    }
+}
+
+/// Where null values are placed in ordered results regardless of the sort direction. Without a
+/// policy index scans treat nulls as the smallest values.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Nulls {
+    First,
+    Last,
+}
+
+impl Nulls {
+    pub fn reversed(self) -> Nulls {
+        match self {
+            Nulls::First => Nulls::Last,
+            Nulls::Last => Nulls::First,
+        }
+    }
+
+    /// Whether `nulls` moves the nulls away from where an index scan in `sort` order finds them.
+    pub(crate) fn overrides(nulls: Option<Nulls>, sort: Sort) -> bool {
+        matches!(
+            (nulls, sort),
+            (Some(Nulls::Last), Sort::Ascending) | (Some(Nulls::First), Sort::Descending)
+        )
+    }
+}
+
+/// Total and count of sampled object sizes.
+#[derive(Default)]
+struct SizeSample {
+    rows: u64,
+    bytes: u64,
+}
+
+impl SizeSample {
+    fn add(&mut self, size: usize) {
+        self.rows += 1;
+        self.bytes += size as u64;
+    }
+
+    /// Extrapolates the average sampled size to `rows` objects.
+    fn estimate(&self, rows: u64) -> u64 {
+        if self.rows == 0 {
+            0
+        } else {
+            let rows = rows.max(self.rows);
+            (self.bytes as u128 * rows as u128 / self.rows as u128) as u64
+        }
+    }
+}
+
 pub enum Case {
     Sensitive,
     Insensitive,
 }
 
+pub type SlowQueryCallback = Box<dyn Fn(&str, Duration) + Send + Sync + 'static>;
+
+/// Reports every query that takes at least `threshold` to the callback together with a short
+/// description of its plan.
+pub struct SlowQueryLog {
+    pub threshold: Duration,
+    pub callback: SlowQueryCallback,
+}
+
 #[derive(Clone)]
 pub struct Query {
     instance_id: u64,
     where_clauses: Vec<WhereClause>,
     where_clauses_dup: bool,
     filter: Option<Filter>,
-    sort: Vec<(Property, Sort)>,
+    sort: Vec<(Property, Sort, Option<Nulls>)>,
     distinct: Vec<(Property, bool)>,
     offset: usize,
     limit: usize,
+    cancel_token: Option<Arc<AtomicBool>>,
+    prefetch: bool,
+    max_scan: Option<u32>,
+    aggregate_cache: Option<Arc<Mutex<AggregateCache>>>,
 }
 
 impl<'txn> Query {
+    const CANCEL_CHECK_INTERVAL: usize = 64;
+
+    const SIZE_SAMPLE_ROWS: usize = 64;
+
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         instance_id: u64,
         where_clauses: Vec<WhereClause>,
         filter: Option<Filter>,
-        sort: Vec<(Property, Sort)>,
+        sort: Vec<(Property, Sort, Option<Nulls>)>,
         distinct: Vec<(Property, bool)>,
         offset: usize,
         limit: usize,
+        cancel_token: Option<Arc<AtomicBool>>,
+        prefetch: bool,
+        max_scan: Option<u32>,
+        aggregate_cache_size: usize,
     ) -> Self {
         let where_clauses_dup = Self::check_where_clauses_duplicates(&where_clauses);
         Query {
@@ -64,6 +169,35 @@ impl<'txn> Query {
             distinct,
             offset,
             limit,
+            cancel_token,
+            prefetch,
+            max_scan,
+            aggregate_cache: (aggregate_cache_size > 0)
+                .then(|| Arc::new(Mutex::new(AggregateCache::new(aggregate_cache_size)))),
+        }
+    }
+
+    /// Makes running and later executions of the query fail with [IsarError::Cancelled]. The token
+    /// is never reset, the query keeps failing until the owner of the token clears it.
+    pub fn cancel(&self) {
+        if let Some(cancel_token) = &self.cancel_token {
+            cancel_token.store(true, atomic::Ordering::Relaxed);
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        if let Some(cancel_token) = &self.cancel_token {
+            cancel_token.load(atomic::Ordering::Relaxed)
+        } else {
+            false
+        }
+    }
+
+    fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(IsarError::Cancelled {})
+        } else {
+            Ok(())
         }
     }
 
@@ -84,6 +218,26 @@ impl<'txn> Query {
     pub(crate) fn execute_raw<'env, F>(
         &self,
         cursors: &IsarCursors<'txn, 'env>,
+        profile: Option<&mut ScanProfile>,
+        callback: F,
+    ) -> Result<()>
+    where
+        F: FnMut(i64, IsarObject<'txn>) -> Result<bool>,
+    {
+        if self.prefetch {
+            cursors.set_prefetch(true);
+            let result = self.execute_where_clauses(cursors, profile, callback);
+            cursors.set_prefetch(false);
+            result
+        } else {
+            self.execute_where_clauses(cursors, profile, callback)
+        }
+    }
+
+    fn execute_where_clauses<'env, F>(
+        &self,
+        cursors: &IsarCursors<'txn, 'env>,
+        mut profile: Option<&mut ScanProfile>,
         mut callback: F,
     ) -> Result<()>
     where
@@ -95,18 +249,51 @@ impl<'txn> Query {
             None
         };
 
+        self.check_cancelled()?;
         let static_filter = Filter::stat(true);
         let filter = self.filter.as_ref().unwrap_or(&static_filter);
 
+        let start = Instant::now();
+        // sorting, distinct and the caller run in the callback and are not part of the scan
+        let mut callback_time = Duration::ZERO;
+        let mut scanned = 0;
         for where_clause in &self.where_clauses {
+            let scanned_before = scanned;
             let result = where_clause.iter(cursors, result_ids.as_mut(), |id, object| {
-                if filter.evaluate(id, object, Some(cursors))? {
-                    callback(id, object)
+                scanned += 1;
+                if scanned % Self::CANCEL_CHECK_INTERVAL == 0 && self.is_cancelled() {
+                    return Err(IsarError::Cancelled {});
+                }
+                if let Some(limit) = self.max_scan {
+                    if scanned > limit as usize {
+                        return Err(IsarError::ScanLimitExceeded { limit });
+                    }
+                }
+                let matches = if let Some(profile) = profile.as_deref_mut() {
+                    let filter_start = Instant::now();
+                    let matches = filter.evaluate(id, object, Some(cursors))?;
+                    profile.filter_time += filter_start.elapsed();
+                    profile.matched += matches as u64;
+                    matches
                 } else {
+                    filter.evaluate(id, object, Some(cursors))?
+                };
+                if !matches {
                     Ok(true)
+                } else if profile.is_some() {
+                    let callback_start = Instant::now();
+                    let result = callback(id, object);
+                    callback_time += callback_start.elapsed();
+                    result
+                } else {
+                    callback(id, object)
                 }
-            })?;
-            if !result {
+            });
+            if let Some(profile) = profile.as_deref_mut() {
+                profile.scanned.push((scanned - scanned_before) as u64);
+                profile.scan_time = start.elapsed().saturating_sub(callback_time);
+            }
+            if !result? {
                 return Ok(());
             }
         }
@@ -117,6 +304,7 @@ impl<'txn> Query {
     fn execute_unsorted<'env, F>(
         &self,
         cursors: &IsarCursors<'txn, 'env>,
+        profile: Option<&mut ScanProfile>,
         callback: F,
     ) -> Result<()>
     where
@@ -125,10 +313,10 @@ impl<'txn> Query {
         if !self.distinct.is_empty() {
             let callback = self.add_distinct_unsorted(callback);
             let callback = self.add_offset_limit_unsorted(callback);
-            self.execute_raw(cursors, callback)
+            self.execute_raw(cursors, profile, callback)
         } else {
             let callback = self.add_offset_limit_unsorted(callback);
-            self.execute_raw(cursors, callback)
+            self.execute_raw(cursors, profile, callback)
         }
     }
 
@@ -182,26 +370,15 @@ impl<'txn> Query {
     fn execute_sorted<'env>(
         &self,
         cursors: &IsarCursors<'txn, 'env>,
+        profile: Option<&mut ScanProfile>,
     ) -> Result<Vec<(i64, IsarObject<'txn>)>> {
         let mut results = vec![];
-        self.execute_raw(cursors, |id, object| {
+        self.execute_raw(cursors, profile, |id, object| {
             results.push((id, object));
             Ok(true)
         })?;
 
-        results.sort_unstable_by(|(_, o1), (_, o2)| {
-            for (p, sort) in &self.sort {
-                let ord = o1.compare_property(o2, p.offset, p.data_type);
-                if ord != Ordering::Equal {
-                    return if *sort == Sort::Ascending {
-                        ord
-                    } else {
-                        ord.reverse()
-                    };
-                }
-            }
-            Ordering::Equal
-        });
+        results.sort_unstable_by(|(_, o1), (_, o2)| Self::compare_sorted(&self.sort, *o1, *o2));
 
         if !self.distinct.is_empty() {
             Ok(self.add_distinct_sorted(results))
@@ -210,6 +387,39 @@ impl<'txn> Query {
         }
     }
 
+    fn compare_sorted(
+        sort: &[(Property, Sort, Option<Nulls>)],
+        o1: IsarObject,
+        o2: IsarObject,
+    ) -> Ordering {
+        for (p, sort, nulls) in sort {
+            if let Some(nulls) = nulls {
+                let null1 = o1.is_null(p.offset, p.data_type);
+                let null2 = o2.is_null(p.offset, p.data_type);
+                if null1 != null2 {
+                    let null_first = if null1 {
+                        Ordering::Less
+                    } else {
+                        Ordering::Greater
+                    };
+                    return match nulls {
+                        Nulls::First => null_first,
+                        Nulls::Last => null_first.reverse(),
+                    };
+                }
+            }
+            let ord = o1.compare_property(&o2, p.offset, p.data_type);
+            if ord != Ordering::Equal {
+                return if *sort == Sort::Ascending {
+                    ord
+                } else {
+                    ord.reverse()
+                };
+            }
+        }
+        Ordering::Equal
+    }
+
     fn add_distinct_sorted(
         &self,
         results: Vec<(i64, IsarObject<'txn>)>,
@@ -248,18 +458,117 @@ impl<'txn> Query {
         }
     }
 
-    pub fn find_while<F>(&self, txn: &'txn mut IsarTxn, mut callback: F) -> Result<()>
+    /// A short description of how the query is executed, e.g.
+    /// `index[name] | filter | sort(1)`.
+    pub fn describe(&self) -> String {
+        let where_clauses = self.where_clauses.iter().map(|wc| wc.describe()).collect();
+        self.describe_with(where_clauses)
+    }
+
+    /// Like [Query::describe] but shows which side of an id range combined with an index range
+    /// is scanned, e.g. `index[name] > id[1..9]`, based on the data in `txn`.
+    pub fn explain(&self, txn: &mut IsarTxn) -> Result<String> {
+        let where_clauses = txn.read(self.instance_id, |cursors| {
+            self.where_clauses
+                .iter()
+                .map(|wc| wc.describe_plan(cursors))
+                .collect::<Result<Vec<_>>>()
+        })?;
+        Ok(self.describe_with(where_clauses))
+    }
+
+    /// Runs the query in `txn` and reports how it was executed: the where clauses with their
+    /// estimated and scanned objects, which steps run in memory and where the time was spent.
+    /// The results are discarded and timing the filter of every object adds some overhead.
+    pub fn profile(&self, txn: &mut IsarTxn) -> Result<QueryStats> {
+        let start = Instant::now();
+        let mut stats = txn.read(self.instance_id, |cursors| {
+            let mut where_clauses = vec![];
+            for wc in &self.where_clauses {
+                where_clauses.push(WhereClauseStats {
+                    plan: wc.describe_plan(cursors)?,
+                    index: wc.index_name().map(|name| name.to_string()),
+                    estimated: wc.estimate_count(cursors)?,
+                    scanned: 0,
+                });
+            }
+
+            let mut profile = ScanProfile::default();
+            let returned = if self.sort.is_empty() {
+                let mut returned = 0;
+                self.execute_unsorted(cursors, Some(&mut profile), |_, _| {
+                    returned += 1;
+                    Ok(true)
+                })?;
+                returned
+            } else {
+                let results = self.execute_sorted(cursors, Some(&mut profile))?;
+                self.add_offset_limit_sorted(results).into_iter().count() as u64
+            };
+
+            for (wc, scanned) in where_clauses.iter_mut().zip(profile.scanned) {
+                wc.scanned = scanned;
+            }
+            Ok(QueryStats {
+                where_clauses,
+                dedup: self.where_clauses_dup,
+                sort_in_memory: !self.sort.is_empty(),
+                distinct_in_memory: !self.distinct.is_empty(),
+                matched: profile.matched,
+                returned,
+                filter_time: profile.filter_time,
+                read_time: profile.scan_time.saturating_sub(profile.filter_time),
+                total_time: Duration::ZERO,
+            })
+        })?;
+        stats.total_time = start.elapsed();
+        Ok(stats)
+    }
+
+    fn describe_with(&self, mut parts: Vec<String>) -> String {
+        if self.where_clauses_dup {
+            parts.push("dedup".to_string());
+        }
+        if self.filter.is_some() {
+            parts.push("filter".to_string());
+        }
+        if !self.sort.is_empty() {
+            parts.push(format!("sort({})", self.sort.len()));
+        }
+        if !self.distinct.is_empty() {
+            parts.push(format!("distinct({})", self.distinct.len()));
+        }
+        parts.join(" | ")
+    }
+
+    pub fn find_while<F>(&self, txn: &'txn mut IsarTxn, callback: F) -> Result<()>
+    where
+        F: FnMut(i64, IsarObject<'txn>) -> bool,
+    {
+        let slow_query_log = txn.slow_query_log();
+        let start = Instant::now();
+        let result = self.find_while_internal(txn, callback);
+        if let Some(log) = slow_query_log {
+            let elapsed = start.elapsed();
+            if elapsed >= log.threshold {
+                (log.callback)(&self.describe(), elapsed);
+            }
+        }
+        result
+    }
+
+    fn find_while_internal<F>(&self, txn: &'txn mut IsarTxn, mut callback: F) -> Result<()>
     where
         F: FnMut(i64, IsarObject<'txn>) -> bool,
     {
         txn.read(self.instance_id, |cursors| {
             if self.sort.is_empty() {
-                self.execute_unsorted(cursors, |id, object| {
+                self.execute_unsorted(cursors, None, |id, object| {
                     let cont = callback(id, object);
                     Ok(cont)
                 })?;
             } else {
-                let results = self.execute_sorted(cursors)?;
+                let results = self.execute_sorted(cursors, None)?;
                 let results_iter = self.add_offset_limit_sorted(results);
                 for (id, object) in results_iter {
                     if !callback(id, object) {
@@ -271,6 +580,121 @@ impl<'txn> Query {
         })
     }
 
+    /// Calls `callback` for the matching objects after skipping `offset` of them until `limit`
+    /// objects have been visited or the callback breaks. The objects cannot escape the callback.
+    /// Returns the number of visited objects.
+    pub fn for_each<F>(
+        &self,
+        txn: &mut IsarTxn,
+        offset: usize,
+        limit: usize,
+        mut callback: F,
+    ) -> Result<u32>
+    where
+        F: FnMut(i64, IsarObject) -> Result<ControlFlow<()>>,
+    {
+        if limit == 0 {
+            return Ok(0);
+        }
+        let mut skipped = 0;
+        let mut visited = 0;
+        let mut result = Ok(());
+        self.find_while(txn, |id, object| {
+            if skipped < offset {
+                skipped += 1;
+                return true;
+            }
+            visited += 1;
+            match callback(id, object) {
+                Ok(ControlFlow::Continue(())) => visited < limit,
+                Ok(ControlFlow::Break(())) => false,
+                Err(e) => {
+                    result = Err(e);
+                    false
+                }
+            }
+        })?;
+        result?;
+        Ok(visited as u32)
+    }
+
+    /// Returns the aggregation result cached under `key` if no write has been committed since it
+    /// was computed. Otherwise `compute` runs and its result is cached. The cache is shared by
+    /// all clones of the query. Write transactions and queries built without an aggregate cache
+    /// always compute the result.
+    pub fn aggregate_cached<F>(
+        &self,
+        txn: &mut IsarTxn,
+        key: u64,
+        compute: F,
+    ) -> Result<CachedValue>
+    where
+        F: FnOnce(&Self, &mut IsarTxn) -> Result<CachedValue>,
+    {
+        let cache = match &self.aggregate_cache {
+            Some(cache) if !txn.is_write() => cache,
+            _ => return compute(self, txn),
+        };
+        let watermark = txn.id();
+        if let Some(value) = cache.lock().unwrap().get(key, watermark) {
+            return Ok(value);
+        }
+        let value = compute(self, txn)?;
+        cache.lock().unwrap().insert(key, watermark, value);
+        Ok(value)
+    }
+
+    /// Returns the ids of the matching objects in query order after skipping `offset` of them,
+    /// at most `limit` ids. Queries without filter, sort and distinct read the ids directly from
+    /// the index or object keys.
+    pub fn find_ids(&self, txn: &mut IsarTxn, offset: usize, limit: usize) -> Result<Vec<i64>> {
+        let mut ids = vec![];
+        if limit == 0 {
+            return Ok(ids);
+        }
+        if self.filter.is_some() || !self.sort.is_empty() || !self.distinct.is_empty() {
+            self.for_each(txn, offset, limit, |id, _| {
+                ids.push(id);
+                Ok(ControlFlow::Continue(()))
+            })?;
+            return Ok(ids);
+        }
+
+        let offset = self.offset.saturating_add(offset);
+        let limit = limit.min(self.limit.saturating_sub(offset - self.offset));
+        self.check_cancelled()?;
+        txn.read(self.instance_id, |cursors| {
+            let mut result_ids = if self.where_clauses_dup {
+                Some(IntMap::new())
+            } else {
+                None
+            };
+            let mut scanned = 0;
+            for where_clause in &self.where_clauses {
+                let cont = where_clause.iter_ids(cursors, result_ids.as_mut(), |id| {
+                    scanned += 1;
+                    if scanned % Self::CANCEL_CHECK_INTERVAL == 0 && self.is_cancelled() {
+                        return Err(IsarError::Cancelled {});
+                    }
+                    if let Some(limit) = self.max_scan {
+                        if scanned > limit as usize {
+                            return Err(IsarError::ScanLimitExceeded { limit });
+                        }
+                    }
+                    if scanned > offset {
+                        ids.push(id);
+                    }
+                    Ok(ids.len() < limit)
+                })?;
+                if !cont {
+                    break;
+                }
+            }
+            Ok(())
+        })?;
+        Ok(ids)
+    }
+
     pub fn find_all_vec(&self, txn: &'txn mut IsarTxn) -> Result<Vec<(i64, IsarObject<'txn>)>> {
         let mut results = vec![];
         self.find_while(txn, |id, object| {
@@ -280,6 +704,108 @@ impl<'txn> Query {
         Ok(results)
     }
 
+    /// Returns the matching objects as an iterator that reads them from the cursors of `txn` as
+    /// they are requested, so breaking early does not read the remaining objects.
+    pub fn find_iter<'env>(&self, txn: &'txn mut IsarTxn<'env>) -> Result<QueryIter<'txn, 'env>> {
+        self.check_cancelled()?;
+        let slow_query_log = txn.slow_query_log();
+        let cursors = txn.cursors(self.instance_id)?;
+        Ok(QueryIter::new(self, cursors, slow_query_log))
+    }
+
+    /// Deletes up to `limit` matching objects and returns how many were deleted. Unsorted queries
+    /// with a single id where clause delete the objects during the scan; other queries collect
+    /// the ids first.
+    pub fn delete(
+        &self,
+        txn: &mut IsarTxn,
+        collection: &IsarCollection,
+        limit: usize,
+    ) -> Result<u32> {
+        let limit = limit.min(self.limit);
+        let in_place = self.sort.is_empty() && self.distinct.is_empty() && self.offset == 0;
+        if let (true, [WhereClause::Id(wc)]) = (in_place, self.where_clauses.as_slice()) {
+            self.check_cancelled()?;
+            let static_filter = Filter::stat(true);
+            let filter = self.filter.as_ref().unwrap_or(&static_filter);
+            let (lower, upper, sort) = wc.bounds();
+            return txn.write(self.instance_id, |cursors, change_set| {
+                collection.delete_between(
+                    cursors,
+                    change_set,
+                    lower,
+                    upper,
+                    sort == Sort::Ascending,
+                    filter,
+                    limit,
+                )
+            });
+        }
+
+        let mut ids = vec![];
+        if limit > 0 {
+            self.find_while(txn, |id, _| {
+                ids.push(id);
+                ids.len() < limit
+            })?;
+        }
+        let mut count = 0;
+        for id in ids {
+            if collection.delete(txn, id)? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Estimates the total size in bytes of the objects returned after skipping `offset` of them,
+    /// at most `limit` objects. Queries with only id and index where clauses extrapolate the
+    /// size of the first objects to the estimated result count. All other queries are scanned
+    /// and return the exact size.
+    pub fn estimate_bytes(&self, txn: &mut IsarTxn, offset: usize, limit: usize) -> Result<u64> {
+        let estimated_count = if self.filter.is_none()
+            && self.sort.is_empty()
+            && self.distinct.is_empty()
+            && !self.where_clauses_dup
+        {
+            txn.read(self.instance_id, |cursors| {
+                let mut count = 0u64;
+                for where_clause in &self.where_clauses {
+                    match where_clause.estimate_count(cursors)? {
+                        Some(wc_count) => count += wc_count,
+                        None => return Ok(None),
+                    }
+                }
+                Ok(Some(count))
+            })?
+        } else {
+            None
+        };
+
+        let sample_limit = if estimated_count.is_some() {
+            limit.min(Self::SIZE_SAMPLE_ROWS)
+        } else {
+            limit
+        };
+        let mut sample = SizeSample::default();
+        self.for_each(txn, offset, sample_limit, |_, object| {
+            sample.add(object.len());
+            Ok(ControlFlow::Continue(()))
+        })?;
+
+        match estimated_count {
+            Some(count) if sample.rows == sample_limit as u64 => {
+                let count = count
+                    .saturating_sub(self.offset as u64)
+                    .min(self.limit as u64)
+                    .saturating_sub(offset as u64)
+                    .min(limit as u64);
+                Ok(sample.estimate(count))
+            }
+            _ => Ok(sample.bytes),
+        }
+    }
+
     pub fn count(&self, txn: &mut IsarTxn) -> Result<u32> {
         let mut counter = 0;
         self.find_while(txn, |_, _| {
@@ -289,14 +815,94 @@ impl<'txn> Query {
         Ok(counter)
     }
 
+    /// Counts the values of a numeric property in buckets of `bucket_width`. Null and non-finite
+    /// values are skipped. Returns the lower bound and count of each non-empty bucket.
+    pub fn histogram(
+        &self,
+        txn: &mut IsarTxn,
+        property: &Property,
+        bucket_width: f64,
+    ) -> Result<Vec<(f64, u64)>> {
+        let mut histogram = Histogram::new(bucket_width)?;
+        let mut result = Ok(());
+        self.find_while(txn, |_, object| {
+            result = histogram.add_property(object, property);
+            result.is_ok()
+        })?;
+        result?;
+        Ok(histogram.finish())
+    }
+
+    /// Returns up to `n` ids picked uniformly at random from the matching objects in a single
+    /// pass. Passing a `seed` makes the result reproducible.
+    pub fn sample(&self, txn: &mut IsarTxn, n: u32, seed: Option<u64>) -> Result<Vec<i64>> {
+        let mut reservoir = Reservoir::new(n as usize, seed);
+        self.find_while(txn, |id, _| {
+            reservoir.add(id);
+            true
+        })?;
+        Ok(reservoir.finish())
+    }
+
+    /// Whether the query could be answered from index entries alone: every where clause is an
+    /// index where clause and all properties used by the filter, sort, distinct and `projection`
+    /// are stored in the index keys.
+    pub fn is_index_covered(&self, projection: &[&Property]) -> bool {
+        let mut offsets: Vec<usize> = projection.iter().map(|p| p.offset).collect();
+        offsets.extend(self.sort.iter().map(|(p, _, _)| p.offset));
+        offsets.extend(self.distinct.iter().map(|(p, _)| p.offset));
+        if let Some(filter) = &self.filter {
+            if let Some(filter_offsets) = filter.scalar_offsets() {
+                offsets.extend(filter_offsets);
+            } else {
+                return false;
+            }
+        }
+        self.where_clauses.iter().all(|wc| match wc {
+            WhereClause::Index(wc) => offsets.iter().all(|offset| wc.covers_property(*offset)),
+            _ => false,
+        })
+    }
+
+    /// Exports the properties at the indices in `properties`, or all properties if it is `None`.
+    /// The values of the properties in `redact` are replaced by
+    /// [crate::object::projected_object::REDACTED_VALUE].
     pub fn export_json(
         &self,
         txn: &mut IsarTxn,
         collection: &IsarCollection,
         id_name: Option<&str>,
         primitive_null: bool,
+        properties: Option<&[u16]>,
+        redact: &[u16],
     ) -> Result<Value> {
+        let projection = ExportProjection::new(&collection.properties, properties, redact)?;
         let mut items = vec![];
+        self.find_while(txn, |id, object| {
+            let mut json =
+                projection.encode(&collection.embedded_properties, object, primitive_null);
+            EnumMapping::encode_names(&collection.enum_mappings, &mut json);
+            if let Some(id_name) = id_name {
+                json.insert(id_name.to_string(), Value::from(id));
+            }
+            items.push(json);
+            true
+        })?;
+        Ok(json!(items))
+    }
+
+    /// Writes the matching objects as a stream of MessagePack maps with the same content as
+    /// [Query::export_json]. Returns the number of exported objects.
+    pub fn export_msgpack<W: Write>(
+        &self,
+        txn: &mut IsarTxn,
+        collection: &IsarCollection,
+        id_name: Option<&str>,
+        primitive_null: bool,
+        writer: &mut W,
+    ) -> Result<u32> {
+        let mut count = 0;
+        let mut result = Ok(());
         self.find_while(txn, |id, object| {
             let mut json = JsonEncodeDecode::encode(
                 &collection.properties,
@@ -304,12 +910,76 @@ impl<'txn> Query {
                 object,
                 primitive_null,
             );
+            EnumMapping::encode_names(&collection.enum_mappings, &mut json);
             if let Some(id_name) = id_name {
                 json.insert(id_name.to_string(), Value::from(id));
             }
-            items.push(json);
-            true
+            result = msgpack::write_value(writer, &Value::Object(json));
+            if result.is_ok() {
+                count += 1;
+            }
+            result.is_ok()
         })?;
-        Ok(json!(items))
+        result.map_err(|e| IsarError::IoError {
+            message: e.to_string(),
+        })?;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::data_type::DataType;
+    use crate::object::object_builder::ObjectBuilder;
+
+    #[test]
+    fn test_size_sample() {
+        let sizes = (0..10_000u64)
+            .map(|i| 100 + (i * 7919) % 400)
+            .collect::<Vec<_>>();
+        let total: u64 = sizes.iter().sum();
+
+        let mut sample = SizeSample::default();
+        for size in sizes.iter().take(Query::SIZE_SAMPLE_ROWS) {
+            sample.add(*size as usize);
+        }
+        let estimate = sample.estimate(sizes.len() as u64);
+        assert!(estimate.abs_diff(total) < total / 10);
+
+        assert_eq!(sample.estimate(1), sample.bytes);
+        assert_eq!(SizeSample::default().estimate(100), 0);
+    }
+
+    #[test]
+    fn test_compare_sorted_nulls() {
+        let p = Property::new("a", DataType::Long, 2, None);
+        let props = vec![p.clone()];
+        let mut b = ObjectBuilder::new(&props, None);
+        b.write_long(2, 5);
+        let five = b.finish().as_bytes().to_vec();
+        let mut b = ObjectBuilder::new(&props, None);
+        b.write_null(2, DataType::Long);
+        let null = b.finish().as_bytes().to_vec();
+        let (five, null) = (IsarObject::from_bytes(&five), IsarObject::from_bytes(&null));
+
+        let order = |sort, nulls| {
+            let sort = vec![(p.clone(), sort, nulls)];
+            Query::compare_sorted(&sort, null, five)
+        };
+        assert_eq!(order(Sort::Ascending, None), Ordering::Less);
+        assert_eq!(order(Sort::Descending, None), Ordering::Greater);
+        assert_eq!(order(Sort::Ascending, Some(Nulls::First)), Ordering::Less);
+        assert_eq!(order(Sort::Descending, Some(Nulls::First)), Ordering::Less);
+        assert_eq!(order(Sort::Ascending, Some(Nulls::Last)), Ordering::Greater);
+        assert_eq!(
+            order(Sort::Descending, Some(Nulls::Last)),
+            Ordering::Greater
+        );
+
+        assert!(Nulls::overrides(Some(Nulls::Last), Sort::Ascending));
+        assert!(Nulls::overrides(Some(Nulls::First), Sort::Descending));
+        assert!(!Nulls::overrides(Some(Nulls::First), Sort::Ascending));
+        assert!(!Nulls::overrides(None, Sort::Descending));
     }
 }
@@ -1,22 +1,47 @@
 use super::index_where_clause::IndexWhereClause;
 use crate::collection::IsarCollection;
-use crate::error::{illegal_arg, Result};
+use crate::error::{illegal_arg, IsarError, Result};
 use crate::index::index_key::IndexKey;
+use crate::index::tokenizer::Tokenizer;
+use crate::object::data_type::DataType;
 use crate::object::property::Property;
 use crate::query::filter::Filter;
+use crate::query::filter_parser::parse_filter;
+use crate::query::id_index_where_clause::IdIndexWhereClause;
+use crate::query::id_set_where_clause::IdSetWhereClause;
 use crate::query::id_where_clause::IdWhereClause;
 use crate::query::link_where_clause::LinkWhereClause;
+use crate::query::time_where_clause::{RelativeTime, TimeWhereClause};
 use crate::query::where_clause::WhereClause;
-use crate::query::{Query, Sort};
+use crate::query::{Nulls, Query, Sort};
+use crate::schema::index_schema::IndexType;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+enum IndexHint {
+    Index(String),
+    NoIndex,
+}
 
 pub struct QueryBuilder<'a> {
     pub collection: &'a IsarCollection,
     where_clauses: Option<Vec<WhereClause>>,
     filter: Option<Filter>,
-    sort: Vec<(Property, Sort)>,
+    conditions: Vec<Filter>,
+    prefix_where_clause: Option<IndexWhereClause>,
+    time_where_clause: Option<TimeWhereClause>,
+    id_range: Option<(i64, i64)>,
+    sort: Vec<(Property, Sort, Option<Nulls>)>,
     distinct: Vec<(Property, bool)>,
     offset: usize,
     limit: usize,
+    reverse: bool,
+    cancel_token: Option<Arc<AtomicBool>>,
+    prefetch: bool,
+    max_scan: Option<u32>,
+    aggregate_cache_size: usize,
+    index_hint: Option<IndexHint>,
 }
 
 impl<'a> QueryBuilder<'a> {
@@ -25,10 +50,20 @@ impl<'a> QueryBuilder<'a> {
             collection,
             where_clauses: None,
             filter: None,
+            conditions: vec![],
+            prefix_where_clause: None,
+            time_where_clause: None,
+            id_range: None,
             sort: vec![],
             distinct: vec![],
             offset: 0,
             limit: usize::MAX,
+            reverse: false,
+            cancel_token: None,
+            prefetch: false,
+            max_scan: None,
+            aggregate_cache_size: 0,
+            index_hint: None,
         }
     }
 
@@ -55,6 +90,20 @@ impl<'a> QueryBuilder<'a> {
         Ok(())
     }
 
+    /// Restricts the query to the given ids. Every id is looked up directly and duplicates are
+    /// ignored.
+    pub fn add_id_set_where_clause(&mut self, ids: &[i64], sort: Sort) -> Result<()> {
+        self.init_where_clauses();
+        let wc = IdSetWhereClause::new(self.collection.db, ids, sort);
+        if !wc.is_empty() {
+            self.where_clauses
+                .as_mut()
+                .unwrap()
+                .push(WhereClause::IdSet(wc))
+        }
+        Ok(())
+    }
+
     pub fn add_index_where_clause(
         &mut self,
         index_id: u64,
@@ -64,7 +113,7 @@ impl<'a> QueryBuilder<'a> {
         skip_duplicates: bool,
     ) -> Result<()> {
         self.init_where_clauses();
-        let index = self.collection.get_index_by_id(index_id)?;
+        let index = self.collection.get_usable_index(index_id)?;
         let wc = IndexWhereClause::new(
             self.collection.db,
             index.clone(),
@@ -102,9 +151,188 @@ impl<'a> QueryBuilder<'a> {
         self.filter = Some(filter);
     }
 
+    /// Parses a filter expression like `age > 18 AND name startsWith "A"` and sets it as the
+    /// filter. Only top-level properties can be referenced.
+    pub fn set_filter_expression(&mut self, expr: &str) -> Result<()> {
+        let filter = parse_filter(&self.collection.properties, expr)?;
+        self.filter = Some(filter);
+        Ok(())
+    }
+
+    /// Requires the string property to start with `prefix` in addition to the filter. If the
+    /// property is the first property of a value index and the query has no where clauses, the
+    /// index is used to scan only the matching range.
+    pub fn add_string_starts_with(
+        &mut self,
+        property: &Property,
+        prefix: &str,
+        case_sensitive: bool,
+    ) -> Result<()> {
+        let condition = Filter::string_starts_with(property, prefix, case_sensitive)?;
+        self.conditions.push(condition);
+
+        if self.prefix_where_clause.is_some()
+            || property.data_type != DataType::String
+            || self.collection.indexes_outdated()
+        {
+            return Ok(());
+        }
+        let index = self.collection.indexes.iter().find(|index| {
+            let first = &index.properties[0];
+            first.property == *property
+                && first.index_type == IndexType::Value
+                && first.case_sensitive == case_sensitive
+        });
+        let range = IndexKey::string_prefix_range(prefix, case_sensitive);
+        if let (Some(index), Some((lower, upper))) = (index, range) {
+            let wc = IndexWhereClause::new(
+                self.collection.db,
+                index.clone(),
+                lower,
+                upper,
+                false,
+                Sort::Ascending,
+            )?;
+            self.prefix_where_clause = Some(wc);
+        }
+        Ok(())
+    }
+
+    /// Requires the string property to contain every word of `query`, see [Filter::words_match].
+    /// The tokenizer and case sensitivity of a words index on the property are used, without one
+    /// the string is split into Unicode words and compared case insensitively. If the query has no
+    /// where clauses, the index is used to scan only the objects containing the longest term.
+    pub fn add_matches(&mut self, property: &Property, query: &str) -> Result<()> {
+        let index = self.collection.indexes.iter().find(|index| {
+            index.properties.len() == 1
+                && index.properties[0].property == *property
+                && index.properties[0].index_type == IndexType::Words
+        });
+        let (tokenizer, case_sensitive) = index.map_or((Tokenizer::Unicode, false), |index| {
+            (
+                index.properties[0].tokenizer,
+                index.properties[0].case_sensitive,
+            )
+        });
+        let condition = Filter::words_match(property, tokenizer, query, case_sensitive)?;
+        self.conditions.push(condition);
+
+        let index = match index {
+            Some(index) if self.prefix_where_clause.is_none() => index,
+            _ => return Ok(()),
+        };
+        if self.collection.indexes_outdated() {
+            return Ok(());
+        }
+        let terms = tokenizer.parse_query(query, case_sensitive);
+        let term = terms
+            .iter()
+            .filter(|term| !term.prefix)
+            .max_by_key(|term| term.word.len())
+            .or_else(|| terms.iter().max_by_key(|term| term.word.len()));
+        let range = term.and_then(|term| {
+            if term.prefix {
+                IndexKey::string_prefix_range(&term.word, true)
+            } else {
+                let mut key = IndexKey::new();
+                key.add_string(Some(&term.word), true);
+                Some((key.clone(), key))
+            }
+        });
+        if let Some((lower, upper)) = range {
+            let wc = IndexWhereClause::new(
+                self.collection.db,
+                index.clone(),
+                lower,
+                upper,
+                false,
+                Sort::Ascending,
+            )?;
+            self.prefix_where_clause = Some(wc);
+        }
+        Ok(())
+    }
+
+    /// Requires the Long timestamp property (microseconds since the Unix epoch) to be at most
+    /// `duration` in the past. The current time is taken when the query is executed so the same
+    /// query can be run repeatedly. If the property has its own value index and the query has no
+    /// where clauses, the index is used to scan only the matching range.
+    pub fn add_newer_than(&mut self, property: &Property, duration: Duration) -> Result<()> {
+        self.conditions
+            .push(Filter::newer_than(property, duration)?);
+        self.add_time_where_clause(property, RelativeTime::newer_than(duration));
+        Ok(())
+    }
+
+    /// Requires the Long timestamp property to be more than `duration` in the past. Null
+    /// timestamps never match.
+    pub fn add_older_than(&mut self, property: &Property, duration: Duration) -> Result<()> {
+        self.conditions
+            .push(Filter::older_than(property, duration)?);
+        self.add_time_where_clause(property, RelativeTime::older_than(duration));
+        Ok(())
+    }
+
+    fn add_time_where_clause(&mut self, property: &Property, time: RelativeTime) {
+        if self.time_where_clause.is_some() || self.collection.indexes_outdated() {
+            return;
+        }
+        let index = self.collection.indexes.iter().find(|index| {
+            index.properties.len() == 1
+                && index.properties[0].property == *property
+                && index.properties[0].index_type == IndexType::Value
+        });
+        if let Some(index) = index {
+            let wc = TimeWhereClause::new(self.collection.db, index.clone(), time);
+            self.time_where_clause = Some(wc);
+        }
+    }
+
+    /// Requires the id to be between `start` and `end` in addition to the filter. If the query
+    /// has no where clauses, only this id range of the collection is scanned. Index where clauses
+    /// are combined with the range and whichever side has fewer entries is scanned. Multiple
+    /// ranges are intersected.
+    pub fn add_id_between(&mut self, start: i64, end: i64, inclusive: bool) {
+        let (mut lower, mut upper) = (start.min(end), start.max(end));
+        if !inclusive {
+            match (lower.checked_add(1), upper.checked_sub(1)) {
+                (Some(l), Some(u)) => (lower, upper) = (l, u),
+                _ => (lower, upper) = (0, -1),
+            }
+        }
+        self.conditions.push(Filter::id(lower, upper));
+        self.id_range = match self.id_range {
+            Some((l, u)) => Some((l.max(lower), u.min(upper))),
+            None => Some((lower, upper)),
+        };
+    }
+
+    /// Requires the string property to end with `suffix` in addition to the filter. Indexes
+    /// cannot be used for suffixes so the condition is always evaluated on every scanned object.
+    pub fn add_string_ends_with(
+        &mut self,
+        property: &Property,
+        suffix: &str,
+        case_sensitive: bool,
+    ) -> Result<()> {
+        let condition = Filter::string_ends_with(property, suffix, case_sensitive)?;
+        self.conditions.push(condition);
+        Ok(())
+    }
+
     pub fn add_sort(&mut self, property: &Property, sort: Sort) -> Result<()> {
+        self.add_sort_with_nulls(property, sort, None)
+    }
+
+    /// Like [QueryBuilder::add_sort] but places null values according to `nulls`.
+    pub fn add_sort_with_nulls(
+        &mut self,
+        property: &Property,
+        sort: Sort,
+        nulls: Option<Nulls>,
+    ) -> Result<()> {
         if property.data_type.is_scalar() {
-            self.sort.push((property.clone(), sort));
+            self.sort.push((property.clone(), sort, nulls));
             Ok(())
         } else {
             illegal_arg("Only scalar types may be used for sorting.")
@@ -123,11 +351,163 @@ impl<'a> QueryBuilder<'a> {
         self.limit = limit;
     }
 
-    pub fn build(mut self) -> Query {
+    /// Returns the results in the opposite order by iterating the where clauses backwards and
+    /// flipping the sort directions. Nothing is sorted in memory unless the query has a sort.
+    /// Offset and limit apply to the reversed results. Link where clauses keep their order.
+    pub fn set_reverse(&mut self, reverse: bool) {
+        self.reverse = reverse;
+    }
+
+    /// The query fails with [IsarError::Cancelled] while the token is set, see [Query::cancel].
+    pub fn set_cancel_token(&mut self, cancel_token: Arc<AtomicBool>) {
+        self.cancel_token = Some(cancel_token);
+    }
+
+    /// Hints the OS to read ahead while scanning. Useful for large scans of big collections that
+    /// are not in the page cache yet. Ignored on platforms without `madvise`.
+    pub fn set_prefetch(&mut self, prefetch: bool) {
+        self.prefetch = prefetch;
+    }
+
+    /// Limits how many objects the where clauses may return before the filter is applied. Queries
+    /// exceeding the limit fail with `IsarError::ScanLimitExceeded`.
+    pub fn set_max_scan(&mut self, max_scan: Option<u32>) {
+        self.max_scan = max_scan;
+    }
+
+    /// Keeps up to `size` aggregation results of the query until the next write. `0` disables
+    /// the cache.
+    pub fn set_aggregate_cache_size(&mut self, size: usize) {
+        self.aggregate_cache_size = size;
+    }
+
+    /// Forces the query to scan the index with the given name. Where clauses on other indexes,
+    /// ids or links make [QueryBuilder::build] fail. The hint is checked when the query is
+    /// built.
+    pub fn use_index(&mut self, index_name: &str) {
+        self.index_hint = Some(IndexHint::Index(index_name.to_string()));
+    }
+
+    /// Forces a full scan of the collection instead of using an index. Mostly useful to compare
+    /// results and performance while debugging.
+    pub fn no_index(&mut self) {
+        self.index_hint = Some(IndexHint::NoIndex);
+    }
+
+    fn apply_index_hint(&mut self) -> Result<()> {
+        match &self.index_hint {
+            Some(IndexHint::Index(name)) => {
+                let index = self
+                    .collection
+                    .indexes
+                    .iter()
+                    .find(|index| index.name == *name)
+                    .ok_or(IsarError::UnknownIndex {})?;
+                if self.collection.indexes_outdated() {
+                    return Err(IsarError::IndexesOutdated {});
+                }
+                if let Some(where_clauses) = &self.where_clauses {
+                    let uses_index = where_clauses.iter().all(|wc| match wc {
+                        WhereClause::Index(wc) => wc.uses_index(index),
+                        _ => false,
+                    });
+                    if !uses_index {
+                        illegal_arg(&format!(
+                            "The where clauses of the query cannot use the index '{}'.",
+                            name
+                        ))?;
+                    }
+                } else {
+                    let time_wc = self.time_where_clause.take();
+                    let wc = match (self.prefix_where_clause.take(), time_wc) {
+                        (Some(wc), _) if wc.uses_index(index) => WhereClause::Index(wc),
+                        (_, Some(wc)) if wc.uses_index(index) => WhereClause::Time(wc),
+                        _ => {
+                            if index.sparse_null_key().is_some() || index.multi_entry {
+                                illegal_arg(&format!(
+                                    "The index '{}' does not contain every object and needs a \
+                                    where clause.",
+                                    name
+                                ))?;
+                            }
+                            let (lower, upper) = index.full_range();
+                            WhereClause::Index(IndexWhereClause::new(
+                                self.collection.db,
+                                index.clone(),
+                                lower,
+                                upper,
+                                false,
+                                Sort::Ascending,
+                            )?)
+                        }
+                    };
+                    self.where_clauses = Some(vec![wc]);
+                }
+            }
+            Some(IndexHint::NoIndex) => {
+                if let Some(where_clauses) = &self.where_clauses {
+                    if where_clauses
+                        .iter()
+                        .any(|wc| matches!(wc, WhereClause::Index(_)))
+                    {
+                        illegal_arg("The query has index where clauses but no index may be used.")?;
+                    }
+                }
+                self.prefix_where_clause = None;
+                self.time_where_clause = None;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    pub fn build(mut self) -> Result<Query> {
+        self.apply_index_hint()?;
+        if let (Some(where_clauses), Some((lower, upper)), None) =
+            (self.where_clauses.as_mut(), self.id_range, &self.index_hint)
+        {
+            for where_clause in where_clauses.iter_mut() {
+                if let WhereClause::Index(wc) = where_clause {
+                    let id_wc =
+                        IdWhereClause::new(self.collection.db, lower, upper, Sort::Ascending);
+                    *where_clause =
+                        WhereClause::IdIndex(IdIndexWhereClause::new(id_wc, wc.clone()));
+                }
+            }
+        }
         if self.where_clauses.is_none() {
-            self.add_id_where_clause(i64::MIN, i64::MAX).unwrap();
+            if let Some((lower, upper)) = self.id_range {
+                self.init_where_clauses();
+                if lower <= upper {
+                    self.add_id_where_clause(lower, upper).unwrap();
+                }
+            } else if let Some(wc) = self.prefix_where_clause {
+                self.where_clauses = Some(vec![WhereClause::Index(wc)]);
+            } else if let Some(wc) = self.time_where_clause {
+                self.where_clauses = Some(vec![WhereClause::Time(wc)]);
+            } else {
+                self.add_id_where_clause(i64::MIN, i64::MAX).unwrap();
+            }
+        }
+        if self.reverse {
+            let where_clauses = self.where_clauses.as_mut().unwrap();
+            where_clauses.reverse();
+            for where_clause in where_clauses {
+                where_clause.reverse();
+            }
+            for (_, sort, nulls) in &mut self.sort {
+                *sort = sort.reversed();
+                *nulls = nulls.map(Nulls::reversed);
+            }
+        }
+        if !self.conditions.is_empty() {
+            let mut filters = self.conditions;
+            if let Some(filter) = self.filter {
+                filters.insert(0, filter);
+            }
+            self.filter = Some(Filter::and(filters));
         }
-        Query::new(
+        let query = Query::new(
             self.collection.instance_id,
             self.where_clauses.unwrap(),
             self.filter,
@@ -135,6 +515,11 @@ impl<'a> QueryBuilder<'a> {
             self.distinct,
             self.offset,
             self.limit,
-        )
+            self.cancel_token,
+            self.prefetch,
+            self.max_scan,
+            self.aggregate_cache_size,
+        );
+        Ok(query)
     }
 }
@@ -0,0 +1,194 @@
+use crate::error::{IsarError, Result};
+use crate::object::isar_object::IsarObject;
+use crate::query::scan::Scan;
+use crate::query::{Query, SlowQueryLog};
+use crate::txn::TxnCursors;
+use intmap::IntMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::vec;
+
+/// The results of [Query::find_iter]. Unsorted queries read the next object from the cursors
+/// when it is requested, sorted queries read all objects on the first call. After an error the
+/// iterator ends.
+pub struct QueryIter<'txn, 'env> {
+    query: Query,
+    // dropped before the cursors are returned to the transaction
+    scan: Option<Scan<'txn>>,
+    cursors: TxnCursors<'txn, 'env>,
+    next_where_clause: usize,
+    sorted: Option<vec::IntoIter<(i64, IsarObject<'txn>)>>,
+    result_ids: Option<IntMap<()>>,
+    distinct_hashes: Option<IntMap<()>>,
+    scanned: usize,
+    count: usize,
+    done: bool,
+    slow_query_log: Option<Arc<SlowQueryLog>>,
+    elapsed: Duration,
+}
+
+impl<'txn, 'env> QueryIter<'txn, 'env> {
+    pub(crate) fn new(
+        query: &Query,
+        cursors: TxnCursors<'txn, 'env>,
+        slow_query_log: Option<Arc<SlowQueryLog>>,
+    ) -> Self {
+        cursors.set_prefetch(query.prefetch);
+        QueryIter {
+            query: query.clone(),
+            scan: None,
+            cursors,
+            next_where_clause: 0,
+            sorted: None,
+            result_ids: query.where_clauses_dup.then(IntMap::new),
+            distinct_hashes: (!query.distinct.is_empty()).then(IntMap::new),
+            scanned: 0,
+            count: 0,
+            done: false,
+            slow_query_log,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// The number of objects left to scan, an upper bound of the remaining results that can be
+    /// used to report the progress of long scans. The where clauses that have not been started
+    /// yet are counted, or estimated if possible and `exact` is not set. `None` if a link where
+    /// clause or an index where clause with several ranges is being scanned.
+    pub fn remaining(&self, exact: bool) -> Result<Option<u64>> {
+        if let Some(sorted) = &self.sorted {
+            return Ok(Some(sorted.len() as u64));
+        }
+        if self.done {
+            return Ok(Some(0));
+        }
+        let mut remaining = match &self.scan {
+            Some(scan) => match scan.remaining(exact)? {
+                Some(remaining) => remaining,
+                None => return Ok(None),
+            },
+            None => 0,
+        };
+        for where_clause in &self.query.where_clauses[self.next_where_clause..] {
+            let estimate = if exact {
+                None
+            } else {
+                where_clause.estimate_count(&self.cursors)?
+            };
+            if let Some(estimate) = estimate {
+                remaining += estimate;
+            } else {
+                where_clause.iter_ids(&self.cursors, None, |_| {
+                    remaining += 1;
+                    Ok(true)
+                })?;
+            }
+        }
+        Ok(Some(remaining))
+    }
+
+    fn next_result(&mut self) -> Result<Option<(i64, IsarObject<'txn>)>> {
+        if !self.query.sort.is_empty() {
+            if self.sorted.is_none() {
+                let results = self.query.execute_sorted(&self.cursors, None)?;
+                let results = self.query.add_offset_limit_sorted(results);
+                self.sorted = Some(results.into_iter().collect::<Vec<_>>().into_iter());
+            }
+            return Ok(self.sorted.as_mut().unwrap().next());
+        }
+
+        let offset = self.query.offset;
+        let max_count = self.query.limit.saturating_add(offset);
+        while self.count < max_count {
+            let (id, object) = match self.next_scanned()? {
+                Some(entry) => entry,
+                None => return Ok(None),
+            };
+            if let Some(filter) = &self.query.filter {
+                if !filter.evaluate(id, object, Some(&self.cursors))? {
+                    continue;
+                }
+            }
+            if let Some(hashes) = &mut self.distinct_hashes {
+                let hash = Query::hash_properties(object, &self.query.distinct);
+                if !hashes.insert_checked(hash, ()) {
+                    continue;
+                }
+            }
+            self.count += 1;
+            if self.count > offset {
+                return Ok(Some((id, object)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_scanned(&mut self) -> Result<Option<(i64, IsarObject<'txn>)>> {
+        loop {
+            if let Some(scan) = &mut self.scan {
+                if let Some((id, object)) = scan.next()? {
+                    if let Some(result_ids) = &mut self.result_ids {
+                        if !result_ids.insert_checked(id as u64, ()) {
+                            continue;
+                        }
+                    }
+                    self.scanned += 1;
+                    if self.scanned.is_multiple_of(Query::CANCEL_CHECK_INTERVAL)
+                        && self.query.is_cancelled()
+                    {
+                        return Err(IsarError::Cancelled {});
+                    }
+                    if let Some(limit) = self.query.max_scan {
+                        if self.scanned > limit as usize {
+                            return Err(IsarError::ScanLimitExceeded { limit });
+                        }
+                    }
+                    return Ok(Some((id, object)));
+                }
+            }
+            if let Some(where_clause) = self.query.where_clauses.get(self.next_where_clause) {
+                self.scan = Some(where_clause.scan(&self.cursors)?);
+                self.next_where_clause += 1;
+            } else {
+                self.scan = None;
+                return Ok(None);
+            }
+        }
+    }
+}
+
+impl<'txn, 'env> Iterator for QueryIter<'txn, 'env> {
+    type Item = Result<(i64, IsarObject<'txn>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let start = Instant::now();
+        let result = self.next_result();
+        self.elapsed += start.elapsed();
+        match result {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.done = true;
+                self.scan = None;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                self.scan = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl Drop for QueryIter<'_, '_> {
+    fn drop(&mut self) {
+        // only the time spent reading the results counts, not the time the caller spent
+        if let Some(log) = &self.slow_query_log {
+            if self.elapsed >= log.threshold {
+                (log.callback)(&self.query.describe(), self.elapsed);
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Uniformly samples up to `size` ids from a stream of unknown length (reservoir sampling).
+pub(crate) struct Reservoir {
+    size: usize,
+    seen: u64,
+    state: u64,
+    ids: Vec<i64>,
+}
+
+impl Reservoir {
+    pub fn new(size: usize, seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64)
+        });
+        Reservoir {
+            size,
+            seen: 0,
+            state: seed,
+            ids: Vec::with_capacity(size.min(1024)),
+        }
+    }
+
+    // splitmix64
+    fn next_random(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn add(&mut self, id: i64) {
+        self.seen += 1;
+        if self.ids.len() < self.size {
+            self.ids.push(id);
+        } else if self.size > 0 {
+            let index = self.next_random() % self.seen;
+            if index < self.size as u64 {
+                self.ids[index as usize] = id;
+            }
+        }
+    }
+
+    pub fn finish(self) -> Vec<i64> {
+        self.ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(n: usize, count: i64, seed: u64) -> Vec<i64> {
+        let mut reservoir = Reservoir::new(n, Some(seed));
+        for id in 0..count {
+            reservoir.add(id);
+        }
+        reservoir.finish()
+    }
+
+    #[test]
+    fn test_reservoir() {
+        assert_eq!(sample(10, 5, 1), vec![0, 1, 2, 3, 4]);
+        assert!(sample(0, 5, 1).is_empty());
+
+        let ids = sample(10, 1000, 42);
+        assert_eq!(ids.len(), 10);
+        assert_eq!(ids, sample(10, 1000, 42));
+        assert_ne!(ids, sample(10, 1000, 43));
+        let mut unique = ids.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(unique.len(), 10);
+        assert!(ids.iter().all(|id| (0..1000).contains(id)));
+    }
+}
@@ -0,0 +1,252 @@
+use crate::cursor::IsarCursors;
+use crate::error::{IsarError, Result};
+use crate::index::index_key::IndexKey;
+use crate::mdbx::cursor::Cursor;
+use crate::mdbx::db::Db;
+use crate::mdbx::Key;
+use crate::object::id::BytesToId;
+use crate::object::isar_object::IsarObject;
+use crate::query::where_clause::WhereClause;
+use std::collections::VecDeque;
+use std::vec;
+
+/// A key range of an index scan. Entries with the `exclude` key are skipped.
+pub(crate) struct IndexRange {
+    pub lower: IndexKey,
+    pub upper: IndexKey,
+    pub exclude: Option<Vec<u8>>,
+}
+
+enum Source<'txn> {
+    /// The objects between two ids read from the data cursor.
+    Ids {
+        lower: i64,
+        upper: i64,
+    },
+    IdList(vec::IntoIter<i64>),
+    /// The ids of the index ranges, scanned in order.
+    Index {
+        cursor: Cursor<'txn>,
+        ranges: VecDeque<IndexRange>,
+        duplicates: bool,
+        skip_duplicates: bool,
+    },
+    /// The targets of the links of one object.
+    Link {
+        cursor: Cursor<'txn>,
+        id: i64,
+    },
+}
+
+/// Reads the objects of a where clause one at a time. Unlike [WhereClause::iter] a scan can be
+/// suspended between two objects.
+pub(crate) struct Scan<'txn> {
+    source: Source<'txn>,
+    data: Cursor<'txn>,
+    ascending: bool,
+    // whether the cursor of the source is positioned in the current range
+    started: bool,
+    check: Option<WhereClause>,
+}
+
+impl<'txn> Scan<'txn> {
+    fn new(
+        cursors: &IsarCursors<'txn, '_>,
+        data_db: Db,
+        source: Source<'txn>,
+        ascending: bool,
+    ) -> Result<Self> {
+        Ok(Scan {
+            source,
+            data: cursors.bind_cursor(data_db)?,
+            ascending,
+            started: false,
+            check: None,
+        })
+    }
+
+    pub fn ids(
+        cursors: &IsarCursors<'txn, '_>,
+        db: Db,
+        lower: i64,
+        upper: i64,
+        ascending: bool,
+    ) -> Result<Self> {
+        Self::new(cursors, db, Source::Ids { lower, upper }, ascending)
+    }
+
+    /// Looks up `ids` in the given order. Missing objects are skipped.
+    pub fn id_list(cursors: &IsarCursors<'txn, '_>, db: Db, ids: Vec<i64>) -> Result<Self> {
+        Self::new(cursors, db, Source::IdList(ids.into_iter()), true)
+    }
+
+    pub fn index(
+        cursors: &IsarCursors<'txn, '_>,
+        data_db: Db,
+        index_db: Db,
+        duplicates: bool,
+        ranges: Vec<IndexRange>,
+        skip_duplicates: bool,
+        ascending: bool,
+    ) -> Result<Self> {
+        let source = Source::Index {
+            cursor: cursors.bind_cursor(index_db)?,
+            ranges: ranges.into(),
+            duplicates,
+            skip_duplicates,
+        };
+        Self::new(cursors, data_db, source, ascending)
+    }
+
+    pub fn link(
+        cursors: &IsarCursors<'txn, '_>,
+        link_db: Db,
+        target_db: Db,
+        id: i64,
+    ) -> Result<Self> {
+        let source = Source::Link {
+            cursor: cursors.bind_cursor(link_db)?,
+            id,
+        };
+        Self::new(cursors, target_db, source, true)
+    }
+
+    /// Skips the objects that do not match `where_clause`.
+    pub fn checked(mut self, where_clause: WhereClause) -> Self {
+        self.check = Some(where_clause);
+        self
+    }
+
+    pub fn next(&mut self) -> Result<Option<(i64, IsarObject<'txn>)>> {
+        while let Some((id, object)) = self.next_unchecked()? {
+            if self
+                .check
+                .as_ref()
+                .is_none_or(|wc| wc.maybe_matches(id, object))
+            {
+                return Ok(Some((id, object)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Counts the entries left in the scan. Index scans count the entries of the current range,
+    /// `exact` counts them one by one instead of estimating from the b-tree. `None` for link scans
+    /// and index scans with more than one range left.
+    pub fn remaining(&self, exact: bool) -> Result<Option<u64>> {
+        fn count<K: Key>(cursor: &Cursor, end: &K, ascending: bool, exact: bool) -> Result<u64> {
+            if exact {
+                cursor.count_remaining(end, ascending)
+            } else {
+                cursor.estimate_remaining(end, ascending)
+            }
+        }
+
+        let ascending = self.ascending;
+        match &self.source {
+            Source::Ids { lower, upper } if self.started => {
+                let end = if ascending { upper } else { lower };
+                Ok(Some(count(&self.data, end, ascending, exact)?))
+            }
+            Source::IdList(ids) => Ok(Some(ids.len() as u64)),
+            Source::Index { cursor, ranges, .. } if self.started && ranges.len() == 1 => {
+                let end = if ascending {
+                    &ranges[0].upper
+                } else {
+                    &ranges[0].lower
+                };
+                Ok(Some(count(cursor, end, ascending, exact)?))
+            }
+            Source::Index { ranges, .. } if ranges.is_empty() => Ok(Some(0)),
+            _ => Ok(None),
+        }
+    }
+
+    fn next_unchecked(&mut self) -> Result<Option<(i64, IsarObject<'txn>)>> {
+        let ascending = self.ascending;
+        match &mut self.source {
+            Source::Ids { lower, upper } => {
+                let entry = if self.started {
+                    self.data
+                        .move_to_next_between(lower, upper, false, ascending)?
+                } else {
+                    self.started = true;
+                    self.data
+                        .move_to_first_between(lower, upper, false, ascending)?
+                };
+                Ok(entry
+                    .map(|(id_bytes, object)| (id_bytes.to_id(), IsarObject::from_bytes(object))))
+            }
+            Source::IdList(ids) => {
+                for id in ids {
+                    if let Some((_, object)) = self.data.move_to(&id)? {
+                        return Ok(Some((id, IsarObject::from_bytes(object))));
+                    }
+                }
+                Ok(None)
+            }
+            Source::Index {
+                cursor,
+                ranges,
+                duplicates,
+                skip_duplicates,
+            } => {
+                while let Some(range) = ranges.front() {
+                    let entry = if self.started {
+                        cursor.move_to_next_between(
+                            &range.lower,
+                            &range.upper,
+                            *skip_duplicates,
+                            ascending,
+                        )?
+                    } else {
+                        self.started = true;
+                        cursor.move_to_first_between(
+                            &range.lower,
+                            &range.upper,
+                            *duplicates,
+                            ascending,
+                        )?
+                    };
+                    match entry {
+                        Some((key, _)) if range.exclude.as_deref() == Some(key) => {}
+                        Some((_, id_bytes)) => {
+                            let id = id_bytes.to_id();
+                            let (_, object) =
+                                self.data.move_to(&id)?.ok_or(IsarError::DbCorrupted {
+                                    message: "Could not find object specified in index."
+                                        .to_string(),
+                                })?;
+                            return Ok(Some((id, IsarObject::from_bytes(object))));
+                        }
+                        None => {
+                            ranges.pop_front();
+                            self.started = false;
+                        }
+                    }
+                }
+                Ok(None)
+            }
+            Source::Link { cursor, id } => {
+                let entry = if self.started {
+                    cursor.move_to_next_dup()?
+                } else {
+                    self.started = true;
+                    cursor.move_to(id)?
+                };
+                if let Some((_, target_bytes)) = entry {
+                    let target_id = target_bytes.to_id();
+                    let (id_bytes, object) =
+                        self.data
+                            .move_to(&target_id)?
+                            .ok_or(IsarError::DbCorrupted {
+                                message: "Target object does not exist".to_string(),
+                            })?;
+                    Ok(Some((id_bytes.to_id(), IsarObject::from_bytes(object))))
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// How one where clause of a query was executed.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WhereClauseStats {
+    /// The plan of the where clause as shown by [crate::query::Query::explain].
+    pub plan: String,
+    /// The index scanned between two keys. `None` for id, id set and link where clauses.
+    pub index: Option<String>,
+    /// The number of entries estimated from the database statistics, `None` if the where clause
+    /// cannot be estimated.
+    pub estimated: Option<u64>,
+    /// The objects read from the where clause including the ones rejected by the filter.
+    pub scanned: u64,
+}
+
+/// Execution statistics returned by [crate::query::Query::profile].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct QueryStats {
+    pub where_clauses: Vec<WhereClauseStats>,
+    /// Whether the ids of the where clauses are deduplicated in memory.
+    pub dedup: bool,
+    pub sort_in_memory: bool,
+    pub distinct_in_memory: bool,
+    /// The objects that passed the filter.
+    pub matched: u64,
+    /// The objects left after distinct, offset and limit.
+    pub returned: u64,
+    /// Time spent evaluating the filter.
+    pub filter_time: Duration,
+    /// Time spent reading objects from the where clauses, excluding the filter.
+    pub read_time: Duration,
+    pub total_time: Duration,
+}
+
+impl QueryStats {
+    pub fn uses_index(&self) -> bool {
+        self.where_clauses.iter().any(|wc| wc.index.is_some())
+    }
+
+    pub fn scanned(&self) -> u64 {
+        self.where_clauses.iter().map(|wc| wc.scanned).sum()
+    }
+
+    /// The statistics as JSON with durations in microseconds.
+    pub fn to_json(&self) -> Value {
+        let where_clauses = self
+            .where_clauses
+            .iter()
+            .map(|wc| {
+                json!({
+                    "plan": wc.plan,
+                    "index": wc.index,
+                    "estimated": wc.estimated,
+                    "scanned": wc.scanned,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "whereClauses": where_clauses,
+            "dedup": self.dedup,
+            "sortInMemory": self.sort_in_memory,
+            "distinctInMemory": self.distinct_in_memory,
+            "scanned": self.scanned(),
+            "matched": self.matched,
+            "returned": self.returned,
+            "filterTimeUs": self.filter_time.as_micros() as u64,
+            "readTimeUs": self.read_time.as_micros() as u64,
+            "totalTimeUs": self.total_time.as_micros() as u64,
+        })
+    }
+}
+
+/// Collected by the where clause loop of a profiled query.
+#[derive(Default)]
+pub(crate) struct ScanProfile {
+    pub scanned: Vec<u64>,
+    pub matched: u64,
+    pub filter_time: Duration,
+    /// The time spent in the where clauses and the filter, without the callback.
+    pub scan_time: Duration,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let stats = QueryStats {
+            where_clauses: vec![
+                WhereClauseStats {
+                    plan: "index[name]".to_string(),
+                    index: Some("name".to_string()),
+                    estimated: Some(10),
+                    scanned: 8,
+                },
+                WhereClauseStats {
+                    plan: "ids[3]".to_string(),
+                    index: None,
+                    estimated: None,
+                    scanned: 3,
+                },
+            ],
+            matched: 5,
+            returned: 2,
+            filter_time: Duration::from_micros(1500),
+            ..Default::default()
+        };
+        assert!(stats.uses_index());
+        assert_eq!(stats.scanned(), 11);
+
+        let json = stats.to_json();
+        assert_eq!(json["scanned"], 11);
+        assert_eq!(json["filterTimeUs"], 1500);
+        assert_eq!(json["whereClauses"][0]["index"], "name");
+        assert!(json["whereClauses"][1]["estimated"].is_null());
+        assert_eq!(json["sortInMemory"], false);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::cursor::IsarCursors;
+use crate::error::Result;
+use crate::index::index_key::IndexKey;
+use crate::index::IsarIndex;
+use crate::mdbx::db::Db;
+use crate::object::isar_object::IsarObject;
+use crate::query::index_where_clause::IndexWhereClause;
+use crate::query::scan::Scan;
+use crate::query::Sort;
+use intmap::IntMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Microseconds since the Unix epoch, the encoding of timestamps in Long properties.
+pub(crate) fn now_micros() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_micros() as i64)
+}
+
+/// A range of timestamps relative to the time a query is executed.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum RelativeTime {
+    /// Timestamps of at most the given number of microseconds ago.
+    NewerThan(i64),
+    /// Timestamps of more than the given number of microseconds ago.
+    OlderThan(i64),
+}
+
+impl RelativeTime {
+    pub fn newer_than(duration: Duration) -> Self {
+        RelativeTime::NewerThan(Self::micros(duration))
+    }
+
+    pub fn older_than(duration: Duration) -> Self {
+        RelativeTime::OlderThan(Self::micros(duration))
+    }
+
+    fn micros(duration: Duration) -> i64 {
+        duration.as_micros().min(i64::MAX as u128) as i64
+    }
+
+    /// The inclusive bounds of the range at `now`. Null timestamps are never included.
+    pub fn bounds(&self, now: i64) -> (i64, i64) {
+        match *self {
+            RelativeTime::NewerThan(micros) => (now.saturating_sub(micros), i64::MAX),
+            RelativeTime::OlderThan(micros) => (
+                IsarObject::NULL_LONG + 1,
+                now.saturating_sub(micros).saturating_sub(1),
+            ),
+        }
+    }
+}
+
+/// Scans a single property Long index for a timestamp range that is resolved when the query is
+/// executed.
+#[derive(Clone)]
+pub(crate) struct TimeWhereClause {
+    db: Db,
+    index: IsarIndex,
+    time: RelativeTime,
+    sort: Sort,
+}
+
+impl TimeWhereClause {
+    pub fn new(db: Db, index: IsarIndex, time: RelativeTime) -> Self {
+        TimeWhereClause {
+            db,
+            index,
+            time,
+            sort: Sort::Ascending,
+        }
+    }
+
+    pub(crate) fn uses_index(&self, index: &IsarIndex) -> bool {
+        self.index == *index
+    }
+
+    pub(crate) fn index_name(&self) -> &str {
+        &self.index.name
+    }
+
+    fn resolve(&self, now: i64) -> Result<IndexWhereClause> {
+        let (lower, upper) = self.time.bounds(now);
+        let mut lower_key = IndexKey::new();
+        lower_key.add_long(lower);
+        let mut upper_key = IndexKey::new();
+        upper_key.add_long(upper);
+        IndexWhereClause::new(
+            self.db,
+            self.index.clone(),
+            lower_key,
+            upper_key,
+            false,
+            self.sort,
+        )
+    }
+
+    pub fn object_matches(&self, object: IsarObject) -> bool {
+        self.resolve(now_micros())
+            .map_or(true, |wc| wc.object_matches(object))
+    }
+
+    pub(crate) fn reverse(&mut self) {
+        self.sort = self.sort.reversed();
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!("index[{}]", self.index.name)
+    }
+
+    pub fn estimate_count(&self, cursors: &IsarCursors) -> Result<u64> {
+        self.resolve(cursors.now_micros())?.estimate_count(cursors)
+    }
+
+    pub fn iter_ids<'txn, 'env, F>(
+        &self,
+        cursors: &IsarCursors<'txn, 'env>,
+        callback: F,
+    ) -> Result<bool>
+    where
+        F: FnMut(i64) -> Result<bool>,
+    {
+        self.resolve(cursors.now_micros())?
+            .iter_ids(cursors, callback)
+    }
+
+    pub fn iter<'txn, 'env, F>(
+        &self,
+        cursors: &IsarCursors<'txn, 'env>,
+        result_ids: Option<&mut IntMap<()>>,
+        callback: F,
+    ) -> Result<bool>
+    where
+        F: FnMut(i64, IsarObject<'txn>) -> Result<bool>,
+    {
+        self.resolve(cursors.now_micros())?
+            .iter(cursors, result_ids, callback)
+    }
+
+    pub(crate) fn scan<'txn>(&self, cursors: &IsarCursors<'txn, '_>) -> Result<Scan<'txn>> {
+        self.resolve(cursors.now_micros())?.scan(cursors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounds() {
+        let hour = Duration::from_secs(3600);
+        let now = 10 * 3_600_000_000;
+        assert_eq!(
+            RelativeTime::newer_than(hour).bounds(now),
+            (9 * 3_600_000_000, i64::MAX)
+        );
+        assert_eq!(
+            RelativeTime::older_than(hour).bounds(now),
+            (IsarObject::NULL_LONG + 1, 9 * 3_600_000_000 - 1)
+        );
+
+        let forever = RelativeTime::newer_than(Duration::MAX);
+        assert_eq!(forever, RelativeTime::NewerThan(i64::MAX));
+        assert_eq!(forever.bounds(now).0, now - i64::MAX);
+        assert!(now_micros() > 0);
+    }
+}
@@ -1,24 +1,34 @@
 use crate::cursor::IsarCursors;
 use crate::error::Result;
 use crate::object::isar_object::IsarObject;
+use crate::query::id_index_where_clause::IdIndexWhereClause;
+use crate::query::id_set_where_clause::IdSetWhereClause;
 use crate::query::id_where_clause::IdWhereClause;
 use crate::query::index_where_clause::IndexWhereClause;
 use crate::query::link_where_clause::LinkWhereClause;
+use crate::query::scan::Scan;
+use crate::query::time_where_clause::TimeWhereClause;
 use intmap::IntMap;
 
 #[derive(Clone)]
 pub(crate) enum WhereClause {
     Id(IdWhereClause),
+    IdSet(IdSetWhereClause),
     Index(IndexWhereClause),
+    IdIndex(IdIndexWhereClause),
     Link(LinkWhereClause),
+    Time(TimeWhereClause),
 }
 
 impl WhereClause {
     pub fn maybe_matches(&self, id: i64, object: IsarObject) -> bool {
         match self {
             WhereClause::Id(wc) => wc.id_matches(id),
+            WhereClause::IdSet(wc) => wc.id_matches(id),
             WhereClause::Index(wc) => wc.object_matches(object),
+            WhereClause::IdIndex(wc) => wc.object_matches(id, object),
             WhereClause::Link(_) => true,
+            WhereClause::Time(wc) => wc.object_matches(object),
         }
     }
 
@@ -33,8 +43,56 @@ impl WhereClause {
     {
         match self {
             WhereClause::Id(wc) => wc.iter(cursors, result_ids, callback),
+            WhereClause::IdSet(wc) => wc.iter(cursors, result_ids, callback),
             WhereClause::Index(wc) => wc.iter(cursors, result_ids, callback),
+            WhereClause::IdIndex(wc) => wc.iter(cursors, result_ids, callback),
             WhereClause::Link(wc) => wc.iter(cursors, result_ids, callback),
+            WhereClause::Time(wc) => wc.iter(cursors, result_ids, callback),
+        }
+    }
+
+    /// Like `iter` but the objects are read one at a time by the returned scan. Duplicates are
+    /// not removed.
+    pub fn scan<'txn>(&self, cursors: &IsarCursors<'txn, '_>) -> Result<Scan<'txn>> {
+        match self {
+            WhereClause::Id(wc) => wc.scan(cursors),
+            WhereClause::IdSet(wc) => wc.scan(cursors),
+            WhereClause::Index(wc) => wc.scan(cursors),
+            WhereClause::IdIndex(wc) => wc.scan(cursors),
+            WhereClause::Link(wc) => wc.scan(cursors),
+            WhereClause::Time(wc) => wc.scan(cursors),
+        }
+    }
+
+    /// Like `iter` but only yields ids. Index where clauses are answered from the index keys
+    /// without reading the objects.
+    pub fn iter_ids<'txn, 'env, F>(
+        &self,
+        cursors: &IsarCursors<'txn, 'env>,
+        mut result_ids: Option<&mut IntMap<()>>,
+        mut callback: F,
+    ) -> Result<bool>
+    where
+        F: FnMut(i64) -> Result<bool>,
+    {
+        match self {
+            WhereClause::Index(wc) => wc.iter_ids(cursors, |id| {
+                if let Some(result_ids) = result_ids.as_deref_mut() {
+                    if !result_ids.insert_checked(id as u64, ()) {
+                        return Ok(true);
+                    }
+                }
+                callback(id)
+            }),
+            WhereClause::Time(wc) => wc.iter_ids(cursors, |id| {
+                if let Some(result_ids) = result_ids.as_deref_mut() {
+                    if !result_ids.insert_checked(id as u64, ()) {
+                        return Ok(true);
+                    }
+                }
+                callback(id)
+            }),
+            _ => self.iter(cursors, result_ids, |id, _| callback(id)),
         }
     }
 
@@ -46,11 +104,68 @@ impl WhereClause {
         }
     }
 
+    /// Iterates in the opposite direction. Link where clauses keep their order.
+    pub(crate) fn reverse(&mut self) {
+        match self {
+            WhereClause::Id(wc) => wc.reverse(),
+            WhereClause::IdSet(wc) => wc.reverse(),
+            WhereClause::Index(wc) => wc.reverse(),
+            WhereClause::IdIndex(wc) => wc.reverse(),
+            WhereClause::Link(_) => {}
+            WhereClause::Time(wc) => wc.reverse(),
+        }
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        match self {
+            WhereClause::Id(wc) => wc.describe(),
+            WhereClause::IdSet(wc) => wc.describe(),
+            WhereClause::Index(wc) => wc.describe(),
+            WhereClause::IdIndex(wc) => wc.describe(),
+            WhereClause::Link(wc) => wc.describe(),
+            WhereClause::Time(wc) => wc.describe(),
+        }
+    }
+
+    /// Like `describe` but also tells which side of combined clauses is scanned.
+    pub(crate) fn describe_plan(&self, cursors: &IsarCursors) -> Result<String> {
+        match self {
+            WhereClause::IdIndex(wc) => wc.describe_plan(cursors),
+            _ => Ok(self.describe()),
+        }
+    }
+
+    /// The index scanned by the where clause.
+    pub(crate) fn index_name(&self) -> Option<&str> {
+        match self {
+            WhereClause::Index(wc) => Some(wc.index_name()),
+            WhereClause::IdIndex(wc) => Some(wc.index_name()),
+            WhereClause::Time(wc) => Some(wc.index_name()),
+            WhereClause::Id(_) | WhereClause::IdSet(_) | WhereClause::Link(_) => None,
+        }
+    }
+
+    /// Estimates the number of entries without reading them. `None` for clauses that cannot be
+    /// estimated.
+    pub(crate) fn estimate_count(&self, cursors: &IsarCursors) -> Result<Option<u64>> {
+        let count = match self {
+            WhereClause::Id(wc) => wc.estimate_count(cursors)?,
+            WhereClause::Index(wc) => wc.estimate_count(cursors)?,
+            WhereClause::IdIndex(wc) => wc.estimate_count(cursors)?,
+            WhereClause::Time(wc) => wc.estimate_count(cursors)?,
+            WhereClause::IdSet(_) | WhereClause::Link(_) => return Ok(None),
+        };
+        Ok(Some(count))
+    }
+
     pub(crate) fn has_duplicates(&self) -> bool {
         match self {
             WhereClause::Id(_) => false,
+            WhereClause::IdSet(_) => false,
             WhereClause::Index(wc) => wc.has_duplicates(),
+            WhereClause::IdIndex(wc) => wc.has_duplicates(),
             WhereClause::Link(_) => false,
+            WhereClause::Time(_) => false,
         }
     }
 }
@@ -3,7 +3,9 @@ use crate::object::data_type::DataType;
 use crate::object::property::Property;
 use crate::schema::index_schema::{IndexSchema, IndexType};
 use crate::schema::link_schema::LinkSchema;
-use crate::schema::property_schema::PropertySchema;
+use crate::schema::property_schema::{
+    EnumMapping, NonFinitePolicy, PropertySchema, StringTransform, DEFAULT_MAX_VALUE_BYTES,
+};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
@@ -21,6 +23,18 @@ pub struct CollectionSchema {
     pub(crate) links: Vec<LinkSchema>,
     #[serde(default)]
     pub(crate) version: u8,
+    #[serde(default)]
+    pub(crate) versioned: bool,
+    #[serde(default)]
+    pub(crate) temporary: bool,
+    #[serde(default)]
+    pub(crate) ttl: Option<String>,
+    #[serde(default)]
+    pub(crate) oplog: bool,
+    /// Auto increment ids count down from `i64::MAX` so id ordered scans return the newest
+    /// objects first.
+    #[serde(default, rename = "descendingIds")]
+    pub(crate) descending_ids: bool,
 }
 
 impl PartialEq for CollectionSchema {
@@ -44,6 +58,11 @@ impl CollectionSchema {
             indexes,
             links,
             version: SchemaManager::ISAR_FILE_VERSION,
+            versioned: false,
+            temporary: false,
+            ttl: None,
+            oplog: false,
+            descending_ids: false,
         }
     }
 
@@ -64,6 +83,23 @@ impl CollectionSchema {
             schema_error("Embedded objects must not have Links or Indexes.")?;
         }
 
+        if self.embedded && (self.versioned || self.temporary || self.oplog) {
+            schema_error("Embedded objects cannot be versioned, temporary or logged.")?;
+        }
+
+        if let Some(ttl) = &self.ttl {
+            if self.embedded {
+                schema_error("Embedded objects cannot have a TTL.")?;
+            }
+            let ttl_property = self
+                .properties
+                .iter()
+                .find(|p| p.name.as_ref() == Some(ttl));
+            if ttl_property.map(|p| p.data_type) != Some(DataType::Long) {
+                schema_error("The TTL property must be an existing Long property.")?;
+            }
+        }
+
         let verify_target_col_exists = |col: &str, embedded: bool| -> Result<()> {
             if !collections
                 .iter()
@@ -91,6 +127,47 @@ impl CollectionSchema {
                     schema_error("Target collection can only be set for object properties.")?;
                 }
             }
+
+            if property.non_finite != NonFinitePolicy::Allow
+                && property.data_type != DataType::Float
+                && property.data_type != DataType::Double
+            {
+                schema_error("Only Float and Double properties may have a non-finite policy.")?;
+            }
+
+            if !property.transforms.is_empty()
+                && property.data_type != DataType::String
+                && property.data_type != DataType::StringList
+            {
+                schema_error("Only String and StringList properties may have transforms.")?;
+            }
+
+            if property.max_value_bytes.is_some() && property.data_type.is_static() {
+                schema_error("Only dynamic properties may have a maximum value size.")?;
+            }
+
+            if let Some(dimension) = property.dimension {
+                if property.data_type != DataType::FloatList {
+                    schema_error("Only FloatList properties may have a dimension.")?;
+                }
+                if dimension == 0 {
+                    schema_error("The dimension of a vector must be greater than zero.")?;
+                }
+            }
+
+            if let Some(names) = &property.enum_values {
+                let max_values = match property.data_type {
+                    DataType::Byte => u8::MAX as usize + 1,
+                    DataType::Int | DataType::Long => i32::MAX as usize,
+                    _ => schema_error("Only Byte, Int and Long properties may be enums.")?,
+                };
+                if names.is_empty() || names.len() > max_values {
+                    schema_error("Invalid number of enum values.")?;
+                }
+                if names.iter().unique().count() != names.len() {
+                    schema_error("Duplicate enum value")?;
+                }
+            }
         }
 
         for link in &self.links {
@@ -127,6 +204,18 @@ impl CollectionSchema {
                 schema_error("Only unique indexes can replace")?;
             }
 
+            if index.comparator.is_some() {
+                let is_string_value = index.properties.len() == 1
+                    && index.properties[0].index_type == IndexType::Value
+                    && self.properties.iter().any(|p| {
+                        p.name.as_ref() == Some(&index.properties[0].name)
+                            && p.data_type == DataType::String
+                    });
+                if !is_string_value {
+                    schema_error("Comparators are only supported for single String value indexes")?;
+                }
+            }
+
             for (i, index_property) in index.properties.iter().enumerate() {
                 let property = self
                     .properties
@@ -157,10 +246,25 @@ impl CollectionSchema {
                     }
                 }
 
-                if property.data_type.get_element_type().is_some() {
+                if index_property.index_type == IndexType::Words {
+                    if property.data_type != DataType::String {
+                        schema_error("Only String indexes may be words indexes.")?;
+                    } else if index.properties.len() > 1 || index.unique || index.sparse {
+                        schema_error("Words indexes cannot be composite, unique or sparse.")?;
+                    }
+                } else if index_property.index_type == IndexType::Blob {
+                    if property.data_type != DataType::ByteList {
+                        schema_error("Only ByteList indexes may be blob indexes.")?;
+                    } else if i != index.properties.len() - 1 {
+                        schema_error("Blob indexes must only be at the end of a composite index.")?;
+                    }
+                } else if property.data_type.get_element_type().is_some() {
                     if index.properties.len() > 1 && index_property.index_type != IndexType::Hash {
                         schema_error("Composite list indexes are not supported.")?;
                     }
+                    if index.sparse && index_property.index_type != IndexType::Hash {
+                        schema_error("Multi-entry indexes cannot be sparse.")?;
+                    }
                 } else if property.data_type == DataType::String
                     && i != index.properties.len() - 1
                     && index_property.index_type != IndexType::Hash
@@ -203,7 +307,12 @@ impl CollectionSchema {
             }
         }
         for property in &self.properties {
-            if !properties.contains(property) {
+            if let Some(existing) = properties.iter_mut().find(|p| *p == property) {
+                existing.non_finite = property.non_finite;
+                existing.transforms = property.transforms.clone();
+                existing.max_value_bytes = property.max_value_bytes;
+                existing.dimension = property.dimension;
+            } else {
                 properties.push(property.clone())
             }
         }
@@ -228,6 +337,85 @@ impl CollectionSchema {
         properties
     }
 
+    pub(crate) fn get_ttl_property(&self) -> Option<Property> {
+        let ttl = self.ttl.as_ref()?;
+        self.get_properties().into_iter().find(|p| &p.name == ttl)
+    }
+
+    pub(crate) fn get_non_finite_policies(&self) -> Vec<(Property, NonFinitePolicy)> {
+        let properties = self.get_properties();
+        self.properties
+            .iter()
+            .filter(|p| p.non_finite != NonFinitePolicy::Allow)
+            .filter_map(|p| {
+                let property = properties
+                    .iter()
+                    .find(|prop| Some(&prop.name) == p.name.as_ref())?;
+                Some((property.clone(), p.non_finite))
+            })
+            .collect()
+    }
+
+    pub(crate) fn get_string_transforms(&self) -> Vec<(Property, Vec<StringTransform>)> {
+        let properties = self.get_properties();
+        self.properties
+            .iter()
+            .filter(|p| !p.transforms.is_empty())
+            .filter_map(|p| {
+                let property = properties
+                    .iter()
+                    .find(|prop| Some(&prop.name) == p.name.as_ref())?;
+                Some((property.clone(), p.transforms.clone()))
+            })
+            .collect()
+    }
+
+    pub(crate) fn get_value_size_limits(&self) -> Vec<(Property, usize)> {
+        let properties = self.get_properties();
+        self.properties
+            .iter()
+            .filter(|p| p.data_type.is_dynamic())
+            .filter_map(|p| {
+                let property = properties
+                    .iter()
+                    .find(|prop| Some(&prop.name) == p.name.as_ref())?;
+                let max = p.max_value_bytes.unwrap_or(DEFAULT_MAX_VALUE_BYTES);
+                Some((property.clone(), max))
+            })
+            .collect()
+    }
+
+    pub(crate) fn get_vector_dimensions(&self) -> Vec<(Property, usize)> {
+        let properties = self.get_properties();
+        self.properties
+            .iter()
+            .filter_map(|p| {
+                let dimension = p.dimension?;
+                let property = properties
+                    .iter()
+                    .find(|prop| Some(&prop.name) == p.name.as_ref())?;
+                Some((property.clone(), dimension))
+            })
+            .collect()
+    }
+
+    pub(crate) fn get_enum_mappings(&self) -> Vec<EnumMapping> {
+        let properties = self.get_properties();
+        self.properties
+            .iter()
+            .filter_map(|p| {
+                let names = p.enum_values.as_ref()?;
+                let property = properties
+                    .iter()
+                    .find(|prop| Some(&prop.name) == p.name.as_ref())?;
+                Some(EnumMapping {
+                    property: property.clone(),
+                    names: names.clone(),
+                })
+            })
+            .collect()
+    }
+
     pub fn to_json_bytes(&self) -> Result<Vec<u8>> {
         serde_json::to_vec(self).map_err(|_| IsarError::SchemaError {
             message: "Could not serialize schema.".to_string(),
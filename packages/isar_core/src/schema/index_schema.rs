@@ -1,6 +1,9 @@
+use crate::index::key_comparator::KeyComparator;
+use crate::index::tokenizer::Tokenizer;
 use crate::index::{IndexProperty, IsarIndex};
 use crate::mdbx::db::Db;
 use crate::object::property::Property;
+use crate::query::Nulls;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
@@ -9,6 +12,20 @@ pub enum IndexType {
     Value,
     Hash,
     HashElements,
+    /// Indexes a whole byte list as one binary key that supports equality and range queries.
+    Blob,
+    /// Indexes every word of a string separately for full-text queries.
+    Words,
+}
+
+/// What happens to string values that are too long to be stored in an index key.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum KeyOverflow {
+    /// The key stores a prefix of the value and a hash of the whole value.
+    #[default]
+    Hash,
+    /// Writing the object fails with `IsarError::KeyTooLong`.
+    Error,
 }
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
@@ -18,6 +35,8 @@ pub struct IndexPropertySchema {
     pub(crate) index_type: IndexType,
     #[serde(rename = "caseSensitive")]
     pub(crate) case_sensitive: bool,
+    #[serde(default)]
+    pub(crate) tokenizer: Tokenizer,
 }
 
 impl IndexPropertySchema {
@@ -26,8 +45,15 @@ impl IndexPropertySchema {
             name: name.to_string(),
             index_type,
             case_sensitive,
+            tokenizer: Tokenizer::default(),
         }
     }
+
+    /// How the words of a [IndexType::Words] index are split.
+    pub fn with_tokenizer(mut self, tokenizer: Tokenizer) -> IndexPropertySchema {
+        self.tokenizer = tokenizer;
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
@@ -37,6 +63,14 @@ pub struct IndexSchema {
     pub(crate) unique: bool,
     #[serde(default)]
     pub(crate) replace: bool,
+    #[serde(default)]
+    pub(crate) comparator: Option<KeyComparator>,
+    #[serde(default)]
+    pub(crate) sparse: bool,
+    #[serde(default, rename = "keyOverflow")]
+    pub(crate) key_overflow: KeyOverflow,
+    #[serde(default)]
+    pub(crate) nulls: Option<Nulls>,
 }
 
 impl IndexSchema {
@@ -51,18 +85,57 @@ impl IndexSchema {
             properties,
             unique,
             replace,
+            comparator: None,
+            sparse: false,
+            key_overflow: KeyOverflow::Hash,
+            nulls: None,
         }
     }
 
+    pub fn with_comparator(mut self, comparator: KeyComparator) -> IndexSchema {
+        self.comparator = Some(comparator);
+        self
+    }
+
+    /// Objects whose indexed properties are all null are not added to a sparse index.
+    pub fn with_sparse(mut self, sparse: bool) -> IndexSchema {
+        self.sparse = sparse;
+        self
+    }
+
+    pub fn with_key_overflow(mut self, key_overflow: KeyOverflow) -> IndexSchema {
+        self.key_overflow = key_overflow;
+        self
+    }
+
+    /// Places objects whose first indexed property is null first or last when the index is
+    /// scanned, regardless of the direction. Ignored for hash and multi-entry indexes.
+    pub fn with_nulls(mut self, nulls: Nulls) -> IndexSchema {
+        self.nulls = Some(nulls);
+        self
+    }
+
     pub(crate) fn as_index(&self, db: Db, properties: &[Property]) -> IsarIndex {
         let index_properties = self
             .properties
             .iter()
             .map(|ip| {
                 let property = properties.iter().find(|p| ip.name == *p.name).unwrap();
-                IndexProperty::new(property.clone(), ip.index_type, ip.case_sensitive)
+                IndexProperty {
+                    tokenizer: ip.tokenizer,
+                    ..IndexProperty::new(property.clone(), ip.index_type, ip.case_sensitive)
+                }
             })
             .collect_vec();
-        IsarIndex::new(&self.name, db, index_properties, self.unique, self.replace)
+        IsarIndex::new(
+            &self.name,
+            db,
+            index_properties,
+            self.unique,
+            self.replace,
+            self.sparse,
+            self.key_overflow,
+            self.nulls,
+        )
     }
 }
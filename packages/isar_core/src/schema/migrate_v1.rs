@@ -6,16 +6,16 @@ use crate::object::id::BytesToId;
 use crate::object::isar_object::IsarObject;
 use crate::object::object_builder::ObjectBuilder;
 use crate::schema::schema_manager::SchemaManager;
-use crate::{cursor::IsarCursors, error::Result, mdbx::txn::Txn};
+use crate::{cursor::CursorPool, cursor::IsarCursors, error::Result, mdbx::txn::Txn};
 
 use super::collection_schema::CollectionSchema;
 
-pub fn migrate_v1(txn: &Txn, schema: &mut CollectionSchema) -> Result<()> {
-    let cursors = IsarCursors::new(txn, vec![]);
+pub fn migrate_v1(manager: &SchemaManager, txn: &Txn, schema: &mut CollectionSchema) -> Result<()> {
+    let cursors = IsarCursors::new(txn, CursorPool::new(0));
     let mut buffer = Some(vec![]);
 
     for index in &schema.indexes {
-        let index_db = SchemaManager::open_index_db(txn, schema, index)?;
+        let index_db = manager.open_index_db(txn, schema, index)?;
         index_db.clear(txn)?;
     }
     schema.indexes.clear();
@@ -38,7 +38,7 @@ pub fn migrate_v1(txn: &Txn, schema: &mut CollectionSchema) -> Result<()> {
         })
         .collect_vec();
 
-    let db = SchemaManager::open_collection_db(txn, schema)?;
+    let db = manager.open_collection_db(txn, schema)?;
     let mut db_cursor = cursors.get_cursor(db)?;
     db_cursor.iter_all(false, true, |cursor, id_bytes, obj| {
         // We need to copy the data here because it will become invalid during the write
@@ -0,0 +1,259 @@
+use super::collection_schema::CollectionSchema;
+use super::Schema;
+use crate::object::data_type::DataType;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum MigrationAction {
+    AddCollection {
+        collection: String,
+    },
+    DropCollection {
+        collection: String,
+    },
+    /// Existing objects read the new property as null.
+    AddProperty {
+        collection: String,
+        property: String,
+    },
+    DropProperty {
+        collection: String,
+        property: String,
+    },
+    /// The stored values of the property are discarded and read as null afterwards.
+    IncompatibleProperty {
+        collection: String,
+        property: String,
+        from: DataType,
+        to: DataType,
+    },
+    AddIndex {
+        collection: String,
+        index: String,
+    },
+    DropIndex {
+        collection: String,
+        index: String,
+    },
+    RebuildIndex {
+        collection: String,
+        index: String,
+    },
+    DropLink {
+        collection: String,
+        link: String,
+    },
+    DropVersions {
+        collection: String,
+    },
+}
+
+impl MigrationAction {
+    /// Whether the action discards existing data.
+    pub fn is_destructive(&self) -> bool {
+        matches!(
+            self,
+            MigrationAction::DropCollection { .. }
+                | MigrationAction::DropProperty { .. }
+                | MigrationAction::IncompatibleProperty { .. }
+                | MigrationAction::DropLink { .. }
+                | MigrationAction::DropVersions { .. }
+        )
+    }
+}
+
+/// The actions opening an instance with a new schema would perform. Creating a plan does not
+/// modify anything.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MigrationPlan {
+    pub actions: Vec<MigrationAction>,
+}
+
+impl MigrationPlan {
+    pub(crate) fn create(existing: &Schema, new: &Schema) -> Self {
+        let mut actions = vec![];
+        for col in &new.collections {
+            if let Some(existing_col) = existing.collections.iter().find(|c| c.name == col.name) {
+                Self::plan_collection(existing_col, col, &mut actions);
+            } else {
+                actions.push(MigrationAction::AddCollection {
+                    collection: col.name.clone(),
+                });
+            }
+        }
+        for existing_col in &existing.collections {
+            if !new.collections.iter().any(|c| c.name == existing_col.name) {
+                actions.push(MigrationAction::DropCollection {
+                    collection: existing_col.name.clone(),
+                });
+            }
+        }
+        MigrationPlan { actions }
+    }
+
+    fn plan_collection(
+        existing: &CollectionSchema,
+        new: &CollectionSchema,
+        actions: &mut Vec<MigrationAction>,
+    ) {
+        let collection = &new.name;
+        let mut changed_properties = vec![];
+        for property in &new.properties {
+            let name = if let Some(name) = &property.name {
+                name
+            } else {
+                continue;
+            };
+            let existing_property = existing
+                .properties
+                .iter()
+                .find(|p| p.name.as_ref() == Some(name));
+            match existing_property {
+                Some(existing_property) if existing_property != property => {
+                    changed_properties.push(name.clone());
+                    actions.push(MigrationAction::IncompatibleProperty {
+                        collection: collection.clone(),
+                        property: name.clone(),
+                        from: existing_property.data_type,
+                        to: property.data_type,
+                    });
+                }
+                Some(_) => {}
+                None => actions.push(MigrationAction::AddProperty {
+                    collection: collection.clone(),
+                    property: name.clone(),
+                }),
+            }
+        }
+        for property in &existing.properties {
+            if let Some(name) = &property.name {
+                if !new.properties.iter().any(|p| p.name.as_ref() == Some(name)) {
+                    changed_properties.push(name.clone());
+                    actions.push(MigrationAction::DropProperty {
+                        collection: collection.clone(),
+                        property: name.clone(),
+                    });
+                }
+            }
+        }
+
+        for index in &new.indexes {
+            if !existing.indexes.contains(index) {
+                actions.push(MigrationAction::AddIndex {
+                    collection: collection.clone(),
+                    index: index.name.clone(),
+                });
+            }
+        }
+        for existing_index in &existing.indexes {
+            if !new.indexes.contains(existing_index) {
+                actions.push(MigrationAction::DropIndex {
+                    collection: collection.clone(),
+                    index: existing_index.name.clone(),
+                });
+            } else if existing_index
+                .properties
+                .iter()
+                .any(|p| changed_properties.contains(&p.name))
+            {
+                actions.push(MigrationAction::RebuildIndex {
+                    collection: collection.clone(),
+                    index: existing_index.name.clone(),
+                });
+            }
+        }
+
+        for link in &existing.links {
+            if !new.links.contains(link) {
+                actions.push(MigrationAction::DropLink {
+                    collection: collection.clone(),
+                    link: link.name.clone(),
+                });
+            }
+        }
+
+        if existing.versioned && !new.versioned {
+            actions.push(MigrationAction::DropVersions {
+                collection: collection.clone(),
+            });
+        }
+    }
+
+    pub fn is_destructive(&self) -> bool {
+        self.actions.iter().any(|a| a.is_destructive())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::index_schema::{IndexPropertySchema, IndexSchema, IndexType};
+    use crate::schema::property_schema::PropertySchema;
+
+    fn property(name: &str, data_type: DataType) -> PropertySchema {
+        PropertySchema::new(Some(name.to_string()), data_type, None)
+    }
+
+    #[test]
+    fn test_plan_migration() {
+        let index = IndexSchema::new(
+            "b",
+            vec![IndexPropertySchema::new("b", IndexType::Value, false)],
+            false,
+            false,
+        );
+        let existing = CollectionSchema::new(
+            "col",
+            false,
+            vec![property("a", DataType::Long), property("b", DataType::Int)],
+            vec![index.clone()],
+            vec![],
+        );
+        let existing = Schema::new(vec![existing]).unwrap();
+
+        let new = CollectionSchema::new(
+            "col",
+            false,
+            vec![
+                property("b", DataType::String),
+                property("c", DataType::Int),
+            ],
+            vec![index],
+            vec![],
+        );
+        let new = Schema::new(vec![new]).unwrap();
+
+        let plan = MigrationPlan::create(&existing, &new);
+        let col = "col".to_string();
+        assert_eq!(
+            plan.actions,
+            vec![
+                MigrationAction::IncompatibleProperty {
+                    collection: col.clone(),
+                    property: "b".to_string(),
+                    from: DataType::Int,
+                    to: DataType::String,
+                },
+                MigrationAction::AddProperty {
+                    collection: col.clone(),
+                    property: "c".to_string(),
+                },
+                MigrationAction::DropProperty {
+                    collection: col.clone(),
+                    property: "a".to_string(),
+                },
+                MigrationAction::RebuildIndex {
+                    collection: col,
+                    index: "b".to_string(),
+                },
+            ]
+        );
+        assert!(plan.is_destructive());
+        assert!(!MigrationPlan::create(&new, &new).is_destructive());
+        assert!(MigrationPlan::create(&new, &new).is_empty());
+        assert!(!plan.is_empty());
+    }
+}
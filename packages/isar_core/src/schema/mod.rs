@@ -2,6 +2,7 @@ pub mod collection_schema;
 pub mod index_schema;
 pub mod link_schema;
 pub(crate) mod migrate_v1;
+pub mod migration_plan;
 pub mod property_schema;
 pub(crate) mod schema_manager;
 
@@ -38,6 +39,15 @@ impl Schema {
         }
     }
 
+    /// Parses a JSON array of schemas as accepted by [Schema::from_json].
+    pub fn from_json_list(json: &[u8]) -> Result<Vec<Schema>> {
+        if let Ok(schemas) = serde_json::from_slice::<Vec<Vec<CollectionSchema>>>(json) {
+            schemas.into_iter().map(Schema::new).collect()
+        } else {
+            schema_error("Could not deserialize schema JSON")
+        }
+    }
+
     pub(crate) fn get_collection(&self, name: &str, embedded: bool) -> Option<&CollectionSchema> {
         self.collections
             .iter()
@@ -50,6 +60,9 @@ impl Schema {
             count += 1;
             count += col.indexes.len();
             count += col.links.len() * 2;
+            if col.versioned {
+                count += 1;
+            }
         }
         count
     }
@@ -1,6 +1,265 @@
+use crate::error::{illegal_arg, IsarError, Result};
 use crate::object::data_type::DataType;
+use crate::object::isar_object::IsarObject;
+use crate::object::object_builder::ObjectBuilder;
 use crate::object::property::Property;
+use byteorder::{ByteOrder, LittleEndian};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Controls how NaN and infinite values of Float and Double properties are written.
+///
+/// NaN is also the null representation of these types so `RejectNonFinite` makes the
+/// property non-nullable.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum NonFinitePolicy {
+    #[default]
+    Allow,
+    RejectNonFinite,
+    StoreAsNull,
+}
+
+impl NonFinitePolicy {
+    /// Returns a patched copy of the object if a value had to be replaced.
+    pub(crate) fn apply(
+        policies: &[(Property, NonFinitePolicy)],
+        object: IsarObject,
+    ) -> Result<Option<Vec<u8>>> {
+        let mut patched: Option<Vec<u8>> = None;
+        for (property, policy) in policies {
+            let finite = match property.data_type {
+                DataType::Float => object.read_float(property.offset).is_finite(),
+                DataType::Double => object.read_double(property.offset).is_finite(),
+                _ => true,
+            };
+            if finite || *policy == NonFinitePolicy::Allow {
+                continue;
+            }
+
+            if *policy == NonFinitePolicy::RejectNonFinite {
+                return illegal_arg(&format!(
+                    "Property \"{}\" does not allow non-finite values.",
+                    property.name
+                ));
+            }
+
+            if object.is_null(property.offset, property.data_type) {
+                continue;
+            }
+            let bytes = patched.get_or_insert_with(|| object.as_bytes().to_vec());
+            if property.data_type == DataType::Float {
+                LittleEndian::write_f32(&mut bytes[property.offset..], IsarObject::NULL_FLOAT);
+            } else {
+                LittleEndian::write_f64(&mut bytes[property.offset..], IsarObject::NULL_DOUBLE);
+            }
+        }
+        Ok(patched)
+    }
+}
+
+pub type CustomStringTransform = fn(&str) -> String;
+
+static CUSTOM_STRING_TRANSFORMS: Lazy<RwLock<HashMap<String, CustomStringTransform>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Makes `transform` available to schemas as `StringTransform::Custom(name)`. Registering a name
+/// again replaces the previous transform.
+pub fn register_string_transform(name: &str, transform: CustomStringTransform) {
+    let mut transforms = CUSTOM_STRING_TRANSFORMS.write().unwrap();
+    transforms.insert(name.to_string(), transform);
+}
+
+/// Normalizes the values of a String or StringList property before they are stored and indexed.
+/// Objects that were stored before a transform was added are not updated.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum StringTransform {
+    TrimWhitespace,
+    Lowercase,
+    Uppercase,
+    Custom(String),
+}
+
+enum TransformedValue {
+    String(String),
+    StringList(Vec<Option<String>>),
+}
+
+impl StringTransform {
+    /// Applies `transforms` in order.
+    pub fn transform(transforms: &[StringTransform], value: &str) -> Result<String> {
+        let mut value = value.to_string();
+        for transform in transforms {
+            value = match transform {
+                StringTransform::TrimWhitespace => value.trim().to_string(),
+                StringTransform::Lowercase => value.to_lowercase(),
+                StringTransform::Uppercase => value.to_uppercase(),
+                StringTransform::Custom(name) => {
+                    let custom = CUSTOM_STRING_TRANSFORMS.read().unwrap().get(name).copied();
+                    if let Some(custom) = custom {
+                        custom(&value)
+                    } else {
+                        return illegal_arg(&format!(
+                            "String transform \"{}\" is not registered.",
+                            name
+                        ));
+                    }
+                }
+            };
+        }
+        Ok(value)
+    }
+
+    /// Returns a rebuilt copy of the object if a value had to be transformed.
+    pub(crate) fn apply(
+        transforms: &[(Property, Vec<StringTransform>)],
+        properties: &[Property],
+        object: IsarObject,
+    ) -> Result<Option<Vec<u8>>> {
+        let mut transformed = vec![];
+        for (property, property_transforms) in transforms {
+            if property.data_type == DataType::String {
+                if let Some(value) = object.read_string(property.offset) {
+                    let new_value = Self::transform(property_transforms, value)?;
+                    if new_value != value {
+                        transformed.push((property.offset, TransformedValue::String(new_value)));
+                    }
+                }
+            } else if let Some(list) = object.read_string_list(property.offset) {
+                let new_list = list
+                    .iter()
+                    .map(|value| {
+                        value
+                            .map(|value| Self::transform(property_transforms, value))
+                            .transpose()
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                if new_list
+                    .iter()
+                    .map(|v| v.as_deref())
+                    .ne(list.iter().copied())
+                {
+                    transformed.push((property.offset, TransformedValue::StringList(new_list)));
+                }
+            }
+        }
+        if transformed.is_empty() {
+            return Ok(None);
+        }
+
+        let mut builder = ObjectBuilder::new(properties, None);
+        for property in properties {
+            let value = transformed
+                .iter()
+                .find(|(offset, _)| *offset == property.offset);
+            match value {
+                Some((_, TransformedValue::String(value))) => {
+                    builder.write_string(property.offset, Some(value))
+                }
+                Some((_, TransformedValue::StringList(list))) => {
+                    let list = list.iter().map(|v| v.as_deref()).collect::<Vec<_>>();
+                    builder.write_string_list(property.offset, Some(&list))
+                }
+                None => builder.copy_property(object, property),
+            }
+        }
+        Ok(Some(builder.finish().as_bytes().to_vec()))
+    }
+}
+
+/// Values of dynamic properties are limited to this size unless the schema sets a different
+/// `maxValueBytes`.
+pub const DEFAULT_MAX_VALUE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Fails with [crate::error::IsarError::ValueTooLarge] if a value of the object exceeds the
+/// limit of its property.
+pub(crate) fn check_value_sizes(limits: &[(Property, usize)], object: IsarObject) -> Result<()> {
+    for (property, max) in limits {
+        let len = object.read_value_size(property.offset, property.data_type);
+        if len > *max {
+            return Err(IsarError::ValueTooLarge {
+                property: property.name.clone(),
+                len,
+                max: *max,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Fails with [crate::error::IsarError::VectorDimensionMismatch] if a vector of the object does
+/// not have the dimension of its property. Null vectors are allowed.
+pub(crate) fn check_vector_dimensions(
+    dimensions: &[(Property, usize)],
+    object: IsarObject,
+) -> Result<()> {
+    for (property, expected) in dimensions {
+        if let Some(len) = object.read_length(property.offset) {
+            if len != *expected {
+                return Err(IsarError::VectorDimensionMismatch {
+                    property: property.name.clone(),
+                    len,
+                    expected: *expected,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Maps the values of a Byte, Int or Long property to symbolic names. Each value is stored as the
+/// position of its name.
+#[derive(Clone)]
+pub(crate) struct EnumMapping {
+    pub property: Property,
+    pub names: Vec<String>,
+}
+
+impl EnumMapping {
+    pub fn value_of(&self, name: &str) -> Result<i64> {
+        if let Some(index) = self.names.iter().position(|n| n == name) {
+            Ok(index as i64)
+        } else {
+            illegal_arg(&format!(
+                "\"{}\" is not a value of enum property \"{}\".",
+                name, self.property.name
+            ))
+        }
+    }
+
+    pub fn name_of(&self, value: i64) -> Option<&str> {
+        let index = usize::try_from(value).ok()?;
+        self.names.get(index).map(|name| name.as_str())
+    }
+
+    /// Replaces the values of enum properties by their names. Values without a name are kept.
+    pub fn encode_names(mappings: &[EnumMapping], json: &mut Map<String, Value>) {
+        for mapping in mappings {
+            if let Some(value) = json.get_mut(&mapping.property.name) {
+                if let Some(name) = value.as_i64().and_then(|v| mapping.name_of(v)) {
+                    *value = Value::String(name.to_string());
+                }
+            }
+        }
+    }
+
+    /// Returns a copy of `json` with the names of enum properties replaced by their values or
+    /// `None` if it does not contain names.
+    pub fn decode_names(mappings: &[EnumMapping], json: &Value) -> Result<Option<Value>> {
+        let mut decoded: Option<Value> = None;
+        for mapping in mappings {
+            let name = json.get(&mapping.property.name).and_then(|v| v.as_str());
+            if let Some(name) = name {
+                let value = mapping.value_of(name)?;
+                let decoded = decoded.get_or_insert_with(|| json.clone());
+                decoded[&mapping.property.name] = Value::from(value);
+            }
+        }
+        Ok(decoded)
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone, Eq)]
 pub struct PropertySchema {
@@ -10,6 +269,21 @@ pub struct PropertySchema {
     #[serde(default)]
     #[serde(rename = "target")]
     pub(crate) target_col: Option<String>,
+    #[serde(default)]
+    #[serde(rename = "nonFinite")]
+    pub(crate) non_finite: NonFinitePolicy,
+    #[serde(default)]
+    #[serde(rename = "enumValues")]
+    pub(crate) enum_values: Option<Vec<String>>,
+    #[serde(default)]
+    #[serde(rename = "transforms")]
+    pub(crate) transforms: Vec<StringTransform>,
+    #[serde(default)]
+    #[serde(rename = "maxValueBytes")]
+    pub(crate) max_value_bytes: Option<usize>,
+    #[serde(default)]
+    #[serde(rename = "dimension")]
+    pub(crate) dimension: Option<usize>,
 }
 
 impl PropertySchema {
@@ -22,9 +296,46 @@ impl PropertySchema {
             name,
             data_type,
             target_col,
+            non_finite: NonFinitePolicy::Allow,
+            enum_values: None,
+            transforms: vec![],
+            max_value_bytes: None,
+            dimension: None,
         }
     }
 
+    pub fn with_non_finite(mut self, policy: NonFinitePolicy) -> PropertySchema {
+        self.non_finite = policy;
+        self
+    }
+
+    /// Stores the property as the position of its value in `names`. JSON imports and exports use
+    /// the names instead of the stored values.
+    pub fn with_enum_values(mut self, names: Vec<String>) -> PropertySchema {
+        self.enum_values = Some(names);
+        self
+    }
+
+    /// Normalizes the values of a String or StringList property with `transforms` in order.
+    pub fn with_transforms(mut self, transforms: Vec<StringTransform>) -> PropertySchema {
+        self.transforms = transforms;
+        self
+    }
+
+    /// Limits the size of the values of a dynamic property. Defaults to
+    /// [DEFAULT_MAX_VALUE_BYTES].
+    pub fn with_max_value_bytes(mut self, max_value_bytes: usize) -> PropertySchema {
+        self.max_value_bytes = Some(max_value_bytes);
+        self
+    }
+
+    /// Stores a FloatList property as a vector with exactly `dimension` values so it can be
+    /// used for nearest neighbor searches.
+    pub fn with_dimension(mut self, dimension: usize) -> PropertySchema {
+        self.dimension = Some(dimension);
+        self
+    }
+
     pub(crate) fn as_property(&self, offset: usize) -> Option<Property> {
         if let Some(name) = &self.name {
             let p = Property::new(name, self.data_type, offset, self.target_col.as_deref());
@@ -49,3 +360,138 @@ impl PartialEq for PropertySchema {
         self.name == other.name && type_eq && self.target_col == other.target_col
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::object_builder::ObjectBuilder;
+
+    fn build_object(value: f64) -> Vec<u8> {
+        let properties = vec![Property::debug(DataType::Double, 2)];
+        let mut builder = ObjectBuilder::new(&properties, None);
+        builder.write_double(2, value);
+        builder.finish().as_bytes().to_vec()
+    }
+
+    fn apply(policy: NonFinitePolicy, value: f64) -> Result<Option<f64>> {
+        let bytes = build_object(value);
+        let policies = vec![(Property::debug(DataType::Double, 2), policy)];
+        let patched = NonFinitePolicy::apply(&policies, IsarObject::from_bytes(&bytes))?;
+        Ok(patched.map(|bytes| IsarObject::from_bytes(&bytes).read_double(2)))
+    }
+
+    #[test]
+    fn test_apply_non_finite_policy() {
+        assert_eq!(apply(NonFinitePolicy::Allow, f64::INFINITY).unwrap(), None);
+        assert_eq!(apply(NonFinitePolicy::Allow, f64::NAN).unwrap(), None);
+
+        assert!(apply(NonFinitePolicy::RejectNonFinite, f64::NAN).is_err());
+        assert!(apply(NonFinitePolicy::RejectNonFinite, f64::NEG_INFINITY).is_err());
+        assert_eq!(apply(NonFinitePolicy::RejectNonFinite, 1.5).unwrap(), None);
+
+        assert_eq!(apply(NonFinitePolicy::StoreAsNull, f64::NAN).unwrap(), None);
+        assert_eq!(apply(NonFinitePolicy::StoreAsNull, 1.5).unwrap(), None);
+        let stored = apply(NonFinitePolicy::StoreAsNull, f64::INFINITY).unwrap();
+        assert!(stored.unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_enum_mapping() {
+        let mapping = EnumMapping {
+            property: Property::new("status", DataType::Byte, 2, None),
+            names: vec!["Inactive".to_string(), "Active".to_string()],
+        };
+        assert_eq!(mapping.value_of("Active").unwrap(), 1);
+        assert!(mapping.value_of("Deleted").is_err());
+        assert_eq!(mapping.name_of(0), Some("Inactive"));
+        assert_eq!(mapping.name_of(2), None);
+        assert_eq!(mapping.name_of(-1), None);
+
+        let mappings = vec![mapping];
+        let json = serde_json::json!({"id": 1, "status": "Active"});
+        let decoded = EnumMapping::decode_names(&mappings, &json)
+            .unwrap()
+            .unwrap();
+        assert_eq!(decoded, serde_json::json!({"id": 1, "status": 1}));
+
+        let mut encoded = decoded.as_object().unwrap().clone();
+        EnumMapping::encode_names(&mappings, &mut encoded);
+        assert_eq!(Value::Object(encoded), json);
+
+        let numeric = serde_json::json!({"status": 0});
+        assert_eq!(
+            EnumMapping::decode_names(&mappings, &numeric).unwrap(),
+            None
+        );
+        let invalid = serde_json::json!({"status": "Deleted"});
+        assert!(EnumMapping::decode_names(&mappings, &invalid).is_err());
+    }
+
+    #[test]
+    fn test_apply_string_transforms() {
+        let properties = vec![
+            Property::debug(DataType::Long, 2),
+            Property::debug(DataType::String, 10),
+            Property::debug(DataType::StringList, 13),
+        ];
+        let mut builder = ObjectBuilder::new(&properties, None);
+        builder.write_long(2, 42);
+        builder.write_string(10, Some("  Jane.Doe@Example.COM "));
+        builder.write_string_list(13, Some(&[Some("A"), None, Some("b")]));
+        let bytes = builder.finish().as_bytes().to_vec();
+        let object = IsarObject::from_bytes(&bytes);
+
+        let lowercase = vec![StringTransform::TrimWhitespace, StringTransform::Lowercase];
+        let transforms = vec![
+            (properties[1].clone(), lowercase.clone()),
+            (properties[2].clone(), lowercase),
+        ];
+        let patched = StringTransform::apply(&transforms, &properties, object)
+            .unwrap()
+            .unwrap();
+        let patched = IsarObject::from_bytes(&patched);
+        assert_eq!(patched.read_long(2), 42);
+        assert_eq!(patched.read_string(10), Some("jane.doe@example.com"));
+        assert_eq!(
+            patched.read_string_list(13),
+            Some(vec![Some("a"), None, Some("b")])
+        );
+        assert_eq!(
+            StringTransform::apply(&transforms, &properties, patched).unwrap(),
+            None
+        );
+
+        register_string_transform("reverse", |value| value.chars().rev().collect());
+        let custom = vec![StringTransform::Custom("reverse".to_string())];
+        assert_eq!(StringTransform::transform(&custom, "abc").unwrap(), "cba");
+        let unknown = vec![StringTransform::Custom("unknown".to_string())];
+        assert!(StringTransform::transform(&unknown, "abc").is_err());
+    }
+
+    #[test]
+    fn test_check_value_sizes() {
+        let properties = vec![
+            Property::debug(DataType::ByteList, 2),
+            Property::debug(DataType::StringList, 5),
+        ];
+        let build = |blob: &[u8], strings: &[Option<&str>]| {
+            let mut builder = ObjectBuilder::new(&properties, None);
+            builder.write_byte_list(2, Some(blob));
+            builder.write_string_list(5, Some(strings));
+            builder.finish().as_bytes().to_vec()
+        };
+        let limits = vec![(properties[0].clone(), 100), (properties[1].clone(), 10)];
+
+        let bytes = build(&[0; 100], &[Some("hello"), None, Some("world")]);
+        assert!(check_value_sizes(&limits, IsarObject::from_bytes(&bytes)).is_ok());
+
+        let bytes = build(&[0; 101], &[]);
+        match check_value_sizes(&limits, IsarObject::from_bytes(&bytes)) {
+            Err(IsarError::ValueTooLarge { len, max, .. }) => assert_eq!((len, max), (101, 100)),
+            _ => panic!("expected ValueTooLarge"),
+        }
+
+        let bytes = build(&[], &[Some("hello"), Some("world!")]);
+        assert!(check_value_sizes(&limits, IsarObject::from_bytes(&bytes)).is_err());
+    }
+}
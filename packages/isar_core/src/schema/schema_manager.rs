@@ -2,8 +2,8 @@ use super::collection_schema::CollectionSchema;
 use super::index_schema::IndexSchema;
 use super::link_schema::LinkSchema;
 use super::Schema;
-use crate::collection::IsarCollection;
-use crate::cursor::IsarCursors;
+use crate::collection::{CollectionOptions, IsarCollection};
+use crate::cursor::{CursorPool, IsarCursors};
 use crate::error::{schema_error, IsarError, Result};
 use crate::index::index_key::IndexKey;
 use crate::index::IsarIndex;
@@ -29,25 +29,42 @@ static OLD_INFO_SCHEMA_KEY: Lazy<IndexKey> = Lazy::new(|| {
     key
 });
 
+// collection names cannot start with an underscore so this key never collides with a schema
+const SCHEMA_VERSION: &[u8] = b"_schema_version";
+
+static SCHEMA_VERSION_KEY: Lazy<IndexKey> =
+    Lazy::new(|| IndexKey::from_bytes(SCHEMA_VERSION.to_vec()));
+
 pub(crate) struct SchemaManager {
     instance_id: u64,
+    db_prefix: String,
     info_db: Db,
+    schema_version: u32,
     pub schemas: Vec<CollectionSchema>,
 }
 
 impl SchemaManager {
     pub const ISAR_FILE_VERSION: u8 = 2;
 
-    pub fn create(instance_id: u64, txn: &Txn) -> Result<Self> {
-        let info_db = Db::open(txn, Some("_info"), false, false, false)?;
+    pub fn create(instance_id: u64, db_prefix: &str, txn: &Txn) -> Result<Self> {
+        let info_db = Db::open(
+            txn,
+            Some(&format!("{}_info", db_prefix)),
+            false,
+            false,
+            false,
+        )?;
         let mut info_cursor = UnboundCursor::new().bind(txn, info_db)?;
 
         Self::migrate_old_info(&mut info_cursor)?;
 
         let schemas = Self::get_schemas(&mut info_cursor)?;
+        let schema_version = Self::get_schema_version(&mut info_cursor)?;
         let manager = SchemaManager {
             instance_id,
+            db_prefix: db_prefix.to_string(),
             info_db,
+            schema_version,
             schemas,
         };
         Ok(manager)
@@ -79,9 +96,45 @@ impl SchemaManager {
         Ok(())
     }
 
+    /// Reads the schema version of an existing instance without migrating it. Returns `0` if the
+    /// instance does not exist or was created before schema versions were tracked.
+    pub fn read_schema_version(txn: &Txn, db_prefix: &str) -> Result<u32> {
+        if let Ok(info_db) = Db::open_existing(txn, &format!("{}_info", db_prefix)) {
+            let mut info_cursor = UnboundCursor::new().bind(txn, info_db)?;
+            Self::get_schema_version(&mut info_cursor)
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Reads the collection schemas stored in an existing instance without migrating it.
+    pub fn read_schemas(txn: &Txn, db_prefix: &str) -> Result<Vec<CollectionSchema>> {
+        if let Ok(info_db) = Db::open_existing(txn, &format!("{}_info", db_prefix)) {
+            let mut info_cursor = UnboundCursor::new().bind(txn, info_db)?;
+            Self::get_schemas(&mut info_cursor)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    fn get_schema_version(info_cursor: &mut Cursor) -> Result<u32> {
+        let version = info_cursor.move_to(SCHEMA_VERSION_KEY.deref())?;
+        if let Some((_, bytes)) = version {
+            let bytes = bytes.try_into().map_err(|_| IsarError::DbCorrupted {
+                message: "Invalid schema version.".to_string(),
+            })?;
+            Ok(u32::from_le_bytes(bytes))
+        } else {
+            Ok(0)
+        }
+    }
+
     fn get_schemas(info_cursor: &mut Cursor) -> Result<Vec<CollectionSchema>> {
         let mut schemas = vec![];
-        info_cursor.iter_all(false, true, |_, _, bytes| {
+        info_cursor.iter_all(false, true, |_, key, bytes| {
+            if key == SCHEMA_VERSION {
+                return Ok(true);
+            }
             let col = serde_json::from_slice::<CollectionSchema>(bytes).map_err(|_| {
                 IsarError::DbCorrupted {
                     message: "Could not deserialize existing schema.".to_string(),
@@ -108,47 +161,74 @@ impl SchemaManager {
         Ok(())
     }
 
-    pub fn open_collection_db(txn: &Txn, col: &CollectionSchema) -> Result<Db> {
-        Db::open(txn, Some(&col.name), true, false, false)
+    pub fn open_collection_db(&self, txn: &Txn, col: &CollectionSchema) -> Result<Db> {
+        let db_name = format!("{}{}", self.db_prefix, col.name);
+        Db::open(txn, Some(&db_name), true, false, false)
     }
 
-    pub fn open_index_db(txn: &Txn, col: &CollectionSchema, index: &IndexSchema) -> Result<Db> {
-        let db_name = format!("_i_{}_{}", col.name, index.name);
-        Db::open(txn, Some(&db_name), false, !index.unique, false)
+    pub fn open_index_db(
+        &self,
+        txn: &Txn,
+        col: &CollectionSchema,
+        index: &IndexSchema,
+    ) -> Result<Db> {
+        let db_name = format!("{}_i_{}_{}", self.db_prefix, col.name, index.name);
+        Db::open_with_comparator(
+            txn,
+            Some(&db_name),
+            false,
+            !index.unique,
+            false,
+            index.comparator,
+        )
     }
 
-    pub fn open_link_dbs(txn: &Txn, col: &CollectionSchema, link: &LinkSchema) -> Result<(Db, Db)> {
-        let link_db_name = format!("_l_{}_{}", col.name, link.name);
+    pub fn open_link_dbs(
+        &self,
+        txn: &Txn,
+        col: &CollectionSchema,
+        link: &LinkSchema,
+    ) -> Result<(Db, Db)> {
+        let link_db_name = format!("{}_l_{}_{}", self.db_prefix, col.name, link.name);
         let db = Db::open(txn, Some(&link_db_name), true, true, true)?;
-        let backlink_db_name = format!("_b_{}_{}", col.name, link.name);
+        let backlink_db_name = format!("{}_b_{}_{}", self.db_prefix, col.name, link.name);
         let bl_db = Db::open(txn, Some(&backlink_db_name), true, true, true)?;
         Ok((db, bl_db))
     }
 
-    fn delete_collection(txn: &Txn, col: &CollectionSchema) -> Result<()> {
-        let db = Self::open_collection_db(txn, col)?;
+    pub fn open_version_db(&self, txn: &Txn, col: &CollectionSchema) -> Result<Db> {
+        let db_name = format!("{}_v_{}", self.db_prefix, col.name);
+        Db::open(txn, Some(&db_name), true, false, false)
+    }
+
+    fn delete_collection(&self, txn: &Txn, col: &CollectionSchema) -> Result<()> {
+        let db = self.open_collection_db(txn, col)?;
         db.drop(txn)?;
+        if col.versioned {
+            self.open_version_db(txn, col)?.drop(txn)?;
+        }
         for index in &col.indexes {
-            Self::delete_index(txn, col, index)?;
+            self.delete_index(txn, col, index)?;
         }
         for link in &col.links {
-            Self::delete_link(txn, col, link)?;
+            self.delete_link(txn, col, link)?;
         }
         Ok(())
     }
 
-    fn delete_index(txn: &Txn, col: &CollectionSchema, index: &IndexSchema) -> Result<()> {
-        let db = Self::open_index_db(txn, col, index)?;
+    fn delete_index(&self, txn: &Txn, col: &CollectionSchema, index: &IndexSchema) -> Result<()> {
+        let db = self.open_index_db(txn, col, index)?;
         db.drop(txn)
     }
 
-    fn delete_link(txn: &Txn, col: &CollectionSchema, link: &LinkSchema) -> Result<()> {
-        let (db, bl_db) = Self::open_link_dbs(txn, col, link)?;
+    fn delete_link(&self, txn: &Txn, col: &CollectionSchema, link: &LinkSchema) -> Result<()> {
+        let (db, bl_db) = self.open_link_dbs(txn, col, link)?;
         db.drop(txn)?;
         bl_db.drop(txn)
     }
 
     fn perform_migration(
+        &self,
         txn: &Txn,
         schema: &mut CollectionSchema,
         existing_schema: &CollectionSchema,
@@ -171,7 +251,7 @@ impl SchemaManager {
                 .any(|p| removed_properties.contains(&p.name));
 
             if removed_index || changed_property {
-                Self::delete_index(txn, existing_schema, existing_index)?;
+                self.delete_index(txn, existing_schema, existing_index)?;
             }
 
             if !removed_index && changed_property {
@@ -182,17 +262,22 @@ impl SchemaManager {
 
         for link in &existing_schema.links {
             if !schema.links.contains(link) {
-                Self::delete_link(txn, existing_schema, link)?;
+                self.delete_link(txn, existing_schema, link)?;
             }
         }
 
+        if existing_schema.versioned && !schema.versioned {
+            self.open_version_db(txn, existing_schema)?.drop(txn)?;
+        }
+
         Ok(added_indexes.keys().copied().collect())
     }
 
     pub fn migrate_schema(&mut self, txn: &Txn, schemas: &mut Schema) -> Result<IntMap<Vec<u64>>> {
-        let cursors = IsarCursors::new(txn, vec![]);
+        let cursors = IsarCursors::new(txn, CursorPool::new(0));
 
         let mut added_indexes = IntMap::new();
+        let mut changed = false;
         for col_schema in &mut schemas.collections {
             let mut existing_schema = self
                 .schemas
@@ -202,20 +287,36 @@ impl SchemaManager {
 
             if let Some(existing_schema) = &mut existing_schema {
                 if existing_schema.version == 1 {
-                    migrate_v1(txn, existing_schema)?;
+                    migrate_v1(self, txn, existing_schema)?;
                 } else if existing_schema.version != Self::ISAR_FILE_VERSION {
                     return Err(IsarError::VersionError {});
                 }
-                let ai = Self::perform_migration(txn, col_schema, existing_schema)?;
+                let ai = self.perform_migration(txn, col_schema, existing_schema)?;
                 if !ai.is_empty() {
                     added_indexes.insert(xxh3_64(col_schema.name.as_bytes()), ai);
                 }
             }
 
-            let mut info_cursor = cursors.get_cursor(self.info_db)?;
             col_schema.version = Self::ISAR_FILE_VERSION;
+            if let Some(existing_schema) = &mut existing_schema {
+                existing_schema.version = Self::ISAR_FILE_VERSION;
+                changed |= existing_schema.to_json_bytes()? != col_schema.to_json_bytes()?;
+            } else {
+                changed = true;
+            }
+
+            let mut info_cursor = cursors.get_cursor(self.info_db)?;
             Self::save_schema(&mut info_cursor, &col_schema)?;
         }
+
+        if changed || !self.schemas.is_empty() {
+            self.schema_version += 1;
+            let mut info_cursor = cursors.get_cursor(self.info_db)?;
+            info_cursor.put(
+                SCHEMA_VERSION_KEY.deref(),
+                &self.schema_version.to_le_bytes(),
+            )?;
+        }
         Ok(added_indexes)
     }
 
@@ -225,18 +326,25 @@ impl SchemaManager {
         schema: &CollectionSchema,
         schemas: &Schema,
         added_indexes: &[u64],
+        oplog_db: Db,
     ) -> Result<IsarCollection> {
-        let cursors = IsarCursors::new(txn, vec![]);
+        let cursors = IsarCursors::new(txn, CursorPool::new(0));
 
-        let db = Self::open_collection_db(txn, &schema)?;
+        let db = self.open_collection_db(txn, &schema)?;
         let properties = schema.get_properties();
 
         let mut embedded_properties = IntMap::new();
         Self::get_embedded_properties(schemas, &properties, &mut embedded_properties);
 
-        let indexes = Self::open_indexes(txn, &schema, &properties)?;
-        let links = Self::open_links(txn, db, &schema, schemas)?;
-        let backlinks = Self::open_backlinks(txn, db, &schema, schemas)?;
+        let indexes = self.open_indexes(txn, &schema, &properties)?;
+        let links = self.open_links(txn, db, &schema, schemas)?;
+        let backlinks = self.open_backlinks(txn, db, &schema, schemas)?;
+        let version_db = if schema.versioned {
+            Some(self.open_version_db(txn, schema)?)
+        } else {
+            None
+        };
+        let options = CollectionOptions::new(schema, version_db, schema.oplog.then_some(oplog_db));
         let col = IsarCollection::new(
             db,
             self.instance_id,
@@ -246,8 +354,13 @@ impl SchemaManager {
             indexes,
             links,
             backlinks,
+            options,
         );
 
+        if schema.temporary {
+            col.clear_internal(&cursors, None)?;
+        }
+
         col.init_auto_increment(&cursors)?;
         if !added_indexes.is_empty() {
             col.fill_indexes(&added_indexes, &cursors)?;
@@ -278,13 +391,14 @@ impl SchemaManager {
     }
 
     fn open_indexes(
+        &self,
         txn: &Txn,
         schema: &CollectionSchema,
         properties: &[Property],
     ) -> Result<Vec<IsarIndex>> {
         let mut indexes = vec![];
         for index_schema in &schema.indexes {
-            let db = Self::open_index_db(txn, schema, index_schema)?;
+            let db = self.open_index_db(txn, schema, index_schema)?;
             let index = index_schema.as_index(db, &properties);
             indexes.push(index);
         }
@@ -292,6 +406,7 @@ impl SchemaManager {
     }
 
     fn open_links(
+        &self,
         txn: &Txn,
         db: Db,
         schema: &CollectionSchema,
@@ -299,11 +414,11 @@ impl SchemaManager {
     ) -> Result<Vec<IsarLink>> {
         let mut links = vec![];
         for link_schema in &schema.links {
-            let (link_db, backlink_db) = Self::open_link_dbs(txn, schema, link_schema)?;
+            let (link_db, backlink_db) = self.open_link_dbs(txn, schema, link_schema)?;
             let target_col_schema = schemas
                 .get_collection(&link_schema.target_col, false)
                 .unwrap();
-            let target_db = Self::open_collection_db(txn, target_col_schema)?;
+            let target_db = self.open_collection_db(txn, target_col_schema)?;
             let link = IsarLink::new(
                 &schema.name,
                 &link_schema.name,
@@ -319,6 +434,7 @@ impl SchemaManager {
     }
 
     fn open_backlinks(
+        &self,
         txn: &Txn,
         db: Db,
         schema: &CollectionSchema,
@@ -328,8 +444,9 @@ impl SchemaManager {
         for other_col_schema in &schemas.collections {
             for link_schema in &other_col_schema.links {
                 if link_schema.target_col == schema.name {
-                    let other_col_db = Self::open_collection_db(txn, other_col_schema)?;
-                    let (link_db, bl_db) = Self::open_link_dbs(txn, other_col_schema, link_schema)?;
+                    let other_col_db = self.open_collection_db(txn, other_col_schema)?;
+                    let (link_db, bl_db) =
+                        self.open_link_dbs(txn, other_col_schema, link_schema)?;
                     let backlink = IsarLink::new(
                         &other_col_schema.name,
                         &link_schema.name,
@@ -349,7 +466,7 @@ impl SchemaManager {
     pub fn delete_unopened_collections(&self, txn: &Txn) -> Result<()> {
         let mut info_cursor = UnboundCursor::new().bind(txn, self.info_db)?;
         for col in &self.schemas {
-            Self::delete_collection(txn, col)?;
+            self.delete_collection(txn, col)?;
             Self::delete_schema(&mut info_cursor, col)?;
         }
         Ok(())
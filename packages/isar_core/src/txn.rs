@@ -1,17 +1,82 @@
-use crate::cursor::IsarCursors;
-use crate::error::{IsarError, Result};
+use crate::cursor::{CursorPool, IsarCursors};
+use crate::error::{illegal_arg, IsarError, Result};
 use crate::mdbx::cursor::UnboundCursor;
 use crate::mdbx::db::Db;
 use crate::mdbx::txn::Txn;
-use crate::watch::change_set::ChangeSet;
-use std::cell::RefCell;
+use crate::query::SlowQueryLog;
+use crate::txn_gate::ActiveWriter;
+use crate::watch::change_set::{ChangeSet, ChangeSetCheckpoint, PendingChanges};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 pub struct IsarTxn<'env> {
     instance_id: u64,
     txn: Txn<'env>,
+    savepoints: Savepoints<'env>,
     write: bool,
+    // a write job succeeded, the transaction has to be committed
+    written: bool,
     change_set: RefCell<Option<ChangeSet<'env>>>,
-    unbound_cursors: RefCell<Option<Vec<UnboundCursor>>>,
+    unbound_cursors: RefCell<Option<CursorPool>>,
+    slow_query_log: Option<Arc<SlowQueryLog>>,
+    scratch: RefCell<HashMap<String, Vec<i64>>>,
+    deferred_changes: Option<&'env Mutex<PendingChanges>>,
+    started_at: Instant,
+    strict_read_commit: bool,
+    abort_log: Option<&'env AbortLog>,
+    active_writer: Option<ActiveWriter<'env>>,
+}
+
+struct Savepoint<'env> {
+    parent: Txn<'env>,
+    changes: Option<ChangeSetCheckpoint>,
+    // a write inside the savepoint failed, its changes have to be rolled back
+    failed: Cell<bool>,
+}
+
+/// The open savepoints of a transaction, innermost last. They are dropped innermost first so
+/// every nested transaction is aborted before its parent.
+#[derive(Default)]
+struct Savepoints<'env>(Vec<Savepoint<'env>>);
+
+impl Drop for Savepoints<'_> {
+    fn drop(&mut self) {
+        while self.0.pop().is_some() {}
+    }
+}
+
+/// Remembers the ids and reasons of the most recent transactions aborted with
+/// [IsarTxn::abort_with_reason], oldest first.
+pub(crate) struct AbortLog {
+    capacity: usize,
+    entries: Mutex<VecDeque<(u64, String)>>,
+}
+
+impl AbortLog {
+    pub fn new(capacity: usize) -> Self {
+        AbortLog {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn record(&self, txn_id: u64, reason: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back((txn_id, reason.to_string()));
+    }
+
+    pub fn recent(&self) -> Vec<(u64, String)> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
 }
 
 impl<'env> IsarTxn<'env> {
@@ -20,21 +85,85 @@ impl<'env> IsarTxn<'env> {
         txn: Txn<'env>,
         write: bool,
         change_set: Option<ChangeSet<'env>>,
+        slow_query_log: Option<Arc<SlowQueryLog>>,
+        cursor_pool_size: usize,
     ) -> Result<Self> {
         Ok(IsarTxn {
             instance_id,
             txn,
+            savepoints: Savepoints::default(),
             write,
+            written: false,
             change_set: RefCell::new(change_set),
-            unbound_cursors: RefCell::new(Some(vec![])),
+            unbound_cursors: RefCell::new(Some(CursorPool::new(cursor_pool_size))),
+            slow_query_log,
+            scratch: RefCell::new(HashMap::new()),
+            deferred_changes: None,
+            started_at: Instant::now(),
+            strict_read_commit: false,
+            abort_log: None,
+            active_writer: None,
         })
     }
 
+    pub fn id(&self) -> u64 {
+        self.txn.id()
+    }
+
+    /// When the transaction began. Long running read transactions prevent MDBX from reusing the
+    /// pages of newer transactions which makes the file grow.
+    pub fn started_at(&self) -> Instant {
+        self.started_at
+    }
+
+    /// Whether this write transaction modified any data. Silent transactions do not track
+    /// their changes and report every successful write.
+    pub fn has_changes(&self) -> bool {
+        if !self.written {
+            return false;
+        }
+        if let Some(change_set) = self.change_set.borrow().as_ref() {
+            change_set.has_changes()
+        } else {
+            true
+        }
+    }
+
+    /// Collects the changes of this transaction in `pending` on commit instead of notifying the
+    /// watchers.
+    pub(crate) fn defer_notifications(&mut self, pending: &'env Mutex<PendingChanges>) {
+        self.deferred_changes = Some(pending);
+    }
+
+    pub(crate) fn set_abort_log(&mut self, log: &'env AbortLog) {
+        self.abort_log = Some(log);
+    }
+
+    pub(crate) fn set_active_writer(&mut self, writer: ActiveWriter<'env>) {
+        self.active_writer = Some(writer);
+    }
+
+    pub(crate) fn set_strict_read_commit(&mut self, strict: bool) {
+        self.strict_read_commit = strict;
+    }
+
+    pub(crate) fn is_write(&self) -> bool {
+        self.write
+    }
+
+    pub(crate) fn is_silent(&self) -> bool {
+        self.write && self.change_set.borrow().is_none()
+    }
+
+    pub(crate) fn slow_query_log(&self) -> Option<Arc<SlowQueryLog>> {
+        self.slow_query_log.clone()
+    }
+
     pub fn is_active(&self) -> bool {
         self.unbound_cursors.borrow().is_some()
     }
 
-    fn verify_instance_id(&self, instance_id: u64) -> Result<()> {
+    pub(crate) fn verify_instance_id(&self, instance_id: u64) -> Result<()> {
         if self.instance_id != instance_id {
             Err(IsarError::InstanceMismatch {})
         } else {
@@ -57,6 +186,23 @@ impl<'env> IsarTxn<'env> {
         }
     }
 
+    /// Like [IsarTxn::read] but the cursors stay open until the returned guard is dropped.
+    pub(crate) fn cursors<'txn>(
+        &'txn mut self,
+        instance_id: u64,
+    ) -> Result<TxnCursors<'txn, 'env>> {
+        self.verify_instance_id(instance_id)?;
+        let txn: &'txn Self = self;
+        if let Some(unbound_cursors) = txn.unbound_cursors.take() {
+            Ok(TxnCursors {
+                cursors: Some(IsarCursors::new(&txn.txn, unbound_cursors)),
+                unbound_cursors: &txn.unbound_cursors,
+            })
+        } else {
+            Err(IsarError::TransactionClosed {})
+        }
+    }
+
     pub(crate) fn write<'txn, T, F>(&'txn mut self, instance_id: u64, job: F) -> Result<T>
     where
         F: FnOnce(&IsarCursors<'txn, 'env>, Option<&mut ChangeSet<'_>>) -> Result<T>,
@@ -70,7 +216,13 @@ impl<'env> IsarTxn<'env> {
             let cursors = IsarCursors::new(&self.txn, unbound_cursors);
             let result = job(&cursors, change_set.as_mut());
             let unbounded_cursors = cursors.close();
-            if result.is_ok() {
+            self.written |= result.is_ok();
+            // a failed write inside a savepoint only invalidates the savepoint
+            let savepoint = self.savepoints.0.last();
+            if result.is_ok() || savepoint.is_some() {
+                if let (Err(_), Some(savepoint)) = (&result, savepoint) {
+                    savepoint.failed.set(true);
+                }
                 self.unbound_cursors.borrow_mut().replace(unbounded_cursors);
                 if let Some(change_set) = change_set {
                     self.change_set.borrow_mut().replace(change_set);
@@ -82,15 +234,160 @@ impl<'env> IsarTxn<'env> {
         }
     }
 
-    pub fn commit(self) -> Result<()> {
+    /// Creates a savepoint in a write transaction. The changes made after it can be undone with
+    /// [IsarTxn::rollback_to_savepoint] without aborting the transaction or kept with
+    /// [IsarTxn::release_savepoint]. Savepoints can be nested and are backed by nested MDBX
+    /// transactions.
+    pub fn savepoint(&mut self) -> Result<()> {
+        if !self.write {
+            return Err(IsarError::WriteTxnRequired {});
+        }
         if !self.is_active() {
             return Err(IsarError::TransactionClosed {});
         }
+        let child = self.txn.begin_nested()?;
+        let parent = std::mem::replace(&mut self.txn, child);
+        let changes = self.change_set.get_mut().as_ref().map(|c| c.checkpoint());
+        self.savepoints.0.push(Savepoint {
+            parent,
+            changes,
+            failed: Cell::new(false),
+        });
+        Ok(())
+    }
 
-        if self.write {
+    /// Undoes the changes since the most recent savepoint and removes it. Watchers are not
+    /// notified about the undone changes.
+    pub fn rollback_to_savepoint(&mut self) -> Result<()> {
+        let savepoint = self.pop_savepoint()?;
+        std::mem::replace(&mut self.txn, savepoint.parent).abort();
+        self.rollback_changes(savepoint.changes);
+        Ok(())
+    }
+
+    /// Keeps the changes since the most recent savepoint and removes it. If a write inside the
+    /// savepoint failed, its changes are rolled back instead and an error is returned.
+    pub fn release_savepoint(&mut self) -> Result<()> {
+        let savepoint = self.pop_savepoint()?;
+        let child = std::mem::replace(&mut self.txn, savepoint.parent);
+        if savepoint.failed.get() {
+            child.abort();
+            self.rollback_changes(savepoint.changes);
+            return illegal_arg("A write in the savepoint failed. Its changes were rolled back.");
+        }
+        if let Err(e) = child.commit() {
+            self.rollback_changes(savepoint.changes);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Runs `job` in a savepoint. Its changes are kept if it succeeds and rolled back otherwise.
+    pub fn with_savepoint<T>(
+        &mut self,
+        job: impl FnOnce(&mut IsarTxn<'env>) -> Result<T>,
+    ) -> Result<T> {
+        self.savepoint()?;
+        let depth = self.savepoints.0.len();
+        let result = job(self);
+        if self.savepoints.0.len() != depth {
+            return illegal_arg("The savepoint was released or rolled back inside the job.");
+        }
+        match result {
+            Ok(result) => {
+                self.release_savepoint()?;
+                Ok(result)
+            }
+            Err(e) => {
+                self.rollback_to_savepoint()?;
+                Err(e)
+            }
+        }
+    }
+
+    fn pop_savepoint(&mut self) -> Result<Savepoint<'env>> {
+        if !self.is_active() {
+            return Err(IsarError::TransactionClosed {});
+        }
+        self.savepoints
+            .0
+            .pop()
+            .map_or_else(|| illegal_arg("The transaction has no savepoint."), Ok)
+    }
+
+    fn rollback_changes(&mut self, checkpoint: Option<ChangeSetCheckpoint>) {
+        if let (Some(change_set), Some(checkpoint)) = (self.change_set.get_mut(), checkpoint) {
+            change_set.rollback(checkpoint);
+        }
+    }
+
+    /// Stores a set of ids under `key` for the lifetime of the transaction. The ids are sorted
+    /// and deduplicated. Existing ids for the same key are replaced.
+    pub fn scratch_put_ids(&self, key: &str, ids: &[i64]) -> Result<()> {
+        if !self.is_active() {
+            return Err(IsarError::TransactionClosed {});
+        }
+        let mut ids = ids.to_vec();
+        ids.sort_unstable();
+        ids.dedup();
+        self.scratch.borrow_mut().insert(key.to_string(), ids);
+        Ok(())
+    }
+
+    /// Returns the sorted ids stored under `key` in this transaction.
+    pub fn scratch_get_ids(&self, key: &str) -> Result<Option<Vec<i64>>> {
+        if !self.is_active() {
+            return Err(IsarError::TransactionClosed {});
+        }
+        Ok(self.scratch.borrow().get(key).cloned())
+    }
+
+    /// Returns the ids that are stored under both keys.
+    pub fn scratch_intersect_ids(&self, key1: &str, key2: &str) -> Result<Vec<i64>> {
+        if !self.is_active() {
+            return Err(IsarError::TransactionClosed {});
+        }
+        let scratch = self.scratch.borrow();
+        let empty = vec![];
+        let ids1 = scratch.get(key1).unwrap_or(&empty);
+        let ids2 = scratch.get(key2).unwrap_or(&empty);
+        Ok(intersect_sorted(ids1, ids2))
+    }
+
+    /// Commits a write transaction. Committing a read transaction releases its reader slot or
+    /// fails with [IsarError::ReadTxnCommit] if strict read commits are enabled for the instance.
+    /// The reader slot is released in both cases. Open savepoints are released first.
+    pub fn commit(mut self) -> Result<()> {
+        if !self.is_active() {
+            return Err(IsarError::TransactionClosed {});
+        }
+        while !self.savepoints.0.is_empty() {
+            self.release_savepoint()?;
+        }
+
+        if !self.write {
+            let strict = self.strict_read_commit;
+            self.txn.abort();
+            return if strict {
+                Err(IsarError::ReadTxnCommit {})
+            } else {
+                Ok(())
+            };
+        }
+
+        // writes that are not tracked by the change set still have to be persisted
+        if !self.written {
+            // nothing to persist, skip the sync and the watcher notifications
+            self.txn.abort();
+        } else {
             self.txn.commit()?;
             if let Some(change_set) = self.change_set.take() {
-                change_set.notify_watchers();
+                if let Some(pending) = self.deferred_changes {
+                    let changes = change_set.into_pending();
+                    pending.lock().unwrap().merge(changes);
+                } else {
+                    change_set.notify_watchers();
+                }
             }
         }
         Ok(())
@@ -100,6 +397,35 @@ impl<'env> IsarTxn<'env> {
         self.txn.abort()
     }
 
+    /// Aborts the transaction and records `reason` in [crate::instance::IsarInstance::recent_aborts].
+    pub fn abort_with_reason(self, reason: &str) {
+        if let Some(log) = self.abort_log {
+            log.record(self.id(), reason);
+        }
+        self.abort()
+    }
+
+    pub(crate) fn into_txn(self) -> Txn<'env> {
+        self.txn
+    }
+
+    #[cfg(feature = "raw-access")]
+    /// Opens the db `name`. Write transactions create it if it does not exist, read transactions
+    /// return `None` instead.
+    pub(crate) fn open_db(&self, name: &str) -> Result<Option<Db>> {
+        if self.write {
+            Db::open(&self.txn, Some(name), false, false, false).map(Some)
+        } else {
+            Db::open_if_exists(&self.txn, name)
+        }
+    }
+
+    pub(crate) fn drop_db(&mut self, name: &str) -> Result<()> {
+        Db::open_existing(&self.txn, name)?.drop(&self.txn)?;
+        self.written = true;
+        Ok(())
+    }
+
     pub(crate) fn db_names(&mut self) -> Result<Vec<String>> {
         let unnamed_db = Db::open(&self.txn, None, false, false, false)?;
         let cursor = UnboundCursor::new();
@@ -113,3 +439,80 @@ impl<'env> IsarTxn<'env> {
         Ok(names)
     }
 }
+
+/// Cursors of a transaction that are returned to it when dropped.
+pub(crate) struct TxnCursors<'txn, 'env> {
+    cursors: Option<IsarCursors<'txn, 'env>>,
+    unbound_cursors: &'txn RefCell<Option<CursorPool>>,
+}
+
+impl<'txn, 'env> Deref for TxnCursors<'txn, 'env> {
+    type Target = IsarCursors<'txn, 'env>;
+
+    fn deref(&self) -> &Self::Target {
+        self.cursors.as_ref().unwrap()
+    }
+}
+
+impl Drop for TxnCursors<'_, '_> {
+    fn drop(&mut self) {
+        let cursors = self.cursors.take().unwrap();
+        self.unbound_cursors.borrow_mut().replace(cursors.close());
+    }
+}
+
+fn intersect_sorted(ids1: &[i64], ids2: &[i64]) -> Vec<i64> {
+    let mut result = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < ids1.len() && j < ids2.len() {
+        match ids1[i].cmp(&ids2[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                result.push(ids1[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intersect_sorted() {
+        let ids1 = [-5, 1, 2, 3, 8, 13, 21];
+        let ids2 = [-5, 0, 2, 4, 8, 16, 21, 22];
+        let expected: Vec<i64> = ids1
+            .iter()
+            .copied()
+            .filter(|id| ids2.contains(id))
+            .collect();
+        assert_eq!(intersect_sorted(&ids1, &ids2), expected);
+        assert_eq!(intersect_sorted(&ids1, &[]), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn test_abort_log() {
+        let log = AbortLog::new(2);
+        assert!(log.recent().is_empty());
+        log.record(1, "conflict");
+        assert_eq!(log.recent(), vec![(1, "conflict".to_string())]);
+        log.record(2, "timeout");
+        log.record(3, "validation failed");
+        assert_eq!(
+            log.recent(),
+            vec![
+                (2, "timeout".to_string()),
+                (3, "validation failed".to_string())
+            ]
+        );
+
+        let disabled = AbortLog::new(0);
+        disabled.record(1, "conflict");
+        assert!(disabled.recent().is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+
+/// Lets waiting writers go ahead of new readers. While write priority is enabled, beginning a
+/// read transaction blocks as long as a writer is waiting for the write lock. Readers that are
+/// already running are not affected, so the added latency for readers is at most the time a
+/// writer needs to acquire the lock.
+///
+/// Reads begun while a write transaction is open never wait. The waiting writer cannot start
+/// before the open one finishes anyway and the thread holding the write lock may itself need a
+/// read transaction.
+pub(crate) struct TxnGate {
+    write_priority: AtomicBool,
+    writers: Mutex<Writers>,
+    writer_started: Condvar,
+}
+
+#[derive(Default)]
+struct Writers {
+    waiting: usize,
+    active: usize,
+}
+
+pub(crate) struct WaitingWriter<'a> {
+    gate: &'a TxnGate,
+}
+
+/// Registered for as long as a write transaction is open.
+pub(crate) struct ActiveWriter<'a> {
+    gate: &'a TxnGate,
+}
+
+impl TxnGate {
+    pub fn new() -> Self {
+        TxnGate {
+            write_priority: AtomicBool::new(false),
+            writers: Mutex::new(Writers::default()),
+            writer_started: Condvar::new(),
+        }
+    }
+
+    pub fn set_write_priority(&self, write_priority: bool) {
+        self.write_priority.store(write_priority, Ordering::Relaxed);
+        if !write_priority {
+            self.writer_started.notify_all();
+        }
+    }
+
+    /// Blocks until no writer is waiting if write priority is enabled and no write transaction
+    /// is open.
+    pub fn enter_read(&self) {
+        if !self.write_priority.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut writers = self.writers.lock().unwrap();
+        while writers.waiting > 0
+            && writers.active == 0
+            && self.write_priority.load(Ordering::Relaxed)
+        {
+            writers = self.writer_started.wait(writers).unwrap();
+        }
+    }
+
+    /// Registers a writer until the returned guard is dropped. Drop it as soon as the write
+    /// transaction has begun or failed to begin.
+    pub fn enter_write(&self) -> WaitingWriter<'_> {
+        self.writers.lock().unwrap().waiting += 1;
+        WaitingWriter { gate: self }
+    }
+}
+
+impl<'a> WaitingWriter<'a> {
+    /// Turns the waiting writer into an active one once its transaction has begun.
+    pub fn started(self) -> ActiveWriter<'a> {
+        self.gate.writers.lock().unwrap().active += 1;
+        ActiveWriter { gate: self.gate }
+    }
+}
+
+impl<'a> Drop for WaitingWriter<'a> {
+    fn drop(&mut self) {
+        let mut writers = self.gate.writers.lock().unwrap();
+        writers.waiting -= 1;
+        if writers.waiting == 0 {
+            self.gate.writer_started.notify_all();
+        }
+    }
+}
+
+impl<'a> Drop for ActiveWriter<'a> {
+    fn drop(&mut self) {
+        self.gate.writers.lock().unwrap().active -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::{Arc, RwLock};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_writer_not_starved() {
+        let gate = Arc::new(TxnGate::new());
+        gate.set_write_priority(true);
+        // Stands in for a lock that readers hold while their transaction is open.
+        let lock = Arc::new(RwLock::new(()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let reads = Arc::new(AtomicUsize::new(0));
+
+        let readers = (0..4)
+            .map(|_| {
+                let (gate, lock, stop, reads) =
+                    (gate.clone(), lock.clone(), stop.clone(), reads.clone());
+                thread::spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        gate.enter_read();
+                        let _read = lock.read().unwrap();
+                        reads.fetch_add(1, Ordering::Relaxed);
+                        thread::sleep(Duration::from_micros(200));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        while reads.load(Ordering::Relaxed) < 100 {
+            thread::yield_now();
+        }
+        let start = Instant::now();
+        for _ in 0..10 {
+            let waiting = gate.enter_write();
+            let _write = lock.write().unwrap();
+            drop(waiting);
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        stop.store(true, Ordering::Relaxed);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_read_during_write() {
+        let gate = TxnGate::new();
+        gate.set_write_priority(true);
+        let active = gate.enter_write().started();
+        let _waiting = gate.enter_write();
+        gate.enter_read();
+        drop(active);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let gate = TxnGate::new();
+        let _waiting = gate.enter_write();
+        gate.enter_read();
+
+        gate.set_write_priority(true);
+        gate.set_write_priority(false);
+        gate.enter_read();
+    }
+}
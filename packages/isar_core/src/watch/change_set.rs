@@ -2,11 +2,71 @@ use crate::object::isar_object::IsarObject;
 use crate::watch::isar_watchers::IsarWatchers;
 use crate::watch::watcher::Watcher;
 use intmap::IntMap;
+use itertools::Itertools;
 use std::sync::{Arc, MutexGuard};
 
+/// Changes of committed transactions whose watchers have not been notified yet.
+#[derive(Default)]
+pub(crate) struct PendingChanges {
+    changed_watchers: IntMap<Arc<Watcher>>,
+    changed_ids: IntMap<Vec<i64>>,
+}
+
+impl PendingChanges {
+    pub fn is_empty(&self) -> bool {
+        self.changed_watchers.is_empty() && self.changed_ids.is_empty()
+    }
+
+    pub fn merge(&mut self, other: PendingChanges) {
+        for (id, watcher) in other.changed_watchers {
+            self.changed_watchers.insert(id, watcher);
+        }
+        for (col_id, mut ids) in other.changed_ids {
+            if let Some(existing) = self.changed_ids.get_mut(col_id) {
+                existing.append(&mut ids);
+            } else {
+                self.changed_ids.insert(col_id, ids);
+            }
+        }
+    }
+
+    /// Calls the watchers after releasing the lock so callbacks may use the instance.
+    pub fn notify(self, mut watchers: MutexGuard<IsarWatchers>) {
+        let mut ids_callbacks = vec![];
+        for (col_id, mut ids) in self.changed_ids {
+            if watchers.has_ids_watchers(col_id) {
+                ids.sort_unstable();
+                ids.dedup();
+                let cw = watchers.get_col_watchers(col_id);
+                let callbacks = cw.ids_watchers.iter().map(|(_, c)| c.clone()).collect_vec();
+                ids_callbacks.push((ids, callbacks));
+            }
+        }
+        drop(watchers);
+
+        for watcher in self.changed_watchers.values() {
+            watcher.notify();
+        }
+        for (ids, callbacks) in ids_callbacks {
+            for callback in callbacks {
+                callback(&ids);
+            }
+        }
+    }
+}
+
+/// The state of a [ChangeSet] when a savepoint was created.
+pub(crate) struct ChangeSetCheckpoint {
+    changed_watchers: IntMap<()>,
+    changed_ids: IntMap<usize>,
+    has_changes: bool,
+}
+
 pub(crate) struct ChangeSet<'a> {
     watchers: MutexGuard<'a, IsarWatchers>,
     changed_watchers: IntMap<Arc<Watcher>>,
+    changed_ids: IntMap<Vec<i64>>,
+    has_changes: bool,
 }
 
 impl<'a> ChangeSet<'a> {
@@ -14,9 +74,20 @@ impl<'a> ChangeSet<'a> {
         ChangeSet {
             watchers,
             changed_watchers: IntMap::new(),
+            changed_ids: IntMap::new(),
+            has_changes: false,
         }
     }
 
+    pub fn has_changes(&self) -> bool {
+        self.has_changes
+    }
+
+    /// Marks the transaction as changed for writes that are not observed by watchers.
+    pub fn mark_changed(&mut self) {
+        self.has_changes = true;
+    }
+
     fn register_watchers(changed_watchers: &mut IntMap<Arc<Watcher>>, watchers: &[Arc<Watcher>]) {
         for w in watchers {
             let registered = changed_watchers.contains_key(w.get_id());
@@ -28,7 +99,24 @@ impl<'a> ChangeSet<'a> {
         }
     }
 
+    pub fn has_ids_watchers(&self, col_id: u64) -> bool {
+        self.watchers.has_ids_watchers(col_id)
+    }
+
+    fn register_id(&mut self, col_id: u64, id: i64) {
+        if let Some(ids) = self.changed_ids.get_mut(col_id) {
+            ids.push(id);
+        } else {
+            self.changed_ids.insert(col_id, vec![id]);
+        }
+    }
+
     pub fn register_change(&mut self, col_id: u64, id: i64, object: IsarObject) {
+        self.has_changes = true;
+        if self.has_ids_watchers(col_id) {
+            self.register_id(col_id, id);
+        }
+
         let cw = self.watchers.get_col_watchers(col_id);
         Self::register_watchers(&mut self.changed_watchers, &cw.watchers);
         if let Some(object_watchers) = cw.object_watchers.get(id as u64) {
@@ -44,7 +132,14 @@ impl<'a> ChangeSet<'a> {
         }
     }
 
+    pub fn register_all_ids(&mut self, col_id: u64, ids: &[i64]) {
+        for id in ids {
+            self.register_id(col_id, *id);
+        }
+    }
+
     pub fn register_all(&mut self, col_id: u64) {
+        self.has_changes = true;
         let cw = self.watchers.get_col_watchers(col_id);
         Self::register_watchers(&mut self.changed_watchers, &cw.watchers);
         for watchers in cw.object_watchers.values() {
@@ -55,9 +150,161 @@ impl<'a> ChangeSet<'a> {
         }
     }
 
+    pub fn checkpoint(&self) -> ChangeSetCheckpoint {
+        let mut changed_watchers = IntMap::new();
+        for id in self.changed_watchers.keys() {
+            changed_watchers.insert(*id, ());
+        }
+        let mut changed_ids = IntMap::new();
+        for (col_id, ids) in self.changed_ids.iter() {
+            changed_ids.insert(*col_id, ids.len());
+        }
+        ChangeSetCheckpoint {
+            changed_watchers,
+            changed_ids,
+            has_changes: self.has_changes,
+        }
+    }
+
+    /// Forgets the changes registered since `checkpoint` was taken.
+    pub fn rollback(&mut self, checkpoint: ChangeSetCheckpoint) {
+        self.changed_watchers
+            .retain(|id, _| checkpoint.changed_watchers.contains_key(id));
+        self.changed_ids
+            .retain(|col_id, _| checkpoint.changed_ids.contains_key(col_id));
+        for (col_id, ids) in self.changed_ids.iter_mut() {
+            ids.truncate(*checkpoint.changed_ids.get(*col_id).unwrap());
+        }
+        self.has_changes = checkpoint.has_changes;
+    }
+
+    /// Releases the watchers lock and returns the recorded changes.
+    pub fn into_pending(self) -> PendingChanges {
+        PendingChanges {
+            changed_watchers: self.changed_watchers,
+            changed_ids: self.changed_ids,
+        }
+    }
+
     pub fn notify_watchers(self) {
-        for watcher in self.changed_watchers.values() {
-            watcher.notify();
+        let pending = PendingChanges {
+            changed_watchers: self.changed_watchers,
+            changed_ids: self.changed_ids,
+        };
+        pending.notify(self.watchers);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::unbounded;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_has_changes() {
+        let (_tx, rx) = unbounded();
+        let watchers = Mutex::new(IsarWatchers::new(rx));
+
+        let change_set = ChangeSet::new(watchers.lock().unwrap());
+        assert!(!change_set.has_changes());
+        drop(change_set);
+
+        let mut change_set = ChangeSet::new(watchers.lock().unwrap());
+        change_set.register_all(1);
+        assert!(change_set.has_changes());
+        drop(change_set);
+
+        let mut change_set = ChangeSet::new(watchers.lock().unwrap());
+        change_set.mark_changed();
+        assert!(change_set.has_changes());
+    }
+
+    #[test]
+    fn test_rollback() {
+        let (_tx, rx) = unbounded();
+        let watchers = Mutex::new(IsarWatchers::new(rx));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_clone = calls.clone();
+        watchers.lock().unwrap().get_col_watchers(2).add_watcher(
+            7,
+            Box::new(move || {
+                calls_clone.fetch_add(1, Ordering::SeqCst);
+            }),
+        );
+
+        let mut change_set = ChangeSet::new(watchers.lock().unwrap());
+        change_set.register_all_ids(1, &[1, 2]);
+        let checkpoint = change_set.checkpoint();
+        change_set.register_all_ids(1, &[3]);
+        change_set.register_all(2);
+        change_set.register_all_ids(2, &[4]);
+        assert!(change_set.has_changes());
+
+        change_set.rollback(checkpoint);
+        assert!(!change_set.has_changes());
+        let pending = change_set.into_pending();
+        assert_eq!(pending.changed_ids.get(1), Some(&vec![1, 2]));
+        assert!(pending.changed_ids.get(2).is_none());
+        pending.notify(watchers.lock().unwrap());
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_pending_changes() {
+        let (_tx, rx) = unbounded();
+        let watchers = Mutex::new(IsarWatchers::new(rx));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_clone = calls.clone();
+        watchers.lock().unwrap().get_col_watchers(1).add_watcher(
+            7,
+            Box::new(move || {
+                calls_clone.fetch_add(1, Ordering::SeqCst);
+            }),
+        );
+
+        let mut pending = PendingChanges::default();
+        for _ in 0..3 {
+            let mut change_set = ChangeSet::new(watchers.lock().unwrap());
+            change_set.register_all(1);
+            pending.merge(change_set.into_pending());
         }
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert!(!pending.is_empty());
+
+        pending.notify(watchers.lock().unwrap());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_notify_ids_without_lock() {
+        let (_tx, rx) = unbounded();
+        let watchers = Arc::new(Mutex::new(IsarWatchers::new(rx)));
+        let weak = Arc::downgrade(&watchers);
+        let reported = Arc::new(Mutex::new(vec![]));
+        let reported_clone = reported.clone();
+        watchers
+            .lock()
+            .unwrap()
+            .get_col_watchers(1)
+            .add_ids_watcher(
+                7,
+                Box::new(move |ids| {
+                    let unlocked = weak.upgrade().unwrap().try_lock().is_ok();
+                    reported_clone
+                        .lock()
+                        .unwrap()
+                        .push((ids.to_vec(), unlocked));
+                }),
+            );
+
+        let mut change_set = ChangeSet::new(watchers.lock().unwrap());
+        assert!(change_set.has_ids_watchers(1));
+        assert!(!change_set.has_ids_watchers(2));
+        change_set.register_all_ids(1, &[3, 1, 2, 3]);
+        change_set.register_all_ids(2, &[4]);
+        change_set.notify_watchers();
+        assert_eq!(*reported.lock().unwrap(), vec![(vec![1, 2, 3], true)]);
     }
 }
@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Runs a task on a background thread some time after it was triggered. Triggers that arrive
+/// while a run is pending are merged into it. Runs never overlap, so a run that was triggered
+/// later always finishes later.
+#[derive(Clone)]
+pub(crate) struct Debouncer {
+    delay: Duration,
+    scheduled: Arc<AtomicBool>,
+    running: Arc<Mutex<()>>,
+    task: Arc<dyn Fn() + Send + Sync>,
+}
+
+impl Debouncer {
+    pub fn new(delay: Duration, task: impl Fn() + Send + Sync + 'static) -> Self {
+        Debouncer {
+            delay,
+            scheduled: Arc::new(AtomicBool::new(false)),
+            running: Arc::new(Mutex::new(())),
+            task: Arc::new(task),
+        }
+    }
+
+    pub fn trigger(&self) {
+        if self.scheduled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let debouncer = self.clone();
+        thread::spawn(move || {
+            if !debouncer.delay.is_zero() {
+                thread::sleep(debouncer.delay);
+            }
+            let _running = debouncer.running.lock().unwrap();
+            // triggers from here on need another run to observe their changes
+            debouncer.scheduled.store(false, Ordering::SeqCst);
+            (debouncer.task)();
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Instant;
+
+    fn wait_for(counter: &AtomicUsize, value: usize) {
+        let start = Instant::now();
+        while counter.load(Ordering::SeqCst) < value {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_debouncer() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let task_runs = runs.clone();
+        let debouncer = Debouncer::new(Duration::from_millis(50), move || {
+            task_runs.fetch_add(1, Ordering::SeqCst);
+        });
+
+        for _ in 0..10 {
+            debouncer.trigger();
+        }
+        wait_for(&runs, 1);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        debouncer.trigger();
+        wait_for(&runs, 2);
+    }
+
+    #[test]
+    fn test_trigger_during_run() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let task_runs = runs.clone();
+        let debouncer = Debouncer::new(Duration::ZERO, move || {
+            thread::sleep(Duration::from_millis(20));
+            task_runs.fetch_add(1, Ordering::SeqCst);
+        });
+
+        debouncer.trigger();
+        thread::sleep(Duration::from_millis(5));
+        debouncer.trigger();
+        wait_for(&runs, 2);
+    }
+}
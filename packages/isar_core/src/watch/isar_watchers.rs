@@ -1,5 +1,7 @@
 use crate::query::Query;
-use crate::watch::watcher::{Watcher, WatcherCallback};
+use crate::watch::watcher::{
+    IdsWatcherCallback, SharedIdsWatcherCallback, Watcher, WatcherCallback,
+};
 use crossbeam_channel::Receiver;
 use intmap::IntMap;
 use itertools::Itertools;
@@ -28,6 +30,13 @@ impl IsarWatchers {
         self.collection_watchers.get_mut(col_id).unwrap()
     }
 
+    /// Unlike [IsarWatchers::get_col_watchers] this does not register the collection.
+    pub(crate) fn has_ids_watchers(&self, col_id: u64) -> bool {
+        self.collection_watchers
+            .get(col_id)
+            .is_some_and(|cw| !cw.ids_watchers.is_empty())
+    }
+
     pub(crate) fn sync(&mut self) {
         let modifiers = self.modifiers.try_iter().collect_vec();
         for modifier in modifiers {
@@ -40,6 +49,7 @@ pub struct IsarCollectionWatchers {
     pub(super) watchers: Vec<Arc<Watcher>>,
     pub(super) object_watchers: IntMap<Vec<Arc<Watcher>>>,
     pub(super) query_watchers: Vec<(Query, Arc<Watcher>)>,
+    pub(super) ids_watchers: Vec<(u64, SharedIdsWatcherCallback)>,
 }
 
 impl IsarCollectionWatchers {
@@ -48,6 +58,7 @@ impl IsarCollectionWatchers {
             watchers: Vec::new(),
             object_watchers: IntMap::new(),
             query_watchers: Vec::new(),
+            ids_watchers: Vec::new(),
         }
     }
 
@@ -96,4 +107,12 @@ impl IsarCollectionWatchers {
             .unwrap();
         self.query_watchers.remove(position);
     }
+
+    pub fn add_ids_watcher(&mut self, watcher_id: u64, callback: IdsWatcherCallback) {
+        self.ids_watchers.push((watcher_id, Arc::from(callback)));
+    }
+
+    pub fn remove_ids_watcher(&mut self, watcher_id: u64) {
+        self.ids_watchers.retain(|(id, _)| *id != watcher_id);
+    }
 }
@@ -1,4 +1,5 @@
 pub(crate) mod change_set;
+pub(crate) mod debouncer;
 pub(crate) mod isar_watchers;
 pub(crate) mod watcher;
 
@@ -1,5 +1,12 @@
+use std::sync::Arc;
+
 pub type WatcherCallback = Box<dyn Fn() + Send + Sync + 'static>;
 
+pub type IdsWatcherCallback = Box<dyn Fn(&[i64]) + Send + Sync + 'static>;
+
+/// Shared so the callback can be called after the watchers lock has been released.
+pub(super) type SharedIdsWatcherCallback = Arc<dyn Fn(&[i64]) + Send + Sync + 'static>;
+
 pub(super) struct Watcher {
     id: u64,
     callback: WatcherCallback,
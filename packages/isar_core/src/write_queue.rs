@@ -0,0 +1,108 @@
+use crate::error::{IsarError, Result};
+use crate::instance::IsarInstance;
+use crate::txn::IsarTxn;
+use crossbeam_channel::{bounded, Receiver, Sender};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// A write operation that can be batched with the operations of other threads. It may be
+/// executed more than once if another operation of the same batch fails.
+pub type WriteOp = Box<dyn Fn(&mut IsarTxn) -> Result<()> + Send + Sync + 'static>;
+
+struct PendingWrite {
+    op: WriteOp,
+    result: Sender<Result<()>>,
+}
+
+/// Combines the queued writes of all threads into as few write transactions as possible. The
+/// thread that acquires the writer lock executes everything that is queued at that point.
+pub(crate) struct WriteQueue {
+    sender: Sender<PendingWrite>,
+    receiver: Receiver<PendingWrite>,
+    writer: Mutex<()>,
+    batches: AtomicU64,
+}
+
+impl WriteQueue {
+    const CAPACITY: usize = 1024;
+    const MAX_BATCH_SIZE: usize = 256;
+
+    pub fn new() -> Self {
+        let (sender, receiver) = bounded(Self::CAPACITY);
+        WriteQueue {
+            sender,
+            receiver,
+            writer: Mutex::new(()),
+            batches: AtomicU64::new(0),
+        }
+    }
+
+    pub fn batch_count(&self) -> u64 {
+        self.batches.load(Ordering::Relaxed)
+    }
+
+    /// Blocks while the queue is full and until the operation has been committed.
+    pub fn submit(&self, instance: &IsarInstance, op: WriteOp) -> Result<()> {
+        let (result_sender, result) = bounded(1);
+        let pending = PendingWrite {
+            op,
+            result: result_sender,
+        };
+        self.sender.send(pending).unwrap();
+
+        loop {
+            if let Ok(result) = result.try_recv() {
+                return result;
+            }
+            let _lock = self.writer.lock().unwrap();
+            if let Ok(result) = result.try_recv() {
+                return result;
+            }
+            let batch = self
+                .receiver
+                .try_iter()
+                .take(Self::MAX_BATCH_SIZE)
+                .collect();
+            self.run_batch(instance, batch);
+        }
+    }
+
+    fn run_batch(&self, instance: &IsarInstance, mut batch: Vec<PendingWrite>) {
+        while !batch.is_empty() {
+            match Self::try_batch(instance, &batch) {
+                Ok(()) => {
+                    self.batches.fetch_add(1, Ordering::Relaxed);
+                    for write in batch {
+                        let _ = write.result.send(Ok(()));
+                    }
+                    return;
+                }
+                Err((Some(failed), e)) => {
+                    // retry the other operations without the failed one
+                    let write = batch.remove(failed);
+                    let _ = write.result.send(Err(e));
+                }
+                Err((None, e)) => {
+                    for write in batch {
+                        let _ = write.result.send(Err(e.clone()));
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    fn try_batch(
+        instance: &IsarInstance,
+        batch: &[PendingWrite],
+    ) -> std::result::Result<(), (Option<usize>, IsarError)> {
+        let mut txn = instance.begin_txn(true, false).map_err(|e| (None, e))?;
+        for (i, write) in batch.iter().enumerate() {
+            if let Err(e) = (write.op)(&mut txn) {
+                txn.abort();
+                return Err((Some(i), e));
+            }
+        }
+        txn.commit().map_err(|e| (None, e))
+    }
+}
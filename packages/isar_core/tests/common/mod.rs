@@ -0,0 +1,75 @@
+#![allow(dead_code)]
+
+use isar_core::collection::IsarCollection;
+use isar_core::instance::IsarInstance;
+use isar_core::object::object_builder::ObjectBuilder;
+use isar_core::object::property::Property;
+use isar_core::schema::Schema;
+use isar_core::txn::IsarTxn;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A name that is unique within the test process so instances and directories never collide.
+pub fn unique_name(prefix: &str) -> String {
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{}_{}_{}", prefix, std::process::id(), n)
+}
+
+pub fn test_dir(name: &str) -> String {
+    let dir = std::env::temp_dir().join("isar_tests").join(name);
+    dir.to_str().unwrap().to_string()
+}
+
+pub fn open_in(name: &str, dir: &str, schema: &str) -> Arc<IsarInstance> {
+    let schema = Schema::from_json(schema.as_bytes()).unwrap();
+    IsarInstance::open(name, Some(dir), schema, 64, None, true, None, true, None).unwrap()
+}
+
+/// Opens a new instance in its own directory.
+pub fn open(prefix: &str, schema: &str) -> Arc<IsarInstance> {
+    let name = unique_name(prefix);
+    open_in(&name, &test_dir(&name), schema)
+}
+
+pub fn close_and_delete(instance: Arc<IsarInstance>) {
+    let dir = instance.dir.clone();
+    instance.close_and_delete();
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+pub fn property(col: &IsarCollection, name: &str) -> Property {
+    col.properties
+        .iter()
+        .find(|p| p.name == name)
+        .unwrap()
+        .clone()
+}
+
+/// Builds an object by writing the given values as longs or strings.
+pub fn build(col: &IsarCollection, values: &[(&str, Value)]) -> Vec<u8> {
+    let mut builder = col.new_object_builder(None);
+    for p in &col.properties {
+        match values.iter().find(|(name, _)| *name == p.name) {
+            Some((_, Value::Long(v))) => builder.write_long(p.offset, *v),
+            Some((_, Value::String(v))) => builder.write_string(p.offset, Some(v)),
+            None => builder.write_null(p.offset, p.data_type),
+        }
+    }
+    builder.finish().as_bytes().to_vec()
+}
+
+pub enum Value<'a> {
+    Long(i64),
+    String(&'a str),
+}
+
+pub fn put(col: &IsarCollection, txn: &mut IsarTxn, id: i64, values: &[(&str, Value)]) {
+    let bytes = build(col, values);
+    col.put_raw(txn, Some(id), &bytes).unwrap();
+}
+
+pub fn builder_of(col: &IsarCollection) -> ObjectBuilder {
+    col.new_object_builder(None)
+}
@@ -0,0 +1,977 @@
+mod common;
+
+use common::*;
+use isar_core::collection::{ImportResult, InsertConflict, IsarCollection};
+use isar_core::error::IsarError;
+use isar_core::index::index_key::IndexKey;
+use isar_core::instance::{CloseResult, IsarInstance};
+use isar_core::object::isar_object::IsarObject;
+use isar_core::query::Query;
+use isar_core::schema::Schema;
+use isar_core::txn::IsarTxn;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use xxhash_rust::xxh3::{xxh3_64, xxh3_64_with_seed};
+
+const SCHEMA: &str = r#"[{"name": "Item", "properties": [{"name": "value", "type": "Long"}]}]"#;
+
+#[test]
+fn test_is_empty() {
+    let instance = open("collection_empty", SCHEMA);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    assert!(col.is_empty(&mut txn).unwrap());
+    put(col, &mut txn, 5, &[("value", Value::Long(1))]);
+    assert!(!col.is_empty(&mut txn).unwrap());
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    assert!(!col.is_empty(&mut txn).unwrap());
+    col.clear(&mut txn).unwrap();
+    assert!(col.is_empty(&mut txn).unwrap());
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert!(col.is_empty(&mut txn).unwrap());
+    txn.abort();
+    close_and_delete(instance);
+}
+
+fn value(col: &IsarCollection, txn: &mut IsarTxn, id: i64) -> Option<i64> {
+    let offset = property(col, "value").offset;
+    let object = col.get(txn, id).unwrap();
+    object.map(|object| object.read_long(offset))
+}
+
+fn id_violated(id: i64) -> IsarError {
+    IsarError::UniqueViolated {
+        index_name: "id".to_string(),
+        key: id.to_string(),
+    }
+}
+
+#[test]
+fn test_insert_conflict() {
+    let batch = r#"[{"id": 2, "value": 99}, {"id": 3, "value": 30}]"#;
+    for conflict in [
+        InsertConflict::Overwrite,
+        InsertConflict::Ignore,
+        InsertConflict::Abort,
+    ] {
+        let instance = open("collection_conflict", SCHEMA);
+        let col = &instance.collections[0];
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        put(col, &mut txn, 1, &[("value", Value::Long(10))]);
+        put(col, &mut txn, 2, &[("value", Value::Long(20))]);
+        txn.commit().unwrap();
+
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        let result =
+            col.import_json_reader_with_conflict(&mut txn, Some("id"), batch.as_bytes(), conflict);
+        let expected_values = match conflict {
+            InsertConflict::Overwrite => {
+                let expected = ImportResult {
+                    inserted: 1,
+                    skipped: 0,
+                    overwritten: 1,
+                };
+                assert_eq!(result.unwrap(), expected);
+                txn.commit().unwrap();
+                [Some(10), Some(99), Some(30)]
+            }
+            InsertConflict::Ignore => {
+                let expected = ImportResult {
+                    inserted: 1,
+                    skipped: 1,
+                    overwritten: 0,
+                };
+                assert_eq!(result.unwrap(), expected);
+                txn.commit().unwrap();
+                [Some(10), Some(20), Some(30)]
+            }
+            InsertConflict::Abort => {
+                assert_eq!(result, Err(id_violated(2)));
+                txn.abort();
+                [Some(10), Some(20), None]
+            }
+        };
+
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        for (id, expected) in (1..=3).zip(expected_values) {
+            assert_eq!(value(col, &mut txn, id), expected);
+        }
+        txn.abort();
+
+        // single puts follow the same rules
+        let bytes = build(col, &[("value", Value::Long(7))]);
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        let result =
+            col.put_with_conflict(&mut txn, Some(1), IsarObject::from_bytes(&bytes), conflict);
+        match conflict {
+            InsertConflict::Overwrite => assert_eq!(result, Ok(Some(1))),
+            InsertConflict::Ignore => assert_eq!(result, Ok(None)),
+            InsertConflict::Abort => assert_eq!(result, Err(id_violated(1))),
+        }
+        txn.abort();
+
+        // batches count the skipped and overwritten objects
+        let existing = build(col, &[("value", Value::Long(8))]);
+        let new = build(col, &[("value", Value::Long(9))]);
+        let objects = [
+            (Some(3), IsarObject::from_bytes(&new)),
+            (Some(1), IsarObject::from_bytes(&existing)),
+            (Some(4), IsarObject::from_bytes(&new)),
+        ];
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        let result = col.put_all_with_conflict(&mut txn, &objects, conflict);
+        match conflict {
+            InsertConflict::Overwrite => {
+                let (ids, result) = result.unwrap();
+                assert_eq!(ids, vec![Some(3), Some(1), Some(4)]);
+                assert_eq!((result.inserted, result.overwritten), (1, 2));
+            }
+            InsertConflict::Ignore => {
+                let (ids, result) = result.unwrap();
+                assert_eq!(ids, vec![None, None, Some(4)]);
+                assert_eq!((result.inserted, result.skipped), (1, 2));
+            }
+            InsertConflict::Abort => assert_eq!(result, Err(id_violated(1))),
+        }
+        txn.abort();
+        close_and_delete(instance);
+    }
+}
+
+const TEMPORARY_SCHEMA: &str = r#"[
+    {"name": "Item", "properties": [{"name": "value", "type": "Long"}]},
+    {"name": "Scratch", "properties": [{"name": "value", "type": "Long"}], "temporary": true}
+]"#;
+
+fn collection<'a>(instance: &'a IsarInstance, name: &str) -> &'a IsarCollection {
+    instance
+        .collections
+        .iter()
+        .find(|c| c.name == name)
+        .unwrap()
+}
+
+#[test]
+fn test_get_many() {
+    let instance = open("collection_get_many", SCHEMA);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in [4, 1, 9, -3] {
+        put(col, &mut txn, id, &[("value", Value::Long(id * 10))]);
+    }
+    txn.commit().unwrap();
+
+    let ids = [9, 2, -3, 9, 1, 100, 4];
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let offset = property(col, "value").offset;
+    let values: Vec<_> = col
+        .get_many(&mut txn, &ids)
+        .unwrap()
+        .into_iter()
+        .map(|object| object.map(|o| o.read_long(offset)))
+        .collect();
+    let expected = [
+        Some(90),
+        None,
+        Some(-30),
+        Some(90),
+        Some(10),
+        None,
+        Some(40),
+    ];
+    assert_eq!(values, expected);
+    assert!(col.get_many(&mut txn, &[]).unwrap().is_empty());
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_import_json_twice() {
+    let json = r#"[{"id": 1, "value": 10}, {"id": 2, "value": 20}, {"id": 3, "value": 30}]"#;
+    for conflict in [
+        InsertConflict::Overwrite,
+        InsertConflict::Ignore,
+        InsertConflict::Abort,
+    ] {
+        let instance = open("collection_import_twice", SCHEMA);
+        let col = &instance.collections[0];
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        let result =
+            col.import_json_reader_with_conflict(&mut txn, Some("id"), json.as_bytes(), conflict);
+        let expected = ImportResult {
+            inserted: 3,
+            skipped: 0,
+            overwritten: 0,
+        };
+        assert_eq!(result.unwrap(), expected);
+        txn.commit().unwrap();
+
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        let result =
+            col.import_json_reader_with_conflict(&mut txn, Some("id"), json.as_bytes(), conflict);
+        match conflict {
+            InsertConflict::Overwrite => {
+                let expected = ImportResult {
+                    inserted: 0,
+                    skipped: 0,
+                    overwritten: 3,
+                };
+                assert_eq!(result.unwrap(), expected);
+                txn.commit().unwrap();
+            }
+            InsertConflict::Ignore => {
+                let expected = ImportResult {
+                    inserted: 0,
+                    skipped: 3,
+                    overwritten: 0,
+                };
+                assert_eq!(result.unwrap(), expected);
+                txn.commit().unwrap();
+            }
+            InsertConflict::Abort => {
+                assert_eq!(result, Err(id_violated(1)));
+                txn.abort();
+            }
+        }
+
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        assert_eq!(col.count(&mut txn).unwrap(), 3);
+        for id in 1..=3 {
+            assert_eq!(value(col, &mut txn, id), Some(id * 10));
+        }
+        txn.abort();
+        close_and_delete(instance);
+    }
+}
+#[test]
+fn test_export_json() {
+    let schema = r#"[{"name": "Item", "properties": [
+        {"name": "name", "type": "String"},
+        {"name": "value", "type": "Long"}
+    ]}]"#;
+    let instance = open("collection_export_json", schema);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(
+        col,
+        &mut txn,
+        1,
+        &[("name", Value::String("a")), ("value", Value::Long(1))],
+    );
+    put(col, &mut txn, 2, &[("value", Value::Long(2))]);
+    put(col, &mut txn, 3, &[]);
+    txn.commit().unwrap();
+
+    let export = |query: Option<&Query>| {
+        let mut json = vec![];
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        let mut serializer = serde_json::Serializer::new(&mut json);
+        col.export_json(&mut txn, Some("id"), query, &mut serializer)
+            .unwrap();
+        txn.abort();
+        json
+    };
+    let json = export(None);
+    let expected = serde_json::json!([
+        {"id": 1, "name": "a", "value": 1},
+        {"id": 2, "name": null, "value": 2},
+        {"id": 3, "name": null, "value": null}
+    ]);
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&json).unwrap(),
+        expected
+    );
+
+    let mut qb = col.new_query_builder();
+    qb.set_filter_expression("value >= 2").unwrap();
+    let query = qb.build().unwrap();
+    let filtered = export(Some(&query));
+    let filtered: serde_json::Value = serde_json::from_slice(&filtered).unwrap();
+    assert_eq!(filtered, serde_json::json!([expected[1]]));
+
+    // importing the export restores the same objects
+    let copy = open("collection_export_json_copy", schema);
+    let copy_col = &copy.collections[0];
+    let mut txn = copy.begin_txn(true, false).unwrap();
+    assert_eq!(
+        copy_col.import_json_reader(&mut txn, Some("id"), json.as_slice()),
+        Ok(3)
+    );
+    txn.commit().unwrap();
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let mut copy_txn = copy.begin_txn(false, false).unwrap();
+    for id in 1..=3 {
+        let original = col.get(&mut txn, id).unwrap().unwrap();
+        let copied = copy_col.get(&mut copy_txn, id).unwrap().unwrap();
+        assert_eq!(original.as_bytes(), copied.as_bytes());
+    }
+    txn.abort();
+    copy_txn.abort();
+    close_and_delete(instance);
+    close_and_delete(copy);
+}
+
+#[test]
+fn test_temporary_collection() {
+    let name = unique_name("collection_temporary");
+    let dir = test_dir(&name);
+    let instance = open_in(&name, &dir, TEMPORARY_SCHEMA);
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for col_name in ["Item", "Scratch"] {
+        let col = collection(&instance, col_name);
+        put(col, &mut txn, 1, &[("value", Value::Long(1))]);
+        put(col, &mut txn, 2, &[("value", Value::Long(2))]);
+    }
+    txn.commit().unwrap();
+
+    // during the session the temporary collection behaves like any other
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert_eq!(collection(&instance, "Scratch").count(&mut txn).unwrap(), 2);
+    txn.abort();
+    assert!(matches!(instance.close(), CloseResult::Closed));
+
+    let instance = open_in(&name, &dir, TEMPORARY_SCHEMA);
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert_eq!(collection(&instance, "Item").count(&mut txn).unwrap(), 2);
+    assert!(collection(&instance, "Scratch").is_empty(&mut txn).unwrap());
+    txn.abort();
+    close_and_delete(instance);
+}
+
+const TTL_SCHEMA: &str = r#"[
+    {
+        "name": "Session",
+        "properties": [{"name": "expiresAt", "type": "Long"}],
+        "ttl": "expiresAt"
+    },
+    {
+        "name": "IndexedSession",
+        "properties": [{"name": "expiresAt", "type": "Long"}],
+        "indexes": [{
+            "name": "expiresAt",
+            "properties": [{"name": "expiresAt", "type": "Value", "caseSensitive": false}],
+            "unique": false
+        }],
+        "ttl": "expiresAt"
+    }
+]"#;
+
+#[test]
+fn test_purge_expired() {
+    let instance = open("collection_ttl", TTL_SCHEMA);
+    for col in &instance.collections {
+        let reported = Arc::new(Mutex::new(vec![]));
+        let reported_clone = reported.clone();
+        let _handle = instance.watch_collection_ids(
+            col,
+            Box::new(move |ids: &[i64]| reported_clone.lock().unwrap().push(ids.to_vec())),
+        );
+
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        for (id, expires_at) in [(1, 50), (2, 100), (3, 150), (4, -10)] {
+            put(col, &mut txn, id, &[("expiresAt", Value::Long(expires_at))]);
+        }
+        // objects without an expiry are kept
+        put(col, &mut txn, 5, &[]);
+        txn.commit().unwrap();
+        reported.lock().unwrap().clear();
+
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        assert_eq!(col.purge_expired(&mut txn, 100).unwrap(), 3);
+        txn.commit().unwrap();
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        for id in 1..=5 {
+            let kept = col.get(&mut txn, id).unwrap().is_some();
+            assert_eq!(kept, id == 3 || id == 5);
+        }
+        txn.abort();
+        assert_eq!(*reported.lock().unwrap(), vec![vec![1, 2, 4]]);
+
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        assert_eq!(col.purge_expired(&mut txn, 100).unwrap(), 0);
+        txn.commit().unwrap();
+    }
+
+    let instance_without_ttl = open("collection_no_ttl", SCHEMA);
+    let mut txn = instance_without_ttl.begin_txn(true, false).unwrap();
+    assert!(matches!(
+        instance_without_ttl.collections[0].purge_expired(&mut txn, 0),
+        Err(IsarError::IllegalArg { .. })
+    ));
+    txn.abort();
+    close_and_delete(instance_without_ttl);
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_id_range() {
+    let instance = open("collection_id_range", SCHEMA);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    assert_eq!(col.id_range(&mut txn).unwrap(), None);
+
+    for id in [40, -7, 9842, 3, 500] {
+        put(col, &mut txn, id, &[("value", Value::Long(id))]);
+    }
+    assert_eq!(col.id_range(&mut txn).unwrap(), Some((-7, 9842)));
+
+    // removing the endpoints moves the range inwards
+    col.delete(&mut txn, -7).unwrap();
+    col.delete(&mut txn, 9842).unwrap();
+    assert_eq!(col.id_range(&mut txn).unwrap(), Some((3, 500)));
+    for id in [3, 40, 500] {
+        col.delete(&mut txn, id).unwrap();
+    }
+    assert_eq!(col.id_range(&mut txn).unwrap(), None);
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_descending_ids() {
+    let schema = r#"[{
+        "name": "Item",
+        "descendingIds": true,
+        "properties": [{"name": "value", "type": "Long"}]
+    }]"#;
+    let name = unique_name("collection_descending_ids");
+    let dir = test_dir(&name);
+    let insert = |instance: &IsarInstance, values: &[i64]| {
+        let col = &instance.collections[0];
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        for value in values {
+            let bytes = build(col, &[("value", Value::Long(*value))]);
+            col.put_raw(&mut txn, None, &bytes).unwrap();
+        }
+        txn.commit().unwrap();
+    };
+
+    let instance = open_in(&name, &dir, schema);
+    insert(&instance, &[1, 2, 3]);
+    assert_eq!(instance.close(), CloseResult::Closed);
+
+    // the ids keep counting down after reopening
+    let instance = open_in(&name, &dir, schema);
+    insert(&instance, &[4]);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let query = col.new_query_builder().build().unwrap();
+    let mut scanned = vec![];
+    query
+        .find_while(&mut txn, |id, object| {
+            scanned.push((id, object.read_long(property(col, "value").offset)));
+            true
+        })
+        .unwrap();
+    let newest_first = vec![
+        (i64::MAX - 4, 4),
+        (i64::MAX - 3, 3),
+        (i64::MAX - 2, 2),
+        (i64::MAX - 1, 1),
+    ];
+    assert_eq!(scanned, newest_first);
+    assert_eq!(
+        col.id_range(&mut txn).unwrap(),
+        Some((i64::MAX - 4, i64::MAX - 1))
+    );
+    txn.abort();
+    close_and_delete(instance);
+}
+#[test]
+fn test_warm() {
+    for page_size in [None, Some(16384)] {
+        let name = unique_name("collection_warm");
+        let dir = test_dir(&name);
+        let schema = Schema::from_json(TTL_SCHEMA.as_bytes()).unwrap();
+        let instance = IsarInstance::open(
+            &name,
+            Some(&dir),
+            schema,
+            64,
+            page_size,
+            true,
+            None,
+            true,
+            None,
+        )
+        .unwrap();
+        let col = &instance.collections[1];
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        for id in 0..1000 {
+            put(col, &mut txn, id, &[("expiresAt", Value::Long(id))]);
+        }
+        txn.commit().unwrap();
+
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        for include_indexes in [false, true] {
+            let size = col.get_size(&mut txn, include_indexes, false).unwrap();
+            assert!(size > 0);
+            assert_eq!(col.warm(&mut txn, include_indexes, None).unwrap(), size);
+        }
+        assert!(col.warm(&mut txn, true, None).unwrap() > col.warm(&mut txn, false, None).unwrap());
+
+        let cancelled = AtomicBool::new(true);
+        assert_eq!(
+            col.warm(&mut txn, false, Some(&cancelled)),
+            Err(IsarError::Cancelled {})
+        );
+        txn.abort();
+        close_and_delete(instance);
+    }
+}
+
+#[test]
+fn test_defragment() {
+    let instance = open("collection_defragment", TTL_SCHEMA);
+    let (other, col) = (&instance.collections[0], &instance.collections[1]);
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 0..2000 {
+        put(col, &mut txn, id, &[("expiresAt", Value::Long(id))]);
+        put(other, &mut txn, id, &[("expiresAt", Value::Long(id))]);
+    }
+    txn.commit().unwrap();
+
+    // deleting every other object leaves half empty pages behind
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in (0..2000).step_by(2) {
+        col.delete(&mut txn, id).unwrap();
+    }
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    let size_before = col.get_size(&mut txn, true, false).unwrap();
+    let other_size = other.get_size(&mut txn, true, false).unwrap();
+    let reclaimed = col.defragment(&mut txn).unwrap();
+    assert!(reclaimed > 0);
+    assert_eq!(
+        col.get_size(&mut txn, true, false).unwrap(),
+        size_before - reclaimed
+    );
+    assert_eq!(other.get_size(&mut txn, true, false).unwrap(), other_size);
+    txn.commit().unwrap();
+
+    // the pages are dense now, the objects and their index entries are kept
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    assert_eq!(col.defragment(&mut txn).unwrap(), 0);
+    assert_eq!(col.count(&mut txn).unwrap(), 1000);
+    assert_eq!(col.id_range(&mut txn).unwrap(), Some((1, 1999)));
+    assert_eq!(col.purge_expired(&mut txn, 999).unwrap(), 500);
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_hash() {
+    let hash = |instance: &IsarInstance| {
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        let hash = instance.collections[0].hash(&mut txn).unwrap();
+        txn.abort();
+        hash
+    };
+    let name = unique_name("collection_hash");
+    let dir = test_dir(&name);
+    let instance = open_in(&name, &dir, SCHEMA);
+    let other = open("collection_hash_other", SCHEMA);
+    assert_eq!(hash(&instance), 0);
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=5 {
+        put(
+            &instance.collections[0],
+            &mut txn,
+            id,
+            &[("value", Value::Long(id * 10))],
+        );
+    }
+    txn.commit().unwrap();
+    let mut txn = other.begin_txn(true, false).unwrap();
+    for id in (1..=5).rev() {
+        put(
+            &other.collections[0],
+            &mut txn,
+            id,
+            &[("value", Value::Long(id * 10))],
+        );
+    }
+    txn.commit().unwrap();
+    let expected = hash(&instance);
+    assert_ne!(expected, 0);
+    assert_eq!(hash(&other), expected);
+
+    assert!(matches!(instance.close(), CloseResult::Closed));
+    let instance = open_in(&name, &dir, SCHEMA);
+    assert_eq!(hash(&instance), expected);
+
+    // a changed value or the same value under another id changes the hash
+    let mut txn = other.begin_txn(true, false).unwrap();
+    put(
+        &other.collections[0],
+        &mut txn,
+        3,
+        &[("value", Value::Long(31))],
+    );
+    txn.commit().unwrap();
+    assert_ne!(hash(&other), expected);
+
+    let mut txn = other.begin_txn(true, false).unwrap();
+    put(
+        &other.collections[0],
+        &mut txn,
+        3,
+        &[("value", Value::Long(30))],
+    );
+    txn.commit().unwrap();
+    assert_eq!(hash(&other), expected);
+
+    let mut txn = other.begin_txn(true, false).unwrap();
+    other.collections[0].delete(&mut txn, 3).unwrap();
+    put(
+        &other.collections[0],
+        &mut txn,
+        6,
+        &[("value", Value::Long(30))],
+    );
+    txn.commit().unwrap();
+    assert_ne!(hash(&other), expected);
+    close_and_delete(instance);
+    close_and_delete(other);
+}
+
+const GROUP_SCHEMA: &str = r#"[{
+    "name": "Item",
+    "properties": [{"name": "group", "type": "Long"}],
+    "indexes": [{
+        "name": "group",
+        "properties": [{"name": "group", "type": "Value", "caseSensitive": false}],
+        "unique": false
+    }]
+}]"#;
+
+const UNINDEXED_GROUP_SCHEMA: &str =
+    r#"[{"name": "Item", "properties": [{"name": "group", "type": "Long"}]}]"#;
+
+#[test]
+fn test_query_distinct_values() {
+    let values = [
+        Some(5),
+        Some(2),
+        None,
+        Some(5),
+        Some(9),
+        Some(2),
+        None,
+        Some(-1),
+    ];
+    for (schema, indexed) in [(GROUP_SCHEMA, true), (UNINDEXED_GROUP_SCHEMA, false)] {
+        let instance = open("collection_distinct_values", schema);
+        let col = &instance.collections[0];
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        for (id, value) in values.iter().enumerate() {
+            let bytes = match value {
+                Some(value) => build(col, &[("group", Value::Long(*value))]),
+                None => build(col, &[]),
+            };
+            col.put_raw(&mut txn, Some(id as i64 + 1), &bytes).unwrap();
+        }
+        txn.commit().unwrap();
+
+        let group = property(col, "group");
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        let distinct = col.query_distinct_values(&mut txn, &group, None).unwrap();
+        let limited = col
+            .query_distinct_values(&mut txn, &group, Some(2))
+            .unwrap();
+        txn.abort();
+        let expected = if indexed {
+            // index order
+            serde_json::json!([null, -1, 2, 5, 9])
+        } else {
+            // order of the first occurrence
+            serde_json::json!([5, 2, null, 9, -1])
+        };
+        assert_eq!(serde_json::Value::Array(distinct), expected);
+        let expected = expected.as_array().unwrap()[..2].to_vec();
+        assert_eq!(limited, expected);
+        close_and_delete(instance);
+    }
+}
+
+#[test]
+fn test_import_json_batched() {
+    let instance = open("collection_import_batched", GROUP_SCHEMA);
+    let col = &instance.collections[0];
+    let json: Vec<_> = (1..=10_000)
+        .map(|id| serde_json::json!({"id": id, "group": id % 10}))
+        .collect();
+    let json = serde_json::Value::Array(json);
+
+    let txn = instance.begin_txn(true, false).unwrap();
+    let (txn, count) = instance
+        .import_json_batched(txn, col, Some("id"), json.clone(), Some(1000))
+        .unwrap();
+    assert_eq!(count, 10_000);
+
+    // all batches but the last one are already committed
+    let committed = {
+        let instance = instance.clone();
+        thread::spawn(move || {
+            let mut txn = instance.begin_txn(false, false).unwrap();
+            let count = instance.collections[0].count(&mut txn).unwrap();
+            txn.abort();
+            count
+        })
+        .join()
+        .unwrap()
+    };
+    assert_eq!(committed, 9000);
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert_eq!(col.count(&mut txn).unwrap(), 10_000);
+    for group in 0..10 {
+        let mut key = IndexKey::new();
+        key.add_long(group);
+        let count = col.count_by_index(&mut txn, xxh3_64(b"group"), &key);
+        assert_eq!(count.unwrap(), 1000);
+    }
+    txn.abort();
+
+    let txn = instance.begin_txn(true, false).unwrap();
+    assert!(matches!(
+        instance.import_json_batched(txn, col, Some("id"), json, Some(0)),
+        Err(IsarError::IllegalArg { .. })
+    ));
+    close_and_delete(instance);
+}
+
+const LINKED_SCHEMA: &str = r#"[
+    {
+        "name": "Item",
+        "properties": [{"name": "group", "type": "Long"}],
+        "indexes": [{
+            "name": "group",
+            "properties": [{"name": "group", "type": "Value", "caseSensitive": false}],
+            "unique": false
+        }],
+        "links": [{"name": "owner", "target": "Owner"}]
+    },
+    {"name": "Owner", "properties": [{"name": "value", "type": "Long"}]}
+]"#;
+
+#[test]
+fn test_replace_json_reader() {
+    let instance = open("collection_replace", GROUP_SCHEMA);
+    let col = &instance.collections[0];
+    let old: Vec<_> = (1..=100)
+        .map(|id| serde_json::json!({"id": id, "group": 0}))
+        .collect();
+    let new: Vec<_> = (51..=100)
+        .map(|id| serde_json::json!({"id": id, "group": 1}))
+        .collect();
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    col.import_json(&mut txn, Some("id"), serde_json::Value::Array(old))
+        .unwrap();
+    txn.commit().unwrap();
+
+    let notifications = Arc::new(AtomicUsize::new(0));
+    let counter = notifications.clone();
+    let _handle = instance.watch_collection(
+        col,
+        Box::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }),
+    );
+
+    let group_counts = |instance: &IsarInstance| {
+        let col = &instance.collections[0];
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        let mut counts = vec![col.count(&mut txn).unwrap()];
+        for group in 0..2 {
+            let mut key = IndexKey::new();
+            key.add_long(group);
+            counts.push(
+                col.count_by_index(&mut txn, xxh3_64(b"group"), &key)
+                    .unwrap(),
+            );
+        }
+        txn.abort();
+        counts
+    };
+    let done = Arc::new(AtomicBool::new(false));
+    let reader = {
+        let instance = instance.clone();
+        let done = done.clone();
+        thread::spawn(move || {
+            let mut observed = vec![];
+            while !done.load(Ordering::SeqCst) {
+                observed.push(group_counts(&instance));
+            }
+            observed
+        })
+    };
+
+    let json = serde_json::to_vec(&new).unwrap();
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    assert_eq!(
+        col.replace_json_reader(&mut txn, Some("id"), json.as_slice()),
+        Ok(50)
+    );
+    txn.commit().unwrap();
+    done.store(true, Ordering::SeqCst);
+
+    // readers see the old or the new objects but never a mix
+    for counts in reader.join().unwrap() {
+        assert!(counts == vec![100, 100, 0] || counts == vec![50, 0, 50]);
+    }
+    assert_eq!(group_counts(&instance), vec![50, 0, 50]);
+    assert_eq!(notifications.load(Ordering::SeqCst), 1);
+    close_and_delete(instance);
+}
+#[test]
+fn test_reindex_ids() {
+    let instance = open("collection_reindex", LINKED_SCHEMA);
+    let col = collection(&instance, "Item");
+    let owners = collection(&instance, "Owner");
+    let group = |txn: &mut IsarTxn, id: i64| {
+        let offset = property(col, "group").offset;
+        col.get(txn, id).unwrap().map(|o| o.read_long(offset))
+    };
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=100 {
+        put(col, &mut txn, id, &[("group", Value::Long(id % 3))]);
+    }
+    for id in 1..=100 {
+        if ![3, 10, 42, 100].contains(&id) {
+            col.delete(&mut txn, id).unwrap();
+        }
+    }
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    let remap = col.reindex_ids(&mut txn, false).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(remap, vec![(3, 1), (10, 2), (42, 3), (100, 4)]);
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    let groups: Vec<_> = (1..=5).map(|id| group(&mut txn, id)).collect();
+    assert_eq!(groups, vec![Some(0), Some(1), Some(0), Some(1), None]);
+    let mut key = IndexKey::new();
+    key.add_long(1);
+    assert_eq!(
+        col.count_by_index(&mut txn, xxh3_64(b"group"), &key)
+            .unwrap(),
+        2
+    );
+    assert_eq!(col.auto_increment(&mut txn).unwrap(), 5);
+    txn.abort();
+
+    // links are only remapped when forced
+    let link_id = xxh3_64_with_seed(b"owner", xxh3_64_with_seed(b"Item", 0));
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(owners, &mut txn, 7, &[("value", Value::Long(7))]);
+    col.delete(&mut txn, 1).unwrap();
+    assert!(col.link(&mut txn, link_id, 3, 7).unwrap());
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    assert!(matches!(
+        col.reindex_ids(&mut txn, false),
+        Err(IsarError::IllegalArg { .. })
+    ));
+    txn.abort();
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    let remap = col.reindex_ids(&mut txn, true).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(remap, vec![(2, 1), (3, 2), (4, 3)]);
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    col.verify_link(&mut txn, link_id, &[(2, 7)]).unwrap();
+    assert_eq!(group(&mut txn, 2), Some(0));
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_repair_id_counter() {
+    let instance = open("collection_repair_id_counter", SCHEMA);
+    let col = &instance.collections[0];
+    let insert = || {
+        let bytes = build(col, &[("value", Value::Long(1))]);
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        let id = col.put_raw(&mut txn, None, &bytes).unwrap();
+        txn.commit().unwrap();
+        id
+    };
+    let json = r#"[{"id": 5000, "value": 1}, {"id": 70, "value": 2}, {"id": 9000, "value": 3}]"#;
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    col.import_json_reader(&mut txn, Some("id"), json.as_bytes())
+        .unwrap();
+    assert_eq!(col.repair_id_counter(&mut txn), Ok(9000));
+    txn.commit().unwrap();
+    assert_eq!(insert(), 9001);
+
+    // an aborted clear resets the counter although the objects are kept
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    col.clear(&mut txn).unwrap();
+    txn.abort();
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    assert_eq!(col.repair_id_counter(&mut txn), Ok(9001));
+    txn.commit().unwrap();
+    assert_eq!(insert(), 9002);
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert_eq!(col.count(&mut txn).unwrap(), 5);
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_reserve_ids() {
+    let name = unique_name("collection_reserve_ids");
+    let dir = test_dir(&name);
+    let instance = open_in(&name, &dir, SCHEMA);
+    let insert = |instance: &IsarInstance, id: Option<i64>| {
+        let col = &instance.collections[0];
+        let bytes = build(col, &[("value", Value::Long(1))]);
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        let id = col.put_raw(&mut txn, id, &bytes).unwrap();
+        txn.commit().unwrap();
+        id
+    };
+    assert_eq!(insert(&instance, None), 1);
+    assert_eq!(insert(&instance, None), 2);
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    assert_eq!(instance.reserve_ids(&mut txn, 0, 5).unwrap(), 3);
+    assert!(matches!(
+        instance.reserve_ids(&mut txn, 0, 0),
+        Err(IsarError::IllegalArg { .. })
+    ));
+    txn.abort();
+    // the aborted transaction did not persist the reservation but it is kept for this session
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    assert_eq!(instance.reserve_ids(&mut txn, 0, 10).unwrap(), 8);
+    txn.commit().unwrap();
+
+    for id in [3, 7, 8] {
+        assert_eq!(insert(&instance, Some(id)), id);
+    }
+    assert_eq!(insert(&instance, None), 18);
+
+    // reserved ids stay reserved after reopening even if they were not used
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    assert_eq!(instance.reserve_ids(&mut txn, 0, 100).unwrap(), 19);
+    txn.commit().unwrap();
+    assert_eq!(instance.close(), CloseResult::Closed);
+    let instance = open_in(&name, &dir, SCHEMA);
+    assert_eq!(insert(&instance, None), 119);
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert_eq!(instance.collections[0].count(&mut txn).unwrap(), 7);
+    txn.abort();
+    close_and_delete(instance);
+}
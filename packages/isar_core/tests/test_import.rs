@@ -0,0 +1,102 @@
+mod common;
+
+use common::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Tracks the allocated bytes of the test process and their peak.
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Generates a JSON array of `count` objects padded with whitespace, a few bytes per read.
+struct SlowJson {
+    count: i64,
+    next: i64,
+    pending: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for SlowJson {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.pending.len() {
+            self.pending.clear();
+            self.pos = 0;
+            if self.next == 0 {
+                self.pending.push(b'[');
+            } else if self.next > self.count {
+                return Ok(0);
+            } else {
+                if self.next > 1 {
+                    self.pending.push(b',');
+                }
+                let object = format!(r#"{{"id": {}, "value": {}}}"#, self.next, self.next * 2);
+                self.pending.extend_from_slice(object.as_bytes());
+                self.pending.resize(self.pending.len() + PADDING, b' ');
+                if self.next == self.count {
+                    self.pending.push(b']');
+                }
+            }
+            self.next += 1;
+        }
+        let len = buf.len().min(7).min(self.pending.len() - self.pos);
+        buf[..len].copy_from_slice(&self.pending[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+const PADDING: usize = 10_000;
+
+#[test]
+fn test_import_json_reader_streams() {
+    let schema = r#"[{"name": "Item", "properties": [{"name": "value", "type": "Long"}]}]"#;
+    let instance = open("import_stream", schema);
+    let col = &instance.collections[0];
+    let count = 2000;
+    let reader = SlowJson {
+        count,
+        next: 0,
+        pending: vec![],
+        pos: 0,
+    };
+
+    let before = ALLOCATED.load(Ordering::SeqCst);
+    PEAK.store(before, Ordering::SeqCst);
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    let imported = col
+        .import_json_reader(&mut txn, Some("id"), reader)
+        .unwrap();
+    txn.commit().unwrap();
+    let peak = PEAK.load(Ordering::SeqCst) - before;
+
+    assert_eq!(imported, count as u32);
+    // the JSON is 20 MB, only the imported objects stay in memory
+    assert!(peak < 4 << 20, "peak allocation grew by {} bytes", peak);
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert_eq!(col.count(&mut txn).unwrap(), count as u64);
+    txn.abort();
+    close_and_delete(instance);
+}
@@ -0,0 +1,335 @@
+mod common;
+
+use common::*;
+use isar_core::error::IsarError;
+use isar_core::index::index_key::IndexKey;
+use isar_core::query::Sort;
+use xxhash_rust::xxh3::xxh3_64;
+
+const UNIQUE_SCHEMA: &str = r#"[{
+    "name": "User",
+    "properties": [
+        {"name": "email", "type": "String"},
+        {"name": "tenantId", "type": "Long"}
+    ],
+    "indexes": [{
+        "name": "tenant_email",
+        "properties": [
+            {"name": "tenantId", "type": "Value", "caseSensitive": false},
+            {"name": "email", "type": "Hash", "caseSensitive": false}
+        ],
+        "unique": true
+    }]
+}]"#;
+
+#[test]
+fn test_composite_unique_index() {
+    let instance = open("index_unique", UNIQUE_SCHEMA);
+    let col = &instance.collections[0];
+    let user = |tenant_id: i64, email: &str| {
+        build(
+            col,
+            &[
+                ("tenantId", Value::Long(tenant_id)),
+                ("email", Value::String(email)),
+            ],
+        )
+    };
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    col.put_raw(&mut txn, Some(1), &user(1, "a@b.c")).unwrap();
+    // sharing one component of the key is fine
+    col.put_raw(&mut txn, Some(2), &user(1, "x@b.c")).unwrap();
+    col.put_raw(&mut txn, Some(3), &user(2, "a@b.c")).unwrap();
+    // replacing the object itself is not a collision
+    col.put_raw(&mut txn, Some(1), &user(1, "a@b.c")).unwrap();
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    assert_eq!(
+        col.put_raw(&mut txn, Some(4), &user(1, "A@b.c")),
+        Err(IsarError::UniqueViolated {
+            index_name: "tenant_email".to_string(),
+            key: r#"{"email":"A@b.c","tenantId":1}"#.to_string(),
+        })
+    );
+    txn.abort();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert_eq!(col.count(&mut txn).unwrap(), 3);
+    txn.abort();
+    close_and_delete(instance);
+}
+
+const VALUE_SCHEMA: &str = r#"[{
+    "name": "Item",
+    "properties": [{"name": "value", "type": "Long"}],
+    "indexes": [{
+        "name": "value",
+        "properties": [{"name": "value", "type": "Value", "caseSensitive": false}],
+        "unique": false
+    }]
+}]"#;
+
+fn long_key(value: i64) -> Vec<u8> {
+    ((value as u64) ^ 1 << 63).to_be_bytes().to_vec()
+}
+
+#[test]
+fn test_index_stats() {
+    let instance = open("index_stats", VALUE_SCHEMA);
+    let col = &instance.collections[0];
+    let index_id = xxh3_64(b"value");
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let stats = col.get_index_stats(&mut txn, index_id).unwrap();
+    assert_eq!((stats.entries, stats.distinct_keys), (0, 0));
+    assert_eq!((stats.min_key, stats.max_key), (None, None));
+    txn.abort();
+
+    // most objects share one key
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=90 {
+        put(col, &mut txn, id, &[("value", Value::Long(1))]);
+    }
+    put(col, &mut txn, 91, &[("value", Value::Long(42))]);
+    put(col, &mut txn, 92, &[("value", Value::Long(-5))]);
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let stats = col.get_index_stats(&mut txn, index_id).unwrap();
+    assert_eq!(stats.entries, 92);
+    assert_eq!(stats.distinct_keys, 3);
+    assert_eq!(stats.min_key, Some(long_key(-5)));
+    assert_eq!(stats.max_key, Some(long_key(42)));
+    assert!(matches!(
+        col.get_index_stats(&mut txn, xxh3_64(b"missing")),
+        Err(IsarError::UnknownIndex {})
+    ));
+    txn.abort();
+    close_and_delete(instance);
+}
+
+const BLOB_SCHEMA: &str = r#"[{
+    "name": "Item",
+    "properties": [{"name": "hash", "type": "ByteList"}],
+    "indexes": [{
+        "name": "hash",
+        "properties": [{"name": "hash", "type": "Blob", "caseSensitive": false}],
+        "unique": false
+    }]
+}]"#;
+
+fn blob_key(value: &[u8]) -> IndexKey {
+    let mut key = IndexKey::new();
+    key.add_blob(Some(value));
+    key
+}
+
+#[test]
+fn test_blob_index() {
+    let instance = open("index_blob", BLOB_SCHEMA);
+    let col = &instance.collections[0];
+    let offset = property(col, "hash").offset;
+    let index_id = xxh3_64(b"hash");
+    let blobs: [&[u8]; 7] = [&[1], &[0, 1], &[0], &[0, 0, 255], &[], &[0, 0], &[255, 0]];
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for (id, blob) in blobs.iter().enumerate() {
+        let mut builder = builder_of(col);
+        builder.write_byte_list(offset, Some(blob));
+        let bytes = builder.finish().as_bytes().to_vec();
+        col.put_raw(&mut txn, Some(id as i64), &bytes).unwrap();
+    }
+    txn.commit().unwrap();
+
+    let find = |lower: &[u8], upper: &[u8]| {
+        let mut qb = col.new_query_builder();
+        qb.add_index_where_clause(
+            index_id,
+            blob_key(lower),
+            blob_key(upper),
+            Sort::Ascending,
+            false,
+        )
+        .unwrap();
+        let query = qb.build().unwrap();
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        let mut ids = vec![];
+        query
+            .find_while(&mut txn, |id, _| {
+                ids.push(id);
+                true
+            })
+            .unwrap();
+        txn.abort();
+        ids
+    };
+
+    // zero bytes do not end the key
+    assert_eq!(find(&[0], &[0]), vec![2]);
+    assert_eq!(find(&[0, 0], &[0, 0]), vec![5]);
+    assert_eq!(find(&[], &[]), vec![4]);
+    assert!(find(&[0, 0, 0], &[0, 0, 0]).is_empty());
+
+    // ranges are ordered by content, not by length
+    assert_eq!(find(&[0, 0], &[0, 1]), vec![5, 3, 1]);
+    assert_eq!(find(&[], &[255, 255]), vec![4, 2, 5, 3, 1, 0, 6]);
+
+    // long blobs are not hashed, writing them fails
+    let long = vec![7u8; 2000];
+    let mut builder = builder_of(col);
+    builder.write_byte_list(offset, Some(&long));
+    let bytes = builder.finish().as_bytes().to_vec();
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    assert!(matches!(
+        col.put_raw(&mut txn, Some(100), &bytes),
+        Err(IsarError::KeyTooLong { len: 2000, .. })
+    ));
+    txn.abort();
+    close_and_delete(instance);
+}
+
+const VERSION_SCHEMA: &str = r#"[{
+    "name": "Release",
+    "properties": [{"name": "version", "type": "String"}],
+    "indexes": [{
+        "name": "version",
+        "properties": [{"name": "version", "type": "Value", "caseSensitive": true}],
+        "unique": false,
+        "comparator": "Version"
+    }]
+}]"#;
+
+#[test]
+fn test_version_comparator_index() {
+    let instance = open("index_version", VERSION_SCHEMA);
+    let col = &instance.collections[0];
+    let index_id = xxh3_64(b"version");
+    let versions = ["1.10", "2.0", "1.9", "1.100", "1.2", "1.11"];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for (id, version) in versions.iter().enumerate() {
+        put(
+            col,
+            &mut txn,
+            id as i64,
+            &[("version", Value::String(version))],
+        );
+    }
+    txn.commit().unwrap();
+
+    let version_key = |version: &str| {
+        let mut key = IndexKey::new();
+        key.add_string(Some(version), true);
+        key
+    };
+    let find = |lower: &str, upper: &str, sort: Sort| {
+        let mut qb = col.new_query_builder();
+        qb.add_index_where_clause(
+            index_id,
+            version_key(lower),
+            version_key(upper),
+            sort,
+            false,
+        )
+        .unwrap();
+        let query = qb.build().unwrap();
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        let mut found = vec![];
+        query
+            .find_while(&mut txn, |id, _| {
+                found.push(versions[id as usize]);
+                true
+            })
+            .unwrap();
+        txn.abort();
+        found
+    };
+
+    // byte order would put "1.10" and "1.11" before "1.9" and make this range empty
+    assert_eq!(
+        find("1.9", "1.11", Sort::Ascending),
+        vec!["1.9", "1.10", "1.11"]
+    );
+    assert_eq!(
+        find("1.9", "1.11", Sort::Descending),
+        vec!["1.11", "1.10", "1.9"]
+    );
+    assert_eq!(
+        find("1.0", "9.0", Sort::Ascending),
+        vec!["1.2", "1.9", "1.10", "1.11", "1.100", "2.0"]
+    );
+    close_and_delete(instance);
+}
+
+const SPARSE_SCHEMA: &str = r#"[{
+    "name": "Contact",
+    "properties": [{"name": "nickname", "type": "String"}],
+    "indexes": [{
+        "name": "nickname",
+        "properties": [{"name": "nickname", "type": "Value", "caseSensitive": true}],
+        "unique": false,
+        "sparse": true
+    }]
+}]"#;
+
+#[test]
+fn test_sparse_index() {
+    let instance = open("index_sparse", SPARSE_SCHEMA);
+    let col = &instance.collections[0];
+    let index_id = xxh3_64(b"nickname");
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=10 {
+        put(col, &mut txn, id, &[]);
+    }
+    put(col, &mut txn, 11, &[("nickname", Value::String("bob"))]);
+    put(col, &mut txn, 12, &[("nickname", Value::String("al"))]);
+    put(col, &mut txn, 13, &[("nickname", Value::String(""))]);
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let stats = col.get_index_stats(&mut txn, index_id).unwrap();
+    assert_eq!((stats.entries, stats.distinct_keys), (3, 3));
+    txn.abort();
+
+    let key = |value: Option<&str>| {
+        let mut key = IndexKey::new();
+        key.add_string(value, true);
+        key
+    };
+    let find = |lower: Option<&str>, upper: Option<&str>| {
+        let mut qb = col.new_query_builder();
+        qb.add_index_where_clause(index_id, key(lower), key(upper), Sort::Ascending, false)?;
+        let query = qb.build()?;
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        let mut ids = vec![];
+        query.find_while(&mut txn, |id, _| {
+            ids.push(id);
+            true
+        })?;
+        txn.abort();
+        Ok::<_, IsarError>(ids)
+    };
+    assert_eq!(find(Some("bob"), Some("bob")).unwrap(), vec![11]);
+    assert_eq!(find(Some(""), Some("zzz")).unwrap(), vec![13, 12, 11]);
+
+    // null values are not in the index, so it cannot find them
+    assert!(matches!(
+        find(None, None),
+        Err(IsarError::IllegalArg { .. })
+    ));
+    assert!(matches!(
+        find(None, Some("zzz")),
+        Err(IsarError::IllegalArg { .. })
+    ));
+
+    // clearing the value of an indexed object removes its entry
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(col, &mut txn, 11, &[]);
+    txn.commit().unwrap();
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert_eq!(col.get_index_stats(&mut txn, index_id).unwrap().entries, 2);
+    txn.abort();
+    assert!(find(Some("bob"), Some("bob")).unwrap().is_empty());
+    close_and_delete(instance);
+}
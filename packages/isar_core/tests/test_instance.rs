@@ -0,0 +1,553 @@
+mod common;
+
+use common::*;
+use isar_core::error::{illegal_arg, IsarError};
+use isar_core::health::HealthStatus;
+use isar_core::index::index_key::IndexKey;
+use isar_core::instance::{CloseResult, CompactCondition, IsarInstance, SharedEnv};
+use isar_core::schema::migration_plan::MigrationAction;
+use isar_core::schema::Schema;
+use isar_core::txn::IsarTxn;
+use std::cell::RefCell;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use xxhash_rust::xxh3::{xxh3_64, xxh3_64_with_seed};
+
+const SCHEMA: &str = r#"[{
+    "name": "Item",
+    "properties": [{"name": "value", "type": "Long"}],
+    "indexes": [{
+        "name": "value",
+        "properties": [{"name": "value", "type": "Value", "caseSensitive": false}],
+        "unique": false
+    }]
+}]"#;
+
+fn schema() -> Schema {
+    Schema::from_json(SCHEMA.as_bytes()).unwrap()
+}
+
+#[test]
+fn test_named_instances_are_isolated() {
+    let name = unique_name("shared");
+    let dir = test_dir(&name);
+    let other_dir = test_dir(&unique_name("shared_other"));
+    let env = SharedEnv::open(&name, &dir, &[schema(), schema()], 64, None, true).unwrap();
+    let other_env = SharedEnv::open(&name, &other_dir, &[schema()], 64, None, true).unwrap();
+
+    let a = IsarInstance::open_named(&env, "main", schema()).unwrap();
+    let b = IsarInstance::open_named(&env, "cache", schema()).unwrap();
+    let other = IsarInstance::open_named(&other_env, "main", schema()).unwrap();
+    assert!(Arc::ptr_eq(
+        &a,
+        &IsarInstance::open_named(&env, "main", schema()).unwrap()
+    ));
+    assert!(!Arc::ptr_eq(&a, &other));
+
+    // a plain instance with the same name is a different instance
+    let plain = open_in("main", &test_dir(&unique_name("plain")), SCHEMA);
+    assert!(!Arc::ptr_eq(&a, &plain));
+
+    let mut txn = a.begin_txn(true, false).unwrap();
+    put(&a.collections[0], &mut txn, 1, &[("value", Value::Long(1))]);
+    put(&a.collections[0], &mut txn, 2, &[("value", Value::Long(2))]);
+    txn.commit().unwrap();
+
+    for (instance, expected) in [(&a, 2), (&b, 0), (&other, 0), (&plain, 0)] {
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        assert_eq!(instance.collections[0].count(&mut txn).unwrap(), expected);
+        txn.abort();
+    }
+
+    // the environment has no room for an instance that was not counted
+    assert!(IsarInstance::open_named(&other_env, "extra", schema()).is_err());
+
+    for instance in [a, b, other, plain] {
+        instance.close();
+    }
+    drop((env, other_env));
+    let _ = std::fs::remove_dir_all(dir);
+    let _ = std::fs::remove_dir_all(other_dir);
+}
+
+#[test]
+fn test_diagnostics_report() {
+    let instance = open("diagnostics", SCHEMA);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=10 {
+        put(col, &mut txn, id, &[("value", Value::Long(id % 4))]);
+    }
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let report = instance.diagnostics_report(&mut txn).unwrap();
+    txn.abort();
+
+    let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+    let collection = &report["collections"][0];
+    assert_eq!(collection["name"], "Item");
+    assert_eq!(collection["count"], 10);
+    let index = &collection["indexes"][0];
+    assert_eq!(index["name"], "value");
+    assert_eq!(index["entries"], 10);
+    assert_eq!(index["distinctKeys"], 4);
+    assert_eq!(index["distinctKeysCapped"], false);
+    assert_eq!(index["minKey"]["truncated"], false);
+    assert!(report["env"]["pageSize"].as_u64().unwrap() > 0);
+
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_health_check() {
+    let name = unique_name("health");
+    let dir = test_dir(&name);
+    let schema = r#"[{"name": "Blob", "properties": [{"name": "text", "type": "String"}]}]"#;
+    let schema = Schema::from_json(schema.as_bytes()).unwrap();
+    let instance =
+        IsarInstance::open(&name, Some(&dir), schema, 1, None, true, None, true, None).unwrap();
+    assert_eq!(instance.health_check().unwrap(), HealthStatus::Healthy);
+
+    // fill the map, which is at least 10 MiB, until the health check warns about it
+    let col = &instance.collections[0];
+    let text = "x".repeat(8000);
+    let mut status = HealthStatus::Healthy;
+    for batch in 0..40 {
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        for id in batch * 50..(batch + 1) * 50 {
+            put(col, &mut txn, id, &[("text", Value::String(&text))]);
+        }
+        txn.commit().unwrap();
+        status = instance.health_check().unwrap();
+        if !status.is_healthy() {
+            break;
+        }
+    }
+    match status {
+        HealthStatus::Degraded(reasons) => {
+            assert_eq!(reasons.len(), 1);
+            assert!(
+                reasons[0].starts_with("The database map is"),
+                "{}",
+                reasons[0]
+            );
+        }
+        HealthStatus::Healthy => panic!("the map never got full"),
+    }
+    close_and_delete(instance);
+}
+
+fn reported_page_size(instance: &IsarInstance) -> u64 {
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let report = instance.diagnostics_report(&mut txn).unwrap();
+    txn.abort();
+    let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+    report["env"]["pageSize"].as_u64().unwrap()
+}
+
+#[test]
+fn test_page_size() {
+    let name = unique_name("page_size");
+    let dir = test_dir(&name);
+    let open_with = |page_size| {
+        IsarInstance::open(
+            &name,
+            Some(&dir),
+            schema(),
+            64,
+            page_size,
+            true,
+            None,
+            true,
+            None,
+        )
+    };
+    assert!(matches!(
+        open_with(Some(3000)),
+        Err(IsarError::IllegalArg { .. })
+    ));
+
+    let instance = open_with(Some(16384)).unwrap();
+    assert_eq!(reported_page_size(&instance), 16384);
+    assert!(matches!(instance.close(), CloseResult::Closed));
+
+    // the page size of an existing database is kept
+    let instance = open_with(Some(4096)).unwrap();
+    assert_eq!(reported_page_size(&instance), 16384);
+    close_and_delete(instance);
+}
+
+fn free_pages(instance: &IsarInstance) -> u64 {
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let info = instance.freelist_info(&mut txn).unwrap();
+    txn.abort();
+    assert_eq!(
+        info.reclaimable_bytes,
+        (info.free_pages + info.unused_pages) * info.page_size as u64
+    );
+    info.free_pages
+}
+
+#[test]
+fn test_freelist_info() {
+    let name = unique_name("freelist");
+    let dir = test_dir(&name);
+    let instance = open_in(&name, &dir, SCHEMA);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 0..5000 {
+        put(col, &mut txn, id, &[("value", Value::Long(id))]);
+    }
+    txn.commit().unwrap();
+    let before = free_pages(&instance);
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    col.clear(&mut txn).unwrap();
+    txn.commit().unwrap();
+    let after_delete = free_pages(&instance);
+    assert!(after_delete > before);
+
+    let condition = CompactCondition {
+        min_file_size: 0,
+        min_bytes: 0,
+        min_ratio: 0.0,
+    };
+    assert!(matches!(
+        instance.close_and_compact(condition),
+        CloseResult::Closed
+    ));
+    let instance = open_in(&name, &dir, SCHEMA);
+    assert!(free_pages(&instance) < after_delete);
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_close_result() {
+    let name = unique_name("close");
+    let dir = test_dir(&name);
+    let instance = open_in(&name, &dir, SCHEMA);
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(
+        &instance.collections[0],
+        &mut txn,
+        1,
+        &[("value", Value::Long(1))],
+    );
+    txn.commit().unwrap();
+    assert_eq!(instance.close(), CloseResult::Closed);
+
+    // opening the same instance again hands out another reference
+    let first = open_in(&name, &dir, SCHEMA);
+    let second = open_in(&name, &dir, SCHEMA);
+    let third = open_in(&name, &dir, SCHEMA);
+    assert_eq!(first.close(), CloseResult::StillReferenced { remaining: 2 });
+    assert_eq!(
+        second.close(),
+        CloseResult::StillReferenced { remaining: 1 }
+    );
+
+    // the data was flushed by the first close
+    let mut txn = third.begin_txn(false, false).unwrap();
+    assert_eq!(third.collections[0].count(&mut txn).unwrap(), 1);
+    txn.abort();
+    assert_eq!(third.close_and_delete(), CloseResult::Closed);
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_schema_version() {
+    let name = unique_name("schema_version");
+    let dir = test_dir(&name);
+    let with_tag = r#"[
+        {"name": "Item", "properties": [{"name": "value", "type": "Long"}]},
+        {"name": "Tag", "properties": [{"name": "name", "type": "String"}]}
+    ]"#;
+    let without_tag = r#"[{"name": "Item", "properties": [
+        {"name": "value", "type": "Long"},
+        {"name": "name", "type": "String"}
+    ]}]"#;
+    let versions = [
+        (SCHEMA, 1),
+        (SCHEMA, 1),
+        (with_tag, 2),
+        (with_tag, 2),
+        (without_tag, 3),
+    ];
+    for (schema, version) in versions {
+        let instance = open_in(&name, &dir, schema);
+        assert_eq!(instance.schema_version().unwrap(), version);
+        assert_eq!(instance.close(), CloseResult::Closed);
+    }
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_schema_matches() {
+    let instance = open("schema_matches", SCHEMA);
+    assert!(instance.schema_matches(&schema()).unwrap());
+    assert!(instance.schema_diff(&schema()).unwrap().is_empty());
+
+    let added = r#"[{"name": "Item", "properties": [
+        {"name": "value", "type": "Long"},
+        {"name": "name", "type": "String"}
+    ], "indexes": [{
+        "name": "value",
+        "properties": [{"name": "value", "type": "Value", "caseSensitive": false}],
+        "unique": false
+    }]}]"#;
+    let added = Schema::from_json(added.as_bytes()).unwrap();
+    assert!(!instance.schema_matches(&added).unwrap());
+    assert_eq!(
+        instance.schema_diff(&added).unwrap().actions,
+        vec![MigrationAction::AddProperty {
+            collection: "Item".to_string(),
+            property: "name".to_string(),
+        }]
+    );
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_collections_indexed() {
+    let schema = r#"[
+        {"name": "B", "properties": [{"name": "value", "type": "Long"}]},
+        {"name": "A", "properties": [{"name": "value", "type": "Long"}]},
+        {"name": "C", "properties": [{"name": "value", "type": "Long"}]}
+    ]"#;
+    let instance = open("collections_indexed", schema);
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for (count, col) in instance.collections.iter().enumerate() {
+        for id in 0..count as i64 + 1 {
+            put(col, &mut txn, id, &[("value", Value::Long(id))]);
+        }
+    }
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let mut counts = vec![];
+    for (index, name) in instance.collections_indexed() {
+        let col = instance.get_collection_by_index(index).unwrap();
+        assert_eq!(col.name, name);
+        counts.push((name, instance.count(&mut txn, index).unwrap()));
+    }
+    let names = instance.collections.iter().map(|c| c.name.as_str());
+    let expected: Vec<_> = names.zip(1..).collect();
+    assert_eq!(counts, expected);
+    assert!(matches!(
+        instance.count(&mut txn, 3),
+        Err(IsarError::IllegalArg { .. })
+    ));
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_close_and_compact() {
+    let name = unique_name("close_compact");
+    let dir = test_dir(&name);
+    let file_size = || {
+        let path = std::path::Path::new(&dir).join(format!("{}.isar", name));
+        std::fs::metadata(path).unwrap().len()
+    };
+    let instance = open_in(&name, &dir, SCHEMA);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 0..5000 {
+        put(col, &mut txn, id, &[("value", Value::Long(id))]);
+    }
+    txn.commit().unwrap();
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 0..4500 {
+        col.delete(&mut txn, id).unwrap();
+    }
+    txn.commit().unwrap();
+    assert_eq!(instance.close(), CloseResult::Closed);
+    let full_size = file_size();
+
+    // compaction is skipped if the condition is not met
+    let instance = open_in(&name, &dir, SCHEMA);
+    let condition = CompactCondition {
+        min_file_size: 0,
+        min_bytes: 0,
+        min_ratio: 1000.0,
+    };
+    assert_eq!(instance.close_and_compact(condition), CloseResult::Closed);
+    assert_eq!(file_size(), full_size);
+
+    let instance = open_in(&name, &dir, SCHEMA);
+    let condition = CompactCondition {
+        min_file_size: 0,
+        min_bytes: 0,
+        min_ratio: 1.5,
+    };
+    assert_eq!(instance.close_and_compact(condition), CloseResult::Closed);
+    assert!(file_size() < full_size);
+
+    let instance = open_in(&name, &dir, SCHEMA);
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert_eq!(instance.collections[0].count(&mut txn).unwrap(), 500);
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_run_checkpointed() {
+    let instance = open("checkpointed", SCHEMA);
+    let col = &instance.collections[0];
+    let steps = RefCell::new(vec![]);
+    let step = |interrupt_at: Option<u32>| {
+        let steps = &steps;
+        move |txn: &mut IsarTxn, item: u32| {
+            if Some(item) == interrupt_at {
+                return illegal_arg("interrupted");
+            }
+            steps.borrow_mut().push(item);
+            put(
+                col,
+                txn,
+                item as i64,
+                &[("value", Value::Long(item as i64))],
+            );
+            Ok(())
+        }
+    };
+
+    // the batch of items 20 to 29 is not committed
+    let result = instance.run_checkpointed("import", 40, 10, step(Some(25)));
+    assert!(matches!(result, Err(IsarError::IllegalArg { .. })));
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert_eq!(col.count(&mut txn).unwrap(), 20);
+    txn.abort();
+
+    assert_eq!(
+        instance.run_checkpointed("import", 40, 10, step(None)),
+        Ok(20)
+    );
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert_eq!(col.count(&mut txn).unwrap(), 40);
+    txn.abort();
+    let mut expected: Vec<u32> = (0..25).collect();
+    expected.extend(20..40);
+    assert_eq!(steps.take(), expected);
+
+    // a completed job starts over
+    assert_eq!(
+        instance.run_checkpointed("import", 40, 10, step(None)),
+        Ok(0)
+    );
+    assert_eq!(steps.take(), (0..40).collect::<Vec<_>>());
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_check_readers() {
+    // the test runs again in a child process that exits while its read txn is still open
+    if let Ok(dir) = std::env::var("ISAR_STALE_READER_DIR") {
+        let name = std::env::var("ISAR_STALE_READER_NAME").unwrap();
+        let instance = open_in(&name, &dir, SCHEMA);
+        let _txn = instance.begin_txn(false, false).unwrap();
+        std::process::exit(0);
+    }
+
+    let name = unique_name("check_readers");
+    let dir = test_dir(&name);
+    let instance = open_in(&name, &dir, SCHEMA);
+    assert_eq!(instance.check_readers(), Ok(0));
+
+    let status = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "test_check_readers", "--test-threads", "1"])
+        .env("ISAR_STALE_READER_DIR", &dir)
+        .env("ISAR_STALE_READER_NAME", &name)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(instance.check_readers(), Ok(1));
+    assert_eq!(instance.check_readers(), Ok(0));
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_export_all() {
+    let schema = r#"[
+        {
+            "name": "Item",
+            "properties": [{"name": "value", "type": "Long"}],
+            "indexes": [{
+                "name": "value",
+                "properties": [{"name": "value", "type": "Value", "caseSensitive": false}],
+                "unique": false
+            }],
+            "links": [{"name": "owner", "target": "Owner"}]
+        },
+        {"name": "Owner", "properties": [{"name": "name", "type": "String"}]}
+    ]"#;
+    let link_id = xxh3_64_with_seed(b"owner", xxh3_64_with_seed(b"Item", 0));
+    let source = open("export_all", schema);
+    let (items, owners) = (&source.collections[0], &source.collections[1]);
+    let mut txn = source.begin_txn(true, false).unwrap();
+    for id in 1..=5 {
+        put(items, &mut txn, id, &[("value", Value::Long(id % 2))]);
+    }
+    put(owners, &mut txn, 1, &[("name", Value::String("a"))]);
+    put(owners, &mut txn, 2, &[("name", Value::String("b"))]);
+    for (item, owner) in [(1, 1), (2, 1), (5, 2)] {
+        items.link(&mut txn, link_id, item, owner).unwrap();
+    }
+    txn.commit().unwrap();
+
+    // objects written after the export began are not part of the snapshot
+    let mut txn = source.begin_txn(false, false).unwrap();
+    {
+        let source = source.clone();
+        std::thread::spawn(move || {
+            let mut txn = source.begin_txn(true, false).unwrap();
+            put(
+                &source.collections[0],
+                &mut txn,
+                6,
+                &[("value", Value::Long(0))],
+            );
+            txn.commit().unwrap();
+        })
+        .join()
+        .unwrap();
+    }
+    let mut archive = vec![];
+    let exported = source.export_all(&mut txn, &mut archive).unwrap();
+    txn.abort();
+    let counts: Vec<_> = exported
+        .collections
+        .iter()
+        .map(|c| (c.name.as_str(), c.objects, c.links))
+        .collect();
+    assert_eq!(counts, vec![("Item", 5, 3), ("Owner", 2, 0)]);
+
+    let target = open("export_all_target", schema);
+    let mut txn = target.begin_txn(true, false).unwrap();
+    let imported = target
+        .import_all(&mut txn, &mut archive.as_slice())
+        .unwrap();
+    txn.commit().unwrap();
+    assert_eq!(imported, exported);
+    let schema = Schema::from_json(schema.as_bytes()).unwrap();
+    assert!(target.schema_matches(&schema).unwrap());
+
+    let mut txn = target.begin_txn(false, false).unwrap();
+    let (items, owners) = (&target.collections[0], &target.collections[1]);
+    assert_eq!(items.count(&mut txn).unwrap(), 5);
+    assert_eq!(owners.count(&mut txn).unwrap(), 2);
+    items
+        .verify_link(&mut txn, link_id, &[(1, 1), (2, 1), (5, 2)])
+        .unwrap();
+    let mut key = IndexKey::new();
+    key.add_long(1);
+    assert_eq!(
+        items.count_by_index(&mut txn, xxh3_64(b"value"), &key),
+        Ok(3)
+    );
+    txn.abort();
+    close_and_delete(source);
+    close_and_delete(target);
+}
@@ -0,0 +1,221 @@
+mod common;
+
+use common::*;
+use isar_core::error::IsarError;
+use isar_core::instance::IsarInstance;
+use isar_core::oplog::{OpLogEntry, OpType};
+use std::sync::{Arc, Mutex};
+
+const SCHEMA: &str = r#"[
+    {"name": "Logged", "oplog": true, "properties": [{"name": "value", "type": "Long"}]},
+    {"name": "Other", "oplog": true, "properties": [{"name": "value", "type": "Long"}]},
+    {"name": "Plain", "properties": [{"name": "value", "type": "Long"}]}
+]"#;
+
+fn changes(
+    instance: &Arc<IsarInstance>,
+    index: usize,
+    since_seq: i64,
+    limit: Option<u32>,
+) -> Vec<OpLogEntry> {
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let entries = instance
+        .changes_since(&mut txn, &instance.collections[index], since_seq, limit)
+        .unwrap();
+    txn.abort();
+    entries
+}
+
+fn ops(entries: &[OpLogEntry]) -> Vec<(i64, OpType)> {
+    entries.iter().map(|e| (e.id, e.op)).collect()
+}
+
+#[test]
+fn test_changes_since() {
+    let instance = open("oplog_changes", SCHEMA);
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=4 {
+        put(
+            &instance.collections[0],
+            &mut txn,
+            id,
+            &[("value", Value::Long(id))],
+        );
+        put(
+            &instance.collections[1],
+            &mut txn,
+            id,
+            &[("value", Value::Long(id))],
+        );
+        put(
+            &instance.collections[2],
+            &mut txn,
+            id,
+            &[("value", Value::Long(id))],
+        );
+    }
+    txn.commit().unwrap();
+
+    let all = changes(&instance, 0, -1, None);
+    let expected: Vec<_> = (1..=4).map(|id| (id, OpType::Insert)).collect();
+    assert_eq!(ops(&all), expected);
+    let collection_id = instance.collections[0].id;
+    assert!(all.iter().all(|e| e.collection_id == collection_id));
+    assert!(all.windows(2).all(|w| w[0].seq < w[1].seq));
+    assert_eq!(changes(&instance, 1, -1, None).len(), 4);
+
+    let first = changes(&instance, 0, -1, Some(2));
+    assert_eq!(ops(&first), expected[..2]);
+    let rest = changes(&instance, 0, first.last().unwrap().seq, None);
+    assert_eq!(ops(&rest), expected[2..]);
+    let last_seq = rest.last().unwrap().seq;
+    assert!(changes(&instance, 0, last_seq, None).is_empty());
+    assert!(changes(&instance, 0, i64::MAX, None).is_empty());
+    assert!(changes(&instance, 0, -1, Some(0)).is_empty());
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(
+        &instance.collections[0],
+        &mut txn,
+        5,
+        &[("value", Value::Long(5))],
+    );
+    txn.commit().unwrap();
+    let next = changes(&instance, 0, last_seq, None);
+    assert_eq!(ops(&next), vec![(5, OpType::Insert)]);
+    assert!(next[0].seq > last_seq);
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let result = instance.changes_since(&mut txn, &instance.collections[2], -1, None);
+    assert!(matches!(result, Err(IsarError::IllegalArg { .. })));
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_prune_changes() {
+    let instance = open("oplog_prune", SCHEMA);
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=4 {
+        put(
+            &instance.collections[0],
+            &mut txn,
+            id,
+            &[("value", Value::Long(id))],
+        );
+    }
+    txn.commit().unwrap();
+    let entries = changes(&instance, 0, -1, None);
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    let count = instance.prune_changes(&mut txn, entries[2].seq).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(count, 2);
+    assert_eq!(changes(&instance, 0, -1, None), entries[2..]);
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    let count = instance.prune_changes(&mut txn, i64::MAX).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(count, 1);
+    let kept = changes(&instance, 0, -1, None);
+    assert_eq!(kept, entries[3..]);
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(
+        &instance.collections[0],
+        &mut txn,
+        5,
+        &[("value", Value::Long(5))],
+    );
+    txn.commit().unwrap();
+    let next = changes(&instance, 0, kept[0].seq, None);
+    assert_eq!(ops(&next), vec![(5, OpType::Insert)]);
+    assert_eq!(next[0].seq, kept[0].seq + 1);
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_logged_operations() {
+    let instance = open("oplog_operations", SCHEMA);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(col, &mut txn, 1, &[("value", Value::Long(1))]);
+    put(col, &mut txn, 2, &[("value", Value::Long(2))]);
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(col, &mut txn, 1, &[("value", Value::Long(10))]);
+    assert!(col.delete(&mut txn, 2).unwrap());
+    assert!(!col.delete(&mut txn, 3).unwrap());
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(
+        &instance.collections[2],
+        &mut txn,
+        1,
+        &[("value", Value::Long(1))],
+    );
+    col.clear(&mut txn).unwrap();
+    txn.commit().unwrap();
+
+    let entries = changes(&instance, 0, -1, None);
+    let expected = vec![
+        (1, OpType::Insert),
+        (2, OpType::Insert),
+        (1, OpType::Update),
+        (2, OpType::Delete),
+        (0, OpType::Clear),
+    ];
+    assert_eq!(ops(&entries), expected);
+    assert!(entries.windows(2).all(|w| w[0].seq < w[1].seq));
+    assert_eq!(entries[0].txn_id, entries[1].txn_id);
+    assert!(entries[1].txn_id < entries[2].txn_id);
+    assert_eq!(entries[2].txn_id, entries[3].txn_id);
+    assert!(entries[3].txn_id < entries[4].txn_id);
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_touch() {
+    let schema = r#"[{
+        "name": "Doc",
+        "oplog": true,
+        "versioned": true,
+        "properties": [{"name": "value", "type": "Long"}]
+    }]"#;
+    let instance = open("oplog_touch", schema);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(col, &mut txn, 1, &[("value", Value::Long(7))]);
+    txn.commit().unwrap();
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let before = col.get_row_meta(&mut txn, 1).unwrap().unwrap();
+    txn.abort();
+
+    let reported = Arc::new(Mutex::new(vec![]));
+    let reported_clone = reported.clone();
+    let _handle = instance.watch_collection_ids(
+        col,
+        Box::new(move |ids: &[i64]| reported_clone.lock().unwrap().push(ids.to_vec())),
+    );
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    assert_eq!(col.touch(&mut txn, 1), Ok(true));
+    assert_eq!(col.touch(&mut txn, 2), Ok(false));
+    txn.commit().unwrap();
+
+    assert_eq!(*reported.lock().unwrap(), vec![vec![1]]);
+    let entries = changes(&instance, 0, -1, None);
+    assert_eq!(
+        ops(&entries),
+        vec![(1, OpType::Insert), (1, OpType::Update)]
+    );
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let after = col.get_row_meta(&mut txn, 1).unwrap().unwrap();
+    assert_eq!(after.created_txn, before.created_txn);
+    assert!(after.modified_txn > before.modified_txn);
+    let object = col.get(&mut txn, 1).unwrap().unwrap();
+    assert_eq!(object.read_long(property(col, "value").offset), 7);
+    txn.abort();
+    close_and_delete(instance);
+}
@@ -0,0 +1,1084 @@
+mod common;
+
+use common::*;
+use isar_core::error::IsarError;
+use isar_core::index::index_key::IndexKey;
+use isar_core::instance::IsarInstance;
+use isar_core::query::filter::Filter;
+use isar_core::query::{Query, SlowQueryLog, Sort};
+use isar_core::txn::IsarTxn;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use xxhash_rust::xxh3::{xxh3_64, xxh3_64_with_seed};
+
+const SCHEMA: &str = r#"[{
+    "name": "Item",
+    "properties": [
+        {"name": "group", "type": "Long"},
+        {"name": "value", "type": "Long"}
+    ],
+    "indexes": [{
+        "name": "value",
+        "properties": [{"name": "value", "type": "Value", "caseSensitive": false}],
+        "unique": false
+    }]
+}]"#;
+
+/// Ids 1 to 40 with every fifth value null.
+fn open_filled() -> Arc<IsarInstance> {
+    let instance = open("query", SCHEMA);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=40 {
+        let group = Value::Long(id % 3);
+        if id % 5 == 0 {
+            put(col, &mut txn, id, &[("group", group)]);
+        } else {
+            let value = Value::Long((id * 7) % 13);
+            put(col, &mut txn, id, &[("group", group), ("value", value)]);
+        }
+    }
+    txn.commit().unwrap();
+    instance
+}
+
+fn value_key(value: i64) -> IndexKey {
+    let mut key = IndexKey::new();
+    key.add_long(value);
+    key
+}
+
+fn find_while_ids(query: &Query, txn: &mut IsarTxn) -> Vec<i64> {
+    let mut ids = vec![];
+    query
+        .find_while(txn, |id, _| {
+            ids.push(id);
+            true
+        })
+        .unwrap();
+    ids
+}
+
+fn find_iter_ids(query: &Query, txn: &mut IsarTxn) -> Vec<i64> {
+    query
+        .find_iter(txn)
+        .unwrap()
+        .map(|r| r.unwrap().0)
+        .collect()
+}
+
+#[test]
+fn test_find_iter_matches_find_while() {
+    let instance = open_filled();
+    let col = &instance.collections[0];
+    let index_id = xxh3_64(b"value");
+
+    let mut queries = vec![];
+    for sort in [Sort::Ascending, Sort::Descending] {
+        let mut qb = col.new_query_builder();
+        qb.add_id_where_clause(35, 3).unwrap();
+        queries.push(qb.build().unwrap());
+
+        let mut qb = col.new_query_builder();
+        qb.add_id_set_where_clause(&[7, 3, 99, 12], sort).unwrap();
+        queries.push(qb.build().unwrap());
+
+        let mut qb = col.new_query_builder();
+        qb.add_index_where_clause(index_id, value_key(i64::MIN), value_key(8), sort, false)
+            .unwrap();
+        queries.push(qb.build().unwrap());
+
+        let mut qb = col.new_query_builder();
+        qb.add_index_where_clause(index_id, value_key(2), value_key(9), sort, true)
+            .unwrap();
+        qb.set_filter_expression("group == 1").unwrap();
+        queries.push(qb.build().unwrap());
+
+        // overlapping where clauses are deduplicated
+        let mut qb = col.new_query_builder();
+        qb.add_index_where_clause(index_id, value_key(3), value_key(10), sort, false)
+            .unwrap();
+        qb.add_id_where_clause(1, 20).unwrap();
+        qb.set_offset(4);
+        qb.set_limit(9);
+        queries.push(qb.build().unwrap());
+
+        let mut qb = col.new_query_builder();
+        qb.add_distinct(&property(col, "group"), false);
+        queries.push(qb.build().unwrap());
+
+        let mut qb = col.new_query_builder();
+        qb.add_sort(&property(col, "value"), sort).unwrap();
+        qb.set_offset(2);
+        qb.set_limit(10);
+        queries.push(qb.build().unwrap());
+    }
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    for query in &queries {
+        let expected = find_while_ids(query, &mut txn);
+        assert!(!expected.is_empty(), "{}", query.describe());
+        assert_eq!(
+            find_iter_ids(query, &mut txn),
+            expected,
+            "{}",
+            query.describe()
+        );
+
+        let even: Vec<i64> = query
+            .find_iter(&mut txn)
+            .unwrap()
+            .map(|r| r.unwrap().0)
+            .filter(|id| id % 2 == 0)
+            .take(3)
+            .collect();
+        let expected_even: Vec<i64> = expected
+            .into_iter()
+            .filter(|id| id % 2 == 0)
+            .take(3)
+            .collect();
+        assert_eq!(even, expected_even, "{}", query.describe());
+    }
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_find_iter_is_lazy() {
+    let instance = open_filled();
+    let col = &instance.collections[0];
+    let mut qb = col.new_query_builder();
+    qb.set_max_scan(Some(5));
+    let query = qb.build().unwrap();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert!(query.find_while(&mut txn, |_, _| true).is_err());
+
+    let first: Vec<i64> = query
+        .find_iter(&mut txn)
+        .unwrap()
+        .take(3)
+        .map(|r| r.unwrap().0)
+        .collect();
+    assert_eq!(first, vec![1, 2, 3]);
+
+    let mut iter = query.find_iter(&mut txn).unwrap();
+    assert_eq!(iter.by_ref().take(5).filter(|r| r.is_ok()).count(), 5);
+    assert!(iter.next().unwrap().is_err());
+    assert!(iter.next().is_none());
+    drop(iter);
+
+    // the cursors are returned to the transaction
+    assert_eq!(col.count(&mut txn).unwrap(), 40);
+    txn.abort();
+    close_and_delete(instance);
+}
+
+const WORDS_SCHEMA: &str = r#"[
+    {
+        "name": "Text",
+        "properties": [{"name": "text", "type": "String"}],
+        "indexes": [{
+            "name": "text",
+            "properties": [{"name": "text", "type": "Words", "caseSensitive": false}],
+            "unique": false
+        }]
+    },
+    {
+        "name": "Strict",
+        "properties": [{"name": "text", "type": "String"}],
+        "indexes": [{
+            "name": "text",
+            "properties": [{"name": "text", "type": "Words", "caseSensitive": false}],
+            "unique": false,
+            "keyOverflow": "Error"
+        }]
+    }
+]"#;
+
+#[test]
+fn test_words_index_long_word() {
+    let instance = open("words_long", WORDS_SCHEMA);
+    let (text, strict) = (&instance.collections[0], &instance.collections[1]);
+    let long = "a".repeat(5000);
+    let value = format!("short {} tail", long);
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(text, &mut txn, 1, &[("text", Value::String(&value))]);
+    put(
+        text,
+        &mut txn,
+        2,
+        &[("text", Value::String(&format!("{}b", long)))],
+    );
+    put(
+        strict,
+        &mut txn,
+        2,
+        &[("text", Value::String("short tail"))],
+    );
+    txn.commit().unwrap();
+
+    // a failed write closes the transaction
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    let bytes = build(strict, &[("text", Value::String(&value))]);
+    assert!(matches!(
+        strict.put_raw(&mut txn, Some(1), &bytes),
+        Err(IsarError::KeyTooLong { len: 5000, .. })
+    ));
+    txn.abort();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    for (query, expected) in [
+        (long.as_str(), vec![1]),
+        ("tail", vec![1]),
+        ("a*", vec![1, 2]),
+    ] {
+        let mut qb = text.new_query_builder();
+        qb.add_matches(&property(text, "text"), query).unwrap();
+        let mut ids = find_while_ids(&qb.build().unwrap(), &mut txn);
+        ids.sort();
+        assert_eq!(ids, expected);
+    }
+    assert_eq!(strict.count(&mut txn).unwrap(), 1);
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_words_index_scan() {
+    let instance = open("words_scan", WORDS_SCHEMA);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=50 {
+        let text = match id {
+            7 => "A rare, Quick fox".to_string(),
+            30 => "quick rarely".to_string(),
+            _ => format!("common quick text {}", id),
+        };
+        put(col, &mut txn, id, &[("text", Value::String(&text))]);
+    }
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    // only the entries of the longest term are scanned, the other terms are filtered
+    for (query, expected) in [
+        ("rare", vec![7]),
+        ("RARE fox", vec![7]),
+        ("rare*", vec![7, 30]),
+        ("rar* fox", vec![7]),
+        ("fox rarely", vec![]),
+    ] {
+        let mut qb = col.new_query_builder();
+        qb.add_matches(&property(col, "text"), query).unwrap();
+        qb.set_max_scan(Some(2));
+        let query = qb.build().unwrap();
+        assert_eq!(find_while_ids(&query, &mut txn), expected);
+        assert_eq!(find_iter_ids(&query, &mut txn), expected);
+    }
+
+    // without a selective term the whole index range is scanned
+    let mut qb = col.new_query_builder();
+    qb.add_matches(&property(col, "text"), "quick").unwrap();
+    qb.set_max_scan(Some(2));
+    let query = qb.build().unwrap();
+    assert!(matches!(
+        query.find_while(&mut txn, |_, _| true),
+        Err(IsarError::ScanLimitExceeded { limit: 2 })
+    ));
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_profile_index_query() {
+    let instance = open_filled();
+    let col = &instance.collections[0];
+    let values: Vec<(i64, i64)> = (1..=40)
+        .filter(|id| id % 5 != 0)
+        .map(|id| (id, (id * 7) % 13))
+        .filter(|(_, value)| (2..=9).contains(value))
+        .collect();
+    let matched = values.iter().filter(|(id, _)| id % 3 == 1).count() as u64;
+
+    let mut qb = col.new_query_builder();
+    qb.add_index_where_clause(
+        xxh3_64(b"value"),
+        value_key(2),
+        value_key(9),
+        Sort::Ascending,
+        false,
+    )
+    .unwrap();
+    qb.set_filter_expression("group == 1").unwrap();
+    qb.add_sort(&property(col, "group"), Sort::Descending)
+        .unwrap();
+    qb.set_limit(2);
+    let query = qb.build().unwrap();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let stats = query.profile(&mut txn).unwrap();
+    txn.abort();
+    assert!(stats.uses_index());
+    assert_eq!(stats.where_clauses.len(), 1);
+    assert_eq!(stats.where_clauses[0].index.as_deref(), Some("value"));
+    assert_eq!(stats.where_clauses[0].estimated, Some(values.len() as u64));
+    assert_eq!(stats.scanned(), values.len() as u64);
+    assert_eq!(stats.matched, matched);
+    assert_eq!(stats.returned, 2);
+    assert!(stats.sort_in_memory);
+    assert!(!stats.dedup);
+    assert!(stats.filter_time + stats.read_time <= stats.total_time);
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_cancel_from_other_thread() {
+    let instance = open("query_cancel", SCHEMA);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=200 {
+        put(col, &mut txn, id, &[("value", Value::Long(id))]);
+    }
+    txn.commit().unwrap();
+
+    let token = Arc::new(AtomicBool::new(false));
+    let mut qb = col.new_query_builder();
+    qb.set_cancel_token(token.clone());
+    let query = qb.build().unwrap();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let mut seen = 0;
+    let result = query.find_while(&mut txn, |_, _| {
+        seen += 1;
+        if seen == 1 {
+            thread::scope(|s| s.spawn(|| query.cancel()).join().unwrap());
+        }
+        true
+    });
+    assert!(matches!(result, Err(IsarError::Cancelled {})));
+    assert!(seen < 200);
+    txn.abort();
+
+    // the token stays set until its owner clears it
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert!(matches!(
+        query.count(&mut txn),
+        Err(IsarError::Cancelled {})
+    ));
+    assert!(query.find_iter(&mut txn).is_err());
+    token.store(false, Ordering::Relaxed);
+    assert_eq!(query.count(&mut txn).unwrap(), 200);
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_find_iter_remaining() {
+    let instance = open_filled();
+    let col = &instance.collections[0];
+    let index_id = xxh3_64(b"value");
+
+    let mut qb = col.new_query_builder();
+    qb.add_id_where_clause(40, 1).unwrap();
+    let ids = qb.build().unwrap();
+
+    let mut qb = col.new_query_builder();
+    qb.add_index_where_clause(
+        index_id,
+        value_key(0),
+        value_key(12),
+        Sort::Ascending,
+        false,
+    )
+    .unwrap();
+    // the ids with a null value are not in the index range
+    qb.add_id_set_where_clause(&[5, 10, 15], Sort::Ascending)
+        .unwrap();
+    let index_and_ids = qb.build().unwrap();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    for query in [&ids, &index_and_ids] {
+        let total = find_while_ids(query, &mut txn).len() as u64;
+        let mut iter = query.find_iter(&mut txn).unwrap();
+        assert_eq!(iter.remaining(true).unwrap(), Some(total));
+        let mut consumed = 0;
+        while let Some(result) = iter.next() {
+            result.unwrap();
+            consumed += 1;
+            assert_eq!(iter.remaining(true).unwrap(), Some(total - consumed));
+            assert!(iter.remaining(false).unwrap().is_some());
+        }
+        assert_eq!(iter.remaining(false).unwrap(), Some(0));
+    }
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_id_range_with_index_where_clause() {
+    let instance = open("query_id_index", SCHEMA);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=200 {
+        put(col, &mut txn, id, &[("value", Value::Long(id % 4))]);
+    }
+    txn.commit().unwrap();
+
+    // each side alone matches at least 20 objects, together only a few
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    for ((lower, upper), plan) in [
+        ((1, 20), "id[1..20] > index[value]"),
+        ((1, 150), "index[value] > id[1..150]"),
+    ] {
+        let mut qb = col.new_query_builder();
+        qb.add_index_where_clause(
+            xxh3_64(b"value"),
+            value_key(1),
+            value_key(1),
+            Sort::Ascending,
+            false,
+        )
+        .unwrap();
+        qb.add_id_between(lower, upper, true);
+        qb.set_max_scan(Some((upper - lower + 1).min(50) as u32));
+        let query = qb.build().unwrap();
+        assert_eq!(
+            query.explain(&mut txn).unwrap(),
+            format!("{} | filter", plan)
+        );
+
+        let expected: Vec<i64> = (lower..=upper).filter(|id| id % 4 == 1).collect();
+        assert_eq!(find_while_ids(&query, &mut txn), expected);
+        assert_eq!(find_iter_ids(&query, &mut txn), expected);
+    }
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_estimate_bytes() {
+    let schema = r#"[{"name": "Note", "properties": [
+        {"name": "group", "type": "Long"},
+        {"name": "text", "type": "String"}
+    ]}]"#;
+    let instance = open("query_bytes", schema);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=500 {
+        let text = "x".repeat((id * 37 % 200) as usize);
+        let values = [
+            ("group", Value::Long(id % 2)),
+            ("text", Value::String(&text)),
+        ];
+        put(col, &mut txn, id, &values);
+    }
+    txn.commit().unwrap();
+
+    let actual_bytes = |query: &Query, txn: &mut IsarTxn, offset: usize, limit: usize| {
+        let mut bytes = 0;
+        query
+            .for_each(txn, offset, limit, |_, object| {
+                bytes += object.len() as u64;
+                Ok(ControlFlow::Continue(()))
+            })
+            .unwrap();
+        bytes
+    };
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    // unfiltered queries extrapolate a sample of the objects
+    let all = col.new_query_builder().build().unwrap();
+    for (offset, limit) in [(0, usize::MAX), (100, 300), (490, 50)] {
+        let actual = actual_bytes(&all, &mut txn, offset, limit) as f64;
+        let estimate = all.estimate_bytes(&mut txn, offset, limit).unwrap() as f64;
+        assert!(
+            (estimate - actual).abs() <= actual * 0.25,
+            "{} {}",
+            estimate,
+            actual
+        );
+    }
+
+    // filtered queries are scanned
+    let mut qb = col.new_query_builder();
+    qb.set_filter_expression("group == 1").unwrap();
+    let odd = qb.build().unwrap();
+    assert_eq!(
+        odd.estimate_bytes(&mut txn, 10, 100).unwrap(),
+        actual_bytes(&odd, &mut txn, 10, 100)
+    );
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_id_set_where_clause() {
+    let instance = open_filled();
+    let col = &instance.collections[0];
+    let ids = [9, 3, 12, 3, 100, 6, 5];
+    let mut txn = instance.begin_txn(false, false).unwrap();
+
+    for (sort, expected) in [
+        (Sort::Ascending, vec![3, 5, 6, 9, 12]),
+        (Sort::Descending, vec![12, 9, 6, 5, 3]),
+    ] {
+        let mut qb = col.new_query_builder();
+        qb.add_id_set_where_clause(&ids, sort).unwrap();
+        let query = qb.build().unwrap();
+        assert_eq!(find_while_ids(&query, &mut txn), expected);
+        assert_eq!(find_iter_ids(&query, &mut txn), expected);
+    }
+
+    // only the ids of the set that match the filter are returned
+    let mut qb = col.new_query_builder();
+    qb.add_id_set_where_clause(&ids, Sort::Ascending).unwrap();
+    qb.set_filter_expression("group == 0").unwrap();
+    let query = qb.build().unwrap();
+    assert_eq!(find_while_ids(&query, &mut txn), vec![3, 6, 9, 12]);
+    assert_eq!(query.count(&mut txn).unwrap(), 4);
+
+    let mut qb = col.new_query_builder();
+    qb.add_id_set_where_clause(&[], Sort::Ascending).unwrap();
+    let query = qb.build().unwrap();
+    assert!(find_while_ids(&query, &mut txn).is_empty());
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_slow_query_log() {
+    let instance = open_filled();
+    let col = &instance.collections[0];
+    let reported = Arc::new(Mutex::new(vec![]));
+    let reported_clone = reported.clone();
+    instance.set_slow_query_log(Some(SlowQueryLog {
+        threshold: Duration::from_millis(20),
+        callback: Box::new(move |plan: &str, elapsed| {
+            reported_clone
+                .lock()
+                .unwrap()
+                .push((plan.to_string(), elapsed));
+        }),
+    }));
+    let mut qb = col.new_query_builder();
+    qb.set_filter_expression("group == 1").unwrap();
+    let query = qb.build().unwrap();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    query.find_while(&mut txn, |_, _| true).unwrap();
+    assert!(reported.lock().unwrap().is_empty());
+
+    query
+        .find_while(&mut txn, |_, _| {
+            thread::sleep(Duration::from_millis(2));
+            true
+        })
+        .unwrap();
+    txn.abort();
+    {
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 1);
+        let (plan, elapsed) = &reported[0];
+        assert_eq!(plan, &query.describe());
+        // 14 objects match, each took at least 2ms
+        assert!(*elapsed >= Duration::from_millis(28));
+        assert!(*elapsed < Duration::from_secs(10));
+    }
+
+    instance.set_slow_query_log(None);
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    query
+        .find_while(&mut txn, |_, _| {
+            thread::sleep(Duration::from_millis(2));
+            true
+        })
+        .unwrap();
+    txn.abort();
+    assert_eq!(reported.lock().unwrap().len(), 1);
+    close_and_delete(instance);
+}
+
+const LINK_SCHEMA: &str = r#"[
+    {
+        "name": "User",
+        "properties": [{"name": "age", "type": "Long"}],
+        "links": [{"name": "orders", "target": "Order"}]
+    },
+    {"name": "Order", "properties": [{"name": "amount", "type": "Long"}]}
+]"#;
+
+#[test]
+fn test_link_any() {
+    let instance = open("query_link_any", LINK_SCHEMA);
+    let users = &instance.collections[0];
+    let orders = &instance.collections[1];
+    let link_id = xxh3_64_with_seed(b"orders", xxh3_64_with_seed(b"User", 0));
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=4 {
+        put(users, &mut txn, id, &[("age", Value::Long(20 + id))]);
+    }
+    // user 4 has no orders
+    let user_orders = [
+        (1, 10, 50),
+        (1, 11, 150),
+        (2, 20, 80),
+        (3, 30, 100),
+        (3, 31, 500),
+    ];
+    for (user, order, amount) in user_orders {
+        put(orders, &mut txn, order, &[("amount", Value::Long(amount))]);
+        users.link(&mut txn, link_id, user, order).unwrap();
+    }
+    txn.commit().unwrap();
+
+    let amount = property(orders, "amount");
+    let find = |lower: i64, upper: i64| {
+        let filter = Filter::long(&amount, lower, upper).unwrap();
+        let mut qb = users.new_query_builder();
+        qb.set_filter(Filter::link_any(users, "orders", filter).unwrap());
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        let ids = find_while_ids(&qb.build().unwrap(), &mut txn);
+        txn.abort();
+        ids
+    };
+    assert_eq!(find(101, i64::MAX), vec![1, 3]);
+    assert_eq!(find(100, 100), vec![3]);
+    assert_eq!(find(0, 99), vec![1, 2]);
+    assert!(find(1000, i64::MAX).is_empty());
+
+    let filter = Filter::long(&amount, 0, i64::MAX).unwrap();
+    assert!(matches!(
+        Filter::link_any(users, "missing", filter),
+        Err(IsarError::IllegalArg { .. })
+    ));
+    close_and_delete(instance);
+}
+
+const PAYLOAD_SCHEMA: &str = r#"[{
+    "name": "Item",
+    "properties": [
+        {"name": "value", "type": "Long"},
+        {"name": "payload", "type": "String"}
+    ],
+    "indexes": [{
+        "name": "value",
+        "properties": [{"name": "value", "type": "Value", "caseSensitive": false}],
+        "unique": false
+    }]
+}]"#;
+
+#[test]
+fn test_prefetch() {
+    // large enough for the scans to advance the prefetched window several times
+    let instance = open("query_prefetch", PAYLOAD_SCHEMA);
+    let col = &instance.collections[0];
+    let payload = "x".repeat(1000);
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=5000 {
+        let value = Value::Long((id * 7) % 101);
+        put(
+            col,
+            &mut txn,
+            id,
+            &[("value", value), ("payload", Value::String(&payload))],
+        );
+    }
+    txn.commit().unwrap();
+
+    let ids = |prefetch: bool, index: bool, sort: Sort| {
+        let mut qb = col.new_query_builder();
+        if index {
+            qb.add_index_where_clause(xxh3_64(b"value"), value_key(10), value_key(90), sort, false)
+                .unwrap();
+        } else if sort == Sort::Ascending {
+            qb.add_id_where_clause(100, 4900).unwrap();
+        } else {
+            qb.add_id_where_clause(4900, 100).unwrap();
+        }
+        qb.set_prefetch(prefetch);
+        let query = qb.build().unwrap();
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        let ids = find_while_ids(&query, &mut txn);
+        assert_eq!(find_iter_ids(&query, &mut txn), ids);
+        txn.abort();
+        ids
+    };
+    for index in [false, true] {
+        for sort in [Sort::Ascending, Sort::Descending] {
+            let expected = ids(false, index, sort);
+            assert!(expected.len() > 3000);
+            assert_eq!(ids(true, index, sort), expected);
+        }
+    }
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_is_index_covered() {
+    let instance = open("query_covered", SCHEMA);
+    let col = &instance.collections[0];
+    let value = property(col, "value");
+    let group = property(col, "group");
+    let query = |index: bool, filter: Option<Filter>| {
+        let mut qb = col.new_query_builder();
+        if index {
+            qb.add_index_where_clause(
+                xxh3_64(b"value"),
+                value_key(0),
+                value_key(10),
+                Sort::Ascending,
+                false,
+            )
+            .unwrap();
+        } else {
+            qb.add_id_where_clause(0, 10).unwrap();
+        }
+        if let Some(filter) = filter {
+            qb.set_filter(filter);
+        }
+        qb.build().unwrap()
+    };
+
+    let value_filter = || Some(Filter::long(&value, 2, 8).unwrap());
+    assert!(query(true, None).is_index_covered(&[&value]));
+    assert!(query(true, value_filter()).is_index_covered(&[&value]));
+    assert!(query(true, value_filter()).is_index_covered(&[]));
+
+    assert!(!query(true, None).is_index_covered(&[&value, &group]));
+    let group_filter = Filter::long(&group, 1, 1).unwrap();
+    let filter = Filter::and(vec![value_filter().unwrap(), group_filter]);
+    assert!(!query(true, Some(filter)).is_index_covered(&[&value]));
+    assert!(!query(false, value_filter()).is_index_covered(&[&value]));
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_max_scan() {
+    let instance = open_filled();
+    let col = &instance.collections[0];
+    let find = |expr: &str, max_scan: u32, index: bool, limit: Option<usize>| {
+        let mut qb = col.new_query_builder();
+        if index {
+            qb.add_index_where_clause(
+                xxh3_64(b"value"),
+                value_key(12),
+                value_key(12),
+                Sort::Ascending,
+                false,
+            )
+            .unwrap();
+        }
+        qb.set_filter_expression(expr).unwrap();
+        qb.set_max_scan(Some(max_scan));
+        if let Some(limit) = limit {
+            qb.set_limit(limit);
+        }
+        let query = qb.build().unwrap();
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        let mut ids = vec![];
+        let result = query.find_while(&mut txn, |id, _| {
+            ids.push(id);
+            true
+        });
+        txn.abort();
+        result.map(|_| ids)
+    };
+
+    // only ids 11, 24 and 37 have the value 12 but the scan reads all 40 objects
+    assert_eq!(
+        find("value == 12", 10, false, None),
+        Err(IsarError::ScanLimitExceeded { limit: 10 })
+    );
+    assert_eq!(find("value == 12", 40, false, None), Ok(vec![11, 24, 37]));
+    // the limit ends the scan at the next match
+    assert_eq!(find("value == 12", 24, false, Some(1)), Ok(vec![11]));
+    assert_eq!(find("value == 12", 3, true, None), Ok(vec![11, 24, 37]));
+    assert_eq!(
+        find("value == 12", 2, true, None),
+        Err(IsarError::ScanLimitExceeded { limit: 2 })
+    );
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_delete_in_place() {
+    let instance = open("delete_in_place", SCHEMA);
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in 1..=20 {
+        let values = [("group", Value::Long(id % 2)), ("value", Value::Long(id))];
+        put(col, &mut txn, id, &values);
+    }
+    txn.commit().unwrap();
+
+    let delete = |limit: usize| {
+        let mut qb = col.new_query_builder();
+        qb.set_filter_expression("group == 0").unwrap();
+        let query = qb.build().unwrap();
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        let count = query.delete(&mut txn, col, limit).unwrap();
+        txn.commit().unwrap();
+        count
+    };
+    assert_eq!(delete(3), 3);
+    assert_eq!(delete(usize::MAX), 7);
+    assert_eq!(delete(usize::MAX), 0);
+
+    let odd: Vec<i64> = (1..=20).step_by(2).collect();
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let query = col.new_query_builder().build().unwrap();
+    assert_eq!(find_while_ids(&query, &mut txn), odd);
+    let mut qb = col.new_query_builder();
+    qb.add_index_where_clause(
+        xxh3_64(b"value"),
+        value_key(i64::MIN),
+        value_key(i64::MAX),
+        Sort::Ascending,
+        false,
+    )
+    .unwrap();
+    let query = qb.build().unwrap();
+    assert_eq!(find_while_ids(&query, &mut txn), odd);
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_for_each() {
+    let instance = open_filled();
+    let col = &instance.collections[0];
+    let offset = property(col, "value").offset;
+    let query = col.new_query_builder().build().unwrap();
+    let mut txn = instance.begin_txn(false, false).unwrap();
+
+    // ids 1 to 4 have the values 7, 1, 8 and 2
+    let mut sum = 0;
+    let visited = query.for_each(&mut txn, 0, usize::MAX, |_, object| {
+        sum += object.read_long(offset);
+        if sum >= 18 {
+            Ok(ControlFlow::Break(()))
+        } else {
+            Ok(ControlFlow::Continue(()))
+        }
+    });
+    assert_eq!(visited, Ok(4));
+    assert_eq!(sum, 18);
+
+    let mut ids = vec![];
+    let visited = query.for_each(&mut txn, 2, 3, |id, _| {
+        ids.push(id);
+        Ok(ControlFlow::Continue(()))
+    });
+    assert_eq!(visited, Ok(3));
+    assert_eq!(ids, vec![3, 4, 5]);
+
+    let visited = query.for_each(&mut txn, 0, usize::MAX, |id, _| {
+        if id == 3 {
+            Err(IsarError::DbCorrupted {
+                message: "stop".to_string(),
+            })
+        } else {
+            Ok(ControlFlow::Continue(()))
+        }
+    });
+    let error = IsarError::DbCorrupted {
+        message: "stop".to_string(),
+    };
+    assert_eq!(visited, Err(error));
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_find_ids() {
+    let instance = open_filled();
+    let col = &instance.collections[0];
+    let index_id = xxh3_64(b"value");
+
+    let mut queries = vec![];
+    for sort in [Sort::Ascending, Sort::Descending] {
+        let mut qb = col.new_query_builder();
+        qb.add_id_where_clause(35, 3).unwrap();
+        queries.push(qb.build().unwrap());
+
+        let mut qb = col.new_query_builder();
+        qb.add_index_where_clause(index_id, value_key(2), value_key(9), sort, false)
+            .unwrap();
+        queries.push(qb.build().unwrap());
+
+        // overlapping where clauses are deduplicated
+        let mut qb = col.new_query_builder();
+        qb.add_index_where_clause(index_id, value_key(3), value_key(10), sort, false)
+            .unwrap();
+        qb.add_id_where_clause(1, 20).unwrap();
+        qb.set_offset(2);
+        qb.set_limit(15);
+        queries.push(qb.build().unwrap());
+
+        let mut qb = col.new_query_builder();
+        qb.add_index_where_clause(index_id, value_key(2), value_key(9), sort, true)
+            .unwrap();
+        qb.set_filter_expression("group == 1").unwrap();
+        queries.push(qb.build().unwrap());
+
+        let mut qb = col.new_query_builder();
+        qb.add_sort(&property(col, "value"), sort).unwrap();
+        queries.push(qb.build().unwrap());
+    }
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    for query in &queries {
+        let all = find_while_ids(query, &mut txn);
+        assert!(!all.is_empty(), "{}", query.describe());
+        for (offset, limit) in [(0, usize::MAX), (3, 4), (0, 1), (all.len(), 5)] {
+            let expected: Vec<i64> = all.iter().copied().skip(offset).take(limit).collect();
+            let ids = query.find_ids(&mut txn, offset, limit).unwrap();
+            assert_eq!(ids, expected, "{}", query.describe());
+        }
+    }
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_id_between() {
+    let instance = open_filled();
+    let col = &instance.collections[0];
+    let query = |ranges: &[(i64, i64, bool)], filter: Option<&str>, index: bool| {
+        let mut qb = col.new_query_builder();
+        if index {
+            qb.add_index_where_clause(
+                xxh3_64(b"value"),
+                value_key(i64::MIN),
+                value_key(i64::MAX),
+                Sort::Ascending,
+                false,
+            )
+            .unwrap();
+        }
+        for (start, end, inclusive) in ranges {
+            qb.add_id_between(*start, *end, *inclusive);
+        }
+        if let Some(filter) = filter {
+            qb.set_filter_expression(filter).unwrap();
+        }
+        qb.build().unwrap()
+    };
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    let q = query(&[(5, 12, true)], None, false);
+    assert_eq!(q.explain(&mut txn).unwrap(), "id[5..12] | filter");
+    assert_eq!(find_while_ids(&q, &mut txn), (5..=12).collect::<Vec<_>>());
+
+    let q = query(&[(12, 5, false)], Some("group == 1"), false);
+    assert_eq!(q.explain(&mut txn).unwrap(), "id[6..11] | filter");
+    assert_eq!(find_while_ids(&q, &mut txn), vec![7, 10]);
+
+    let q = query(&[(1, 20, true), (15, 30, true)], None, false);
+    assert_eq!(q.explain(&mut txn).unwrap(), "id[15..20] | filter");
+    assert_eq!(find_while_ids(&q, &mut txn), (15..=20).collect::<Vec<_>>());
+
+    // the id range is scanned instead of the index if it is smaller
+    let q = query(&[(1, 10, true)], None, true);
+    assert_eq!(
+        q.explain(&mut txn).unwrap(),
+        "id[1..10] > index[value] | filter"
+    );
+    let mut ids = find_while_ids(&q, &mut txn);
+    ids.sort_unstable();
+    assert_eq!(ids, (1..=10).collect::<Vec<_>>());
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_reverse() {
+    let instance = open_filled();
+    let col = &instance.collections[0];
+    let query = |index: bool, reverse: bool, offset: usize, limit: usize| {
+        let mut qb = col.new_query_builder();
+        if index {
+            qb.add_index_where_clause(
+                xxh3_64(b"value"),
+                value_key(2),
+                value_key(9),
+                Sort::Ascending,
+                false,
+            )
+            .unwrap();
+        }
+        qb.set_reverse(reverse);
+        qb.set_offset(offset);
+        qb.set_limit(limit);
+        qb.build().unwrap()
+    };
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    for index in [false, true] {
+        let forward = find_while_ids(&query(index, false, 0, usize::MAX), &mut txn);
+        let mut expected = forward.clone();
+        expected.reverse();
+        let reversed = find_while_ids(&query(index, true, 0, usize::MAX), &mut txn);
+        assert_eq!(reversed, expected);
+
+        // offset and limit count from the reversed start
+        let page = find_while_ids(&query(index, true, 2, 5), &mut txn);
+        assert_eq!(page, expected[2..7].to_vec());
+    }
+    txn.abort();
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_index_hint() {
+    let instance = open_filled();
+    let col = &instance.collections[0];
+    let mut txn = instance.begin_txn(false, false).unwrap();
+
+    let mut qb = col.new_query_builder();
+    qb.use_index("value");
+    qb.set_filter_expression("group == 2").unwrap();
+    let query = qb.build().unwrap();
+    assert_eq!(query.explain(&mut txn).unwrap(), "index[value] | filter");
+    let mut ids = find_while_ids(&query, &mut txn);
+    ids.sort_unstable();
+    let expected: Vec<i64> = (1..=40).filter(|id| id % 3 == 2).collect();
+    assert_eq!(ids, expected);
+
+    let mut qb = col.new_query_builder();
+    qb.use_index("missing");
+    assert_eq!(qb.build().err(), Some(IsarError::UnknownIndex {}));
+
+    // an id where clause cannot be answered by the index
+    let mut qb = col.new_query_builder();
+    qb.add_id_where_clause(1, 10).unwrap();
+    qb.use_index("value");
+    assert!(matches!(qb.build(), Err(IsarError::IllegalArg { .. })));
+
+    let mut qb = col.new_query_builder();
+    qb.no_index();
+    let query = qb.build().unwrap();
+    assert!(query.explain(&mut txn).unwrap().starts_with("id["));
+    assert_eq!(
+        find_while_ids(&query, &mut txn),
+        (1..=40).collect::<Vec<_>>()
+    );
+
+    let mut qb = col.new_query_builder();
+    qb.add_index_where_clause(
+        xxh3_64(b"value"),
+        value_key(2),
+        value_key(9),
+        Sort::Ascending,
+        false,
+    )
+    .unwrap();
+    qb.no_index();
+    assert!(matches!(qb.build(), Err(IsarError::IllegalArg { .. })));
+    txn.abort();
+    close_and_delete(instance);
+}
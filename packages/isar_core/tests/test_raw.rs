@@ -0,0 +1,51 @@
+#![cfg(feature = "raw-access")]
+
+mod common;
+
+use common::*;
+use isar_core::error::IsarError;
+
+const SCHEMA: &str = r#"[{"name": "Item", "properties": [{"name": "value", "type": "Long"}]}]"#;
+
+#[test]
+fn test_raw_put_get() {
+    let instance = open("raw", SCHEMA);
+    let other = open("raw_other", SCHEMA);
+
+    // reading a db that was never written does not create it
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert_eq!(instance.raw_get(&mut txn, "meta", b"key").unwrap(), None);
+    assert!(matches!(
+        instance.raw_put(&mut txn, "meta", b"key", b"value"),
+        Err(IsarError::WriteTxnRequired {})
+    ));
+    txn.abort();
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    instance
+        .raw_put(&mut txn, "meta", b"key", b"value")
+        .unwrap();
+    txn.commit().unwrap();
+
+    let mut txn = instance.begin_txn(false, false).unwrap();
+    assert_eq!(
+        instance.raw_get(&mut txn, "meta", b"key").unwrap(),
+        Some(&b"value"[..])
+    );
+    assert_eq!(
+        instance.raw_get(&mut txn, "meta", b"missing").unwrap(),
+        None
+    );
+    txn.abort();
+
+    let mut txn = other.begin_txn(false, false).unwrap();
+    assert!(matches!(
+        instance.raw_get(&mut txn, "meta", b"key"),
+        Err(IsarError::InstanceMismatch {})
+    ));
+    assert_eq!(other.raw_get(&mut txn, "meta", b"key").unwrap(), None);
+    txn.abort();
+
+    close_and_delete(instance);
+    close_and_delete(other);
+}
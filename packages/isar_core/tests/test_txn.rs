@@ -0,0 +1,401 @@
+mod common;
+
+use common::*;
+use isar_core::error::IsarError;
+use isar_core::instance::IsarInstance;
+use isar_core::object::isar_object::IsarObject;
+use isar_core::txn::IsarTxn;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SCHEMA: &str = r#"[{
+    "name": "Item",
+    "properties": [{"name": "value", "type": "Long"}]
+}]"#;
+
+#[test]
+fn test_read_during_write_with_write_priority() {
+    let isar = open("txn_priority", SCHEMA);
+    isar.set_write_priority(true);
+    let col = &isar.collections[0];
+
+    let mut txn = isar.begin_txn(true, true).unwrap();
+    put(col, &mut txn, 1, &[("value", Value::Long(1))]);
+
+    // a second writer waits for the write lock held by `txn`
+    let second = {
+        let isar = isar.clone();
+        thread::spawn(move || {
+            let mut txn = isar.begin_txn(true, true).unwrap();
+            let col = &isar.collections[0];
+            put(col, &mut txn, 2, &[("value", Value::Long(2))]);
+            txn.commit().unwrap();
+        })
+    };
+    thread::sleep(Duration::from_millis(100));
+
+    let (sender, receiver) = mpsc::channel();
+    let reader = {
+        let isar = isar.clone();
+        thread::spawn(move || {
+            let mut txn = isar.begin_txn(false, false).unwrap();
+            let count = isar.collections[0].count(&mut txn).unwrap();
+            txn.abort();
+            sender.send(count).unwrap();
+        })
+    };
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(0));
+    txn.commit().unwrap();
+
+    reader.join().unwrap();
+    second.join().unwrap();
+    let mut txn = isar.begin_txn(false, false).unwrap();
+    assert_eq!(col.count(&mut txn).unwrap(), 2);
+    txn.abort();
+    close_and_delete(isar);
+}
+
+#[test]
+fn test_commit_untracked_writes() {
+    let isar = open("txn_untracked", SCHEMA);
+    let col = &isar.collections[0];
+    let notified = Arc::new(AtomicUsize::new(0));
+    let notified_clone = notified.clone();
+    let _handle = isar.watch_collection(
+        col,
+        Box::new(move || {
+            notified_clone.fetch_add(1, Ordering::SeqCst);
+        }),
+    );
+
+    // counters do not notify watchers but have to be committed
+    let mut txn = isar.begin_txn(true, false).unwrap();
+    assert!(!txn.has_changes());
+    assert_eq!(isar.counter_incr(&mut txn, "counter", 2).unwrap(), 2);
+    assert!(txn.has_changes());
+    txn.commit().unwrap();
+
+    let mut txn = isar.begin_txn(true, true).unwrap();
+    assert!(!txn.has_changes());
+    isar.counter_incr(&mut txn, "counter", 1).unwrap();
+    assert!(txn.has_changes());
+    txn.commit().unwrap();
+
+    // deleting a missing object does not change anything
+    let mut txn = isar.begin_txn(true, false).unwrap();
+    assert!(!col.delete(&mut txn, 1).unwrap());
+    assert!(!txn.has_changes());
+    txn.commit().unwrap();
+
+    let mut txn = isar.begin_txn(false, false).unwrap();
+    assert!(!txn.has_changes());
+    assert_eq!(isar.counter_get(&mut txn, "counter").unwrap(), 3);
+    txn.abort();
+    assert_eq!(notified.load(Ordering::SeqCst), 0);
+
+    let mut txn = isar.begin_txn(true, false).unwrap();
+    put(col, &mut txn, 1, &[("value", Value::Long(1))]);
+    assert!(txn.has_changes());
+    txn.commit().unwrap();
+    assert_eq!(notified.load(Ordering::SeqCst), 1);
+    close_and_delete(isar);
+}
+
+fn ids(isar: &IsarInstance, txn: &mut IsarTxn) -> Vec<i64> {
+    let query = isar.collections[0].new_query_builder().build().unwrap();
+    query.find_ids(txn, 0, usize::MAX).unwrap()
+}
+
+#[test]
+fn test_nested_savepoints() {
+    let isar = open("txn_savepoints", SCHEMA);
+    let col = &isar.collections[0];
+    let reported = Arc::new(Mutex::new(vec![]));
+    let reported_clone = reported.clone();
+    let _handle = isar.watch_collection_ids(
+        col,
+        Box::new(move |ids: &[i64]| reported_clone.lock().unwrap().push(ids.to_vec())),
+    );
+
+    let mut txn = isar.begin_txn(true, false).unwrap();
+    put(col, &mut txn, 1, &[("value", Value::Long(1))]);
+    txn.savepoint().unwrap();
+    put(col, &mut txn, 2, &[("value", Value::Long(2))]);
+    txn.savepoint().unwrap();
+    put(col, &mut txn, 3, &[("value", Value::Long(3))]);
+    txn.rollback_to_savepoint().unwrap();
+    assert_eq!(ids(&isar, &mut txn), vec![1, 2]);
+
+    txn.savepoint().unwrap();
+    put(col, &mut txn, 4, &[("value", Value::Long(4))]);
+    txn.release_savepoint().unwrap();
+    txn.release_savepoint().unwrap();
+    assert!(txn.release_savepoint().is_err());
+
+    // a released savepoint is undone with its parent
+    txn.savepoint().unwrap();
+    put(col, &mut txn, 5, &[("value", Value::Long(5))]);
+    txn.savepoint().unwrap();
+    put(col, &mut txn, 6, &[("value", Value::Long(6))]);
+    txn.release_savepoint().unwrap();
+    txn.rollback_to_savepoint().unwrap();
+
+    let result: Result<(), IsarError> = txn.with_savepoint(|txn| {
+        put(col, txn, 7, &[("value", Value::Long(7))]);
+        Err(IsarError::Cancelled {})
+    });
+    assert_eq!(result, Err(IsarError::Cancelled {}));
+    assert_eq!(ids(&isar, &mut txn), vec![1, 2, 4]);
+
+    // open savepoints are released on commit
+    txn.savepoint().unwrap();
+    put(col, &mut txn, 8, &[("value", Value::Long(8))]);
+    txn.commit().unwrap();
+
+    let mut txn = isar.begin_txn(false, false).unwrap();
+    assert_eq!(ids(&isar, &mut txn), vec![1, 2, 4, 8]);
+    txn.abort();
+    assert_eq!(*reported.lock().unwrap(), vec![vec![1, 2, 4, 8]]);
+    close_and_delete(isar);
+}
+
+#[test]
+fn test_put_if_version_conflict() {
+    let schema = r#"[{
+        "name": "Doc",
+        "versioned": true,
+        "properties": [{"name": "value", "type": "Long"}]
+    }]"#;
+    let isar = open("txn_versions", schema);
+    let col = &isar.collections[0];
+    let mut txn = isar.begin_txn(true, false).unwrap();
+    put(col, &mut txn, 1, &[("value", Value::Long(0))]);
+    txn.commit().unwrap();
+
+    // both clients read the same version
+    let read_version = || {
+        let mut txn = isar.begin_txn(false, false).unwrap();
+        let version = col.get_version(&mut txn, 1).unwrap().unwrap();
+        txn.abort();
+        version
+    };
+    let version_a = read_version();
+    let version_b = read_version();
+    assert_eq!(version_a, version_b);
+
+    let update = |version: u64, value: i64| {
+        let bytes = build(col, &[("value", Value::Long(value))]);
+        let mut txn = isar.begin_txn(true, false).unwrap();
+        let updated = col
+            .put_if_version(&mut txn, 1, version, IsarObject::from_bytes(&bytes))
+            .unwrap();
+        txn.commit().unwrap();
+        updated
+    };
+    assert!(update(version_a, 1));
+    assert!(!update(version_b, 2));
+
+    // the second client retries with the new version
+    let version_b = read_version();
+    assert!(version_b > version_a);
+    assert!(update(version_b, 2));
+
+    let mut txn = isar.begin_txn(false, false).unwrap();
+    assert!(col.get_version(&mut txn, 1).unwrap().unwrap() > version_b);
+    assert!(col.get_version(&mut txn, 2).unwrap().is_none());
+    txn.abort();
+    close_and_delete(isar);
+}
+
+#[test]
+fn test_row_meta() {
+    let schema = r#"[{
+        "name": "Doc",
+        "versioned": true,
+        "properties": [{"name": "value", "type": "Long"}]
+    }]"#;
+    let isar = open("txn_row_meta", schema);
+    let col = &isar.collections[0];
+    let write = |ids: &[i64], value: i64| {
+        let mut txn = isar.begin_txn(true, false).unwrap();
+        for id in ids {
+            put(col, &mut txn, *id, &[("value", Value::Long(value))]);
+        }
+        txn.commit().unwrap();
+    };
+    let meta = |id: i64| {
+        let mut txn = isar.begin_txn(false, false).unwrap();
+        let meta = col.get_row_meta(&mut txn, id).unwrap();
+        assert_eq!(
+            col.get_version(&mut txn, id).unwrap(),
+            meta.map(|m| m.modified_txn)
+        );
+        txn.abort();
+        meta
+    };
+
+    write(&[1], 0);
+    let created = meta(1).unwrap();
+    assert_eq!(created.created_txn, created.modified_txn);
+    write(&[1, 2], 1);
+    let updated = meta(1).unwrap();
+    assert_eq!(updated.created_txn, created.created_txn);
+    assert!(updated.modified_txn > created.modified_txn);
+    let inserted = meta(2).unwrap();
+    assert_eq!(inserted.created_txn, updated.modified_txn);
+    assert_eq!(inserted.modified_txn, updated.modified_txn);
+    assert_eq!(meta(3), None);
+
+    // an object that is deleted and put again is created anew
+    let mut txn = isar.begin_txn(true, false).unwrap();
+    col.delete(&mut txn, 1).unwrap();
+    txn.commit().unwrap();
+    write(&[1], 2);
+    assert!(meta(1).unwrap().created_txn > updated.modified_txn);
+    close_and_delete(isar);
+}
+
+#[test]
+fn test_counter_concurrent_increments() {
+    let isar = open("txn_counter", SCHEMA);
+    let mut txn = isar.begin_txn(false, false).unwrap();
+    assert_eq!(isar.counter_get(&mut txn, "hits").unwrap(), 0);
+    txn.abort();
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let isar = isar.clone();
+            thread::spawn(move || {
+                for _ in 0..25 {
+                    let mut txn = isar.begin_txn(true, false).unwrap();
+                    isar.counter_incr(&mut txn, "hits", 2).unwrap();
+                    txn.commit().unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let mut txn = isar.begin_txn(true, false).unwrap();
+    assert_eq!(isar.counter_get(&mut txn, "hits").unwrap(), 200);
+    assert_eq!(isar.counter_incr(&mut txn, "hits", -50).unwrap(), 150);
+    assert_eq!(isar.counter_incr(&mut txn, "other", 1).unwrap(), 1);
+    txn.abort();
+
+    // aborted increments are lost
+    let mut txn = isar.begin_txn(false, false).unwrap();
+    assert_eq!(isar.counter_get(&mut txn, "hits").unwrap(), 200);
+    assert_eq!(isar.counter_get(&mut txn, "other").unwrap(), 0);
+    txn.abort();
+    close_and_delete(isar);
+}
+
+#[test]
+fn test_enqueue_write_batches() {
+    let isar = open("txn_write_queue", SCHEMA);
+    // holding the write lock lets the writes of all threads pile up in the queue
+    let txn = isar.begin_txn(true, false).unwrap();
+    let threads: Vec<_> = (0..8)
+        .map(|t| {
+            let isar = isar.clone();
+            thread::spawn(move || {
+                for i in 0..20 {
+                    let instance = isar.clone();
+                    let op = Box::new(move |txn: &mut IsarTxn| {
+                        let col = &instance.collections[0];
+                        let bytes = build(col, &[("value", Value::Long(i))]);
+                        col.put_raw(txn, Some(t * 100 + i), &bytes)?;
+                        Ok(())
+                    });
+                    isar.enqueue_write(op).unwrap();
+                }
+            })
+        })
+        .collect();
+    thread::sleep(Duration::from_millis(100));
+    txn.commit().unwrap();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let mut txn = isar.begin_txn(false, false).unwrap();
+    assert_eq!(isar.collections[0].count(&mut txn).unwrap(), 160);
+    txn.abort();
+    let batches = isar.write_batch_count();
+    assert!(batches > 0 && batches < 160, "{} batches", batches);
+    close_and_delete(isar);
+}
+
+#[test]
+fn test_pooled_reads() {
+    let isar = open("txn_pooled_reads", SCHEMA);
+    let col = &isar.collections[0];
+    let count = || isar.read(|txn| col.count(txn)).unwrap();
+    assert_eq!(count(), 0);
+    assert_eq!(isar.reused_read_count(), 0);
+
+    // each read sees the latest commit even though it renews the same handle
+    for id in 1..=3 {
+        let mut txn = isar.begin_txn(true, false).unwrap();
+        put(col, &mut txn, id, &[("value", Value::Long(id))]);
+        txn.commit().unwrap();
+        assert_eq!(count(), id as u64);
+    }
+    assert_eq!(isar.reused_read_count(), 3);
+
+    // a read keeps its snapshot while other transactions commit
+    let reused = isar.reused_read_count();
+    let result = isar.read(|txn| {
+        let mut write = isar.begin_txn(true, false).unwrap();
+        put(col, &mut write, 4, &[("value", Value::Long(4))]);
+        write.commit().unwrap();
+        col.count(txn)
+    });
+    assert_eq!(result.unwrap(), 3);
+    assert_eq!(isar.reused_read_count(), reused + 1);
+
+    let error = isar.read(|_| Err::<(), _>(IsarError::DbFull {}));
+    assert_eq!(error, Err(IsarError::DbFull {}));
+    assert_eq!(count(), 4);
+    assert_eq!(isar.reused_read_count(), reused + 3);
+
+    isar.clear_read_pool();
+    assert_eq!(count(), 4);
+    assert_eq!(isar.reused_read_count(), reused + 3);
+    assert_eq!(count(), 4);
+    assert_eq!(isar.reused_read_count(), reused + 4);
+    close_and_delete(isar);
+}
+
+#[test]
+fn test_txn_started_at() {
+    let isar = open("txn_started_at", SCHEMA);
+    let col = &isar.collections[0];
+    let before = Instant::now();
+    let mut reader = isar.begin_txn(false, false).unwrap();
+    let after = Instant::now();
+    assert!(reader.started_at() >= before && reader.started_at() <= after);
+    assert_eq!(isar.txn_lag(&reader).unwrap(), 0);
+
+    thread::sleep(Duration::from_millis(20));
+    assert!(reader.started_at().elapsed() >= Duration::from_millis(20));
+    for id in 1..=3 {
+        let mut txn = isar.begin_txn(true, false).unwrap();
+        assert!(txn.started_at() > reader.started_at());
+        put(col, &mut txn, id, &[("value", Value::Long(id))]);
+        txn.commit().unwrap();
+    }
+
+    // the old reader still sees its snapshot and is behind the three commits
+    assert_eq!(isar.txn_lag(&reader).unwrap(), 3);
+    assert_eq!(col.count(&mut reader).unwrap(), 0);
+    reader.abort();
+    let reader = isar.begin_txn(false, false).unwrap();
+    assert_eq!(isar.txn_lag(&reader).unwrap(), 0);
+    reader.abort();
+    close_and_delete(isar);
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use common::*;
+use isar_core::query::aggregate_cache::CachedValue;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+const SCHEMA: &str = r#"[
+    {"name": "A", "properties": [{"name": "value", "type": "Long"}]},
+    {"name": "B", "properties": [{"name": "value", "type": "Long"}]}
+]"#;
+
+type Reported = Arc<Mutex<Vec<Vec<i64>>>>;
+
+type IdsCallback = Box<dyn Fn(&[i64]) + Send + Sync + 'static>;
+
+fn recorder() -> (Reported, IdsCallback) {
+    let reported: Reported = Arc::new(Mutex::new(vec![]));
+    let reported_clone = reported.clone();
+    let callback = Box::new(move |ids: &[i64]| {
+        reported_clone.lock().unwrap().push(ids.to_vec());
+    });
+    (reported, callback)
+}
+
+#[test]
+fn test_watch_collection_ids() {
+    let instance = open("watch_ids", SCHEMA);
+    let (a, b) = (&instance.collections[0], &instance.collections[1]);
+    let (reported_a, callback_a) = recorder();
+    let (reported_b, callback_b) = recorder();
+    let _handle_a = instance.watch_collection_ids(a, callback_a);
+    let _handle_b = instance.watch_collection_ids(b, callback_b);
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    for id in [3, 1, 2] {
+        put(a, &mut txn, id, &[("value", Value::Long(id))]);
+    }
+    put(a, &mut txn, 1, &[("value", Value::Long(10))]);
+    txn.commit().unwrap();
+    assert_eq!(*reported_a.lock().unwrap(), vec![vec![1, 2, 3]]);
+    assert!(reported_b.lock().unwrap().is_empty());
+
+    // clearing a collection only reports its own ids
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(b, &mut txn, 5, &[("value", Value::Long(5))]);
+    txn.commit().unwrap();
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    a.clear(&mut txn).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(
+        *reported_a.lock().unwrap(),
+        vec![vec![1, 2, 3], vec![1, 2, 3]]
+    );
+    assert_eq!(*reported_b.lock().unwrap(), vec![vec![5]]);
+
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_watch_aggregate_sum() {
+    let instance = open("watch_aggregate", SCHEMA);
+    let (a, b) = (&instance.collections[0], &instance.collections[1]);
+    let offset = property(a, "value").offset;
+    let (sender, receiver) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let _handle = instance.watch_aggregate(
+        a,
+        a.new_query_builder().build().unwrap(),
+        None,
+        move |query, txn| {
+            let mut sum = 0;
+            query.find_while(txn, |_, object| {
+                sum += object.read_long(offset);
+                true
+            })?;
+            Ok(CachedValue::Long(sum))
+        },
+        move |value| sender.lock().unwrap().send(value).unwrap(),
+    );
+    let next = || receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(next(), CachedValue::Long(0));
+
+    for (id, value, sum) in [(1, 5, 5), (2, 7, 12), (1, 1, 8)] {
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        put(a, &mut txn, id, &[("value", Value::Long(value))]);
+        txn.commit().unwrap();
+        assert_eq!(next(), CachedValue::Long(sum));
+    }
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    a.delete(&mut txn, 2).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(next(), CachedValue::Long(1));
+
+    // changes of other collections do not recompute the aggregate
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(b, &mut txn, 1, &[("value", Value::Long(100))]);
+    txn.commit().unwrap();
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+
+    close_and_delete(instance);
+}
+
+#[test]
+fn test_deferred_notifications() {
+    let instance = open("watch_deferred", SCHEMA);
+    let a = &instance.collections[0];
+    let (reported, callback) = recorder();
+    let _handle = instance.watch_collection_ids(a, callback);
+
+    let mut txn = instance.begin_txn_deferred(true).unwrap();
+    put(a, &mut txn, 2, &[("value", Value::Long(2))]);
+    put(a, &mut txn, 1, &[("value", Value::Long(1))]);
+    txn.commit().unwrap();
+    let mut txn = instance.begin_txn_deferred(true).unwrap();
+    put(a, &mut txn, 1, &[("value", Value::Long(10))]);
+    put(a, &mut txn, 3, &[("value", Value::Long(3))]);
+    txn.commit().unwrap();
+    let mut txn = instance.begin_txn_deferred(true).unwrap();
+    put(a, &mut txn, 4, &[("value", Value::Long(4))]);
+    txn.abort();
+    assert!(reported.lock().unwrap().is_empty());
+
+    // the changes of all deferred commits are reported once
+    instance.notify_all();
+    assert_eq!(*reported.lock().unwrap(), vec![vec![1, 2, 3]]);
+    instance.notify_all();
+    assert_eq!(reported.lock().unwrap().len(), 1);
+
+    let mut txn = instance.begin_txn(true, false).unwrap();
+    put(a, &mut txn, 5, &[("value", Value::Long(5))]);
+    txn.commit().unwrap();
+    assert_eq!(*reported.lock().unwrap(), vec![vec![1, 2, 3], vec![5]]);
+    close_and_delete(instance);
+}
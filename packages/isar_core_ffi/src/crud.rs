@@ -1,11 +1,11 @@
 use crate::c_object_set::{CObject, CObjectSet};
+use crate::filter::get_property;
 use crate::txn::CIsarTxn;
 use crate::{from_c_str, BoolSend, UintSend};
 use intmap::IntMap;
-use isar_core::collection::IsarCollection;
-use isar_core::error::IsarError;
+use isar_core::collection::{InsertConflict, IsarCollection};
 use isar_core::index::index_key::IndexKey;
-use serde_json::Value;
+use isar_core::query::knn::Metric;
 use std::os::raw::c_char;
 
 #[no_mangle]
@@ -43,6 +43,21 @@ pub unsafe extern "C" fn isar_get_by_index(
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_count_by_index(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    index_id: u64,
+    key: *mut IndexKey,
+    count: &'static mut u64,
+) -> i64 {
+    let key = *Box::from_raw(key);
+    isar_try_txn!(txn, move |txn| {
+        *count = collection.count_by_index(txn, index_id, &key)?;
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_get_all(
     collection: &'static IsarCollection,
@@ -50,9 +65,10 @@ pub unsafe extern "C" fn isar_get_all(
     objects: &'static mut CObjectSet,
 ) -> i64 {
     isar_try_txn!(txn, move |txn| {
-        for object in objects.get_objects() {
-            let id = object.get_id();
-            let result = collection.get(txn, id)?;
+        let objects = objects.get_objects();
+        let ids = objects.iter_mut().map(|o| o.get_id()).collect::<Vec<_>>();
+        let results = collection.get_many(txn, &ids)?;
+        for (object, result) in objects.iter_mut().zip(results) {
             object.set_object(result);
         }
         Ok(())
@@ -101,6 +117,24 @@ pub unsafe extern "C" fn isar_put(
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_put_raw(
+    collection: &'static mut IsarCollection,
+    txn: &mut CIsarTxn,
+    object: &'static mut CObject,
+) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        let id = if object.get_id() != i64::MIN {
+            Some(object.get_id())
+        } else {
+            None
+        };
+        let id = collection.put_raw(txn, id, object.get_object().as_bytes())?;
+        object.set_id(id);
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_put_by_index(
     collection: &'static mut IsarCollection,
@@ -135,6 +169,44 @@ pub unsafe extern "C" fn isar_put_all(
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_put_all_with_conflict(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    objects: &'static mut CObjectSet,
+    conflict: u8,
+    skipped: &'static mut u32,
+) -> i64 {
+    let conflict = match conflict {
+        0 => InsertConflict::Overwrite,
+        1 => InsertConflict::Abort,
+        _ => InsertConflict::Ignore,
+    };
+    let skipped = UintSend(skipped);
+    isar_try_txn!(txn, move |txn| {
+        let objects = objects.get_objects();
+        let entries = objects
+            .iter_mut()
+            .map(|object| {
+                let id = if object.get_id() != i64::MIN {
+                    Some(object.get_id())
+                } else {
+                    None
+                };
+                (id, object.get_object())
+            })
+            .collect::<Vec<_>>();
+        let (ids, result) = collection.put_all_with_conflict(txn, &entries, conflict)?;
+        for (object, id) in objects.iter_mut().zip(ids) {
+            if let Some(id) = id {
+                object.set_id(id);
+            }
+        }
+        *skipped.0 = result.skipped;
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_put_all_by_index(
     collection: &'static IsarCollection,
@@ -165,6 +237,32 @@ pub unsafe extern "C" fn isar_delete(
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_repair_id_counter(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    counter: &'static mut i64,
+) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        *counter = collection.repair_id_counter(txn)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_touch(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    id: i64,
+    touched: &'static mut bool,
+) -> i64 {
+    let touched = BoolSend(touched);
+    isar_try_txn!(txn, move |txn| {
+        *touched.0 = collection.touch(txn, id)?;
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_delete_by_index(
     collection: &'static IsarCollection,
@@ -246,8 +344,70 @@ pub unsafe extern "C" fn isar_json_import(
     let id_name = from_c_str(id_name).unwrap();
     let bytes = std::slice::from_raw_parts(json_bytes, json_length as usize);
     isar_try_txn!(txn, move |txn| {
-        let json: Value = serde_json::from_slice(bytes).map_err(|_| IsarError::InvalidJson {})?;
-        collection.import_json(txn, id_name, json)
+        collection.import_json_reader(txn, id_name, bytes)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_json_replace(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    id_name: *const c_char,
+    json_bytes: *const u8,
+    json_length: u32,
+    count: &'static mut u32,
+) -> i64 {
+    let id_name = from_c_str(id_name).unwrap();
+    let bytes = std::slice::from_raw_parts(json_bytes, json_length as usize);
+    let count = UintSend(count);
+    isar_try_txn!(txn, move |txn| {
+        *count.0 = collection.replace_json_reader(txn, id_name, bytes)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_json_import_with_conflict(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    id_name: *const c_char,
+    json_bytes: *const u8,
+    json_length: u32,
+    conflict: u8,
+    skipped: &'static mut u32,
+    overwritten: &'static mut u32,
+) -> i64 {
+    let id_name = from_c_str(id_name).unwrap();
+    let bytes = std::slice::from_raw_parts(json_bytes, json_length as usize);
+    let conflict = match conflict {
+        0 => InsertConflict::Overwrite,
+        1 => InsertConflict::Abort,
+        _ => InsertConflict::Ignore,
+    };
+    let skipped = UintSend(skipped);
+    let overwritten = UintSend(overwritten);
+    isar_try_txn!(txn, move |txn| {
+        let result = collection.import_json_reader_with_conflict(txn, id_name, bytes, conflict)?;
+        *skipped.0 = result.skipped;
+        *overwritten.0 = result.overwritten;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_msgpack_import(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    id_name: *const c_char,
+    bytes: *const u8,
+    length: u32,
+) -> i64 {
+    let id_name = from_c_str(id_name).unwrap();
+    let bytes = std::slice::from_raw_parts(bytes, length as usize);
+    isar_try_txn!(txn, move |txn| {
+        collection.import_msgpack(txn, id_name, bytes)?;
+        Ok(())
     })
 }
 
@@ -263,6 +423,74 @@ pub unsafe extern "C" fn isar_count(
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_is_empty(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    empty: &'static mut bool,
+) -> i64 {
+    let empty = BoolSend(empty);
+    isar_try_txn!(txn, move |txn| {
+        *empty.0 = collection.is_empty(txn)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_purge_expired(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    now: i64,
+    count: &'static mut u32,
+) -> i64 {
+    let count = UintSend(count);
+    isar_try_txn!(txn, move |txn| {
+        *count.0 = collection.purge_expired(txn, now)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_rebuild_index(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    index_id: u64,
+    count: &'static mut u32,
+) -> i64 {
+    let count = UintSend(count);
+    isar_try_txn!(txn, move |txn| {
+        *count.0 = collection.rebuild_index(txn, index_id)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_get_id_range(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    min_id: &'static mut i64,
+    max_id: &'static mut i64,
+) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        let (min, max) = collection.id_range(txn)?.unwrap_or((i64::MIN, i64::MIN));
+        *min_id = min;
+        *max_id = max;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_get_hash(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    hash: &'static mut u64,
+) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        *hash = collection.hash(txn)?;
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_get_size(
     collection: &'static IsarCollection,
@@ -277,6 +505,76 @@ pub unsafe extern "C" fn isar_get_size(
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_defragment(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    reclaimed: &'static mut i64,
+) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        *reclaimed = collection.defragment(txn)? as i64;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_set_indexing_enabled(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    enabled: bool,
+) -> i64 {
+    isar_try_txn!(txn, move |txn| collection
+        .set_indexing_enabled(txn, enabled))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_warm(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    include_indexes: bool,
+    warmed: &'static mut i64,
+) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        *warmed = collection.warm(txn, include_indexes, None)? as i64;
+        Ok(())
+    })
+}
+
+/// `ids` and `distances` must have room for `k` values. `metric` is 0 for cosine and 1 for L2.
+#[no_mangle]
+pub unsafe extern "C" fn isar_query_knn(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    property_id: u64,
+    query: *const f32,
+    length: u32,
+    k: u32,
+    metric: u8,
+    ids: *mut i64,
+    distances: *mut f32,
+    count: &'static mut u32,
+) -> i64 {
+    let metric = if metric == 0 {
+        Metric::Cosine
+    } else {
+        Metric::L2
+    };
+    let query = std::slice::from_raw_parts(query, length as usize).to_vec();
+    let ids = std::slice::from_raw_parts_mut(ids, k as usize);
+    let distances = std::slice::from_raw_parts_mut(distances, k as usize);
+    let count = UintSend(count);
+    isar_try_txn!(txn, move |txn| {
+        let property = get_property(collection, 0, property_id)?;
+        let neighbors = collection.query_knn(txn, property, &query, k, metric)?;
+        for (i, (id, distance)) in neighbors.iter().enumerate() {
+            ids[i] = *id;
+            distances[i] = *distance;
+        }
+        *count.0 = neighbors.len() as u32;
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_verify(
     collection: &'static IsarCollection,
@@ -289,3 +587,52 @@ pub unsafe extern "C" fn isar_verify(
     }
     isar_try_txn!(txn, move |txn| { collection.verify(txn, &objects_map) })
 }
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_get_version(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    id: i64,
+    version: &'static mut i64,
+) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        *version = collection.get_version(txn, id)?.map_or(-1, |v| v as i64);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_get_row_meta(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    id: i64,
+    created_txn: &'static mut i64,
+    modified_txn: &'static mut i64,
+) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        let meta = collection.get_row_meta(txn, id)?;
+        *created_txn = meta.map_or(-1, |m| m.created_txn as i64);
+        *modified_txn = meta.map_or(-1, |m| m.modified_txn as i64);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_put_if_version(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    expected_version: i64,
+    object: &'static mut CObject,
+    updated: &'static mut bool,
+) -> i64 {
+    let updated = BoolSend(updated);
+    isar_try_txn!(txn, move |txn| {
+        *updated.0 = collection.put_if_version(
+            txn,
+            object.get_id(),
+            expected_version as u64,
+            object.get_object(),
+        )?;
+        Ok(())
+    })
+}
@@ -5,6 +5,7 @@ use isar_core::error::Result;
 use isar_core::object::data_type::DataType;
 use isar_core::object::property::Property;
 use isar_core::query::filter::*;
+use std::borrow::Cow;
 use std::os::raw::c_char;
 use std::slice;
 
@@ -323,11 +324,21 @@ pub unsafe extern "C" fn isar_filter_string(
 ) -> i64 {
     isar_try! {
         let property = get_property(collection, embedded_col_id, property_id)?;
+        let normalize = |value: Option<&'static str>| -> Result<_> {
+            match value {
+                Some(value) if embedded_col_id == 0 => {
+                    Ok(Some(collection.normalize_string(property, value)?))
+                }
+                _ => Ok(value.map(Cow::Borrowed)),
+            }
+        };
 
-        let lower_bytes = Filter::string_to_bytes(from_c_str(lower)?, case_sensitive);
+        let lower = normalize(from_c_str(lower)?)?;
+        let lower_bytes = Filter::string_to_bytes(lower.as_deref(), case_sensitive);
         let lower = get_lower_str(lower_bytes, include_lower);
 
-        let upper_bytes = Filter::string_to_bytes(from_c_str(upper)?, case_sensitive);
+        let upper = normalize(from_c_str(upper)?)?;
+        let upper_bytes = Filter::string_to_bytes(upper.as_deref(), case_sensitive);
         let upper = get_upper_str(upper_bytes, include_upper);
 
         let query_filter = if let Some(upper) = upper {
@@ -367,3 +378,65 @@ filter_string_ffi!(string_starts_with, isar_filter_string_starts_with);
 filter_string_ffi!(string_ends_with, isar_filter_string_ends_with);
 filter_string_ffi!(string_contains, isar_filter_string_contains);
 filter_string_ffi!(string_matches, isar_filter_string_matches);
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_filter_property_cmp(
+    collection: &IsarCollection,
+    filter: *mut *const Filter,
+    op: u8,
+    embedded_col_id: u64,
+    lhs_property_id: u64,
+    rhs_property_id: u64,
+) -> i64 {
+    isar_try! {
+        let op = match op {
+            0 => CmpOp::Eq,
+            1 => CmpOp::NotEq,
+            2 => CmpOp::Lt,
+            3 => CmpOp::Lte,
+            4 => CmpOp::Gt,
+            5 => CmpOp::Gte,
+            _ => illegal_arg("Unknown comparison operator.")?,
+        };
+        let lhs = get_property(collection, embedded_col_id, lhs_property_id)?;
+        let rhs = get_property(collection, embedded_col_id, rhs_property_id)?;
+        let query_filter = Filter::property_cmp(lhs, op, rhs)?;
+        let ptr = Box::into_raw(Box::new(query_filter));
+        filter.write(ptr);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_filter_enum_eq(
+    collection: &IsarCollection,
+    filter: *mut *const Filter,
+    name: *const c_char,
+    property_id: u64,
+) -> i64 {
+    isar_try! {
+        let property = get_property(collection, 0, property_id)?;
+        let name = from_c_str(name)?.unwrap_or_default();
+        let query_filter = Filter::enum_eq(collection, property, name)?;
+        let ptr = Box::into_raw(Box::new(query_filter));
+        filter.write(ptr);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_filter_string_fuzzy(
+    collection: &IsarCollection,
+    filter: *mut *const Filter,
+    value: *const c_char,
+    max_distance: u32,
+    case_sensitive: bool,
+    embedded_col_id: u64,
+    property_id: u64,
+) -> i64 {
+    isar_try! {
+        let property = get_property(collection, embedded_col_id, property_id)?;
+        let str = from_c_str(value)?.unwrap();
+        let query_filter = Filter::string_fuzzy(property, str, max_distance, case_sensitive)?;
+        let ptr = Box::into_raw(Box::new(query_filter));
+        filter.write(ptr);
+    }
+}
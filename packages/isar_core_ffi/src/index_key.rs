@@ -1,4 +1,6 @@
+use crate::filter::get_property;
 use crate::from_c_str;
+use isar_core::collection::IsarCollection;
 use isar_core::index::index_key::IndexKey;
 use isar_core::object::isar_object::IsarObject;
 use paste::paste;
@@ -61,6 +63,38 @@ pub unsafe extern "C" fn isar_key_add_string(
     key.add_string(value, case_sensitive)
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_key_add_normalized_string(
+    collection: &IsarCollection,
+    key: &mut IndexKey,
+    value: *const c_char,
+    case_sensitive: bool,
+    hash: bool,
+    property_id: u64,
+) -> i64 {
+    isar_try! {
+        let property = get_property(collection, 0, property_id)?;
+        let value = from_c_str(value)?
+            .map(|value| collection.normalize_string(property, value))
+            .transpose()?;
+        if hash {
+            key.add_hash(IsarObject::hash_string(value.as_deref(), case_sensitive, 0));
+        } else {
+            key.add_string(value.as_deref(), case_sensitive);
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_key_add_blob(key: &mut IndexKey, value: *const u8, length: u32) {
+    let value = if !value.is_null() {
+        Some(std::slice::from_raw_parts(value, length as usize))
+    } else {
+        None
+    };
+    key.add_blob(value);
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_key_add_string_hash(
     key: &mut IndexKey,
@@ -5,12 +5,19 @@ use crate::txn::run_async;
 use crate::txn::CIsarTxn;
 use crate::CharsSend;
 use isar_core::collection::IsarCollection;
-use isar_core::error::{illegal_arg, Result};
-use isar_core::instance::{CompactCondition, IsarInstance};
+use isar_core::error::{illegal_arg, IsarError, Result};
+use isar_core::health::HealthStatus;
+use isar_core::instance::{CloseResult, CompactCondition, IsarInstance, SharedEnv};
+use isar_core::query::SlowQueryLog;
 use isar_core::schema::Schema;
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
 use std::os::raw::c_char;
+use std::ptr;
 use std::sync::Arc;
+use std::time::Duration;
 
 include!(concat!(env!("OUT_DIR"), "/version.rs"));
 
@@ -18,6 +25,10 @@ struct IsarInstanceSend(*mut *const IsarInstance);
 
 unsafe impl Send for IsarInstanceSend {}
 
+struct StringSend(*mut *mut c_char);
+
+unsafe impl Send for StringSend {}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_version() -> *const c_char {
     ISAR_VERSION.as_ptr() as *const c_char
@@ -30,10 +41,14 @@ pub unsafe extern "C" fn isar_instance_create(
     path: *const c_char,
     schema_json: *const c_char,
     max_size_mib: i64,
+    page_size: i64,
     relaxed_durability: bool,
     compact_min_file_size: u32,
     compact_min_bytes: u32,
     compact_min_ratio: f64,
+    create_dir: bool,
+    open_retry_attempts: u32,
+    open_retry_delay_ms: u32,
 ) -> i64 {
     let open = || -> Result<()> {
         let name = from_c_str(name).unwrap().unwrap();
@@ -51,13 +66,27 @@ pub unsafe extern "C" fn isar_instance_create(
             })
         };
 
+        let open_retry = if open_retry_attempts > 0 {
+            let delay = Duration::from_millis(open_retry_delay_ms as u64);
+            Some((open_retry_attempts, delay))
+        } else {
+            None
+        };
+
         let instance = IsarInstance::open(
             name,
             path,
             schema,
             max_size_mib as usize,
+            if page_size > 0 {
+                Some(page_size as usize)
+            } else {
+                None
+            },
             relaxed_durability,
             compact_condition,
+            create_dir,
+            open_retry,
         )?;
         isar.write(Arc::into_raw(instance));
         Ok(())
@@ -73,10 +102,14 @@ pub unsafe extern "C" fn isar_instance_create_async(
     path: *const c_char,
     schema_json: *const c_char,
     max_size_mib: i64,
+    page_size: i64,
     relaxed_durability: bool,
     compact_min_file_size: u32,
     compact_min_bytes: u32,
     compact_min_ratio: f64,
+    create_dir: bool,
+    open_retry_attempts: u32,
+    open_retry_delay_ms: u32,
     port: DartPort,
 ) {
     let isar = IsarInstanceSend(isar);
@@ -94,25 +127,107 @@ pub unsafe extern "C" fn isar_instance_create_async(
             path.0,
             schema_json.0,
             max_size_mib,
+            page_size,
             relaxed_durability,
             compact_min_file_size,
             compact_min_bytes,
             compact_min_ratio,
+            create_dir,
+            open_retry_attempts,
+            open_retry_delay_ms,
         );
         dart_post_int(port, result);
     });
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn isar_instance_close(isar: *const IsarInstance) -> bool {
+pub unsafe extern "C" fn isar_shared_env_create(
+    env: *mut *mut SharedEnv,
+    name: *const c_char,
+    path: *const c_char,
+    schemas_json: *const c_char,
+    max_size_mib: i64,
+    page_size: i64,
+    relaxed_durability: bool,
+) -> i64 {
+    let name = from_c_str(name).unwrap().unwrap();
+    let path = from_c_str(path).unwrap().unwrap();
+    let schemas_json = from_c_str(schemas_json).unwrap().unwrap();
+    isar_try! {
+        let schemas = Schema::from_json_list(schemas_json.as_bytes())?;
+        let shared_env = SharedEnv::open(
+            name,
+            path,
+            &schemas,
+            max_size_mib as usize,
+            if page_size > 0 {
+                Some(page_size as usize)
+            } else {
+                None
+            },
+            relaxed_durability,
+        )?;
+        env.write(Box::into_raw(Box::new(shared_env)));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_shared_env_free(env: *mut SharedEnv) {
+    let _ = Box::from_raw(env);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_instance_create_named(
+    isar: *mut *const IsarInstance,
+    env: &SharedEnv,
+    name: *const c_char,
+    schema_json: *const c_char,
+) -> i64 {
+    let name = from_c_str(name).unwrap().unwrap();
+    let schema_json = from_c_str(schema_json).unwrap().unwrap();
+    isar_try! {
+        let schema = Schema::from_json(schema_json.as_bytes())?;
+        let instance = IsarInstance::open_named(env, name, schema)?;
+        isar.write(Arc::into_raw(instance));
+    }
+}
+
+/// `-1` if the instance was closed, `-2` if closing failed and otherwise the number of handles
+/// that keep the instance open.
+fn close_result_code(result: CloseResult) -> i32 {
+    match result {
+        CloseResult::Closed => -1,
+        CloseResult::StillReferenced { remaining } => remaining as i32,
+        CloseResult::Error(_) => -2,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_instance_close(isar: *const IsarInstance) -> i32 {
     let isar = Arc::from_raw(isar);
-    isar.close()
+    close_result_code(isar.close())
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn isar_instance_close_and_delete(isar: *const IsarInstance) -> bool {
+pub unsafe extern "C" fn isar_instance_close_and_compact(
+    isar: *const IsarInstance,
+    compact_min_file_size: u32,
+    compact_min_bytes: u32,
+    compact_min_ratio: f64,
+) -> i32 {
     let isar = Arc::from_raw(isar);
-    isar.close_and_delete()
+    let compact_condition = CompactCondition {
+        min_file_size: compact_min_file_size as u64,
+        min_bytes: compact_min_bytes as u64,
+        min_ratio: compact_min_ratio,
+    };
+    close_result_code(isar.close_and_compact(compact_condition))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_instance_close_and_delete(isar: *const IsarInstance) -> i32 {
+    let isar = Arc::from_raw(isar);
+    close_result_code(isar.close_and_delete())
 }
 
 #[no_mangle]
@@ -120,6 +235,80 @@ pub unsafe extern "C" fn isar_instance_get_path(isar: &'static IsarInstance) ->
     CString::new(isar.dir.as_str()).unwrap().into_raw()
 }
 
+/// Posts the duration in microseconds of every query that takes at least `threshold_ms` to
+/// `port`. A negative threshold disables the slow query log.
+#[no_mangle]
+pub unsafe extern "C" fn isar_instance_set_slow_query_port(
+    isar: &'static IsarInstance,
+    threshold_ms: i64,
+    port: DartPort,
+) {
+    let log = if threshold_ms >= 0 {
+        Some(SlowQueryLog {
+            threshold: Duration::from_millis(threshold_ms as u64),
+            callback: Box::new(move |_, elapsed| {
+                dart_post_int(port, elapsed.as_micros() as i64);
+            }),
+        })
+    } else {
+        None
+    };
+    isar.set_slow_query_log(log);
+}
+
+/// A negative `max` removes the limit.
+#[no_mangle]
+pub extern "C" fn isar_set_max_instances(max: i64) {
+    IsarInstance::set_max_instances(usize::try_from(max).ok());
+}
+
+#[no_mangle]
+pub extern "C" fn isar_instance_set_strict_read_commit(isar: &IsarInstance, strict: bool) {
+    isar.set_strict_read_commit(strict);
+}
+
+#[no_mangle]
+pub extern "C" fn isar_instance_set_cursor_pool_size(isar: &IsarInstance, size: u32) {
+    isar.set_cursor_pool_size(size as usize);
+}
+
+#[no_mangle]
+pub extern "C" fn isar_instance_set_write_priority(isar: &IsarInstance, write_priority: bool) {
+    isar.set_write_priority(write_priority);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_instance_check_readers(
+    instance: &'static IsarInstance,
+    reclaimed: &mut u32,
+) -> i64 {
+    isar_try! {
+        *reclaimed = instance.check_readers()?;
+    }
+}
+
+/// Writes null to `reasons` if the instance is healthy. Otherwise the reasons why it is degraded
+/// are written, separated by newlines.
+#[no_mangle]
+pub unsafe extern "C" fn isar_instance_health_check(
+    instance: &'static IsarInstance,
+    reasons: *mut *mut c_char,
+) -> i64 {
+    isar_try! {
+        let status = instance.health_check()?;
+        let reasons_str = match status {
+            HealthStatus::Healthy => ptr::null_mut(),
+            HealthStatus::Degraded(reasons) => CString::new(reasons.join("\n")).unwrap().into_raw(),
+        };
+        reasons.write(reasons_str);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn isar_instance_set_auto_reader_check(isar: &IsarInstance, enabled: bool) {
+    isar.set_auto_reader_check(enabled);
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_instance_get_collection<'a>(
     isar: &'a IsarInstance,
@@ -136,6 +325,27 @@ pub unsafe extern "C" fn isar_instance_get_collection<'a>(
     }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_instance_pin_collection(
+    instance: &'static IsarInstance,
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    pinned: &'static mut i64,
+) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        *pinned = instance.pin_collection(txn, collection)? as i64;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn isar_instance_unpin_collection(
+    instance: &IsarInstance,
+    collection: &IsarCollection,
+) {
+    instance.unpin_collection(collection);
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_instance_get_size(
     instance: &'static IsarInstance,
@@ -150,6 +360,60 @@ pub unsafe extern "C" fn isar_instance_get_size(
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_instance_get_schema_version(
+    instance: &'static IsarInstance,
+    version: &mut u32,
+) -> i64 {
+    isar_try! {
+        *version = instance.schema_version()?;
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_instance_get_freelist_info(
+    instance: &'static IsarInstance,
+    txn: &mut CIsarTxn,
+    free_pages: &'static mut i64,
+    reclaimable_bytes: &'static mut i64,
+) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        let info = instance.freelist_info(txn)?;
+        *free_pages = info.free_pages as i64;
+        *reclaimable_bytes = info.reclaimable_bytes as i64;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_counter_incr(
+    instance: &'static IsarInstance,
+    txn: &mut CIsarTxn,
+    name: *const c_char,
+    by: i64,
+    value: &'static mut i64,
+) -> i64 {
+    let name = from_c_str(name).unwrap().unwrap();
+    isar_try_txn!(txn, move |txn| {
+        *value = instance.counter_incr(txn, name, by)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_counter_get(
+    instance: &'static IsarInstance,
+    txn: &mut CIsarTxn,
+    name: *const c_char,
+    value: &'static mut i64,
+) -> i64 {
+    let name = from_c_str(name).unwrap().unwrap();
+    isar_try_txn!(txn, move |txn| {
+        *value = instance.counter_get(txn, name)?;
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_instance_copy_to_file(
     instance: &'static IsarInstance,
@@ -165,6 +429,88 @@ pub unsafe extern "C" fn isar_instance_copy_to_file(
     });
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_instance_export_all(
+    instance: &'static IsarInstance,
+    txn: &mut CIsarTxn,
+    path: *const c_char,
+) -> i64 {
+    let path = from_c_str(path).unwrap().unwrap().to_string();
+    isar_try_txn!(txn, move |txn| {
+        let file = File::create(&path).map_err(|e| IsarError::IoError {
+            message: e.to_string(),
+        })?;
+        let mut writer = BufWriter::new(file);
+        instance.export_all(txn, &mut writer)?;
+        writer.flush().map_err(|e| IsarError::IoError {
+            message: e.to_string(),
+        })?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_instance_export_delta(
+    instance: &'static IsarInstance,
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    path: *const c_char,
+    peer_ids: *const i64,
+    peer_hashes: *const u64,
+    peer_length: u32,
+) -> i64 {
+    let path = from_c_str(path).unwrap().unwrap().to_string();
+    let peer_hashes = if peer_length > 0 {
+        let ids = std::slice::from_raw_parts(peer_ids, peer_length as usize);
+        let hashes = std::slice::from_raw_parts(peer_hashes, peer_length as usize);
+        ids.iter().copied().zip(hashes.iter().copied()).collect()
+    } else {
+        HashMap::new()
+    };
+    isar_try_txn!(txn, move |txn| {
+        let file = File::create(&path).map_err(|e| IsarError::IoError {
+            message: e.to_string(),
+        })?;
+        let mut writer = BufWriter::new(file);
+        instance.export_delta(txn, collection, &peer_hashes, &mut writer)?;
+        writer.flush().map_err(|e| IsarError::IoError {
+            message: e.to_string(),
+        })?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_instance_import_all(
+    instance: &'static IsarInstance,
+    txn: &mut CIsarTxn,
+    path: *const c_char,
+) -> i64 {
+    let path = from_c_str(path).unwrap().unwrap().to_string();
+    isar_try_txn!(txn, move |txn| {
+        let file = File::open(&path).map_err(|e| IsarError::IoError {
+            message: e.to_string(),
+        })?;
+        instance.import_all(txn, &mut BufReader::new(file))?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_instance_get_diagnostics(
+    instance: &'static IsarInstance,
+    txn: &mut CIsarTxn,
+    report: *mut *mut c_char,
+) -> i64 {
+    let report = StringSend(report);
+    isar_try_txn!(txn, move |txn| {
+        let report = report;
+        let json = instance.diagnostics_report(txn)?;
+        report.0.write(CString::new(json).unwrap().into_raw());
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_instance_verify(
     instance: &'static IsarInstance,
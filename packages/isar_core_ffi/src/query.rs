@@ -6,8 +6,12 @@ use isar_core::collection::IsarCollection;
 use isar_core::index::index_key::IndexKey;
 use isar_core::query::filter::Filter;
 use isar_core::query::query_builder::QueryBuilder;
-use isar_core::query::{Query, Sort};
+use isar_core::query::{Nulls, Query, Sort};
+use std::ffi::CString;
 use std::os::raw::c_char;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[no_mangle]
 pub extern "C" fn isar_qb_create(collection: &IsarCollection) -> *mut QueryBuilder {
@@ -26,6 +30,24 @@ pub unsafe extern "C" fn isar_qb_add_id_where_clause(
     }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_qb_add_id_set_where_clause(
+    builder: &mut QueryBuilder,
+    ids: *const i64,
+    ids_length: u32,
+    ascending: bool,
+) -> i64 {
+    let ids = std::slice::from_raw_parts(ids, ids_length as usize);
+    let sort = if ascending {
+        Sort::Ascending
+    } else {
+        Sort::Descending
+    };
+    isar_try! {
+        builder.add_id_set_where_clause(ids, sort)?;
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_qb_add_index_where_clause(
     builder: &mut QueryBuilder,
@@ -71,6 +93,17 @@ pub unsafe extern "C" fn isar_qb_set_filter(builder: &mut QueryBuilder, filter:
     builder.set_filter(filter);
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_qb_set_filter_expression(
+    builder: &mut QueryBuilder,
+    expr: *const c_char,
+) -> i64 {
+    isar_try! {
+        let expr = from_c_str(expr)?.unwrap_or_default();
+        builder.set_filter_expression(expr)?;
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_qb_add_sort_by(
     builder: &mut QueryBuilder,
@@ -88,6 +121,30 @@ pub unsafe extern "C" fn isar_qb_add_sort_by(
     }
 }
 
+/// `nulls` is 0 for the default order, 1 to place nulls first and 2 to place them last.
+#[no_mangle]
+pub unsafe extern "C" fn isar_qb_add_sort_by_nulls(
+    builder: &mut QueryBuilder,
+    property_id: u64,
+    asc: bool,
+    nulls: u8,
+) -> i64 {
+    let sort = if asc {
+        Sort::Ascending
+    } else {
+        Sort::Descending
+    };
+    let nulls = match nulls {
+        1 => Some(Nulls::First),
+        2 => Some(Nulls::Last),
+        _ => None,
+    };
+    isar_try! {
+        let property = get_property(builder.collection, 0, property_id)?;
+        builder.add_sort_with_nulls(property, sort, nulls)?;
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_qb_add_distinct_by(
     builder: &mut QueryBuilder,
@@ -117,9 +174,136 @@ pub unsafe extern "C" fn isar_qb_set_offset_limit(
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn isar_qb_build(builder: *mut QueryBuilder) -> *mut Query {
-    let query = Box::from_raw(builder).build();
-    Box::into_raw(Box::new(query))
+pub unsafe extern "C" fn isar_qb_set_cancellable(builder: &mut QueryBuilder) {
+    builder.set_cancel_token(Arc::new(AtomicBool::new(false)));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_qb_set_prefetch(builder: &mut QueryBuilder, prefetch: bool) {
+    builder.set_prefetch(prefetch);
+}
+
+#[no_mangle]
+pub extern "C" fn isar_qb_set_reverse(builder: &mut QueryBuilder, reverse: bool) {
+    builder.set_reverse(reverse);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_qb_add_string_starts_with(
+    builder: &mut QueryBuilder,
+    property_id: u64,
+    prefix: *const c_char,
+    case_sensitive: bool,
+) -> i64 {
+    isar_try! {
+        let property = get_property(builder.collection, 0, property_id)?;
+        let prefix = from_c_str(prefix)?.unwrap_or_default();
+        builder.add_string_starts_with(property, prefix, case_sensitive)?;
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_qb_add_matches(
+    builder: &mut QueryBuilder,
+    property_id: u64,
+    query: *const c_char,
+) -> i64 {
+    isar_try! {
+        let property = get_property(builder.collection, 0, property_id)?;
+        let query = from_c_str(query)?.unwrap_or_default();
+        builder.add_matches(property, query)?;
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_qb_add_newer_than(
+    builder: &mut QueryBuilder,
+    property_id: u64,
+    duration_us: i64,
+) -> i64 {
+    isar_try! {
+        let property = get_property(builder.collection, 0, property_id)?;
+        let duration = Duration::from_micros(duration_us.max(0) as u64);
+        builder.add_newer_than(property, duration)?;
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_qb_add_older_than(
+    builder: &mut QueryBuilder,
+    property_id: u64,
+    duration_us: i64,
+) -> i64 {
+    isar_try! {
+        let property = get_property(builder.collection, 0, property_id)?;
+        let duration = Duration::from_micros(duration_us.max(0) as u64);
+        builder.add_older_than(property, duration)?;
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_qb_add_string_ends_with(
+    builder: &mut QueryBuilder,
+    property_id: u64,
+    suffix: *const c_char,
+    case_sensitive: bool,
+) -> i64 {
+    isar_try! {
+        let property = get_property(builder.collection, 0, property_id)?;
+        let suffix = from_c_str(suffix)?.unwrap_or_default();
+        builder.add_string_ends_with(property, suffix, case_sensitive)?;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn isar_qb_add_id_between(
+    builder: &mut QueryBuilder,
+    start_id: i64,
+    end_id: i64,
+    inclusive: bool,
+) {
+    builder.add_id_between(start_id, end_id, inclusive);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_qb_set_max_scan(builder: &mut QueryBuilder, max_scan: i64) {
+    let max_scan = if max_scan < 0 {
+        None
+    } else {
+        Some(max_scan.min(u32::MAX as i64) as u32)
+    };
+    builder.set_max_scan(max_scan);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_qb_set_aggregate_cache_size(builder: &mut QueryBuilder, size: u32) {
+    builder.set_aggregate_cache_size(size as usize);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_qb_use_index(
+    builder: &mut QueryBuilder,
+    index_name: *const c_char,
+) -> i64 {
+    isar_try! {
+        let index_name = from_c_str(index_name)?.unwrap_or_default();
+        builder.use_index(index_name);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn isar_qb_no_index(builder: &mut QueryBuilder) {
+    builder.no_index();
+}
+
+/// Consumes the builder even if building the query fails.
+#[no_mangle]
+pub unsafe extern "C" fn isar_qb_build(builder: *mut QueryBuilder, query: *mut *mut Query) -> i64 {
+    let builder = Box::from_raw(builder);
+    isar_try! {
+        let built = builder.build()?;
+        query.write(Box::into_raw(Box::new(built)));
+    }
 }
 
 #[no_mangle]
@@ -127,6 +311,11 @@ pub unsafe extern "C" fn isar_q_free(query: *mut Query) {
     let _ = Box::from_raw(query);
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_q_cancel(query: &Query) {
+    query.cancel();
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_q_find(
     query: &'static Query,
@@ -162,15 +351,40 @@ pub unsafe extern "C" fn isar_q_delete(
     let limit = limit as usize;
     let count = UintSend(count);
     isar_try_txn!(txn, move |txn| {
-        let mut ids_to_delete = vec![];
-        query.find_while(txn, |id, _| {
-            ids_to_delete.push(id);
-            ids_to_delete.len() <= limit
-        })?;
-        *count.0 = ids_to_delete.len() as u32;
-        for id in ids_to_delete {
-            collection.delete(txn, id)?;
-        }
+        *count.0 = query.delete(txn, collection, limit)?;
+        Ok(())
+    })
+}
+
+/// Writes the estimated total size of the objects `isar_q_find` would return to `bytes`.
+#[no_mangle]
+pub unsafe extern "C" fn isar_q_estimate_bytes(
+    query: &'static Query,
+    txn: &mut CIsarTxn,
+    limit: u32,
+    bytes: &'static mut i64,
+) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        *bytes = query.estimate_bytes(txn, 0, limit as usize)? as i64;
+        Ok(())
+    })
+}
+
+struct StatsJson(*mut *mut c_char);
+unsafe impl Send for StatsJson {}
+
+/// Runs the query and writes its execution statistics as a JSON string to `stats`.
+#[no_mangle]
+pub unsafe extern "C" fn isar_q_profile(
+    query: &'static Query,
+    txn: &mut CIsarTxn,
+    stats: *mut *mut c_char,
+) -> i64 {
+    let stats = StatsJson(stats);
+    isar_try_txn!(txn, move |txn| {
+        let stats = stats;
+        let json = query.profile(txn)?.to_json().to_string();
+        stats.0.write(CString::new(json).unwrap().into_raw());
         Ok(())
     })
 }
@@ -187,16 +401,34 @@ pub unsafe extern "C" fn isar_q_export_json(
     collection: &'static IsarCollection,
     txn: &mut CIsarTxn,
     id_name: *const c_char,
+    properties: *const u16,
+    properties_length: u32,
+    redact: *const u16,
+    redact_length: u32,
     json_bytes: *mut *mut u8,
     json_length: *mut u32,
 ) -> i64 {
     let id_name = from_c_str(id_name).unwrap();
+    let properties = if properties.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(
+            properties,
+            properties_length as usize,
+        ))
+    };
+    let redact = if redact.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts(redact, redact_length as usize)
+    };
     let json = JsonBytes(json_bytes);
     let json_length = JsonLen(json_length);
     isar_try_txn!(txn, move |txn| {
         let json = json;
         let json_length = json_length;
-        let exported_json = query.export_json(txn, collection, id_name, true)?;
+        let exported_json =
+            query.export_json(txn, collection, id_name, true, properties, redact)?;
         let bytes = serde_json::to_vec(&exported_json).unwrap();
         let mut bytes = bytes.into_boxed_slice();
         json_length.0.write(bytes.len() as u32);
@@ -206,6 +438,59 @@ pub unsafe extern "C" fn isar_q_export_json(
     })
 }
 
+/// Returns the distinct values of a property as a JSON array. A negative `limit` returns all
+/// values.
+#[no_mangle]
+pub unsafe extern "C" fn isar_distinct_values_json(
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    property_id: u64,
+    limit: i64,
+    json_bytes: *mut *mut u8,
+    json_length: *mut u32,
+) -> i64 {
+    let limit = u32::try_from(limit).ok();
+    let json = JsonBytes(json_bytes);
+    let json_length = JsonLen(json_length);
+    isar_try_txn!(txn, move |txn| {
+        let json = json;
+        let json_length = json_length;
+        let property = get_property(collection, 0, property_id)?;
+        let values = collection.query_distinct_values(txn, property, limit)?;
+        let bytes = serde_json::to_vec(&values).unwrap();
+        let mut bytes = bytes.into_boxed_slice();
+        json_length.0.write(bytes.len() as u32);
+        json.0.write(bytes.as_mut_ptr());
+        std::mem::forget(bytes);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_q_export_msgpack(
+    query: &'static Query,
+    collection: &'static IsarCollection,
+    txn: &mut CIsarTxn,
+    id_name: *const c_char,
+    bytes: *mut *mut u8,
+    length: *mut u32,
+) -> i64 {
+    let id_name = from_c_str(id_name).unwrap();
+    let bytes = JsonBytes(bytes);
+    let length = JsonLen(length);
+    isar_try_txn!(txn, move |txn| {
+        let bytes = bytes;
+        let length = length;
+        let mut exported = vec![];
+        query.export_msgpack(txn, collection, id_name, true, &mut exported)?;
+        let mut exported = exported.into_boxed_slice();
+        length.0.write(exported.len() as u32);
+        bytes.0.write(exported.as_mut_ptr());
+        std::mem::forget(exported);
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_free_json(json_bytes: *mut u8, json_length: u32) {
     Vec::from_raw_parts(json_bytes, json_length as usize, json_length as usize);
@@ -1,13 +1,16 @@
 use crate::filter::get_property;
 use crate::txn::CIsarTxn;
+use crate::UintSend;
 use isar_core::collection::IsarCollection;
-use isar_core::error::Result;
+use isar_core::error::{illegal_arg, Result};
 use isar_core::object::data_type::DataType;
 use isar_core::object::isar_object::IsarObject;
 use isar_core::object::property::Property;
+use isar_core::query::aggregate_cache::CachedValue;
 use isar_core::query::Query;
 use isar_core::txn::IsarTxn;
 use std::cmp::Ordering;
+use std::slice;
 
 pub enum AggregationResult {
     Long(i64),
@@ -15,7 +18,27 @@ pub enum AggregationResult {
     Null,
 }
 
-#[derive(PartialEq)]
+impl From<CachedValue> for AggregationResult {
+    fn from(value: CachedValue) -> Self {
+        match value {
+            CachedValue::Long(value) => AggregationResult::Long(value),
+            CachedValue::Double(value) => AggregationResult::Double(value),
+            CachedValue::Null => AggregationResult::Null,
+        }
+    }
+}
+
+impl From<AggregationResult> for CachedValue {
+    fn from(value: AggregationResult) -> Self {
+        match value {
+            AggregationResult::Long(value) => CachedValue::Long(value),
+            AggregationResult::Double(value) => CachedValue::Double(value),
+            AggregationResult::Null => CachedValue::Null,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
 #[repr(u8)]
 pub enum AggregationOp {
     Min,
@@ -24,10 +47,12 @@ pub enum AggregationOp {
     Average,
     Count,
     IsEmpty,
+    All,
+    Any,
 }
 
 impl AggregationOp {
-    fn from_u8(index: u8) -> AggregationOp {
+    pub(crate) fn from_u8(index: u8) -> AggregationOp {
         match index {
             0 => AggregationOp::Min,
             1 => AggregationOp::Max,
@@ -35,6 +60,8 @@ impl AggregationOp {
             3 => AggregationOp::Average,
             4 => AggregationOp::Count,
             5 => AggregationOp::IsEmpty,
+            6 => AggregationOp::All,
+            7 => AggregationOp::Any,
             _ => unreachable!(),
         }
     }
@@ -47,38 +74,53 @@ const EMPTY_PROP: &Property = &Property {
     target_id: None,
 };
 
-fn aggregate(
-    query: &Query,
-    txn: &mut IsarTxn,
+struct Accumulator<'a> {
     op: AggregationOp,
-    property: Option<&Property>,
-) -> Result<AggregationResult> {
-    let mut count = 0usize;
+    property: &'a Property,
+    count: usize,
+    long_value: i64,
+    double_value: f64,
+    min_max_cmp: Ordering,
+    done: bool,
+}
 
-    let (mut long_value, mut double_value) = if op == AggregationOp::Min {
-        (i64::MAX, f64::INFINITY)
-    } else if op == AggregationOp::Max {
-        (i64::MIN, f64::NEG_INFINITY)
-    } else {
-        (0, 0.0)
-    };
+impl<'a> Accumulator<'a> {
+    fn new(op: AggregationOp, property: Option<&'a Property>) -> Self {
+        let (long_value, double_value) = if op == AggregationOp::Min {
+            (i64::MAX, f64::INFINITY)
+        } else if op == AggregationOp::Max {
+            (i64::MIN, f64::NEG_INFINITY)
+        } else {
+            (0, 0.0)
+        };
 
-    let min_max_cmp = if op == AggregationOp::Max {
-        Ordering::Greater
-    } else {
-        Ordering::Less
-    };
+        let min_max_cmp = if op == AggregationOp::Max {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        };
 
-    let property = property.unwrap_or(EMPTY_PROP);
+        Accumulator {
+            op,
+            property: property.unwrap_or(EMPTY_PROP),
+            count: 0,
+            long_value,
+            double_value,
+            min_max_cmp,
+            done: false,
+        }
+    }
 
-    query.find_while(txn, |_, obj| {
-        match op {
+    /// Returns `false` once the result is decided and further objects are not needed.
+    fn add(&mut self, obj: IsarObject) -> bool {
+        let property = self.property;
+        match self.op {
             AggregationOp::Min | AggregationOp::Max => {
                 if obj.is_null(property.offset, property.data_type) {
                     return true;
                 }
 
-                count += 1;
+                self.count += 1;
                 match property.data_type {
                     DataType::Int | DataType::Long => {
                         let value = if property.data_type == DataType::Int {
@@ -86,8 +128,8 @@ fn aggregate(
                         } else {
                             obj.read_long(property.offset)
                         };
-                        if value.cmp(&long_value) == min_max_cmp {
-                            long_value = value;
+                        if value.cmp(&self.long_value) == self.min_max_cmp {
+                            self.long_value = value;
                         }
                     }
                     DataType::Float | DataType::Double => {
@@ -96,71 +138,162 @@ fn aggregate(
                         } else {
                             obj.read_double(property.offset)
                         };
-                        if value.total_cmp(&double_value) == min_max_cmp {
-                            double_value = value;
+                        if value.total_cmp(&self.double_value) == self.min_max_cmp {
+                            self.double_value = value;
                         }
                     }
                     _ => {}
                 }
             }
             AggregationOp::Sum | AggregationOp::Average => {
+                // NaN is treated as null. Infinite values can only be stored with the
+                // `Allow` non-finite policy and propagate into the result.
                 if obj.is_null(property.offset, property.data_type) {
                     return true;
                 }
 
-                count += 1;
+                self.count += 1;
                 match property.data_type {
                     DataType::Int => {
-                        long_value = long_value.saturating_add(obj.read_int(property.offset) as i64)
+                        self.long_value = self
+                            .long_value
+                            .saturating_add(obj.read_int(property.offset) as i64)
                     }
                     DataType::Long => {
-                        long_value = long_value.saturating_add(obj.read_long(property.offset))
+                        self.long_value = self
+                            .long_value
+                            .saturating_add(obj.read_long(property.offset))
                     }
-                    DataType::Float => double_value += obj.read_float(property.offset) as f64,
-                    DataType::Double => double_value += obj.read_double(property.offset),
+                    DataType::Float => self.double_value += obj.read_float(property.offset) as f64,
+                    DataType::Double => self.double_value += obj.read_double(property.offset),
                     _ => {}
                 }
             }
             AggregationOp::Count => {
-                count += 1;
+                self.count += 1;
             }
             AggregationOp::IsEmpty => {
-                count += 1;
+                self.count += 1;
                 return false;
             }
+            // null counts as false, the scan stops at the first row that decides the result
+            AggregationOp::All => {
+                if obj.read_bool(property.offset) != Some(true) {
+                    self.count += 1;
+                    return false;
+                }
+            }
+            AggregationOp::Any => {
+                if obj.read_bool(property.offset) == Some(true) {
+                    self.count += 1;
+                    return false;
+                }
+            }
         }
         true
-    })?;
+    }
 
-    match op {
-        AggregationOp::Min | AggregationOp::Max | AggregationOp::Average => {
-            if count == 0 {
-                return Ok(AggregationResult::Null);
+    fn finish(self) -> AggregationResult {
+        let count = self.count;
+        match self.op {
+            AggregationOp::Min | AggregationOp::Max | AggregationOp::Average => {
+                if count == 0 {
+                    return AggregationResult::Null;
+                }
             }
+            _ => {}
+        }
+
+        match self.op {
+            AggregationOp::Average => match self.property.data_type {
+                DataType::Int | DataType::Long => {
+                    AggregationResult::Double((self.long_value as f64) / (count as f64))
+                }
+                DataType::Float | DataType::Double => {
+                    AggregationResult::Double(self.double_value / (count as f64))
+                }
+                _ => AggregationResult::Null,
+            },
+            AggregationOp::Count => AggregationResult::Long(count as i64),
+            AggregationOp::IsEmpty => AggregationResult::Long(if count > 0 { 0 } else { 1 }),
+            AggregationOp::All => AggregationResult::Long(if count > 0 { 0 } else { 1 }),
+            AggregationOp::Any => AggregationResult::Long(if count > 0 { 1 } else { 0 }),
+            _ => match self.property.data_type {
+                DataType::Int | DataType::Long => AggregationResult::Long(self.long_value),
+                DataType::Float | DataType::Double => AggregationResult::Double(self.double_value),
+                _ => AggregationResult::Null,
+            },
         }
-        _ => {}
     }
+}
 
-    let result = match op {
-        AggregationOp::Average => match property.data_type {
-            DataType::Int | DataType::Long => {
-                AggregationResult::Double((long_value as f64) / (count as f64))
-            }
-            DataType::Float | DataType::Double => {
-                AggregationResult::Double(double_value / (count as f64))
-            }
-            _ => AggregationResult::Null,
-        },
-        AggregationOp::Count => AggregationResult::Long(count as i64),
-        AggregationOp::IsEmpty => AggregationResult::Long(if count > 0 { 0 } else { 1 }),
-        _ => match property.data_type {
-            DataType::Int | DataType::Long => AggregationResult::Long(long_value),
-            DataType::Float | DataType::Double => AggregationResult::Double(double_value),
-            _ => AggregationResult::Null,
-        },
-    };
+pub(crate) fn aggregate(
+    query: &Query,
+    txn: &mut IsarTxn,
+    op: AggregationOp,
+    property: Option<&Property>,
+) -> Result<AggregationResult> {
+    let key = (op as u64) << 32 | property.map_or(0, |p| p.offset as u64);
+    let result = query.aggregate_cached(txn, key, |query, txn| {
+        let mut accumulator = Accumulator::new(op, property);
+        query.find_while(txn, |_, obj| accumulator.add(obj))?;
+        Ok(accumulator.finish().into())
+    })?;
+    Ok(result.into())
+}
 
-    Ok(result)
+/// Returns the sum of the non-null values and their count so callers can compute an exact
+/// average of integer properties.
+fn aggregate_sum_count(
+    query: &Query,
+    txn: &mut IsarTxn,
+    property: &Property,
+) -> Result<(AggregationResult, u32)> {
+    let mut accumulator = Accumulator::new(AggregationOp::Sum, Some(property));
+    query.find_while(txn, |_, obj| accumulator.add(obj))?;
+    let count = accumulator.count as u32;
+    Ok((accumulator.finish(), count))
+}
+
+/// Computes several aggregations in a single scan. The scan stops once every result is decided.
+fn aggregate_multi(
+    query: &Query,
+    txn: &mut IsarTxn,
+    aggregations: Vec<(AggregationOp, Option<&Property>)>,
+) -> Result<Vec<AggregationResult>> {
+    let mut accumulators = aggregations
+        .into_iter()
+        .map(|(op, property)| Accumulator::new(op, property))
+        .collect::<Vec<_>>();
+    query.find_while(txn, |_, obj| {
+        let mut cont = false;
+        for accumulator in accumulators.iter_mut().filter(|a| !a.done) {
+            accumulator.done = !accumulator.add(obj);
+            cont |= !accumulator.done;
+        }
+        cont
+    })?;
+    Ok(accumulators.into_iter().map(|a| a.finish()).collect())
+}
+
+pub(crate) fn get_aggregation_property<'a>(
+    collection: &'a IsarCollection,
+    op: &AggregationOp,
+    property_id: u64,
+) -> Result<Option<&'a Property>> {
+    let property = if *op != AggregationOp::Count {
+        Some(get_property(collection, 0, property_id)?)
+    } else {
+        None
+    };
+    if *op == AggregationOp::All || *op == AggregationOp::Any {
+        if let Some(property) = property {
+            if property.data_type != DataType::Bool {
+                illegal_arg("Property does not support this aggregation.")?;
+            }
+        }
+    }
+    Ok(property)
 }
 
 pub struct AggregationResultSend(*mut *const AggregationResult);
@@ -180,17 +313,74 @@ pub unsafe extern "C" fn isar_q_aggregate(
     let result = AggregationResultSend(result);
     isar_try_txn!(txn, move |txn| {
         let result = result;
-        let property = if op != AggregationOp::Count {
-            Some(get_property(collection, 0, property_id)?)
-        } else {
-            None
-        };
+        let property = get_aggregation_property(collection, &op, property_id)?;
         let aggregate_result = aggregate(query, txn, op, property)?;
         result.0.write(Box::into_raw(Box::new(aggregate_result)));
         Ok(())
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_q_aggregate_sum_count(
+    collection: &'static IsarCollection,
+    query: &'static Query,
+    txn: &mut CIsarTxn,
+    property_id: u64,
+    sum: *mut *const AggregationResult,
+    count: &'static mut u32,
+) -> i64 {
+    let sum = AggregationResultSend(sum);
+    let count = UintSend(count);
+    isar_try_txn!(txn, move |txn| {
+        let sum = sum;
+        let property = get_property(collection, 0, property_id)?;
+        if !matches!(
+            property.data_type,
+            DataType::Int | DataType::Long | DataType::Float | DataType::Double
+        ) {
+            illegal_arg("Property does not support this aggregation.")?;
+        }
+        let (sum_result, count_result) = aggregate_sum_count(query, txn, property)?;
+        sum.0.write(Box::into_raw(Box::new(sum_result)));
+        *count.0 = count_result;
+        Ok(())
+    })
+}
+
+/// Writes one result per operation to `results`. `operations` and `property_ids` must have
+/// `length` elements.
+#[no_mangle]
+pub unsafe extern "C" fn isar_q_aggregate_multi(
+    collection: &'static IsarCollection,
+    query: &'static Query,
+    txn: &mut CIsarTxn,
+    operations: *const u8,
+    property_ids: *const u64,
+    length: u32,
+    results: *mut *const AggregationResult,
+) -> i64 {
+    let operations = slice::from_raw_parts(operations, length as usize);
+    let property_ids = slice::from_raw_parts(property_ids, length as usize);
+    let results = AggregationResultSend(results);
+    isar_try_txn!(txn, move |txn| {
+        let results = results;
+        let mut aggregations = vec![];
+        for (op, property_id) in operations.iter().zip(property_ids) {
+            let op = AggregationOp::from_u8(*op);
+            let property = get_aggregation_property(collection, &op, *property_id)?;
+            aggregations.push((op, property));
+        }
+        let aggregate_results = aggregate_multi(query, txn, aggregations)?;
+        for (i, aggregate_result) in aggregate_results.into_iter().enumerate() {
+            results
+                .0
+                .add(i)
+                .write(Box::into_raw(Box::new(aggregate_result)));
+        }
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_q_aggregate_long_result(result: &AggregationResult) -> i64 {
     match result {
@@ -208,3 +398,164 @@ pub unsafe extern "C" fn isar_q_aggregate_double_result(result: &AggregationResu
         AggregationResult::Null => IsarObject::NULL_DOUBLE,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use isar_core::instance::IsarInstance;
+    use isar_core::object::object_builder::ObjectBuilder;
+    use isar_core::schema::Schema;
+    use std::sync::Arc;
+
+    /// Feeds objects with the given values to an accumulator like a query would and returns the
+    /// result together with the number of objects read before the scan stopped.
+    fn scan(op: AggregationOp, values: &[Option<bool>]) -> (AggregationResult, usize) {
+        let property = Property::new("paid", DataType::Bool, 2, None);
+        let properties = vec![property.clone()];
+        let mut accumulator = Accumulator::new(op, Some(&property));
+        let mut scanned = 0;
+        for value in values {
+            let mut builder = ObjectBuilder::new(&properties, None);
+            builder.write_bool(property.offset, *value);
+            scanned += 1;
+            if !accumulator.add(builder.finish()) {
+                break;
+            }
+        }
+        (accumulator.finish(), scanned)
+    }
+
+    fn is_long(result: AggregationResult, expected: i64) -> bool {
+        matches!(result, AggregationResult::Long(value) if value == expected)
+    }
+
+    #[test]
+    fn test_all_any() {
+        let all_true = [Some(true); 5];
+        let all_false = [Some(false); 5];
+        let mixed = [Some(true), Some(true), Some(false), None, Some(true)];
+
+        let (result, scanned) = scan(AggregationOp::All, &all_true);
+        assert!(is_long(result, 1));
+        assert_eq!(scanned, 5);
+        let (result, scanned) = scan(AggregationOp::All, &all_false);
+        assert!(is_long(result, 0));
+        assert_eq!(scanned, 1);
+        let (result, scanned) = scan(AggregationOp::All, &mixed);
+        assert!(is_long(result, 0));
+        assert_eq!(scanned, 3);
+
+        let (result, scanned) = scan(AggregationOp::Any, &all_true);
+        assert!(is_long(result, 1));
+        assert_eq!(scanned, 1);
+        let (result, scanned) = scan(AggregationOp::Any, &all_false);
+        assert!(is_long(result, 0));
+        assert_eq!(scanned, 5);
+        let (result, scanned) = scan(AggregationOp::Any, &mixed[2..]);
+        assert!(is_long(result, 1));
+        assert_eq!(scanned, 3);
+
+        // null is not true
+        assert!(is_long(scan(AggregationOp::All, &[None]).0, 0));
+        assert!(is_long(scan(AggregationOp::Any, &[None]).0, 0));
+
+        assert!(is_long(scan(AggregationOp::All, &[]).0, 1));
+        assert!(is_long(scan(AggregationOp::Any, &[]).0, 0));
+    }
+
+    fn value(result: &AggregationResult) -> Option<f64> {
+        match result {
+            AggregationResult::Long(value) => Some(*value as f64),
+            AggregationResult::Double(value) => Some(*value),
+            AggregationResult::Null => None,
+        }
+    }
+
+    /// Opens an instance with a single long property and inserts the given objects.
+    fn open_longs(prefix: &str, values: &[(i64, Option<i64>)]) -> (Arc<IsarInstance>, String) {
+        let schema = r#"[{"name": "Item", "properties": [{"name": "value", "type": "Long"}]}]"#;
+        let schema = Schema::from_json(schema.as_bytes()).unwrap();
+        let name = format!("{}_{}", prefix, std::process::id());
+        let dir = std::env::temp_dir().join("isar_tests").join(&name);
+        let dir = dir.to_str().unwrap().to_string();
+        let instance =
+            IsarInstance::open(&name, Some(&dir), schema, 64, None, true, None, true, None)
+                .unwrap();
+        let col = &instance.collections[0];
+        let property = &col.properties[0];
+        let mut txn = instance.begin_txn(true, false).unwrap();
+        for (id, value) in values {
+            let mut builder = col.new_object_builder(None);
+            match value {
+                Some(value) => builder.write_long(property.offset, *value),
+                None => builder.write_null(property.offset, DataType::Long),
+            }
+            col.put(&mut txn, Some(*id), builder.finish()).unwrap();
+        }
+        txn.commit().unwrap();
+        (instance, dir)
+    }
+
+    #[test]
+    fn test_aggregate_multi() {
+        let values = [
+            (1, Some(4)),
+            (2, None),
+            (3, Some(-7)),
+            (4, Some(12)),
+            (5, Some(6)),
+        ];
+        let (instance, dir) = open_longs("aggregate_multi", &values);
+        let col = &instance.collections[0];
+        let property = &col.properties[0];
+
+        let ops = [
+            AggregationOp::Count,
+            AggregationOp::Min,
+            AggregationOp::Max,
+            AggregationOp::Average,
+            AggregationOp::Sum,
+        ];
+        let mut qb = col.new_query_builder();
+        qb.set_filter_expression("value != 12").unwrap();
+        let query = qb.build().unwrap();
+        let mut txn = instance.begin_txn(false, false).unwrap();
+        let aggregations = ops.iter().map(|op| (*op, Some(property))).collect();
+        let multi = aggregate_multi(&query, &mut txn, aggregations).unwrap();
+        let multi: Vec<_> = multi.iter().map(value).collect();
+        let single: Vec<_> = ops
+            .iter()
+            .map(|op| value(&aggregate(&query, &mut txn, *op, Some(property)).unwrap()))
+            .collect();
+        assert_eq!(multi, single);
+        assert_eq!(
+            multi,
+            vec![Some(4.0), Some(-7.0), Some(6.0), Some(1.0), Some(3.0)]
+        );
+        txn.abort();
+        instance.close_and_delete();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+    #[test]
+    fn test_aggregate_sum_count() {
+        let large = 1 << 53;
+        let values = [(1, Some(large + 1)), (2, None), (3, Some(large + 2))];
+        let (instance, dir) = open_longs("aggregate_sum_count", &values);
+        let col = &instance.collections[0];
+        let property = &col.properties[0];
+        let query = col.new_query_builder().build().unwrap();
+        let mut txn = instance.begin_txn(false, false).unwrap();
+
+        // the exact average is 2^53 + 1.5 which is not representable as f64
+        let average = aggregate(&query, &mut txn, AggregationOp::Average, Some(property));
+        let average = value(&average.unwrap()).unwrap();
+        let (sum, count) = aggregate_sum_count(&query, &mut txn, property).unwrap();
+        assert_eq!(count, 2);
+        assert!(is_long(sum, 2 * large + 3));
+        assert_eq!(average.fract(), 0.0);
+        assert_ne!(average as i64 * 2, 2 * large + 3);
+        txn.abort();
+        instance.close_and_delete();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
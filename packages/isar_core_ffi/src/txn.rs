@@ -48,6 +48,41 @@ pub unsafe extern "C" fn isar_txn_finish(txn: *mut CIsarTxn, commit: bool) -> i6
     }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn isar_txn_get_id(txn: &mut CIsarTxn, id: &'static mut i64) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        *id = txn.id() as i64;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_txn_has_changes(
+    txn: &mut CIsarTxn,
+    has_changes: &'static mut bool,
+) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        *has_changes = txn.has_changes();
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_txn_savepoint(txn: &mut CIsarTxn) -> i64 {
+    isar_try_txn!(txn, move |txn| txn.savepoint())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_txn_finish_savepoint(txn: &mut CIsarTxn, release: bool) -> i64 {
+    isar_try_txn!(txn, move |txn| {
+        if release {
+            txn.release_savepoint()
+        } else {
+            txn.rollback_to_savepoint()
+        }
+    })
+}
+
 pub struct IsarTxnSend(IsarTxn<'static>);
 
 unsafe impl Send for IsarTxnSend {}
@@ -3,6 +3,10 @@ use isar_core::instance::IsarInstance;
 use isar_core::query::Query;
 use isar_core::watch::WatchHandle;
 use crate::dart::{dart_post_int, DartPort};
+use crate::query_aggregation::{
+    aggregate, get_aggregation_property, AggregationOp, AggregationResult,
+};
+use std::time::Duration;
 
 #[no_mangle]
 pub extern "C" fn isar_watch_collection(
@@ -19,6 +23,43 @@ pub extern "C" fn isar_watch_collection(
     Box::into_raw(Box::new(handle))
 }
 
+/// The ids posted by [isar_watch_collection_ids].
+#[repr(C)]
+pub struct ChangedIds {
+    pub ids: *mut i64,
+    pub length: u32,
+}
+
+/// Posts a pointer to the sorted `ChangedIds` of every committed transaction that changed the
+/// collection to `port`. The receiver frees them with [isar_free_changed_ids].
+#[no_mangle]
+pub extern "C" fn isar_watch_collection_ids(
+    isar: &IsarInstance,
+    collection: &IsarCollection,
+    port: DartPort,
+) -> *mut WatchHandle {
+    let handle = isar.watch_collection_ids(
+        collection,
+        Box::new(move |ids| {
+            let mut ids = ids.to_vec().into_boxed_slice();
+            let changed = ChangedIds {
+                ids: ids.as_mut_ptr(),
+                length: ids.len() as u32,
+            };
+            std::mem::forget(ids);
+            dart_post_int(port, Box::into_raw(Box::new(changed)) as i64);
+        }),
+    );
+    Box::into_raw(Box::new(handle))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn isar_free_changed_ids(changed: *mut ChangedIds) {
+    let changed = Box::from_raw(changed);
+    let length = changed.length as usize;
+    Vec::from_raw_parts(changed.ids, length, length);
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_watch_object(
     isar: &IsarInstance,
@@ -53,6 +94,36 @@ pub extern "C" fn isar_watch_query(
     Box::into_raw(Box::new(handle))
 }
 
+/// Posts a pointer to the current `AggregationResult` to `port` and a new one after every change
+/// of the collection. Changes within `debounce_ms` of each other are posted once.
+#[no_mangle]
+pub unsafe extern "C" fn isar_watch_aggregate(
+    isar: &IsarInstance,
+    collection: &'static IsarCollection,
+    query: &Query,
+    operation: u8,
+    property_id: u64,
+    debounce_ms: u32,
+    port: DartPort,
+) -> *mut WatchHandle {
+    let op = AggregationOp::from_u8(operation);
+    let property = match get_aggregation_property(collection, &op, property_id) {
+        Ok(property) => property.cloned(),
+        Err(_) => return std::ptr::null_mut(),
+    };
+    let handle = isar.watch_aggregate(
+        collection,
+        query.clone(),
+        Some(Duration::from_millis(debounce_ms as u64)),
+        move |query, txn| Ok(aggregate(query, txn, op, property.as_ref())?.into()),
+        move |value| {
+            let result = AggregationResult::from(value);
+            dart_post_int(port, Box::into_raw(Box::new(result)) as i64);
+        },
+    );
+    Box::into_raw(Box::new(handle))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn isar_stop_watching(handle: *mut WatchHandle) {
     Box::from_raw(handle).stop();